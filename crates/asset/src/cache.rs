@@ -0,0 +1,217 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory of cooked assets.
+///
+/// Loaders can store a compact binary version of their output here, see [`LoadContext::cooked`](super::LoadContext::cooked).
+/// Blobs are keyed by a hash of the source content and path, the paths and modification times of the files the
+/// loader declared as cook dependencies, the import settings and the importer version, so they never go stale: a
+/// changed source or dependency simply maps to a new blob.
+pub struct AssetCache {
+	directory: PathBuf,
+}
+
+impl AssetCache {
+	/// Creates a cache that stores its blobs in `directory`, which is created when needed.
+	pub fn new(directory: impl AsRef<Path>) -> Self {
+		Self {
+			directory: directory.as_ref().to_path_buf(),
+		}
+	}
+
+	pub fn directory(&self) -> &Path {
+		&self.directory
+	}
+
+	/// Reads the blob stored under `key`, if any.
+	pub fn get(&self, key: CacheKey) -> Option<Vec<u8>> {
+		std::fs::read(self.blob_path(key)).ok()
+	}
+
+	/// Stores `bytes` under `key`.
+	///
+	/// The blob is written to a temporary file first, so a crash never leaves a truncated blob behind.
+	pub fn put(&self, key: CacheKey, bytes: &[u8]) -> io::Result<()> {
+		std::fs::create_dir_all(&self.directory)?;
+
+		let path = self.blob_path(key);
+		let temp_path = path.with_extension("tmp");
+
+		std::fs::write(&temp_path, bytes)?;
+		std::fs::rename(&temp_path, &path)
+	}
+
+	fn blob_path(&self, key: CacheKey) -> PathBuf {
+		self.directory.join(format!("{:016x}.bin", key.0))
+	}
+}
+
+/// Hash identifying a cooked blob.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey(pub u64);
+
+impl CacheKey {
+	/// Computes a key from a list of byte strings, using 64-bit FNV-1a.
+	///
+	/// Every part is prefixed with its length, so `["ab", "c"]` and `["a", "bc"]` get different keys.
+	pub fn new(parts: &[&[u8]]) -> Self {
+		const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+		const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+		let mut hash = OFFSET_BASIS;
+
+		for part in parts {
+			for byte in (part.len() as u64).to_le_bytes().iter().chain(part.iter()) {
+				hash ^= *byte as u64;
+				hash = hash.wrapping_mul(PRIME);
+			}
+		}
+
+		Self(hash)
+	}
+}
+
+/// Plain old data that can be copied to and from bytes as is.
+///
+/// # Safety
+///
+/// The type must not contain padding, pointers or references, and every bit pattern must be a valid value.
+pub unsafe trait Pod: Copy + 'static {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for u64 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for f32 {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// Serializes values into a cooked blob. Read them back with a [`BlobReader`] in the same order.
+///
+/// Values are stored in native byte order, cooked data is not meant to be moved between machines.
+#[derive(Default)]
+pub struct BlobWriter {
+	bytes: Vec<u8>,
+}
+
+impl BlobWriter {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn write<T: Pod>(&mut self, value: T) {
+		self.write_bytes(std::slice::from_ref(&value));
+	}
+
+	/// Writes the length of `values`, followed by their bytes.
+	pub fn write_slice<T: Pod>(&mut self, values: &[T]) {
+		self.write(values.len() as u64);
+		self.write_bytes(values);
+	}
+
+	pub fn write_str(&mut self, value: &str) {
+		self.write_slice(value.as_bytes());
+	}
+
+	pub fn finish(self) -> Vec<u8> {
+		self.bytes
+	}
+
+	fn write_bytes<T: Pod>(&mut self, values: &[T]) {
+		// SAFETY: `T` is plain old data without padding.
+		let bytes = unsafe {
+			std::slice::from_raw_parts(values.as_ptr() as *const u8, size_of_val(values))
+		};
+		self.bytes.extend_from_slice(bytes);
+	}
+}
+
+/// Deserializes values written by a [`BlobWriter`].
+pub struct BlobReader<'a> {
+	bytes: &'a [u8],
+}
+
+impl<'a> BlobReader<'a> {
+	pub fn new(bytes: &'a [u8]) -> Self {
+		Self { bytes }
+	}
+
+	pub fn read<T: Pod>(&mut self) -> io::Result<T> {
+		Ok(self.read_vec_of_len::<T>(1)?[0])
+	}
+
+	/// Reads a slice written by [`BlobWriter::write_slice`] with a single copy.
+	pub fn read_vec<T: Pod>(&mut self) -> io::Result<Vec<T>> {
+		let len = self.read::<u64>()? as usize;
+		self.read_vec_of_len(len)
+	}
+
+	pub fn read_string(&mut self) -> io::Result<String> {
+		String::from_utf8(self.read_vec()?).map_err(|error| invalid_data(error.to_string()))
+	}
+
+	/// Whether all bytes have been read.
+	pub fn is_empty(&self) -> bool {
+		self.bytes.is_empty()
+	}
+
+	fn read_vec_of_len<T: Pod>(&mut self, len: usize) -> io::Result<Vec<T>> {
+		let size = len
+			.checked_mul(size_of::<T>())
+			.filter(|size| *size <= self.bytes.len())
+			.ok_or_else(|| invalid_data("Unexpected end of cooked data".to_string()))?;
+
+		let mut values = Vec::<T>::with_capacity(len);
+
+		// SAFETY: The source holds `size` bytes, the destination has capacity for `len` values of `T`,
+		// and any bit pattern is a valid `T`. Copying bytes also handles unaligned sources.
+		unsafe {
+			std::ptr::copy_nonoverlapping(
+				self.bytes.as_ptr(),
+				values.as_mut_ptr() as *mut u8,
+				size,
+			);
+			values.set_len(len);
+		}
+
+		self.bytes = &self.bytes[size..];
+		Ok(values)
+	}
+}
+
+fn invalid_data(message: String) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn blob_round_trip() {
+		let mut writer = BlobWriter::new();
+		writer.write(7u32);
+		writer.write_slice(&[[1.0f32, 2.0, 3.0], [4.0, 5.0, 6.0]]);
+		writer.write_str("mesh");
+		let bytes = writer.finish();
+
+		// Read from an unaligned offset.
+		let mut unaligned = vec![0u8];
+		unaligned.extend_from_slice(&bytes);
+
+		let mut reader = BlobReader::new(&unaligned[1..]);
+		assert_eq!(reader.read::<u32>().unwrap(), 7);
+		assert_eq!(
+			reader.read_vec::<[f32; 3]>().unwrap(),
+			[[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]
+		);
+		assert_eq!(reader.read_string().unwrap(), "mesh");
+		assert!(reader.is_empty());
+		assert!(reader.read::<u32>().is_err());
+	}
+
+	#[test]
+	fn cache_key() {
+		assert_ne!(CacheKey::new(&[b"ab", b"c"]), CacheKey::new(&[b"a", b"bc"]));
+		assert_eq!(CacheKey::new(&[b"abc"]), CacheKey::new(&[b"abc"]));
+	}
+}
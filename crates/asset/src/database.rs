@@ -0,0 +1,260 @@
+use super::{AssetGuid, AssetMeta, Vfs};
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Maps asset GUIDs to the paths of their source files.
+///
+/// Built by scanning a directory for `.meta` sidecars. Because a GUID lives in the sidecar that moves
+/// together with its source file, renamed or moved assets keep their GUID after a [`AssetDatabase::refresh`].
+#[derive(Default)]
+pub struct AssetDatabase {
+	root: PathBuf,
+	/// Mount point the root is mounted at, paths are stored as [`Vfs`] paths when set.
+	mount_point: Option<String>,
+	paths: HashMap<AssetGuid, PathBuf>,
+	guids: HashMap<PathBuf, AssetGuid>,
+}
+
+impl AssetDatabase {
+	/// Creates a database for all assets in `root` and its subdirectories.
+	pub fn scan(root: impl AsRef<Path>) -> io::Result<Self> {
+		let mut database = Self {
+			root: normalize_path(root.as_ref()),
+			..Default::default()
+		};
+		database.refresh()?;
+		Ok(database)
+	}
+
+	/// Creates a database for all assets under a [`Vfs`] mount point, which must be mounted from a directory.
+	///
+	/// Paths are stored as virtual paths, e.g. `assets://usd/scene.usdc`.
+	pub fn scan_mounted(vfs: &Vfs, mount_point: &str) -> io::Result<Self> {
+		let root = vfs.native_path(mount_point).ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::Unsupported,
+				format!("{mount_point} is not mounted from a directory"),
+			)
+		})?;
+
+		let mut database = Self {
+			root: normalize_path(&root),
+			mount_point: Some(mount_point.to_string()),
+			..Default::default()
+		};
+		database.refresh()?;
+		Ok(database)
+	}
+
+	/// Rescans the root directory, picking up new, moved and renamed assets.
+	pub fn refresh(&mut self) -> io::Result<()> {
+		self.paths.clear();
+		self.guids.clear();
+
+		let mut directories = vec![self.root.clone()];
+
+		while let Some(directory) = directories.pop() {
+			for entry in std::fs::read_dir(&directory)? {
+				let path = entry?.path();
+
+				if path.is_dir() {
+					directories.push(path);
+					continue;
+				}
+
+				if path.extension().is_none_or(|extension| extension != "meta") {
+					continue;
+				}
+
+				// The source path is the sidecar path without the `.meta` extension.
+				let source = path.with_extension("");
+
+				if !source.exists() {
+					log::warn!("Ignoring orphaned meta file {}", path.display());
+					continue;
+				}
+
+				let meta = match std::fs::read_to_string(&path)
+					.and_then(|text| text.parse::<AssetMeta>())
+				{
+					Ok(meta) => meta,
+					Err(error) => {
+						log::warn!("Ignoring invalid meta file {}: {}", path.display(), error);
+						continue;
+					}
+				};
+
+				if let Some(existing) = self.paths.get(&meta.guid) {
+					log::warn!(
+						"Duplicate asset guid {} in {} and {}",
+						meta.guid,
+						existing.display(),
+						source.display()
+					);
+					continue;
+				}
+
+				let source = self.mounted_path(&source);
+				self.insert(meta.guid, &source);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Maps `guid` to `path`, replacing the previous path of the GUID and the previous GUID of the path.
+	pub fn insert(&mut self, guid: AssetGuid, path: &Path) {
+		let path = normalize_path(path);
+
+		if let Some(previous) = self.paths.insert(guid, path.clone()) {
+			self.guids.remove(&previous);
+		}
+
+		if let Some(previous) = self.guids.insert(path, guid)
+			&& previous != guid
+		{
+			self.paths.remove(&previous);
+		}
+	}
+
+	/// Path of the source file of the asset with `guid`.
+	pub fn path(&self, guid: AssetGuid) -> Option<&Path> {
+		self.paths.get(&guid).map(PathBuf::as_path)
+	}
+
+	/// GUID of the asset with the source file at `path`.
+	pub fn guid(&self, path: &Path) -> Option<AssetGuid> {
+		self.guids.get(&normalize_path(path)).copied()
+	}
+
+	/// Turns a native path below the root into a virtual path, if the database is mounted.
+	fn mounted_path(&self, path: &Path) -> PathBuf {
+		let Some(mount_point) = &self.mount_point else {
+			return path.to_path_buf();
+		};
+
+		let relative = path
+			.strip_prefix(&self.root)
+			.unwrap_or(path)
+			.components()
+			.map(|component| component.as_os_str().to_string_lossy())
+			.collect::<Vec<_>>()
+			.join("/");
+
+		if mount_point.ends_with("://") {
+			PathBuf::from(format!("{mount_point}{relative}"))
+		} else {
+			PathBuf::from(format!("{mount_point}/{relative}"))
+		}
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (AssetGuid, &Path)> {
+		self.paths
+			.iter()
+			.map(|(guid, path)| (*guid, path.as_path()))
+	}
+}
+
+/// Lexically normalizes a path by removing `.` components and resolving `..` where possible,
+/// so that different spellings of the same path map to the same asset.
+///
+/// The `scheme://` prefix of virtual paths is kept as is.
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+	if let Some((scheme, rest)) = path.to_str().and_then(|path| path.split_once("://")) {
+		let rest = normalize_path(Path::new(rest));
+		return PathBuf::from(format!("{scheme}://{}", rest.display()));
+	}
+
+	let mut normalized = PathBuf::new();
+
+	for component in path.components() {
+		match component {
+			Component::CurDir => {}
+			Component::ParentDir => {
+				if matches!(
+					normalized.components().next_back(),
+					Some(Component::Normal(_))
+				) {
+					normalized.pop();
+				} else {
+					normalized.push(component);
+				}
+			}
+			_ => normalized.push(component),
+		}
+	}
+
+	normalized
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn normalize() {
+		assert_eq!(
+			normalize_path(Path::new("../assets/./usd/../hdri/sky.exr")),
+			Path::new("../assets/hdri/sky.exr")
+		);
+		assert_eq!(
+			normalize_path(Path::new("assets://usd/../hdri/sky.exr")),
+			Path::new("assets://hdri/sky.exr")
+		);
+	}
+
+	#[test]
+	fn rename() {
+		let root = std::env::temp_dir().join("asset_database_rename");
+		let _ = std::fs::remove_dir_all(&root);
+		std::fs::create_dir_all(&root).unwrap();
+
+		let before = root.join("before.txt");
+		let after = root.join("after.txt");
+		std::fs::write(&before, "").unwrap();
+
+		let guid = AssetMeta::load_or_create(&before).unwrap().guid;
+
+		let mut database = AssetDatabase::scan(&root).unwrap();
+		assert_eq!(database.path(guid), Some(before.as_path()));
+
+		std::fs::rename(&before, &after).unwrap();
+		std::fs::rename(AssetMeta::meta_path(&before), AssetMeta::meta_path(&after)).unwrap();
+
+		database.refresh().unwrap();
+		assert_eq!(database.path(guid), Some(after.as_path()));
+		assert_eq!(database.guid(&after), Some(guid));
+		assert_eq!(database.guid(&before), None);
+
+		// Assets that are loaded from another path, or files that get another GUID, replace their old entry.
+		database.insert(guid, &before);
+		assert_eq!(database.guid(&after), None);
+
+		let other = AssetGuid::new_random();
+		database.insert(other, &before);
+		assert_eq!(database.path(guid), None);
+		assert_eq!(database.path(other), Some(before.as_path()));
+	}
+
+	#[test]
+	fn mounted() {
+		let root = std::env::temp_dir().join("asset_database_mounted");
+		let _ = std::fs::remove_dir_all(&root);
+		std::fs::create_dir_all(root.join("meshes")).unwrap();
+
+		let path = root.join("meshes/model.txt");
+		std::fs::write(&path, "").unwrap();
+		let guid = AssetMeta::load_or_create(&path).unwrap().guid;
+
+		let mut vfs = Vfs::new();
+		vfs.mount("assets://", crate::DirectorySource::new(&root));
+
+		let database = AssetDatabase::scan_mounted(&vfs, "assets://").unwrap();
+		assert_eq!(
+			database.path(guid),
+			Some(Path::new("assets://meshes/model.txt"))
+		);
+	}
+}
@@ -0,0 +1,66 @@
+use super::LoadError;
+
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Error of an asset that failed to load, see [`LoadState::Failed`](super::LoadState::Failed).
+#[derive(Debug)]
+pub struct AssetError {
+	path: PathBuf,
+	loader: Option<&'static str>,
+	source: LoadError,
+}
+
+impl AssetError {
+	pub(crate) fn new(path: &Path, loader: Option<&'static str>, source: LoadError) -> Self {
+		Self {
+			path: path.to_path_buf(),
+			loader,
+			source,
+		}
+	}
+
+	/// Path of the source file that failed to load.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Type name of the loader that failed, or `None` if no loader was found for the file.
+	pub fn loader(&self) -> Option<&'static str> {
+		self.loader
+	}
+
+	/// The error returned by the loader, followed by the errors that caused it.
+	pub fn causes(&self) -> impl Iterator<Item = &(dyn Error + 'static)> {
+		std::iter::successors(
+			Some(self.source.as_ref() as &(dyn Error + 'static)),
+			|&error| error.source(),
+		)
+	}
+}
+
+impl fmt::Display for AssetError {
+	/// Formats the error with its entire cause chain, e.g.
+	/// `Failed to load sky.exr with ImageLoader: invalid header: unexpected end of file`.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Failed to load {}", self.path.display())?;
+
+		if let Some(loader) = self.loader {
+			// Strip the module path from the type name.
+			write!(f, " with {}", loader.rsplit("::").next().unwrap_or(loader))?;
+		}
+
+		for cause in self.causes() {
+			write!(f, ": {cause}")?;
+		}
+
+		Ok(())
+	}
+}
+
+impl Error for AssetError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		Some(self.source.as_ref())
+	}
+}
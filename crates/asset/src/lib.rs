@@ -1,40 +1,22 @@
-use std::collections::HashMap;
+mod cache;
+mod database;
+mod error;
+mod loader;
+mod meta;
+mod server;
+mod storage;
+mod vfs;
 
-#[derive(Default)]
-pub struct AssetServer {
-	id: u64,
-	assets: HashMap<u64, Box<dyn std::any::Any>>,
-}
+pub use cache::*;
+pub use database::*;
+pub use error::*;
+pub use loader::*;
+pub use meta::*;
+pub use server::*;
+pub use storage::*;
+pub use vfs::*;
 
-impl AssetServer {
-	pub fn new() -> Self {
-		Default::default()
-	}
-
-	pub fn insert<T: Asset>(&mut self, asset: T) -> AssetId<T> {
-		let handle = AssetId {
-			id: self.id,
-			phantom: std::marker::PhantomData,
-		};
-		self.assets.insert(handle.id, Box::new(asset));
-		self.id += 1;
-		handle
-	}
-
-	pub fn get<T: Asset>(&self, handle: &AssetId<T>) -> Option<&T> {
-		self.assets
-			.get(&handle.id)
-			.and_then(|asset| asset.downcast_ref::<T>())
-	}
-
-	pub fn get_mut<T: Asset>(&mut self, handle: &AssetId<T>) -> Option<&mut T> {
-		self.assets
-			.get_mut(&handle.id)
-			.and_then(|asset| asset.downcast_mut::<T>())
-	}
-}
-
-pub trait Asset: std::any::Any {}
+pub trait Asset: std::any::Any + Send + Sync {}
 
 pub struct AssetId<T> {
 	id: u64,
@@ -42,6 +24,13 @@ pub struct AssetId<T> {
 }
 
 impl<T> AssetId<T> {
+	pub(crate) fn new(id: UntypedAssetId) -> Self {
+		Self {
+			id,
+			phantom: std::marker::PhantomData,
+		}
+	}
+
 	pub fn id(&self) -> UntypedAssetId {
 		self.id
 	}
@@ -55,4 +44,24 @@ impl<T: Asset> Clone for AssetId<T> {
 
 impl<T: Asset> Copy for AssetId<T> {}
 
+impl<T> PartialEq for AssetId<T> {
+	fn eq(&self, other: &Self) -> bool {
+		self.id == other.id
+	}
+}
+
+impl<T> Eq for AssetId<T> {}
+
+impl<T> std::hash::Hash for AssetId<T> {
+	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+		self.id.hash(state);
+	}
+}
+
+impl<T> std::fmt::Debug for AssetId<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "AssetId({})", self.id)
+	}
+}
+
 pub type UntypedAssetId = u64;
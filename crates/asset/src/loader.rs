@@ -0,0 +1,239 @@
+use super::database::normalize_path;
+use super::server::AssetIndex;
+use super::storage::BoxedAsset;
+use super::{Asset, AssetCache, AssetId, CacheKey, ImportSettings, UntypedAssetId, Vfs};
+use std::any::TypeId;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+pub type LoadError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Turns a source file into an [`Asset`].
+///
+/// Loaders run on the asset server's worker thread, so they must not touch any main thread state.
+pub trait AssetLoader: Send + Sync + 'static {
+	type Asset: Asset;
+
+	/// File extensions handled by this loader, without the leading dot.
+	fn extensions(&self) -> &[&str];
+
+	/// Version of the importer. Bump it whenever the output of [`LoadContext::cooked`] changes,
+	/// so that previously cooked data is rebuilt.
+	fn version(&self) -> u32 {
+		0
+	}
+
+	fn load(&self, ctx: &mut LoadContext) -> Result<Self::Asset, LoadError>;
+}
+
+/// State passed to an [`AssetLoader`] while it loads a single asset.
+pub struct LoadContext {
+	path: PathBuf,
+	settings: ImportSettings,
+	index: Arc<Mutex<AssetIndex>>,
+	importer: Importer,
+	cache: Option<Arc<AssetCache>>,
+	vfs: Arc<Vfs>,
+	pub(crate) labeled_assets: Vec<LabeledAsset>,
+	pub(crate) dependencies: Vec<Dependency>,
+	/// Files besides the source file that cooked data is made from, relative to the source file.
+	cook_dependencies: Vec<PathBuf>,
+}
+
+/// Asset that is part of the source file of another asset.
+pub(crate) struct LabeledAsset {
+	pub id: UntypedAssetId,
+	/// Path of the source file followed by `#label`.
+	pub name: String,
+	pub asset: BoxedAsset,
+}
+
+/// Asset that is loaded because another asset depends on it.
+pub(crate) struct Dependency {
+	pub id: UntypedAssetId,
+	pub path: PathBuf,
+	pub asset_type: TypeId,
+}
+
+/// Identifies the loader that is running, for keying cooked data.
+pub(crate) struct Importer {
+	pub name: &'static str,
+	pub version: u32,
+}
+
+impl LoadContext {
+	pub(crate) fn new(
+		path: PathBuf,
+		settings: ImportSettings,
+		index: Arc<Mutex<AssetIndex>>,
+		importer: Importer,
+		cache: Option<Arc<AssetCache>>,
+		vfs: Arc<Vfs>,
+	) -> Self {
+		Self {
+			path,
+			settings,
+			index,
+			importer,
+			cache,
+			vfs,
+			labeled_assets: Vec::new(),
+			dependencies: Vec::new(),
+			cook_dependencies: Vec::new(),
+		}
+	}
+
+	/// Path of the source file that is being loaded, which can be a [`Vfs`] path like `assets://image.exr`.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Path of the source file on the native file system, see [`Vfs::native_path`].
+	///
+	/// Only for loaders that rely on libraries which open files themselves, prefer [`LoadContext::read`].
+	pub fn native_path(&self) -> Option<PathBuf> {
+		self.vfs.native_path(&self.path)
+	}
+
+	/// Import settings from the asset's `.meta` sidecar.
+	pub fn settings(&self) -> &ImportSettings {
+		&self.settings
+	}
+
+	/// Reads the entire source file into memory, through the [`Vfs`].
+	pub fn read(&self) -> std::io::Result<Vec<u8>> {
+		self.vfs.read(&self.path)
+	}
+
+	/// Reads a file that the source file refers to, like an external buffer, with `path` relative to the source file.
+	///
+	/// Unlike [`LoadContext::load`] this doesn't track the file, so changing it doesn't reload the asset.
+	pub fn read_relative(&self, path: impl AsRef<Path>) -> std::io::Result<Vec<u8>> {
+		let directory = self.path.parent().unwrap_or(Path::new(""));
+		self.vfs.read(normalize_path(&directory.join(path)))
+	}
+
+	/// Declares that cooked data is also made from a file besides the source file, like a sublayer of a USD stage,
+	/// with `path` relative to the source file. Must be called before [`LoadContext::cooked`].
+	pub fn cook_dependency(&mut self, path: impl AsRef<Path>) {
+		let directory = self.path.parent().unwrap_or(Path::new(""));
+		self.cook_dependencies
+			.push(normalize_path(&directory.join(path)));
+	}
+
+	/// Returns the cooked version of the source file, calling `cook` only when it isn't cached yet.
+	///
+	/// Cooked data is keyed by the path and content of the source file, the modification times of the files
+	/// declared with [`LoadContext::cook_dependency`], the import settings and the name and
+	/// [`AssetLoader::version`] of the loader. Without an [`AssetCache`] this always cooks.
+	/// Dependencies and labeled assets must be declared while turning the cooked data into the asset,
+	/// not inside `cook`, since that is skipped on a cache hit.
+	pub fn cooked(
+		&mut self,
+		cook: impl FnOnce(&mut Self) -> Result<Vec<u8>, LoadError>,
+	) -> Result<Vec<u8>, LoadError> {
+		let Some(cache) = self.cache.clone() else {
+			return cook(self);
+		};
+
+		let source = self.read()?;
+		let path = self.path.to_string_lossy();
+		let settings = format!("{:?}", self.settings);
+
+		let mut dependencies = Vec::new();
+		for dependency in &self.cook_dependencies {
+			let modified = self.vfs.modified(dependency);
+			let nanos = modified
+				.and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+				.map_or(0, |duration| duration.as_nanos());
+
+			dependencies.extend_from_slice(dependency.to_string_lossy().as_bytes());
+			dependencies.extend_from_slice(&nanos.to_le_bytes());
+		}
+
+		let key = CacheKey::new(&[
+			&source,
+			path.as_bytes(),
+			&dependencies,
+			settings.as_bytes(),
+			self.importer.name.as_bytes(),
+			&self.importer.version.to_le_bytes(),
+		]);
+
+		if let Some(bytes) = cache.get(key) {
+			return Ok(bytes);
+		}
+
+		let bytes = cook(self)?;
+
+		if let Err(error) = cache.put(key, &bytes) {
+			log::warn!("Failed to cache {}: {}", self.path.display(), error);
+		}
+
+		Ok(bytes)
+	}
+
+	/// Declares a dependency on the asset at `path` and returns its handle.
+	///
+	/// The server starts loading dependencies once this loader is done,
+	/// and reloads this asset whenever one of its dependencies is reloaded.
+	pub fn load<T: Asset>(&mut self, path: impl AsRef<Path>) -> AssetId<T> {
+		let path = normalize_path(path.as_ref());
+		let id = self.index.lock().unwrap().id(&path);
+
+		self.dependencies.push(Dependency {
+			id,
+			path,
+			asset_type: TypeId::of::<T>(),
+		});
+
+		AssetId::new(id)
+	}
+
+	/// Adds an asset that is part of the source file, such as a mesh inside a scene.
+	///
+	/// Labeled assets keep their handle when the source file is reloaded.
+	pub fn add_labeled_asset<T: Asset>(&mut self, label: &str, asset: T) -> AssetId<T> {
+		let mut path = self.path.as_os_str().to_owned();
+		path.push("#");
+		path.push(label);
+
+		let id = self.index.lock().unwrap().id(Path::new(&path));
+		self.labeled_assets.push(LabeledAsset {
+			id,
+			name: path.to_string_lossy().into_owned(),
+			asset: BoxedAsset::new(asset),
+		});
+
+		AssetId::new(id)
+	}
+}
+
+pub(crate) trait ErasedAssetLoader: Send + Sync {
+	fn extensions(&self) -> &[&str];
+	fn asset_type(&self) -> TypeId;
+	fn importer(&self) -> Importer;
+	fn load(&self, ctx: &mut LoadContext) -> Result<BoxedAsset, LoadError>;
+}
+
+impl<L: AssetLoader> ErasedAssetLoader for L {
+	fn extensions(&self) -> &[&str] {
+		AssetLoader::extensions(self)
+	}
+
+	fn asset_type(&self) -> TypeId {
+		TypeId::of::<L::Asset>()
+	}
+
+	fn importer(&self) -> Importer {
+		Importer {
+			name: std::any::type_name::<L>(),
+			version: self.version(),
+		}
+	}
+
+	fn load(&self, ctx: &mut LoadContext) -> Result<BoxedAsset, LoadError> {
+		AssetLoader::load(self, ctx).map(BoxedAsset::new)
+	}
+}
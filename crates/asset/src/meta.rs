@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Globally unique, persistent identifier of a source asset.
+///
+/// Unlike [`UntypedAssetId`](super::UntypedAssetId), which is only valid for a single run,
+/// the GUID is stored in the asset's `.meta` sidecar and can be referenced by saved data.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AssetGuid(pub u128);
+
+impl AssetGuid {
+	/// Generates a new random (version 4) UUID.
+	pub fn new_random() -> Self {
+		let bits = rand::random::<u128>();
+		let version = (bits & !(0xf << 76)) | (0x4 << 76);
+		let variant = (version & !(0x3 << 62)) | (0x2 << 62);
+		Self(variant)
+	}
+}
+
+impl fmt::Display for AssetGuid {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let v = self.0;
+		write!(
+			f,
+			"{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+			(v >> 96) as u32,
+			(v >> 80) as u16,
+			(v >> 64) as u16,
+			(v >> 48) as u16,
+			v & 0xffff_ffff_ffff,
+		)
+	}
+}
+
+impl fmt::Debug for AssetGuid {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "AssetGuid({self})")
+	}
+}
+
+impl FromStr for AssetGuid {
+	type Err = std::num::ParseIntError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		u128::from_str_radix(&s.replace('-', ""), 16).map(Self)
+	}
+}
+
+/// Persistent reference to an asset, for saved data: the GUID of its source file, and the label of assets that
+/// are part of it, like `8f4e3b2a-6c1d-4e5f-9a8b-7c6d5e4f3a2b#mesh0`.
+///
+/// Created with [`AssetServer::asset_ref`](super::AssetServer::asset_ref) and turned back into a handle with
+/// [`AssetServer::load_ref`](super::AssetServer::load_ref).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AssetRef {
+	pub guid: AssetGuid,
+	pub label: Option<String>,
+}
+
+impl fmt::Display for AssetRef {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &self.label {
+			Some(label) => write!(f, "{}#{label}", self.guid),
+			None => write!(f, "{}", self.guid),
+		}
+	}
+}
+
+impl FromStr for AssetRef {
+	type Err = std::num::ParseIntError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (guid, label) = match s.split_once('#') {
+			Some((guid, label)) => (guid, Some(label.to_string())),
+			None => (s, None),
+		};
+
+		Ok(Self {
+			guid: guid.parse()?,
+			label,
+		})
+	}
+}
+
+/// Import settings of an asset, passed to its loader.
+pub type ImportSettings = BTreeMap<String, String>;
+
+/// Contents of the `.meta` sidecar that lives next to every source asset.
+///
+/// The format is a plain text file:
+/// ```text
+/// guid = 8f4e3b2a-6c1d-4e5f-9a8b-7c6d5e4f3a2b
+///
+/// [settings]
+/// key = value
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssetMeta {
+	pub guid: AssetGuid,
+	pub settings: ImportSettings,
+}
+
+impl AssetMeta {
+	pub fn new() -> Self {
+		Self {
+			guid: AssetGuid::new_random(),
+			settings: ImportSettings::new(),
+		}
+	}
+
+	/// Path of the sidecar for the source asset at `path`, e.g. `image.exr.meta` for `image.exr`.
+	pub fn meta_path(path: &Path) -> PathBuf {
+		let mut meta_path = path.as_os_str().to_owned();
+		meta_path.push(".meta");
+		meta_path.into()
+	}
+
+	/// Reads the sidecar of the source asset at `path`.
+	pub fn read(path: &Path) -> io::Result<Self> {
+		std::fs::read_to_string(Self::meta_path(path))?.parse()
+	}
+
+	/// Reads the sidecar of the source asset at `path`, creating it when it does not exist yet.
+	pub fn load_or_create(path: &Path) -> io::Result<Self> {
+		match Self::read(path) {
+			Err(error) if error.kind() == io::ErrorKind::NotFound => {
+				let meta = Self::new();
+				std::fs::write(Self::meta_path(path), meta.to_string())?;
+				Ok(meta)
+			}
+			result => result,
+		}
+	}
+}
+
+impl Default for AssetMeta {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl fmt::Display for AssetMeta {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "guid = {}", self.guid)?;
+
+		if !self.settings.is_empty() {
+			writeln!(f)?;
+			writeln!(f, "[settings]")?;
+
+			for (key, value) in &self.settings {
+				writeln!(f, "{key} = {value}")?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+impl FromStr for AssetMeta {
+	type Err = io::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+		let mut guid = None;
+		let mut settings = ImportSettings::new();
+		let mut in_settings = false;
+
+		for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+			if line == "[settings]" {
+				in_settings = true;
+				continue;
+			}
+
+			let Some((key, value)) = line.split_once('=') else {
+				return Err(invalid(format!("Invalid line in meta file: {line}")));
+			};
+
+			let (key, value) = (key.trim(), value.trim());
+
+			if in_settings {
+				settings.insert(key.to_string(), value.to_string());
+			} else if key == "guid" {
+				guid = Some(
+					value
+						.parse()
+						.map_err(|_| invalid(format!("Invalid guid in meta file: {value}")))?,
+				);
+			}
+		}
+
+		Ok(Self {
+			guid: guid.ok_or_else(|| invalid("Meta file has no guid".to_string()))?,
+			settings,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn guid_round_trip() {
+		let guid = AssetGuid::new_random();
+		let text = guid.to_string();
+
+		assert_eq!(text.len(), 36);
+		assert_eq!(&text[14..15], "4", "Not a version 4 UUID");
+		assert_eq!(text.parse::<AssetGuid>().unwrap(), guid);
+	}
+
+	#[test]
+	fn meta_round_trip() {
+		let mut meta = AssetMeta::new();
+		meta.settings.insert("srgb".into(), "false".into());
+		meta.settings.insert("scale".into(), "0.01".into());
+
+		assert_eq!(meta.to_string().parse::<AssetMeta>().unwrap(), meta);
+	}
+}
@@ -0,0 +1,1079 @@
+use super::database::normalize_path;
+use super::loader::{Dependency, ErasedAssetLoader, LabeledAsset, LoadContext};
+use super::storage::{AnyAssets, Assets, BoxedAsset};
+use super::{
+	Asset, AssetCache, AssetDatabase, AssetError, AssetEvent, AssetGuid, AssetId, AssetLoader,
+	AssetMeta, AssetRef, ImportSettings, UntypedAssetId, Vfs,
+};
+
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Load progress of an asset.
+#[derive(Clone, Debug)]
+pub enum LoadState {
+	/// The asset is being loaded on the worker thread.
+	Loading,
+	/// The asset is loaded and available through [`AssetServer::get`].
+	Loaded,
+	/// The loader returned an error or panicked. See [`AssetServer::or_placeholder`] to show something anyway.
+	Failed(Arc<AssetError>),
+}
+
+/// Allocates asset ids. Shared with the loaders on the worker thread,
+/// so they can hand out handles for their dependencies and labeled assets.
+#[derive(Default)]
+pub(crate) struct AssetIndex {
+	next_id: UntypedAssetId,
+	ids: HashMap<PathBuf, UntypedAssetId>,
+}
+
+impl AssetIndex {
+	pub fn next_id(&mut self) -> UntypedAssetId {
+		let id = self.next_id;
+		self.next_id += 1;
+		id
+	}
+
+	/// Id of the asset at `path`, allocated when the path is seen for the first time.
+	pub fn id(&mut self, path: &Path) -> UntypedAssetId {
+		if let Some(&id) = self.ids.get(path) {
+			return id;
+		}
+
+		let id = self.next_id();
+		self.ids.insert(path.to_path_buf(), id);
+		id
+	}
+}
+
+/// Source file an asset was loaded from, kept around to reload it.
+struct Source {
+	path: PathBuf,
+	loader: Arc<dyn ErasedAssetLoader>,
+	settings: ImportSettings,
+	/// Modification times of the source file and its `.meta` sidecar.
+	modified: (Option<SystemTime>, Option<SystemTime>),
+}
+
+struct Watcher {
+	poll_interval: Duration,
+	last_poll: Instant,
+}
+
+struct LoadRequest {
+	id: UntypedAssetId,
+	path: PathBuf,
+	settings: ImportSettings,
+	loader: Arc<dyn ErasedAssetLoader>,
+	cache: Option<Arc<AssetCache>>,
+	vfs: Arc<Vfs>,
+	/// Whether to reload the dependents of this asset once it is loaded.
+	propagate: bool,
+}
+
+struct LoadedAsset {
+	asset: BoxedAsset,
+	labeled_assets: Vec<LabeledAsset>,
+	dependencies: Vec<Dependency>,
+}
+
+struct LoadResult {
+	id: UntypedAssetId,
+	propagate: bool,
+	result: Result<LoadedAsset, AssetError>,
+}
+
+pub struct AssetServer {
+	index: Arc<Mutex<AssetIndex>>,
+	/// Storage of every asset type, keyed by the type id of the asset.
+	storages: HashMap<TypeId, Box<dyn AnyAssets>>,
+	asset_types: HashMap<u64, TypeId>,
+	names: HashMap<u64, String>,
+	states: HashMap<u64, LoadState>,
+	guids: HashMap<u64, AssetGuid>,
+	database: AssetDatabase,
+	cache: Option<Arc<AssetCache>>,
+	vfs: Arc<Vfs>,
+	sources: HashMap<u64, Source>,
+	dependencies: HashMap<u64, Vec<u64>>,
+	dependents: HashMap<u64, HashSet<u64>>,
+	/// Labeled assets that were added by the loader of each source file.
+	labels: HashMap<u64, Vec<u64>>,
+	loaders: Vec<Arc<dyn ErasedAssetLoader>>,
+	placeholders: HashMap<TypeId, UntypedAssetId>,
+	watcher: Option<Watcher>,
+	requests: mpsc::Sender<LoadRequest>,
+	results: mpsc::Receiver<LoadResult>,
+}
+
+impl Default for AssetServer {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl AssetServer {
+	pub fn new() -> Self {
+		let index = Arc::new(Mutex::new(AssetIndex::default()));
+
+		let (requests, request_receiver) = mpsc::channel::<LoadRequest>();
+		let (result_sender, results) = mpsc::channel();
+
+		let worker_index = index.clone();
+
+		// The worker exits once the server, and with it the request sender, is dropped.
+		std::thread::Builder::new()
+			.name("Asset Loader".into())
+			.spawn(move || {
+				for request in request_receiver {
+					let importer = request.loader.importer();
+					let loader_name = importer.name;
+
+					let mut ctx = LoadContext::new(
+						request.path.clone(),
+						request.settings,
+						worker_index.clone(),
+						importer,
+						request.cache,
+						request.vfs,
+					);
+
+					// A panicking loader must not take down the worker, and with it all future loads.
+					let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+						request.loader.load(&mut ctx)
+					}))
+					.unwrap_or_else(|panic| Err(panic_message(panic).into()))
+					.map(|asset| LoadedAsset {
+						asset,
+						labeled_assets: ctx.labeled_assets,
+						dependencies: ctx.dependencies,
+					})
+					.map_err(|error| AssetError::new(&request.path, Some(loader_name), error));
+
+					if result_sender
+						.send(LoadResult {
+							id: request.id,
+							propagate: request.propagate,
+							result,
+						})
+						.is_err()
+					{
+						break;
+					}
+				}
+			})
+			.unwrap();
+
+		Self {
+			index,
+			storages: HashMap::new(),
+			asset_types: HashMap::new(),
+			names: HashMap::new(),
+			states: HashMap::new(),
+			guids: HashMap::new(),
+			database: AssetDatabase::default(),
+			cache: None,
+			vfs: Arc::new(Vfs::new()),
+			sources: HashMap::new(),
+			dependencies: HashMap::new(),
+			dependents: HashMap::new(),
+			labels: HashMap::new(),
+			loaders: Vec::new(),
+			placeholders: HashMap::new(),
+			watcher: None,
+			requests,
+			results,
+		}
+	}
+
+	pub fn register_loader<L: AssetLoader>(&mut self, loader: L) {
+		self.loaders.push(Arc::new(loader));
+	}
+
+	/// Sets the database used to resolve [`AssetGuid`]s to paths.
+	pub fn set_database(&mut self, database: AssetDatabase) {
+		self.database = database;
+	}
+
+	pub fn database(&self) -> &AssetDatabase {
+		&self.database
+	}
+
+	/// Sets the virtual file system that all asset paths are resolved through.
+	///
+	/// Assets that are already loaded keep reading from the previous one when they are reloaded.
+	pub fn set_vfs(&mut self, vfs: Vfs) {
+		self.vfs = Arc::new(vfs);
+	}
+
+	pub fn vfs(&self) -> &Vfs {
+		&self.vfs
+	}
+
+	/// Sets the cache loaders store their cooked data in, see [`LoadContext::cooked`].
+	pub fn set_cache(&mut self, cache: AssetCache) {
+		self.cache = Some(Arc::new(cache));
+	}
+
+	/// Reloads assets whose source file changed, checking modification times every `poll_interval`.
+	///
+	/// Assets that depend on a reloaded asset are reloaded as well.
+	pub fn watch_for_changes(&mut self, poll_interval: Duration) {
+		self.watcher = Some(Watcher {
+			poll_interval,
+			last_poll: Instant::now(),
+		});
+	}
+
+	pub fn insert<T: Asset>(&mut self, asset: T) -> AssetId<T> {
+		let handle = AssetId::new(self.index.lock().unwrap().next_id());
+		self.store(handle.id, BoxedAsset::new(asset));
+		handle
+	}
+
+	/// Sets the asset that [`AssetServer::or_placeholder`] substitutes for assets of type `T` that failed to load.
+	pub fn set_placeholder<T: Asset>(&mut self, asset: T) -> AssetId<T> {
+		let handle = self.insert(asset);
+		self.set_name(
+			&handle,
+			format!("Placeholder {}", std::any::type_name::<T>()),
+		);
+		self.placeholders.insert(TypeId::of::<T>(), handle.id);
+		handle
+	}
+
+	/// Returns the placeholder for `T` if the asset failed to load and a placeholder is set,
+	/// so that a missing texture or mesh shows up as such instead of leaving a hole.
+	/// Returns `handle` otherwise.
+	pub fn or_placeholder<T: Asset>(&self, handle: &AssetId<T>) -> AssetId<T> {
+		match (
+			self.states.get(&handle.id),
+			self.placeholders.get(&TypeId::of::<T>()),
+		) {
+			(Some(LoadState::Failed(_)), Some(&placeholder)) => AssetId::new(placeholder),
+			_ => *handle,
+		}
+	}
+
+	/// Removes an asset, returning it if it was loaded.
+	///
+	/// Labeled assets of the asset's source file are removed as well. Loading the path again starts a new load.
+	pub fn remove<T: Asset>(&mut self, handle: &AssetId<T>) -> Option<T> {
+		let asset = self.remove_untyped(handle.id)?;
+		asset.downcast().ok().map(|asset| *asset)
+	}
+
+	/// Starts loading the asset at `path` on the worker thread and returns its handle right away.
+	///
+	/// The asset becomes available after the [`AssetServer::update`] that follows the end of the load.
+	/// Dependencies declared by its loader are loaded afterwards, see [`AssetServer::recursive_load_state`].
+	/// Loading the same path twice returns the same handle.
+	///
+	/// Source files get a `.meta` sidecar with a persistent [`AssetGuid`] on their first load.
+	pub fn load<T: Asset>(&mut self, path: impl AsRef<Path>) -> AssetId<T> {
+		let path = normalize_path(path.as_ref());
+		let id = self.index.lock().unwrap().id(&path);
+
+		if !self.states.contains_key(&id) {
+			self.names.insert(id, path.display().to_string());
+			self.start_load(id, path, TypeId::of::<T>());
+		}
+
+		AssetId::new(id)
+	}
+
+	/// Loads the asset with `guid`, or returns `None` if the database doesn't know it.
+	pub fn load_guid<T: Asset>(&mut self, guid: AssetGuid) -> Option<AssetId<T>> {
+		let path = self.database.path(guid)?.to_path_buf();
+		Some(self.load(path))
+	}
+
+	/// Persistent identifier of an asset loaded from a source file.
+	pub fn guid<T: Asset>(&self, handle: &AssetId<T>) -> Option<AssetGuid> {
+		self.guids.get(&handle.id).copied()
+	}
+
+	/// Persistent reference to an asset loaded from a source file or added by the loader of one, to save in
+	/// place of its handle.
+	pub fn asset_ref<T: Asset>(&self, handle: &AssetId<T>) -> Option<AssetRef> {
+		if let Some(&guid) = self.guids.get(&handle.id) {
+			return Some(AssetRef { guid, label: None });
+		}
+
+		let (source, _) = self
+			.labels
+			.iter()
+			.find(|(_, labels)| labels.contains(&handle.id))?;
+		let name = self.names.get(&handle.id)?;
+		let label = name
+			.strip_prefix(self.names.get(source)?.as_str())?
+			.strip_prefix('#')?;
+
+		Some(AssetRef {
+			guid: *self.guids.get(source)?,
+			label: Some(label.to_string()),
+		})
+	}
+
+	/// Loads the asset of a persistent reference, or returns `None` if the database doesn't know its source file.
+	///
+	/// For labeled assets, the source file is loaded with the first loader for its extension, and the asset
+	/// becomes available once its loader added it.
+	pub fn load_ref<T: Asset>(&mut self, reference: &AssetRef) -> Option<AssetId<T>> {
+		let path = self.database.path(reference.guid)?.to_path_buf();
+
+		let Some(label) = &reference.label else {
+			return Some(self.load(path));
+		};
+
+		let source = self.index.lock().unwrap().id(&path);
+		if !self.states.contains_key(&source) {
+			let extension = path.extension()?.to_str()?;
+			let asset_type = self
+				.loaders
+				.iter()
+				.find(|loader| {
+					loader
+						.extensions()
+						.iter()
+						.any(|e| e.eq_ignore_ascii_case(extension))
+				})?
+				.asset_type();
+
+			self.names.insert(source, path.display().to_string());
+			self.start_load(source, path.clone(), asset_type);
+		}
+
+		let mut labeled = path.into_os_string();
+		labeled.push("#");
+		labeled.push(label);
+
+		Some(AssetId::new(
+			self.index.lock().unwrap().id(Path::new(&labeled)),
+		))
+	}
+
+	/// Applies all loads finished by the worker thread since the previous update
+	/// and starts reloading assets whose source file changed.
+	/// Call this once per frame from the main thread.
+	pub fn update(&mut self) {
+		for storage in self.storages.values_mut() {
+			storage.clear_events();
+		}
+
+		self.poll_for_changes();
+
+		while let Ok(LoadResult {
+			id,
+			propagate,
+			result,
+		}) = self.results.try_recv()
+		{
+			match result {
+				Ok(loaded) => {
+					self.store(id, loaded.asset);
+					self.set_labels(id, loaded.labeled_assets);
+
+					self.set_dependencies(id, loaded.dependencies);
+
+					if propagate {
+						self.reload_dependents(id);
+					}
+				}
+				Err(error) if self.asset_types.contains_key(&id) => {
+					// Keep the previous version around when a reload fails.
+					log::error!("{error}");
+				}
+				Err(error) => self.fail(id, error),
+			}
+		}
+	}
+
+	/// Events for assets of type `T` that happened since the previous [`AssetServer::update`].
+	pub fn events<T: Asset>(&self) -> &[AssetEvent<T>] {
+		self.storage::<T>()
+			.map_or(&[], |storage| storage.events.as_slice())
+	}
+
+	/// All loaded assets of type `T`, in no particular order.
+	pub fn iter<T: Asset>(&self) -> impl Iterator<Item = (AssetId<T>, &T)> {
+		self.storage::<T>()
+			.into_iter()
+			.flat_map(|storage| storage.assets.iter())
+			.map(|(&id, asset)| (AssetId::new(id), asset))
+	}
+
+	/// Debug name of an asset, which is the path for assets loaded from a file.
+	pub fn name<T: Asset>(&self, handle: &AssetId<T>) -> Option<&str> {
+		self.names.get(&handle.id).map(String::as_str)
+	}
+
+	pub fn set_name<T: Asset>(&mut self, handle: &AssetId<T>, name: impl Into<String>) {
+		self.names.insert(handle.id, name.into());
+	}
+
+	pub fn load_state<T: Asset>(&self, handle: &AssetId<T>) -> LoadState {
+		self.untyped_load_state(handle.id)
+	}
+
+	/// Load state of an asset together with all of its dependencies, recursively.
+	///
+	/// This is [`LoadState::Loaded`] once everything is loaded, or the first failure that is found.
+	pub fn recursive_load_state<T: Asset>(&self, handle: &AssetId<T>) -> LoadState {
+		let mut state = LoadState::Loaded;
+		let mut visited = HashSet::new();
+		let mut stack = vec![handle.id];
+
+		while let Some(id) = stack.pop() {
+			if !visited.insert(id) {
+				continue;
+			}
+
+			match self.untyped_load_state(id) {
+				LoadState::Loading => state = LoadState::Loading,
+				LoadState::Loaded => stack.extend(self.dependencies.get(&id).into_iter().flatten()),
+				failed => return failed,
+			}
+		}
+
+		state
+	}
+
+	pub fn is_loaded_with_dependencies<T: Asset>(&self, handle: &AssetId<T>) -> bool {
+		matches!(self.recursive_load_state(handle), LoadState::Loaded)
+	}
+
+	pub fn get<T: Asset>(&self, handle: &AssetId<T>) -> Option<&T> {
+		self.storage::<T>()?.assets.get(&handle.id)
+	}
+
+	/// Mutable access to an asset, which is reported as an [`AssetEvent::Modified`].
+	pub fn get_mut<T: Asset>(&mut self, handle: &AssetId<T>) -> Option<&mut T> {
+		let storage = self
+			.storages
+			.get_mut(&TypeId::of::<T>())?
+			.as_any_mut()
+			.downcast_mut::<Assets<T>>()?;
+
+		let asset = storage.assets.get_mut(&handle.id)?;
+		storage.events.push(AssetEvent::Modified(*handle));
+		Some(asset)
+	}
+
+	fn storage<T: Asset>(&self) -> Option<&Assets<T>> {
+		self.storages
+			.get(&TypeId::of::<T>())?
+			.as_any()
+			.downcast_ref::<Assets<T>>()
+	}
+
+	fn untyped_load_state(&self, id: UntypedAssetId) -> LoadState {
+		self.states.get(&id).cloned().unwrap_or(LoadState::Loading)
+	}
+
+	fn start_load(&mut self, id: UntypedAssetId, path: PathBuf, asset_type: TypeId) {
+		let Some(loader) = self.find_loader(&path, asset_type) else {
+			let error = AssetError::new(&path, None, "No loader registered for this file".into());
+			self.fail(id, error);
+			return;
+		};
+
+		let settings = match self.read_meta(&path) {
+			Some(Ok(meta)) => {
+				self.guids.insert(id, meta.guid);
+				self.database.insert(meta.guid, &path);
+				meta.settings
+			}
+			Some(Err(error)) => {
+				self.fail(id, AssetError::new(&path, None, error.into()));
+				return;
+			}
+			None => ImportSettings::new(),
+		};
+
+		self.states.insert(id, LoadState::Loading);
+		self.sources.insert(
+			id,
+			Source {
+				modified: source_modified_time(&self.vfs, &path),
+				path,
+				loader,
+				settings,
+			},
+		);
+
+		self.reload(id, false);
+	}
+
+	/// Sends the asset to the worker thread to run its loader (again).
+	fn reload(&self, id: UntypedAssetId, propagate: bool) {
+		let Some(source) = self.sources.get(&id) else {
+			return;
+		};
+
+		self.requests
+			.send(LoadRequest {
+				id,
+				path: source.path.clone(),
+				settings: source.settings.clone(),
+				loader: source.loader.clone(),
+				cache: self.cache.clone(),
+				vfs: self.vfs.clone(),
+				propagate,
+			})
+			.unwrap();
+	}
+
+	/// Reloads all assets that depend on `id`, directly or indirectly.
+	fn reload_dependents(&self, id: UntypedAssetId) {
+		let mut visited = HashSet::from([id]);
+		let mut stack = vec![id];
+
+		while let Some(id) = stack.pop() {
+			for &dependent in self.dependents.get(&id).into_iter().flatten() {
+				if visited.insert(dependent) {
+					stack.push(dependent);
+				}
+			}
+		}
+
+		visited.remove(&id);
+
+		// These don't propagate any further, which also keeps cyclic dependencies from reloading forever.
+		for dependent in visited {
+			self.reload(dependent, false);
+		}
+	}
+
+	fn store(&mut self, id: UntypedAssetId, asset: BoxedAsset) {
+		self.storages
+			.entry(asset.asset_type)
+			.or_insert_with(asset.new_storage)
+			.insert(id, asset.asset);
+
+		self.asset_types.insert(id, asset.asset_type);
+		self.states.insert(id, LoadState::Loaded);
+	}
+
+	/// Stores the labeled assets of a source file, removing those that are no longer part of it.
+	fn set_labels(&mut self, id: UntypedAssetId, labeled_assets: Vec<LabeledAsset>) {
+		let ids: Vec<_> = labeled_assets.iter().map(|labeled| labeled.id).collect();
+
+		for old in self.labels.remove(&id).into_iter().flatten() {
+			if !ids.contains(&old) {
+				self.remove_untyped(old);
+			}
+		}
+
+		for labeled in labeled_assets {
+			self.names.insert(labeled.id, labeled.name);
+			self.store(labeled.id, labeled.asset);
+		}
+
+		self.labels.insert(id, ids);
+	}
+
+	fn remove_untyped(&mut self, id: UntypedAssetId) -> Option<Box<dyn Any + Send + Sync>> {
+		for labeled in self.labels.remove(&id).into_iter().flatten() {
+			self.remove_untyped(labeled);
+		}
+
+		self.set_dependencies(id, Vec::new());
+		self.dependencies.remove(&id);
+		self.sources.remove(&id);
+		self.states.remove(&id);
+		self.names.remove(&id);
+
+		let asset_type = self.asset_types.remove(&id)?;
+		self.storages.get_mut(&asset_type)?.remove(id)
+	}
+
+	fn set_dependencies(&mut self, id: UntypedAssetId, dependencies: Vec<Dependency>) {
+		for old in self.dependencies.remove(&id).into_iter().flatten() {
+			if let Some(dependents) = self.dependents.get_mut(&old) {
+				dependents.remove(&id);
+			}
+		}
+
+		let mut ids = Vec::with_capacity(dependencies.len());
+
+		for dependency in dependencies {
+			self.dependents.entry(dependency.id).or_default().insert(id);
+			ids.push(dependency.id);
+
+			if !self.states.contains_key(&dependency.id) {
+				self.start_load(dependency.id, dependency.path, dependency.asset_type);
+			}
+		}
+
+		self.dependencies.insert(id, ids);
+	}
+
+	fn fail(&mut self, id: UntypedAssetId, error: AssetError) {
+		log::error!("{error}");
+		self.states.insert(id, LoadState::Failed(Arc::new(error)));
+	}
+
+	fn poll_for_changes(&mut self) {
+		let Some(watcher) = &mut self.watcher else {
+			return;
+		};
+
+		if watcher.last_poll.elapsed() < watcher.poll_interval {
+			return;
+		}
+
+		watcher.last_poll = Instant::now();
+
+		let mut changed = Vec::new();
+
+		for (&id, source) in &mut self.sources {
+			let modified = source_modified_time(&self.vfs, &source.path);
+
+			if modified != source.modified {
+				source.modified = modified;
+
+				// Changed import settings trigger a reimport as well.
+				let meta = self
+					.vfs
+					.native_path(&source.path)
+					.map(|path| AssetMeta::read(&path));
+				if let Some(Ok(meta)) = meta {
+					source.settings = meta.settings;
+				}
+
+				changed.push(id);
+			}
+		}
+
+		for id in changed {
+			self.reload(id, true);
+		}
+	}
+
+	/// Reads the `.meta` sidecar of a source file, or returns `None` if it has none.
+	///
+	/// Loose files get a sidecar on their first load. Packs are read-only, so files in a pack only have one
+	/// when it was packed along with them. Missing files don't get a sidecar, the loader reports them instead.
+	fn read_meta(&self, path: &Path) -> Option<io::Result<AssetMeta>> {
+		if !self.vfs.exists(path) {
+			return None;
+		}
+
+		if let Some(native_path) = self.vfs.native_path(path) {
+			return Some(AssetMeta::load_or_create(&native_path));
+		}
+
+		let bytes = self.vfs.read(AssetMeta::meta_path(path)).ok()?;
+
+		Some(
+			String::from_utf8(bytes)
+				.map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+				.and_then(|text| text.parse()),
+		)
+	}
+
+	fn find_loader(&self, path: &Path, asset_type: TypeId) -> Option<Arc<dyn ErasedAssetLoader>> {
+		let extension = path.extension()?.to_str()?;
+
+		self.loaders
+			.iter()
+			.find(|loader| {
+				loader.asset_type() == asset_type
+					&& loader
+						.extensions()
+						.iter()
+						.any(|e| e.eq_ignore_ascii_case(extension))
+			})
+			.cloned()
+	}
+}
+
+/// Error message for a loader that panicked, with the panic payload if it is a string.
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+	let message = panic
+		.downcast_ref::<&str>()
+		.copied()
+		.or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+		.unwrap_or("unknown cause");
+
+	format!("Loader panicked: {message}")
+}
+
+/// Modification times of a source file and its `.meta` sidecar, `None` for files inside a pack.
+fn source_modified_time(vfs: &Vfs, path: &Path) -> (Option<SystemTime>, Option<SystemTime>) {
+	(vfs.modified(path), vfs.modified(AssetMeta::meta_path(path)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::LoadError;
+
+	struct Text(String);
+
+	impl Asset for Text {}
+
+	struct TextLoader;
+
+	impl AssetLoader for TextLoader {
+		type Asset = Text;
+
+		fn extensions(&self) -> &[&str] {
+			&["txt"]
+		}
+
+		fn load(&self, ctx: &mut LoadContext) -> Result<Text, LoadError> {
+			Ok(Text(String::from_utf8(ctx.read()?)?))
+		}
+	}
+
+	struct Include(AssetId<Text>);
+
+	impl Asset for Include {}
+
+	struct IncludeLoader;
+
+	impl AssetLoader for IncludeLoader {
+		type Asset = Include;
+
+		fn extensions(&self) -> &[&str] {
+			&["inc"]
+		}
+
+		fn load(&self, ctx: &mut LoadContext) -> Result<Include, LoadError> {
+			let path = String::from_utf8(ctx.read()?)?;
+			Ok(Include(ctx.load(path.trim())))
+		}
+	}
+
+	static COOK_COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+	/// Upper-cases text files, counting how often it had to cook. The cooked data depends on a `.extra` file next
+	/// to the source file.
+	struct UpperCaseLoader;
+
+	impl AssetLoader for UpperCaseLoader {
+		type Asset = Text;
+
+		fn extensions(&self) -> &[&str] {
+			&["upper"]
+		}
+
+		fn load(&self, ctx: &mut LoadContext) -> Result<Text, LoadError> {
+			ctx.cook_dependency(ctx.path().with_extension("extra").file_name().unwrap());
+
+			let bytes = ctx.cooked(|ctx| {
+				COOK_COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+				Ok(ctx.read()?.to_ascii_uppercase())
+			})?;
+
+			Ok(Text(String::from_utf8(bytes)?))
+		}
+	}
+
+	/// Adds every line of a text file as a labeled asset.
+	struct LinesLoader;
+
+	impl AssetLoader for LinesLoader {
+		type Asset = Text;
+
+		fn extensions(&self) -> &[&str] {
+			&["lines"]
+		}
+
+		fn load(&self, ctx: &mut LoadContext) -> Result<Text, LoadError> {
+			let text = String::from_utf8(ctx.read()?)?;
+
+			for (i, line) in text.lines().enumerate() {
+				ctx.add_labeled_asset(&format!("line{i}"), Text(line.to_string()));
+			}
+
+			Ok(Text(text))
+		}
+	}
+
+	fn wait_until_done<T: Asset>(assets: &mut AssetServer, handle: &AssetId<T>) -> LoadState {
+		loop {
+			assets.update();
+			match assets.load_state(handle) {
+				LoadState::Loading => std::thread::yield_now(),
+				state => return state,
+			}
+		}
+	}
+
+	#[test]
+	fn load() {
+		let path = std::env::temp_dir().join("asset_server_load.txt");
+		std::fs::write(&path, "hello").unwrap();
+
+		let mut assets = AssetServer::new();
+		assets.register_loader(TextLoader);
+
+		let handle = assets.load::<Text>(&path);
+		assert_eq!(handle.id(), assets.load::<Text>(&path).id());
+
+		let guid = assets.guid(&handle).unwrap();
+		assert_eq!(assets.load_guid::<Text>(guid).unwrap().id(), handle.id());
+
+		let state = wait_until_done(&mut assets, &handle);
+		assert!(matches!(state, LoadState::Loaded));
+		assert_eq!(assets.get(&handle).unwrap().0, "hello");
+	}
+
+	#[test]
+	fn asset_refs() {
+		let path = std::env::temp_dir().join("asset_server_refs.lines");
+		std::fs::write(&path, "first\nsecond").unwrap();
+
+		let mut assets = AssetServer::new();
+		assets.register_loader(LinesLoader);
+		let handle = assets.load::<Text>(&path);
+		wait_until_done(&mut assets, &handle);
+
+		let second = assets
+			.iter::<Text>()
+			.find(|(_, text)| text.0 == "second")
+			.unwrap()
+			.0;
+		let reference = assets.asset_ref(&second).unwrap();
+		assert_eq!(reference.label.as_deref(), Some("line1"));
+		assert_eq!(
+			reference.to_string().parse::<AssetRef>().unwrap(),
+			reference
+		);
+
+		// The reference is valid in the next run, where loading something else first gives the asset another
+		// handle.
+		let mut database = AssetDatabase::default();
+		database.insert(reference.guid, &path);
+
+		let mut assets = AssetServer::new();
+		assets.register_loader(LinesLoader);
+		assets.load::<Text>(std::env::temp_dir().join("asset_server_refs_other.lines"));
+		assets.set_database(database);
+
+		let second = assets.load_ref::<Text>(&reference).unwrap();
+		while assets.get(&second).is_none() {
+			assets.update();
+			std::thread::yield_now();
+		}
+		assert_eq!(assets.get(&second).unwrap().0, "second");
+	}
+
+	#[test]
+	fn load_failed() {
+		let mut assets = AssetServer::new();
+		assets.register_loader(TextLoader);
+
+		let missing = assets.load::<Text>(std::env::temp_dir().join("asset_server_missing.txt"));
+		let unknown = assets.load::<Text>("unknown.bin");
+
+		let LoadState::Failed(error) = wait_until_done(&mut assets, &missing) else {
+			panic!("Missing file loaded");
+		};
+		assert_eq!(error.loader(), Some(std::any::type_name::<TextLoader>()));
+		assert!(error.path().ends_with("asset_server_missing.txt"));
+
+		let LoadState::Failed(error) = wait_until_done(&mut assets, &unknown) else {
+			panic!("File without loader loaded");
+		};
+		assert_eq!(error.loader(), None);
+
+		assert!(assets.get(&missing).is_none());
+
+		let placeholder = assets.set_placeholder(Text("placeholder".into()));
+		assert_eq!(assets.or_placeholder(&missing), placeholder);
+	}
+
+	#[test]
+	fn loader_panic() {
+		struct PanicLoader;
+
+		impl AssetLoader for PanicLoader {
+			type Asset = Text;
+
+			fn extensions(&self) -> &[&str] {
+				&["panic"]
+			}
+
+			fn load(&self, _ctx: &mut LoadContext) -> Result<Text, LoadError> {
+				panic!("bad attribute");
+			}
+		}
+
+		let mut assets = AssetServer::new();
+		assets.register_loader(PanicLoader);
+		assets.register_loader(TextLoader);
+
+		let panicked = assets.load::<Text>("asset_server_loader.panic");
+		let LoadState::Failed(error) = wait_until_done(&mut assets, &panicked) else {
+			panic!("Panicking loader succeeded");
+		};
+		assert!(
+			error
+				.to_string()
+				.ends_with("Loader panicked: bad attribute")
+		);
+
+		// The worker thread survives the panic.
+		let path = std::env::temp_dir().join("asset_server_after_panic.txt");
+		std::fs::write(&path, "alive").unwrap();
+
+		let handle = assets.load::<Text>(&path);
+		wait_until_done(&mut assets, &handle);
+		assert_eq!(assets.get(&handle).unwrap().0, "alive");
+	}
+
+	#[test]
+	fn hot_reload() {
+		let path = std::env::temp_dir().join("asset_server_hot_reload.txt");
+		std::fs::write(&path, "before").unwrap();
+
+		let mut assets = AssetServer::new();
+		assets.register_loader(TextLoader);
+		assets.watch_for_changes(Duration::ZERO);
+
+		let handle = assets.load::<Text>(&path);
+		wait_until_done(&mut assets, &handle);
+		assert_eq!(assets.events::<Text>(), [AssetEvent::Added(handle)]);
+		assets.update();
+
+		std::fs::write(&path, "after").unwrap();
+
+		// Make sure the modification time differs, even on file systems with a coarse resolution.
+		let file = std::fs::File::options().write(true).open(&path).unwrap();
+		file.set_modified(SystemTime::now() + Duration::from_secs(10))
+			.unwrap();
+
+		while assets.events::<Text>().is_empty() {
+			assets.update();
+		}
+
+		assert_eq!(assets.events::<Text>(), [AssetEvent::Modified(handle)]);
+		assert_eq!(assets.get(&handle).unwrap().0, "after");
+	}
+
+	#[test]
+	fn dependencies() {
+		let text_path = std::env::temp_dir().join("asset_server_dependency.txt");
+		let include_path = std::env::temp_dir().join("asset_server_dependency.inc");
+		std::fs::write(&text_path, "before").unwrap();
+		std::fs::write(&include_path, text_path.to_str().unwrap()).unwrap();
+
+		let mut assets = AssetServer::new();
+		assets.register_loader(TextLoader);
+		assets.register_loader(IncludeLoader);
+		assets.watch_for_changes(Duration::ZERO);
+
+		let include = assets.load::<Include>(&include_path);
+
+		while !assets.is_loaded_with_dependencies(&include) {
+			assets.update();
+		}
+
+		let text = assets.get(&include).unwrap().0;
+		assert_eq!(assets.get(&text).unwrap().0, "before");
+
+		std::fs::write(&text_path, "after").unwrap();
+		let file = std::fs::File::options()
+			.write(true)
+			.open(&text_path)
+			.unwrap();
+		file.set_modified(SystemTime::now() + Duration::from_secs(10))
+			.unwrap();
+
+		// The reload of the dependency propagates to the asset that depends on it.
+		let mut text_modified = false;
+		loop {
+			assets.update();
+			text_modified |= assets.events().contains(&AssetEvent::Modified(text));
+
+			if assets.events().contains(&AssetEvent::Modified(include)) {
+				break;
+			}
+		}
+
+		assert!(text_modified);
+		assert_eq!(assets.get(&text).unwrap().0, "after");
+	}
+
+	#[test]
+	fn typed_storage() {
+		let mut assets = AssetServer::new();
+
+		let a = assets.insert(Text("a".into()));
+		let b = assets.insert(Text("b".into()));
+		assets.set_name(&a, "first");
+
+		assert_eq!(
+			assets.events::<Text>(),
+			[AssetEvent::Added(a), AssetEvent::Added(b)]
+		);
+		assert!(assets.events::<Include>().is_empty());
+		assert_eq!(assets.iter::<Text>().count(), 2);
+		assert_eq!(assets.iter::<Include>().count(), 0);
+
+		assets.update();
+		assert!(assets.events::<Text>().is_empty());
+
+		assert_eq!(assets.remove(&b).unwrap().0, "b");
+		assert_eq!(assets.events::<Text>(), [AssetEvent::Removed(b)]);
+		assert!(assets.get(&b).is_none());
+
+		let names: Vec<_> = assets
+			.iter::<Text>()
+			.map(|(handle, _)| assets.name(&handle))
+			.collect();
+		assert_eq!(names, [Some("first")]);
+	}
+
+	#[test]
+	fn cooked() {
+		let path = std::env::temp_dir().join("asset_server_cooked.upper");
+		let moved_path = std::env::temp_dir().join("asset_server_cooked_moved.upper");
+		let extra_path = path.with_extension("extra");
+		let cache_dir = std::env::temp_dir().join("asset_server_cooked_cache");
+		let _ = std::fs::remove_dir_all(&cache_dir);
+		std::fs::write(&path, "cooked").unwrap();
+		std::fs::write(&moved_path, "cooked").unwrap();
+		std::fs::write(&extra_path, "").unwrap();
+
+		let load = |path: &Path| {
+			let mut assets = AssetServer::new();
+			assets.register_loader(UpperCaseLoader);
+			assets.set_cache(AssetCache::new(&cache_dir));
+
+			let handle = assets.load::<Text>(path);
+			wait_until_done(&mut assets, &handle);
+			assert_eq!(assets.get(&handle).unwrap().0, "COOKED");
+		};
+		let cook_count = || COOK_COUNT.load(std::sync::atomic::Ordering::Relaxed);
+
+		// The second server finds the blob cooked by the first one.
+		load(&path);
+		load(&path);
+		assert_eq!(cook_count(), 1);
+
+		// The same content at another path is cooked again.
+		load(&moved_path);
+		assert_eq!(cook_count(), 2);
+
+		// So is a source whose dependency changed.
+		let file = std::fs::File::options()
+			.write(true)
+			.open(&extra_path)
+			.unwrap();
+		file.set_modified(SystemTime::now() + Duration::from_secs(10))
+			.unwrap();
+		load(&path);
+		assert_eq!(cook_count(), 3);
+	}
+}
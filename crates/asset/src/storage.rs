@@ -0,0 +1,125 @@
+use super::{Asset, AssetId, UntypedAssetId};
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Change to an asset of type `T`, reported for the frame in which it happened. See [`AssetServer::events`](super::AssetServer::events).
+pub enum AssetEvent<T> {
+	/// The asset finished loading or was inserted.
+	Added(AssetId<T>),
+	/// The asset was reloaded or mutably accessed.
+	Modified(AssetId<T>),
+	/// The asset was removed, or is no longer part of its reloaded source file.
+	Removed(AssetId<T>),
+}
+
+impl<T: Asset> AssetEvent<T> {
+	/// Handle of the asset the event is about.
+	pub fn handle(&self) -> AssetId<T> {
+		match *self {
+			Self::Added(handle) | Self::Modified(handle) | Self::Removed(handle) => handle,
+		}
+	}
+}
+
+impl<T: Asset> Clone for AssetEvent<T> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<T: Asset> Copy for AssetEvent<T> {}
+
+impl<T: Asset> PartialEq for AssetEvent<T> {
+	fn eq(&self, other: &Self) -> bool {
+		std::mem::discriminant(self) == std::mem::discriminant(other)
+			&& self.handle() == other.handle()
+	}
+}
+
+impl<T: Asset> Eq for AssetEvent<T> {}
+
+impl<T: Asset> fmt::Debug for AssetEvent<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Added(handle) => f.debug_tuple("Added").field(handle).finish(),
+			Self::Modified(handle) => f.debug_tuple("Modified").field(handle).finish(),
+			Self::Removed(handle) => f.debug_tuple("Removed").field(handle).finish(),
+		}
+	}
+}
+
+/// All assets of type `T`, together with the events of the current frame.
+pub(crate) struct Assets<T> {
+	pub assets: HashMap<UntypedAssetId, T>,
+	pub events: Vec<AssetEvent<T>>,
+}
+
+impl<T> Default for Assets<T> {
+	fn default() -> Self {
+		Self {
+			assets: HashMap::new(),
+			events: Vec::new(),
+		}
+	}
+}
+
+/// Type erased [`Assets`], for asset types that are only known at runtime.
+pub(crate) trait AnyAssets: Send + Sync {
+	/// Inserts or replaces an asset, which must be of the storage's type.
+	fn insert(&mut self, id: UntypedAssetId, asset: Box<dyn Any + Send + Sync>);
+	fn remove(&mut self, id: UntypedAssetId) -> Option<Box<dyn Any + Send + Sync>>;
+	fn clear_events(&mut self);
+	fn as_any(&self) -> &dyn Any;
+	fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Asset> AnyAssets for Assets<T> {
+	fn insert(&mut self, id: UntypedAssetId, asset: Box<dyn Any + Send + Sync>) {
+		let asset = *asset.downcast::<T>().unwrap();
+		let handle = AssetId::new(id);
+
+		if self.assets.insert(id, asset).is_some() {
+			self.events.push(AssetEvent::Modified(handle));
+		} else {
+			self.events.push(AssetEvent::Added(handle));
+		}
+	}
+
+	fn remove(&mut self, id: UntypedAssetId) -> Option<Box<dyn Any + Send + Sync>> {
+		let asset = self.assets.remove(&id)?;
+		self.events.push(AssetEvent::Removed(AssetId::new(id)));
+		Some(Box::new(asset))
+	}
+
+	fn clear_events(&mut self) {
+		self.events.clear();
+	}
+
+	fn as_any(&self) -> &dyn Any {
+		self
+	}
+
+	fn as_any_mut(&mut self) -> &mut dyn Any {
+		self
+	}
+}
+
+/// Asset of any type, as it is passed from the loader thread to the server.
+pub(crate) struct BoxedAsset {
+	pub asset: Box<dyn Any + Send + Sync>,
+	pub asset_type: TypeId,
+	/// Creates the storage for the asset's type, the first time an asset of that type is stored.
+	pub new_storage: fn() -> Box<dyn AnyAssets>,
+}
+
+impl BoxedAsset {
+	pub fn new<T: Asset>(asset: T) -> Self {
+		Self {
+			asset: Box::new(asset),
+			asset_type: TypeId::of::<T>(),
+			new_storage: || Box::new(Assets::<T>::default()),
+		}
+	}
+}
@@ -0,0 +1,391 @@
+use super::{BlobReader, BlobWriter};
+
+use std::collections::HashMap;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Storage behind a mount point of the [`Vfs`].
+///
+/// Paths passed to a source are relative to its mount point and use `/` as separator.
+pub trait MountSource: Send + Sync {
+	fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+	fn exists(&self, path: &Path) -> bool;
+
+	/// Path of the file on the native file system, if it is stored as a loose file.
+	fn native_path(&self, _path: &Path) -> Option<PathBuf> {
+		None
+	}
+}
+
+/// Virtual file system that maps paths like `assets://usd/scene.usdc` to mounted sources.
+///
+/// Paths without a `scheme://` prefix are read from the native file system as is.
+/// When mount points overlap, the longest one wins, so `engine://shaders` takes precedence over `engine://`.
+#[derive(Clone, Default)]
+pub struct Vfs {
+	mounts: Vec<Mount>,
+}
+
+#[derive(Clone)]
+struct Mount {
+	point: String,
+	source: Arc<dyn MountSource>,
+}
+
+impl Vfs {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Mounts `source` at `point`, e.g. `assets://` or `engine://shaders`.
+	pub fn mount(&mut self, point: &str, source: impl MountSource + 'static) {
+		let point = match point.strip_suffix('/') {
+			Some(trimmed) if !point.ends_with("://") => trimmed,
+			_ => point,
+		};
+
+		self.mounts.retain(|mount| mount.point != point);
+		self.mounts.push(Mount {
+			point: point.to_string(),
+			source: Arc::new(source),
+		});
+
+		self.mounts
+			.sort_by_key(|mount| std::cmp::Reverse(mount.point.len()));
+	}
+
+	pub fn read(&self, path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+		let path = path.as_ref();
+
+		match self.find(path) {
+			Some((source, relative)) => source.read(&relative),
+			None if is_virtual(path) => Err(not_mounted(path)),
+			None => std::fs::read(path),
+		}
+	}
+
+	pub fn exists(&self, path: impl AsRef<Path>) -> bool {
+		let path = path.as_ref();
+
+		match self.find(path) {
+			Some((source, relative)) => source.exists(&relative),
+			None => !is_virtual(path) && path.is_file(),
+		}
+	}
+
+	/// Path on the native file system, for virtual paths that are mounted from a directory.
+	///
+	/// This is for code that can only work with native files, like third-party libraries that open files themselves.
+	pub fn native_path(&self, path: impl AsRef<Path>) -> Option<PathBuf> {
+		let path = path.as_ref();
+
+		match self.find(path) {
+			Some((source, relative)) => source.native_path(&relative),
+			None if is_virtual(path) => None,
+			None => Some(path.to_path_buf()),
+		}
+	}
+
+	/// Modification time of the file, used for hot reloading. `None` for files inside a pack.
+	pub fn modified(&self, path: impl AsRef<Path>) -> Option<SystemTime> {
+		let path = self.native_path(path)?;
+		std::fs::metadata(path)
+			.and_then(|metadata| metadata.modified())
+			.ok()
+	}
+
+	fn find(&self, path: &Path) -> Option<(&dyn MountSource, PathBuf)> {
+		let path = path.to_str()?;
+
+		self.mounts.iter().find_map(|mount| {
+			let rest = path.strip_prefix(&mount.point)?;
+
+			// `engine://shaders` must not match `engine://shadersfoo`.
+			let rest = if mount.point.ends_with("://") || rest.is_empty() {
+				rest
+			} else {
+				rest.strip_prefix('/')?
+			};
+
+			Some((mount.source.as_ref(), PathBuf::from(rest)))
+		})
+	}
+}
+
+/// Whether the path has a `scheme://` prefix.
+pub(crate) fn is_virtual(path: &Path) -> bool {
+	path.to_str().is_some_and(|path| path.contains("://"))
+}
+
+fn not_mounted(path: &Path) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::NotFound,
+		format!("No mount point for {}", path.display()),
+	)
+}
+
+/// Loose files in a directory of the native file system.
+pub struct DirectorySource {
+	root: PathBuf,
+}
+
+impl DirectorySource {
+	pub fn new(root: impl AsRef<Path>) -> Self {
+		Self {
+			root: root.as_ref().to_path_buf(),
+		}
+	}
+}
+
+impl MountSource for DirectorySource {
+	fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+		std::fs::read(self.root.join(path))
+	}
+
+	fn exists(&self, path: &Path) -> bool {
+		self.root.join(path).is_file()
+	}
+
+	fn native_path(&self, path: &Path) -> Option<PathBuf> {
+		Some(self.root.join(path))
+	}
+}
+
+const PACK_MAGIC: [u8; 4] = *b"PACK";
+const PACK_VERSION: u32 = 1;
+
+/// Single archive file holding an entire directory tree, created with [`write_pack`].
+///
+/// The file starts with a header and an index of all entries, followed by the file contents.
+/// Only the index is kept in memory, file contents are read on demand.
+pub struct PackSource {
+	path: PathBuf,
+	/// Offset and size in bytes of every file, relative to the start of the contents.
+	entries: HashMap<PathBuf, (u64, u64)>,
+	contents_offset: u64,
+}
+
+impl PackSource {
+	pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+		let path = path.as_ref().to_path_buf();
+		let mut file = std::fs::File::open(&path)?;
+		let file_size = file.metadata()?.len();
+
+		let mut header = [0; 16];
+		file.read_exact(&mut header)?;
+
+		let mut reader = BlobReader::new(&header);
+		let magic = reader.read::<[u8; 4]>()?;
+		let version = reader.read::<u32>()?;
+		let index_size = reader.read::<u64>()?;
+
+		if magic != PACK_MAGIC || version != PACK_VERSION {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("{} is not a version {PACK_VERSION} pack", path.display()),
+			));
+		}
+
+		// Sizes come from the file itself, so check them before allocating anything.
+		let contents_size = (file_size - header.len() as u64)
+			.checked_sub(index_size)
+			.ok_or_else(|| truncated(&path))?;
+
+		let mut index = vec![0; index_size as usize];
+		file.read_exact(&mut index)?;
+
+		let mut reader = BlobReader::new(&index);
+		let mut entries = HashMap::new();
+
+		while !reader.is_empty() {
+			let name = reader.read_string()?;
+			let offset = reader.read::<u64>()?;
+			let size = reader.read::<u64>()?;
+
+			if offset
+				.checked_add(size)
+				.is_none_or(|end| end > contents_size)
+			{
+				return Err(truncated(&path));
+			}
+
+			entries.insert(PathBuf::from(name), (offset, size));
+		}
+
+		Ok(Self {
+			path,
+			entries,
+			contents_offset: header.len() as u64 + index_size,
+		})
+	}
+
+	/// Paths of all files in the pack.
+	pub fn files(&self) -> impl Iterator<Item = &Path> {
+		self.entries.keys().map(PathBuf::as_path)
+	}
+}
+
+impl MountSource for PackSource {
+	fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+		let &(offset, size) = self.entries.get(path).ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::NotFound,
+				format!("{} not found in {}", path.display(), self.path.display()),
+			)
+		})?;
+
+		let mut file = std::fs::File::open(&self.path)?;
+
+		// The pack may have been replaced since it was opened.
+		if self.contents_offset + offset + size > file.metadata()?.len() {
+			return Err(truncated(&self.path));
+		}
+
+		file.seek(SeekFrom::Start(self.contents_offset + offset))?;
+
+		let mut bytes = vec![0; size as usize];
+		file.read_exact(&mut bytes)?;
+		Ok(bytes)
+	}
+
+	fn exists(&self, path: &Path) -> bool {
+		self.entries.contains_key(path)
+	}
+}
+
+fn truncated(path: &Path) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::InvalidData,
+		format!("{} is truncated or corrupted", path.display()),
+	)
+}
+
+/// Packs all files in `directory` and its subdirectories into a single file at `pack_path`,
+/// to be mounted with a [`PackSource`].
+pub fn write_pack(directory: impl AsRef<Path>, pack_path: impl AsRef<Path>) -> io::Result<()> {
+	let directory = directory.as_ref();
+
+	let mut files = Vec::new();
+	let mut directories = vec![directory.to_path_buf()];
+
+	while let Some(current) = directories.pop() {
+		for entry in std::fs::read_dir(&current)? {
+			let path = entry?.path();
+
+			if path.is_dir() {
+				directories.push(path);
+			} else {
+				files.push(path);
+			}
+		}
+	}
+
+	// Sorted, so packing the same directory twice gives the same file.
+	files.sort();
+
+	let mut index = BlobWriter::new();
+	let mut contents = Vec::new();
+
+	for path in &files {
+		let relative = path.strip_prefix(directory).unwrap();
+
+		// Pack paths always use `/`, independent of the platform the pack was created on.
+		let name = relative
+			.components()
+			.map(|component| component.as_os_str().to_string_lossy())
+			.collect::<Vec<_>>()
+			.join("/");
+
+		let bytes = std::fs::read(path)?;
+
+		index.write_str(&name);
+		index.write(contents.len() as u64);
+		index.write(bytes.len() as u64);
+
+		contents.extend_from_slice(&bytes);
+	}
+
+	let index = index.finish();
+
+	let mut header = BlobWriter::new();
+	header.write(PACK_MAGIC);
+	header.write(PACK_VERSION);
+	header.write(index.len() as u64);
+
+	let mut pack = header.finish();
+	pack.extend_from_slice(&index);
+	pack.extend_from_slice(&contents);
+
+	std::fs::write(pack_path, pack)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn mounts() {
+		let root = std::env::temp_dir().join("asset_vfs_mounts");
+		let _ = std::fs::remove_dir_all(&root);
+		std::fs::create_dir_all(root.join("assets/textures")).unwrap();
+		std::fs::create_dir_all(root.join("shaders")).unwrap();
+
+		std::fs::write(root.join("assets/textures/sky.txt"), "sky").unwrap();
+		std::fs::write(root.join("shaders/lib.slang"), "lib").unwrap();
+
+		let pack_path = root.join("assets.pack");
+		write_pack(root.join("assets"), &pack_path).unwrap();
+
+		let mut vfs = Vfs::new();
+		vfs.mount("assets://", PackSource::open(&pack_path).unwrap());
+		vfs.mount("engine://", DirectorySource::new(root.join("missing")));
+		vfs.mount(
+			"engine://shaders",
+			DirectorySource::new(root.join("shaders")),
+		);
+
+		assert_eq!(vfs.read("assets://textures/sky.txt").unwrap(), b"sky");
+		assert_eq!(vfs.read("engine://shaders/lib.slang").unwrap(), b"lib");
+		assert!(vfs.exists("assets://textures/sky.txt"));
+		assert!(!vfs.exists("assets://textures/missing.txt"));
+		assert!(vfs.read("project://file.txt").is_err());
+
+		// Only loose files have a native path.
+		assert_eq!(vfs.native_path("assets://textures/sky.txt"), None);
+		assert_eq!(
+			vfs.native_path("engine://shaders/lib.slang"),
+			Some(root.join("shaders/lib.slang"))
+		);
+	}
+
+	#[test]
+	fn corrupted_pack() {
+		let root = std::env::temp_dir().join("asset_vfs_corrupted_pack");
+		let _ = std::fs::remove_dir_all(&root);
+		std::fs::create_dir_all(root.join("assets")).unwrap();
+		std::fs::write(root.join("assets/sky.txt"), "sky").unwrap();
+
+		let pack_path = root.join("assets.pack");
+		write_pack(root.join("assets"), &pack_path).unwrap();
+		let pack = std::fs::read(&pack_path).unwrap();
+
+		// Index larger than the file.
+		let mut huge_index = pack.clone();
+		huge_index[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+		std::fs::write(&pack_path, &huge_index).unwrap();
+		assert!(PackSource::open(&pack_path).is_err());
+
+		// Entry past the end of the file.
+		std::fs::write(&pack_path, &pack[..pack.len() - 1]).unwrap();
+		assert!(PackSource::open(&pack_path).is_err());
+
+		// Pack truncated after it was opened.
+		std::fs::write(&pack_path, &pack).unwrap();
+		let source = PackSource::open(&pack_path).unwrap();
+		std::fs::write(&pack_path, &pack[..pack.len() - 1]).unwrap();
+		assert!(source.read(Path::new("sky.txt")).is_err());
+	}
+}
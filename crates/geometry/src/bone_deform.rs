@@ -1,6 +1,6 @@
-use crate::mesh::{AttributeGroup, Mesh};
+use crate::mesh::{self, AttributeGroup, Mesh, normalize_or_zero};
 use gpu::{self, BufferImpl, CmdListImpl, DeviceImpl};
-use math::{Mat3x4, Vec3};
+use math::{DualQuaternion, Mat3, Mat3x4, UnitQuaternion, Vec3};
 
 fn to_gpu_data(vertex_groups: &AttributeGroup<f32>) -> (Vec<u32>, Vec<u32>) {
 	let lookup = vertex_groups
@@ -12,12 +12,23 @@ fn to_gpu_data(vertex_groups: &AttributeGroup<f32>) -> (Vec<u32>, Vec<u32>) {
 	let values = vertex_groups
 		.values
 		.iter()
-		.map(|&(attribute_id, value)| (attribute_id as u32) | ((value * 65535.0) as u32) << 16)
+		.map(|&(attribute_id, value)| (attribute_id as u32) | quantize_weight(value) << 16)
 		.collect::<Vec<_>>();
 
 	(lookup, values)
 }
 
+fn quantize_weight(weight: f32) -> u32 {
+	(weight * 65535.0) as u32
+}
+
+fn dequantize_weight(index_weight: u32) -> (usize, f32) {
+	(
+		(index_weight & 0xffff) as usize,
+		(index_weight >> 16) as f32 / 65535.0,
+	)
+}
+
 #[repr(C)]
 struct PushConstants {
 	num_vertices: u32,
@@ -51,7 +62,7 @@ impl BoneDeform {
 		mesh: &Mesh,
 		bone_count: usize,
 	) -> Self {
-		let shader = shader_compiler.compile("shaders/geometry/bone-deform.slang", "main");
+		let shader = shader_compiler.compile("geometry/bone-deform.slang", "main");
 
 		let descriptor_layout = gpu::DescriptorLayout {
 			push_constants: Some(gpu::PushConstantBinding {
@@ -147,3 +158,246 @@ impl BoneDeform {
 		&self.transformed_vertex_buffer
 	}
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkinningMethod {
+	/// Blends the bone matrices, like the compute shader. Joints lose volume when bones twist or bend sharply.
+	LinearBlend,
+	/// Blends the rigid part of the bone transforms as dual quaternions, which keeps the volume of joints. Scale in
+	/// the bone transforms is ignored.
+	DualQuaternion,
+}
+
+/// CPU implementation of [`BoneDeform`], for baking, collision and other uses without a GPU.
+///
+/// Weights are quantized to 16 bits exactly like in the GPU buffers, so linear blend skinning gives the same
+/// results as the compute shader, including vertices without weights ending up at the origin with a zero normal.
+pub struct CpuBoneDeform {
+	lookup: Vec<u32>,
+	weights: Vec<u32>,
+	method: SkinningMethod,
+}
+
+impl CpuBoneDeform {
+	pub fn new(mesh: &Mesh, method: SkinningMethod) -> Self {
+		let (lookup, weights) = to_gpu_data(&mesh.vertex_groups);
+
+		Self {
+			lookup,
+			weights,
+			method,
+		}
+	}
+
+	fn vertex_weights(&self, vertex: usize) -> impl Iterator<Item = (usize, f32)> + '_ {
+		let range = match self.lookup.get(vertex..vertex + 2) {
+			Some(&[start, end]) => start as usize..end as usize,
+			_ => 0..0,
+		};
+
+		self.weights[range]
+			.iter()
+			.map(|&value| dequantize_weight(value))
+	}
+
+	/// Transforms the vertices of the mesh this was created from by the weighted bone transforms.
+	pub fn deform(
+		&self,
+		vertices: &[mesh::Vertex],
+		bone_transforms: &[Mat3x4],
+	) -> Vec<mesh::Vertex> {
+		match self.method {
+			SkinningMethod::LinearBlend => vertices
+				.iter()
+				.enumerate()
+				.map(|(v, vertex)| {
+					let transform = self
+						.vertex_weights(v)
+						.fold(Mat3x4::ZERO, |sum, (bone, weight)| {
+							sum + bone_transforms[bone] * weight
+						});
+
+					let rotation = Mat3::from_array([
+						transform[(0, 0)],
+						transform[(0, 1)],
+						transform[(0, 2)],
+						transform[(1, 0)],
+						transform[(1, 1)],
+						transform[(1, 2)],
+						transform[(2, 0)],
+						transform[(2, 1)],
+						transform[(2, 2)],
+					]);
+
+					mesh::Vertex {
+						p: transform * vertex.p.extend(1.0),
+						n: normalize_or_zero(rotation * vertex.n),
+					}
+				})
+				.collect(),
+			SkinningMethod::DualQuaternion => {
+				let bones: Vec<DualQuaternion<f32>> =
+					bone_transforms.iter().map(to_dual_quaternion).collect();
+
+				vertices
+					.iter()
+					.enumerate()
+					.map(|(v, vertex)| {
+						let mut weights = self.vertex_weights(v).peekable();
+
+						// Blended on the hemisphere of the first bone, so rotations don't take the long way.
+						let Some(&(first, _)) = weights.peek() else {
+							return mesh::Vertex {
+								p: Vec3::ZERO,
+								n: Vec3::ZERO,
+							};
+						};
+						let pivot = bones[first].r;
+
+						let blend = weights.fold(
+							DualQuaternion::new(math::Quaternion::ZERO, math::Quaternion::ZERO),
+							|sum, (bone, weight)| {
+								let bone = bones[bone];
+								let weight = if bone.r.dot(pivot) < 0.0 {
+									-weight
+								} else {
+									weight
+								};
+								DualQuaternion::new(
+									sum.r + bone.r * weight,
+									sum.d + bone.d * weight,
+								)
+							},
+						);
+
+						if blend.r.length_sq() <= f32::MIN_POSITIVE {
+							return mesh::Vertex {
+								p: Vec3::ZERO,
+								n: Vec3::ZERO,
+							};
+						}
+
+						let blend = blend.normalize();
+
+						mesh::Vertex {
+							p: blend.transform_point(vertex.p),
+							n: normalize_or_zero(blend.rotation() * vertex.n),
+						}
+					})
+					.collect()
+			}
+		}
+	}
+}
+
+/// Rigid part of a bone transform. The columns are normalized, which removes scale.
+fn to_dual_quaternion(transform: &Mat3x4) -> DualQuaternion<f32> {
+	let column = |c: usize| {
+		normalize_or_zero(Vec3::new(
+			transform[(0, c)],
+			transform[(1, c)],
+			transform[(2, c)],
+		))
+	};
+
+	let rotation = UnitQuaternion::from_matrix3(Mat3::from_axes(column(0), column(1), column(2)));
+	let translation = Vec3::new(transform[(0, 3)], transform[(1, 3)], transform[(2, 3)]);
+
+	DualQuaternion::from_rotation_translation(rotation, translation)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mesh::VertexGroups;
+
+	/// Transform that rotates around the x axis and then translates.
+	fn bone(angle: f32, translation: Vec3) -> Mat3x4 {
+		let m = UnitQuaternion::from_axis_angle(Vec3::X, angle).to_matrix3();
+		let t = translation;
+
+		Mat3x4 {
+			data: [
+				[m[(0, 0)], m[(0, 1)], m[(0, 2)], t.x],
+				[m[(1, 0)], m[(1, 1)], m[(1, 2)], t.y],
+				[m[(2, 0)], m[(2, 1)], m[(2, 2)], t.z],
+			],
+		}
+	}
+
+	/// Vertices along the x axis, blending from the first to the second bone.
+	fn bar() -> Mesh {
+		let mut mesh = Mesh::new();
+		let mut groups = VertexGroups {
+			names: vec!["a".into(), "b".into()],
+			lookup: vec![0],
+			values: Vec::new(),
+		};
+
+		for i in 0..=10 {
+			let t = i as f32 / 10.0;
+			mesh.vertices.push(mesh::Vertex {
+				p: Vec3::new(t, 0.0, 1.0),
+				n: *Vec3::Z,
+			});
+			groups.values.extend([(0, 1.0 - t), (1, t)]);
+			groups.lookup.push(groups.values.len());
+		}
+
+		mesh.vertex_groups = groups;
+		mesh
+	}
+
+	#[test]
+	fn linear_blend() {
+		let mesh = bar();
+		let bones = [bone(0.0, Vec3::ZERO), bone(1.0, Vec3::new(0.0, 2.0, 0.0))];
+		let deformed =
+			CpuBoneDeform::new(&mesh, SkinningMethod::LinearBlend).deform(&mesh.vertices, &bones);
+
+		for (i, (vertex, deformed)) in mesh.vertices.iter().zip(&deformed).enumerate() {
+			// The weights the shader decodes, which differ slightly from the original ones.
+			let [a, b] = [1.0 - i as f32 / 10.0, i as f32 / 10.0]
+				.map(|weight| (weight * 65535.0) as u32 as f32 / 65535.0);
+
+			let transform = bones[0] * a + bones[1] * b;
+			let expected: Vec3 = transform * vertex.p.extend(1.0);
+			assert!(deformed.p.distance(expected) < 1e-6);
+		}
+
+		// Without weights, vertices collapse like in the shader.
+		let mut mesh = bar();
+		mesh.vertex_groups = VertexGroups::default();
+		let deformed =
+			CpuBoneDeform::new(&mesh, SkinningMethod::LinearBlend).deform(&mesh.vertices, &bones);
+		assert!(deformed.iter().all(|vertex| vertex.p == Vec3::ZERO));
+	}
+
+	#[test]
+	fn dual_quaternion() {
+		let mesh = bar();
+		let linear = CpuBoneDeform::new(&mesh, SkinningMethod::LinearBlend);
+		let dual = CpuBoneDeform::new(&mesh, SkinningMethod::DualQuaternion);
+
+		// Rigid transforms are the same with either method, up to the quantized weights not quite adding up to one.
+		let bones = [bone(0.7, Vec3::new(1.0, 2.0, 3.0)); 2];
+		for (a, b) in linear
+			.deform(&mesh.vertices, &bones)
+			.iter()
+			.zip(dual.deform(&mesh.vertices, &bones))
+		{
+			assert!(a.p.distance(b.p) < 1e-4);
+			assert!(a.n.distance(b.n) < 1e-5);
+		}
+
+		// A half turn twist collapses the middle with linear blending, but keeps its distance to the axis.
+		let bones = [bone(0.0, Vec3::ZERO), bone(math::PI, Vec3::ZERO)];
+		let middle = 5;
+		let p = linear.deform(&mesh.vertices, &bones)[middle].p;
+		assert!(Vec3::new(0.0, p.y, p.z).length() < 0.01);
+
+		let p = dual.deform(&mesh.vertices, &bones)[middle].p;
+		assert!((Vec3::new(0.0, p.y, p.z).length() - 1.0).abs() < 1e-3);
+		assert!((p.x - 0.5).abs() < 1e-5);
+	}
+}
@@ -0,0 +1,616 @@
+//! Bounding volume hierarchy over the triangles of a mesh, for ray casts and proximity queries on the CPU.
+//!
+//! The tree is built with the surface area heuristic over binned triangle centroids. Queries are in the space of
+//! the mesh, so rays and points in world space need to be transformed into it first.
+
+use crate::mesh::Mesh;
+
+use math::{Vec2, Vec3};
+use std::cell::Cell;
+
+/// Number of centroid bins per axis when searching for the best split.
+const BIN_COUNT: usize = 16;
+
+/// Largest number of triangles in a leaf.
+const MAX_LEAF_SIZE: usize = 4;
+
+/// Cost of traversing a node, relative to intersecting a triangle.
+const TRAVERSAL_COST: f32 = 1.0;
+
+/// Axis-aligned bounding box.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Aabb {
+	pub min: Vec3,
+	pub max: Vec3,
+}
+
+impl Aabb {
+	/// Box that contains nothing, and grows to exactly the first point added.
+	pub const EMPTY: Self = Self {
+		min: Vec3::splat(f32::INFINITY),
+		max: Vec3::splat(f32::NEG_INFINITY),
+	};
+
+	pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+		points.into_iter().fold(Self::EMPTY, Self::grow)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		(0..3).any(|axis| self.min[axis] > self.max[axis])
+	}
+
+	pub fn grow(self, p: Vec3) -> Self {
+		Self {
+			min: min(self.min, p),
+			max: max(self.max, p),
+		}
+	}
+
+	pub fn union(self, other: Self) -> Self {
+		Self {
+			min: min(self.min, other.min),
+			max: max(self.max, other.max),
+		}
+	}
+
+	pub fn center(&self) -> Vec3 {
+		(self.min + self.max) * 0.5
+	}
+
+	pub fn size(&self) -> Vec3 {
+		self.max - self.min
+	}
+
+	pub fn surface_area(&self) -> f32 {
+		if self.is_empty() {
+			return 0.0;
+		}
+
+		let size = self.size();
+		2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+	}
+
+	pub fn contains(&self, p: Vec3) -> bool {
+		(0..3).all(|axis| self.min[axis] <= p[axis] && p[axis] <= self.max[axis])
+	}
+
+	pub fn overlaps(&self, other: &Self) -> bool {
+		(0..3).all(|axis| self.min[axis] <= other.max[axis] && other.min[axis] <= self.max[axis])
+	}
+
+	/// Squared distance from a point to the box, zero for points inside.
+	pub fn distance_sq(&self, p: Vec3) -> f32 {
+		let outside = max(max(self.min - p, p - self.max), Vec3::ZERO);
+		outside.length_sq()
+	}
+
+	/// Distance along the ray where it enters the box, if it does so before `t_max`. Rays that start inside the box
+	/// enter it at 0.
+	pub fn intersect_ray(&self, ray: &Ray, t_max: f32) -> Option<f32> {
+		let mut t_near = 0.0_f32;
+		let mut t_far = t_max;
+
+		for axis in 0..3 {
+			let inverse = 1.0 / ray.direction[axis];
+			let t0 = (self.min[axis] - ray.origin[axis]) * inverse;
+			let t1 = (self.max[axis] - ray.origin[axis]) * inverse;
+
+			// Written so that NaNs from rays in the plane of a slab keep the current interval.
+			t_near = if t0.min(t1) > t_near {
+				t0.min(t1)
+			} else {
+				t_near
+			};
+			t_far = if t0.max(t1) < t_far {
+				t0.max(t1)
+			} else {
+				t_far
+			};
+		}
+
+		(t_near <= t_far).then_some(t_near)
+	}
+}
+
+fn min(a: Vec3, b: Vec3) -> Vec3 {
+	Vec3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))
+}
+
+fn max(a: Vec3, b: Vec3) -> Vec3 {
+	Vec3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
+}
+
+/// Half-line from `origin` along `direction`. Distances along the ray are in multiples of `direction`, so they are
+/// only metric for a unit direction.
+#[derive(Clone, Copy)]
+pub struct Ray {
+	pub origin: Vec3,
+	pub direction: Vec3,
+}
+
+impl Ray {
+	pub fn new(origin: Vec3, direction: Vec3) -> Self {
+		Self { origin, direction }
+	}
+
+	pub fn at(&self, t: f32) -> Vec3 {
+		self.origin + self.direction * t
+	}
+}
+
+#[derive(Clone, Copy)]
+pub struct RayHit {
+	/// Distance along the ray.
+	pub t: f32,
+	/// Index of the triangle in the mesh indices, i.e. the first index is `3 * triangle`.
+	pub triangle: usize,
+	/// Weights of the second and third vertex of the triangle; the first has the remaining weight.
+	pub barycentric: Vec2,
+}
+
+#[derive(Clone, Copy)]
+pub struct ClosestPoint {
+	pub point: Vec3,
+	pub distance: f32,
+	pub triangle: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+	bounds: Aabb,
+	/// First triangle of leaves, or the second child of interior nodes. The first child follows the node.
+	offset: usize,
+	/// Number of triangles, zero for interior nodes.
+	count: usize,
+}
+
+pub struct Bvh {
+	nodes: Vec<Node>,
+	/// Triangle vertices, in the order of the leaves.
+	triangles: Vec<[Vec3; 3]>,
+	/// Mesh triangle index of every entry of `triangles`.
+	triangle_ids: Vec<usize>,
+}
+
+impl Bvh {
+	/// Builds the hierarchy over the triangles of a mesh. Degenerate triangles are kept, but never hit.
+	pub fn new(mesh: &Mesh) -> Self {
+		let triangles = mesh
+			.indices
+			.chunks_exact(3)
+			.map(|triangle| [0, 1, 2].map(|i| mesh.vertices[triangle[i]].p))
+			.collect();
+
+		Self::from_triangles(triangles)
+	}
+
+	/// Builds the hierarchy over triangles, which are identified by their index in `triangles` in query results.
+	pub fn from_triangles(triangles: Vec<[Vec3; 3]>) -> Self {
+		let mut bvh = Self {
+			nodes: Vec::with_capacity(triangles.len() * 2),
+			triangle_ids: (0..triangles.len()).collect(),
+			triangles,
+		};
+
+		if bvh.triangles.is_empty() {
+			bvh.nodes.push(Node {
+				bounds: Aabb::EMPTY,
+				offset: 0,
+				count: 0,
+			});
+			return bvh;
+		}
+
+		let bounds: Vec<Aabb> = bvh
+			.triangles
+			.iter()
+			.map(|t| Aabb::from_points(*t))
+			.collect();
+		let centroids: Vec<Vec3> = bounds.iter().map(Aabb::center).collect();
+		let mut order: Vec<usize> = (0..bvh.triangles.len()).collect();
+
+		bvh.build(&bounds, &centroids, &mut order, 0);
+
+		bvh.triangles = order.iter().map(|&t| bvh.triangles[t]).collect();
+		bvh.triangle_ids = order;
+		bvh
+	}
+
+	/// Adds the node for the triangles in `order`, which start at `start` in the leaf order, and its children.
+	fn build(&mut self, bounds: &[Aabb], centroids: &[Vec3], order: &mut [usize], start: usize) {
+		let node_bounds = order.iter().fold(Aabb::EMPTY, |b, &t| b.union(bounds[t]));
+		let node = self.nodes.len();
+
+		self.nodes.push(Node {
+			bounds: node_bounds,
+			offset: start,
+			count: order.len(),
+		});
+
+		if order.len() <= 1 {
+			return;
+		}
+
+		let split = self.find_split(node_bounds, bounds, centroids, order);
+
+		let Some((axis, position)) = split else {
+			return;
+		};
+
+		let mut mid = 0;
+		for i in 0..order.len() {
+			if centroids[order[i]][axis] < position {
+				order.swap(i, mid);
+				mid += 1;
+			}
+		}
+
+		// Splits can be empty when all centroids fall in one bin, then the triangles are split in half.
+		if mid == 0 || mid == order.len() {
+			order.sort_unstable_by(|&a, &b| centroids[a][axis].total_cmp(&centroids[b][axis]));
+			mid = order.len() / 2;
+		}
+
+		let (left, right) = order.split_at_mut(mid);
+		self.build(bounds, centroids, left, start);
+		let second = self.nodes.len();
+		self.build(bounds, centroids, right, start + mid);
+
+		self.nodes[node].offset = second;
+		self.nodes[node].count = 0;
+	}
+
+	/// Axis and centroid position of the split with the lowest surface area heuristic, or `None` if keeping the
+	/// triangles in a leaf is cheaper.
+	fn find_split(
+		&self,
+		node_bounds: Aabb,
+		bounds: &[Aabb],
+		centroids: &[Vec3],
+		order: &[usize],
+	) -> Option<(usize, f32)> {
+		let centroid_bounds = Aabb::from_points(order.iter().map(|&t| centroids[t]));
+		let leaf_cost = order.len() as f32;
+		let mut best: Option<(f32, usize, f32)> = None;
+
+		for axis in [0, 1, 2] {
+			let (low, high) = (centroid_bounds.min[axis], centroid_bounds.max[axis]);
+
+			if high - low <= f32::EPSILON * high.abs().max(low.abs()) {
+				continue;
+			}
+
+			let scale = BIN_COUNT as f32 / (high - low);
+			let bin = |t: usize| (((centroids[t][axis] - low) * scale) as usize).min(BIN_COUNT - 1);
+
+			let mut bins = [(Aabb::EMPTY, 0); BIN_COUNT];
+			for &t in order {
+				let (b, count) = &mut bins[bin(t)];
+				*b = b.union(bounds[t]);
+				*count += 1;
+			}
+
+			// Area times triangle count of the bins left of every split, then right of it.
+			let mut left_costs = [0.0; BIN_COUNT - 1];
+			let (mut b, mut count) = (Aabb::EMPTY, 0);
+			for split in 0..BIN_COUNT - 1 {
+				b = b.union(bins[split].0);
+				count += bins[split].1;
+				left_costs[split] = b.surface_area() * count as f32;
+			}
+
+			let (mut b, mut count) = (Aabb::EMPTY, 0);
+			for split in (0..BIN_COUNT - 1).rev() {
+				b = b.union(bins[split + 1].0);
+				count += bins[split + 1].1;
+				let cost = left_costs[split] + b.surface_area() * count as f32;
+
+				if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+					best = Some((cost, axis, low + (split + 1) as f32 / scale));
+				}
+			}
+		}
+
+		let (cost, axis, position) = best?;
+		let cost = TRAVERSAL_COST + cost / node_bounds.surface_area().max(f32::MIN_POSITIVE);
+
+		(order.len() > MAX_LEAF_SIZE || cost < leaf_cost).then_some((axis, position))
+	}
+
+	pub fn bounds(&self) -> Aabb {
+		self.nodes[0].bounds
+	}
+
+	/// Visits the triangles of the leaves whose bounds `enter` returns a distance for, nearest first, until `visit`
+	/// returns `false`.
+	fn traverse(
+		&self,
+		mut enter: impl FnMut(&Aabb) -> Option<f32>,
+		mut visit: impl FnMut(usize) -> bool,
+	) {
+		let mut stack = vec![(0, 0.0)];
+
+		while let Some((index, _)) = stack.pop() {
+			let node = &self.nodes[index];
+
+			// Checked again, as queries may have become more restrictive since the node was pushed.
+			if enter(&node.bounds).is_none() {
+				continue;
+			}
+
+			if node.count > 0 {
+				for i in node.offset..node.offset + node.count {
+					if !visit(i) {
+						return;
+					}
+				}
+				continue;
+			}
+
+			let children =
+				[index + 1, node.offset].map(|child| (child, enter(&self.nodes[child].bounds)));
+
+			let mut children: Vec<(usize, f32)> = children
+				.into_iter()
+				.filter_map(|(child, distance)| Some((child, distance?)))
+				.collect();
+
+			// The nearest child is popped first.
+			children.sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+			stack.extend(children);
+		}
+	}
+
+	/// Closest intersection of the ray with a triangle before `t_max`. Triangles are hit from both sides.
+	pub fn intersect(&self, ray: &Ray, t_max: f32) -> Option<RayHit> {
+		let mut closest: Option<RayHit> = None;
+		let t_max = Cell::new(t_max);
+
+		self.traverse(
+			|bounds| bounds.intersect_ray(ray, t_max.get()),
+			|i| {
+				if let Some((t, barycentric)) =
+					intersect_triangle(ray, &self.triangles[i], t_max.get())
+				{
+					t_max.set(t);
+					closest = Some(RayHit {
+						t,
+						triangle: self.triangle_ids[i],
+						barycentric,
+					});
+				}
+				true
+			},
+		);
+
+		closest
+	}
+
+	/// Whether the ray hits any triangle before `t_max`, which is faster than finding the closest hit.
+	pub fn intersects(&self, ray: &Ray, t_max: f32) -> bool {
+		let mut hit = false;
+
+		self.traverse(
+			|bounds| bounds.intersect_ray(ray, t_max),
+			|i| {
+				hit = intersect_triangle(ray, &self.triangles[i], t_max).is_some();
+				!hit
+			},
+		);
+
+		hit
+	}
+
+	/// Closest point on any triangle to `p`, if there is one within `max_distance`.
+	pub fn closest_point(&self, p: Vec3, max_distance: f32) -> Option<ClosestPoint> {
+		let mut closest: Option<ClosestPoint> = None;
+		let max_distance_sq = Cell::new(max_distance * max_distance);
+
+		self.traverse(
+			|bounds| {
+				let distance_sq = bounds.distance_sq(p);
+				(distance_sq <= max_distance_sq.get()).then_some(distance_sq)
+			},
+			|i| {
+				let point = closest_point_on_triangle(p, &self.triangles[i]);
+				let distance_sq = point.distance_sq(p);
+
+				if distance_sq <= max_distance_sq.get() {
+					max_distance_sq.set(distance_sq);
+					closest = Some(ClosestPoint {
+						point,
+						distance: distance_sq.sqrt(),
+						triangle: self.triangle_ids[i],
+					});
+				}
+				true
+			},
+		);
+
+		closest
+	}
+
+	/// Triangles whose bounds overlap the box, in no particular order.
+	pub fn overlapping(&self, aabb: &Aabb) -> Vec<usize> {
+		let mut triangles = Vec::new();
+
+		self.traverse(
+			|bounds| bounds.overlaps(aabb).then_some(0.0),
+			|i| {
+				if Aabb::from_points(self.triangles[i]).overlaps(aabb) {
+					triangles.push(self.triangle_ids[i]);
+				}
+				true
+			},
+		);
+
+		triangles
+	}
+}
+
+/// Distance along the ray and barycentric coordinates of the hit, with the Möller-Trumbore algorithm.
+fn intersect_triangle(ray: &Ray, [a, b, c]: &[Vec3; 3], t_max: f32) -> Option<(f32, Vec2)> {
+	let e1 = *b - *a;
+	let e2 = *c - *a;
+	let p = ray.direction.cross(e2);
+	let det = e1.dot(p);
+
+	if det.abs() <= f32::MIN_POSITIVE {
+		return None;
+	}
+
+	let inverse = 1.0 / det;
+	let s = ray.origin - *a;
+	let u = s.dot(p) * inverse;
+
+	if !(0.0..=1.0).contains(&u) {
+		return None;
+	}
+
+	let q = s.cross(e1);
+	let v = ray.direction.dot(q) * inverse;
+
+	if v < 0.0 || u + v > 1.0 {
+		return None;
+	}
+
+	let t = e2.dot(q) * inverse;
+	(t >= 0.0 && t <= t_max).then_some((t, Vec2::new(u, v)))
+}
+
+/// From Christer Ericson's "Real-Time Collision Detection", by the Voronoi region of the point.
+fn closest_point_on_triangle(p: Vec3, [a, b, c]: &[Vec3; 3]) -> Vec3 {
+	let (a, b, c) = (*a, *b, *c);
+	let ab = b - a;
+	let ac = c - a;
+	let ap = p - a;
+
+	let d1 = ab.dot(ap);
+	let d2 = ac.dot(ap);
+	if d1 <= 0.0 && d2 <= 0.0 {
+		return a;
+	}
+
+	let bp = p - b;
+	let d3 = ab.dot(bp);
+	let d4 = ac.dot(bp);
+	if d3 >= 0.0 && d4 <= d3 {
+		return b;
+	}
+
+	let vc = d1 * d4 - d3 * d2;
+	if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+		return a + ab * (d1 / (d1 - d3));
+	}
+
+	let cp = p - c;
+	let d5 = ab.dot(cp);
+	let d6 = ac.dot(cp);
+	if d6 >= 0.0 && d5 <= d6 {
+		return c;
+	}
+
+	let vb = d5 * d2 - d1 * d6;
+	if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+		return a + ac * (d2 / (d2 - d6));
+	}
+
+	let va = d3 * d6 - d5 * d4;
+	if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+		return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+	}
+
+	let denominator = 1.0 / (va + vb + vc);
+	a + ab * (vb * denominator) + ac * (vc * denominator)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::primitives::{sphere, torus};
+
+	/// Deterministic points in [-1, 1].
+	fn points(count: usize) -> Vec<Vec3> {
+		let mut state = 0x2545_f491_u32;
+		let mut next = move || {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			state as f32 / u32::MAX as f32 * 2.0 - 1.0
+		};
+
+		(0..count)
+			.map(|_| Vec3::new(next(), next(), next()))
+			.collect()
+	}
+
+	#[test]
+	fn ray_queries() {
+		let mesh = torus::torus(32, 16, 1.0, 0.3);
+		let bvh = Bvh::new(&mesh);
+		let triangles: Vec<[Vec3; 3]> = mesh
+			.indices
+			.chunks_exact(3)
+			.map(|t| [0, 1, 2].map(|i| mesh.vertices[t[i]].p))
+			.collect();
+
+		let origins = points(200);
+		let targets = points(200);
+
+		for (&origin, &target) in origins.iter().zip(&targets) {
+			let ray = Ray::new(origin * 2.0, target * 0.5 - origin * 2.0);
+
+			let expected = triangles
+				.iter()
+				.enumerate()
+				.filter_map(|(i, t)| Some((intersect_triangle(&ray, t, f32::INFINITY)?.0, i)))
+				.min_by(|a, b| a.0.total_cmp(&b.0));
+
+			let hit = bvh.intersect(&ray, f32::INFINITY);
+			assert_eq!(hit.map(|hit| hit.t), expected.map(|(t, _)| t));
+			assert_eq!(bvh.intersects(&ray, f32::INFINITY), expected.is_some());
+
+			if let Some(hit) = hit {
+				let [a, b, c] = triangles[hit.triangle];
+				let (u, v) = (hit.barycentric.x, hit.barycentric.y);
+				let p = a * (1.0 - u - v) + b * u + c * v;
+				assert!(p.distance(ray.at(hit.t)) < 1e-4);
+				assert!(!bvh.intersects(&ray, hit.t * 0.999));
+			}
+		}
+	}
+
+	#[test]
+	fn proximity_queries() {
+		let mesh = sphere::sphere(1.0, 32, 16);
+		let bvh = Bvh::new(&mesh);
+		assert!(bvh.bounds().contains(Vec3::ZERO));
+
+		for p in points(100) {
+			let p = p * 3.0;
+			let closest = bvh.closest_point(p, f32::INFINITY).unwrap();
+
+			// The tessellated sphere is slightly smaller than the real one.
+			assert!((closest.distance - (p.length() - 1.0).abs()).abs() < 0.02);
+			assert!(closest.point.distance(p) - closest.distance < 1e-4);
+			assert!(bvh.closest_point(p, closest.distance * 0.99).is_none());
+		}
+
+		let aabb = Aabb {
+			min: Vec3::new(0.5, -0.2, -0.2),
+			max: Vec3::new(1.5, 0.2, 0.2),
+		};
+		let mut triangles = bvh.overlapping(&aabb);
+		triangles.sort_unstable();
+
+		let expected: Vec<usize> = (0..mesh.indices.len() / 3)
+			.filter(|&t| {
+				let points = (0..3).map(|i| mesh.vertices[mesh.indices[t * 3 + i]].p);
+				Aabb::from_points(points).overlaps(&aabb)
+			})
+			.collect();
+
+		assert!(!expected.is_empty());
+		assert_eq!(triangles, expected);
+	}
+}
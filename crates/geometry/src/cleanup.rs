@@ -0,0 +1,547 @@
+//! Mesh cleanup: welding, removal of degenerate triangles and unused vertices, and index buffer optimization.
+//!
+//! Triangles are only moved within their submesh, and submesh ranges are updated when triangles are removed.
+
+use crate::mesh::{AttributeValues, Mesh, VertexGroups};
+
+use math::Vec3;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+
+/// Largest difference of normals and attribute values of vertices that are welded.
+const ATTRIBUTE_TOLERANCE: f32 = 1e-5;
+
+/// Vertex cache size that [`optimize_vertex_cache`] optimizes for.
+const CACHE_SIZE: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IndexError {
+	/// The number of indices is not a multiple of 3.
+	IncompleteTriangle,
+	OutOfRange {
+		index: usize,
+		vertex_count: usize,
+	},
+	/// More vertices than fit in 32 bit indices.
+	TooManyVertices(usize),
+}
+
+impl fmt::Display for IndexError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::IncompleteTriangle => write!(f, "Index count is not a multiple of 3"),
+			Self::OutOfRange {
+				index,
+				vertex_count,
+			} => write!(
+				f,
+				"Vertex index {index} is out of range for {vertex_count} vertices"
+			),
+			Self::TooManyVertices(count) => {
+				write!(f, "{count} vertices don't fit in 32 bit indices")
+			}
+		}
+	}
+}
+
+impl std::error::Error for IndexError {}
+
+/// Validates the indices of a mesh and converts them to 32 bit.
+pub fn compact_indices(mesh: &Mesh) -> Result<Vec<u32>, IndexError> {
+	let vertex_count = mesh.vertices.len();
+
+	if vertex_count > u32::MAX as usize {
+		return Err(IndexError::TooManyVertices(vertex_count));
+	}
+
+	if !mesh.indices.len().is_multiple_of(3) {
+		return Err(IndexError::IncompleteTriangle);
+	}
+
+	mesh.indices
+		.iter()
+		.map(|&index| match index < vertex_count {
+			true => Ok(index as u32),
+			false => Err(IndexError::OutOfRange {
+				index,
+				vertex_count,
+			}),
+		})
+		.collect()
+}
+
+/// For every position, the index of the first position within `epsilon` of it that is not itself merged into
+/// another one. An `epsilon` of zero only merges identical positions.
+pub fn weld_positions(positions: &[Vec3], epsilon: f32) -> Vec<usize> {
+	weld(positions, epsilon, |_, _| true)
+}
+
+/// Remap of vertices to representatives within `epsilon` for which `same` is true.
+fn weld(positions: &[Vec3], epsilon: f32, same: impl Fn(usize, usize) -> bool) -> Vec<usize> {
+	let cell = |p: Vec3| -> [i64; 3] {
+		match epsilon > 0.0 {
+			true => [p.x, p.y, p.z].map(|x| (x / epsilon).floor() as i64),
+			false => [p.x, p.y, p.z].map(|x| x.to_bits() as i64),
+		}
+	};
+
+	let neighbors: &[i64] = match epsilon > 0.0 {
+		true => &[-1, 0, 1],
+		false => &[0],
+	};
+
+	let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+	let mut remap = Vec::with_capacity(positions.len());
+
+	for (v, &p) in positions.iter().enumerate() {
+		let [x, y, z] = cell(p);
+
+		let representative = neighbors
+			.iter()
+			.flat_map(|dx| neighbors.iter().map(move |dy| (dx, dy)))
+			.flat_map(|(dx, dy)| neighbors.iter().map(move |dz| [x + dx, y + dy, z + dz]))
+			.filter_map(|key| cells.get(&key))
+			.flatten()
+			.copied()
+			.find(|&r| positions[r].distance(p) <= epsilon && same(r, v));
+
+		match representative {
+			Some(r) => remap.push(r),
+			None => {
+				cells.entry([x, y, z]).or_default().push(v);
+				remap.push(v);
+			}
+		}
+	}
+
+	remap
+}
+
+fn same_attribute(values: &AttributeValues, a: usize, b: usize) -> bool {
+	let difference = match values {
+		AttributeValues::F32(values) => (values[a] - values[b]).abs(),
+		AttributeValues::Vec2(values) => values[a].distance(values[b]),
+		AttributeValues::Vec3(values) => values[a].distance(values[b]),
+		AttributeValues::Vec4(values) => values[a].distance(values[b]),
+	};
+
+	difference <= ATTRIBUTE_TOLERANCE
+}
+
+fn vertex_group_values(groups: &VertexGroups, v: usize) -> &[(usize, f32)] {
+	match groups.lookup.get(v..v + 2) {
+		Some(&[start, end]) => &groups.values[start..end],
+		_ => &[],
+	}
+}
+
+/// Merges vertices with positions within `epsilon` of each other and the same normals, attributes, vertex groups
+/// and morph target deltas, and drops the vertices that are no longer used. Returns the number of vertices that
+/// were removed.
+pub fn weld_vertices(mesh: &mut Mesh, epsilon: f32) -> usize {
+	let positions: Vec<Vec3> = mesh.vertices.iter().map(|vertex| vertex.p).collect();
+
+	let morph_deltas: Vec<Vec<(Vec3, Vec3)>> = mesh
+		.morph_targets
+		.iter()
+		.map(|target| {
+			let mut deltas = vec![(Vec3::ZERO, Vec3::ZERO); positions.len()];
+			// Invalid targets are ignored, like when they are applied.
+			if target.is_valid(positions.len()) {
+				for (v, position, normal) in target.deltas() {
+					deltas[v] = (position, normal);
+				}
+			}
+			deltas
+		})
+		.collect();
+
+	let remap = weld(&positions, epsilon, |a, b| {
+		let (a_groups, b_groups) = (
+			vertex_group_values(&mesh.vertex_groups, a),
+			vertex_group_values(&mesh.vertex_groups, b),
+		);
+
+		mesh.vertices[a].n.distance(mesh.vertices[b].n) <= ATTRIBUTE_TOLERANCE
+			&& mesh
+				.attributes
+				.iter()
+				.all(|(_, values)| same_attribute(values, a, b))
+			&& a_groups.len() == b_groups.len()
+			&& a_groups
+				.iter()
+				.zip(b_groups)
+				.all(|(x, y)| x.0 == y.0 && (x.1 - y.1).abs() <= ATTRIBUTE_TOLERANCE)
+			&& morph_deltas.iter().all(|deltas| {
+				deltas[a].0.distance(deltas[b].0) <= ATTRIBUTE_TOLERANCE
+					&& deltas[a].1.distance(deltas[b].1) <= ATTRIBUTE_TOLERANCE
+			})
+	});
+
+	for index in &mut mesh.indices {
+		*index = remap[*index];
+	}
+
+	remove_unused_vertices(mesh)
+}
+
+/// Keeps the vertices in `kept`, in that order, and remaps the indices.
+fn reorder_vertices(mesh: &mut Mesh, kept: &[usize]) {
+	let mut remap = vec![usize::MAX; mesh.vertices.len()];
+	for (new, &old) in kept.iter().enumerate() {
+		remap[old] = new;
+	}
+
+	mesh.vertices = kept.iter().map(|&v| mesh.vertices[v]).collect();
+	mesh.attributes = mesh.attributes.gather(kept);
+	mesh.vertex_groups = mesh.vertex_groups.gather(kept);
+
+	for target in &mut mesh.morph_targets {
+		*target = target.gather(kept);
+	}
+
+	for index in &mut mesh.indices {
+		*index = remap[*index];
+	}
+}
+
+/// Drops vertices that no triangle uses. Returns the number of vertices that were removed.
+pub fn remove_unused_vertices(mesh: &mut Mesh) -> usize {
+	let mut used = vec![false; mesh.vertices.len()];
+	for &index in &mesh.indices {
+		used[index] = true;
+	}
+
+	let kept: Vec<usize> = (0..mesh.vertices.len()).filter(|&v| used[v]).collect();
+	let removed = mesh.vertices.len() - kept.len();
+
+	if removed > 0 {
+		reorder_vertices(mesh, &kept);
+	}
+
+	removed
+}
+
+/// Ranges of indices that triangles may move within: the submeshes and the parts between them.
+fn segments(mesh: &Mesh) -> Vec<Range<usize>> {
+	let mut bounds: Vec<usize> = mesh
+		.submeshes
+		.iter()
+		.flat_map(|submesh| [submesh.indices.start, submesh.indices.end])
+		.chain([0, mesh.indices.len()])
+		.collect();
+
+	bounds.sort_unstable();
+	bounds.dedup();
+	bounds.windows(2).map(|pair| pair[0]..pair[1]).collect()
+}
+
+/// Removes triangles that use a vertex twice or have no area, and triangles that repeat another triangle of the
+/// same submesh with the same winding. Returns the number of triangles that were removed.
+pub fn remove_degenerate_triangles(mesh: &mut Mesh) -> usize {
+	let mut indices = Vec::with_capacity(mesh.indices.len());
+	// Position in the new indices of every segment bound.
+	let mut bounds = HashMap::new();
+
+	for segment in segments(mesh) {
+		bounds.insert(segment.start, indices.len());
+		let mut seen = HashSet::new();
+
+		for triangle in mesh.indices[segment.clone()].chunks_exact(3) {
+			let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+			let [pa, pb, pc] = [a, b, c].map(|v| mesh.vertices[v].p);
+
+			if a == b || b == c || c == a || (pb - pa).cross(pc - pa).length_sq() == 0.0 {
+				continue;
+			}
+
+			// Rotated so the smallest index is first, which keeps the winding.
+			let key = match a.min(b).min(c) {
+				m if m == a => [a, b, c],
+				m if m == b => [b, c, a],
+				_ => [c, a, b],
+			};
+
+			if seen.insert(key) {
+				indices.extend_from_slice(triangle);
+			}
+		}
+	}
+
+	bounds.insert(mesh.indices.len(), indices.len());
+
+	for submesh in &mut mesh.submeshes {
+		submesh.indices = bounds[&submesh.indices.start]..bounds[&submesh.indices.end];
+	}
+
+	let removed = (mesh.indices.len() - indices.len()) / 3;
+	mesh.indices = indices;
+	removed
+}
+
+/// Score of a vertex for [`optimize_vertex_cache`], from Tom Forsyth's "Linear-Speed Vertex Cache Optimisation".
+fn vertex_score(cache_position: Option<usize>, remaining_triangles: usize) -> f32 {
+	if remaining_triangles == 0 {
+		return -1.0;
+	}
+
+	let cache_score = match cache_position {
+		None => 0.0,
+		// The vertices of the last triangle, which may be used in any order.
+		Some(position) if position < 3 => 0.75,
+		Some(position) => {
+			let scale = 1.0 / (CACHE_SIZE - 3) as f32;
+			(1.0 - (position - 3) as f32 * scale).powf(1.5)
+		}
+	};
+
+	// Favors vertices with few triangles left, to finish them off.
+	cache_score + 2.0 / (remaining_triangles as f32).sqrt()
+}
+
+/// Reorders the triangles of a triangle list to reuse recently transformed vertices.
+fn optimize_triangles(indices: &mut [usize], vertex_count: usize) {
+	let triangle_count = indices.len() / 3;
+
+	// Triangles of every vertex, with the ones that were not emitted yet first.
+	let mut remaining = vec![0; vertex_count];
+	for &v in indices.iter() {
+		remaining[v] += 1;
+	}
+
+	let mut offsets = vec![0; vertex_count + 1];
+	for v in 0..vertex_count {
+		offsets[v + 1] = offsets[v] + remaining[v];
+	}
+
+	let mut vertex_triangles = vec![0; indices.len()];
+	let mut fill = offsets.clone();
+	for (i, &v) in indices.iter().enumerate() {
+		vertex_triangles[fill[v]] = i / 3;
+		fill[v] += 1;
+	}
+
+	let mut cache_positions = vec![None; vertex_count];
+	let mut scores: Vec<f32> = (0..vertex_count)
+		.map(|v| vertex_score(None, remaining[v]))
+		.collect();
+
+	let triangle = |t: usize| [indices[t * 3], indices[t * 3 + 1], indices[t * 3 + 2]];
+	let mut triangle_scores: Vec<f32> = (0..triangle_count)
+		.map(|t| triangle(t).iter().map(|&v| scores[v]).sum())
+		.collect();
+
+	let mut emitted = vec![false; triangle_count];
+	let mut order = Vec::with_capacity(triangle_count);
+	let mut cache: Vec<usize> = Vec::new();
+	let mut best = None;
+
+	while order.len() < triangle_count {
+		// Without candidates from the cache, start over at the best triangle left.
+		let t = best.unwrap_or_else(|| {
+			(0..triangle_count)
+				.filter(|&t| !emitted[t])
+				.max_by(|&a, &b| triangle_scores[a].total_cmp(&triangle_scores[b]))
+				.unwrap()
+		});
+
+		emitted[t] = true;
+		order.push(t);
+
+		let vertices = triangle(t);
+
+		for v in vertices {
+			let triangles = &mut vertex_triangles[offsets[v]..offsets[v] + remaining[v]];
+			let position = triangles.iter().position(|&other| other == t).unwrap();
+			triangles.swap(position, remaining[v] - 1);
+			remaining[v] -= 1;
+		}
+
+		let mut new_cache = vertices.to_vec();
+		new_cache.extend(cache.iter().filter(|v| !vertices.contains(v)));
+
+		// Vertices that fall out of the cache need their score updated as well.
+		for &v in new_cache.iter().skip(CACHE_SIZE) {
+			cache_positions[v] = None;
+			scores[v] = vertex_score(None, remaining[v]);
+		}
+		new_cache.truncate(CACHE_SIZE);
+
+		for (position, &v) in new_cache.iter().enumerate() {
+			cache_positions[v] = Some(position);
+			scores[v] = vertex_score(Some(position), remaining[v]);
+		}
+
+		best = None;
+		let mut best_score = f32::NEG_INFINITY;
+
+		for &v in &new_cache {
+			for &other in &vertex_triangles[offsets[v]..offsets[v] + remaining[v]] {
+				let score = triangle(other).iter().map(|&u| scores[u]).sum();
+				triangle_scores[other] = score;
+
+				if score > best_score {
+					best_score = score;
+					best = Some(other);
+				}
+			}
+		}
+
+		cache = new_cache;
+	}
+
+	let reordered: Vec<usize> = order.iter().flat_map(|&t| triangle(t)).collect();
+	indices.copy_from_slice(&reordered);
+}
+
+/// Reorders triangles within each submesh so that vertices are reused while they are still in the GPU's vertex
+/// cache.
+pub fn optimize_vertex_cache(mesh: &mut Mesh) {
+	let vertex_count = mesh.vertices.len();
+
+	for segment in segments(mesh) {
+		optimize_triangles(&mut mesh.indices[segment], vertex_count);
+	}
+}
+
+/// Reorders vertices by their first use in the indices, so that vertex fetches are mostly sequential, and drops
+/// unused vertices. Best done after [`optimize_vertex_cache`].
+pub fn optimize_vertex_fetch(mesh: &mut Mesh) {
+	let mut used = vec![false; mesh.vertices.len()];
+	let mut kept = Vec::with_capacity(mesh.vertices.len());
+
+	for &index in &mesh.indices {
+		if !used[index] {
+			used[index] = true;
+			kept.push(index);
+		}
+	}
+
+	reorder_vertices(mesh, &kept);
+}
+
+/// Average number of vertex cache misses per triangle, for a FIFO cache of `cache_size` vertices. Ranges from
+/// about 0.5 for well ordered grids to 3.
+pub fn average_cache_miss_ratio(indices: &[usize], cache_size: usize) -> f32 {
+	let mut cache = std::collections::VecDeque::with_capacity(cache_size);
+	let mut misses = 0;
+
+	for &index in indices {
+		if !cache.contains(&index) {
+			misses += 1;
+			if cache.len() == cache_size {
+				cache.pop_front();
+			}
+			cache.push_back(index);
+		}
+	}
+
+	misses as f32 / (indices.len() / 3).max(1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mesh::{Submesh, Vertex};
+	use crate::primitives::{grid, sphere};
+
+	#[test]
+	fn weld_and_remove() {
+		// Every triangle has its own vertices.
+		let sphere = sphere::sphere(1.0, 12, 8);
+		let mut mesh = Mesh::new();
+
+		for &index in &sphere.indices {
+			mesh.indices.push(mesh.vertices.len());
+			mesh.vertices.push(Vertex {
+				p: sphere.vertices[index].p + Vec3::new(1e-6, 0.0, 0.0),
+				n: sphere.vertices[index].n,
+			});
+		}
+
+		let triangles = mesh.indices.len() / 3;
+		assert_eq!(
+			weld_vertices(&mut mesh, 1e-4),
+			triangles * 3 - sphere.vertices.len()
+		);
+		assert_eq!(mesh.vertices.len(), sphere.vertices.len());
+
+		// Vertices with different attributes are not welded.
+		let mut mesh = grid::grid(1.0, 1.0, 3, 3);
+		let uvs = (0..9).map(|v| math::Vec2::new(v as f32, 0.0)).collect();
+		mesh.attributes.insert("uv0", uvs);
+		let copy = mesh.vertices[4];
+		mesh.vertices.push(copy);
+		mesh.attributes.resize(10);
+		mesh.indices.extend([9, 0, 1]);
+		assert_eq!(weld_vertices(&mut mesh, 1e-4), 0);
+
+		// Degenerate and repeated triangles, where the repeated one is in another submesh.
+		let mut mesh = grid::grid(1.0, 1.0, 3, 3);
+		let triangles = mesh.indices.len() / 3;
+		mesh.indices.extend([0, 0, 1, 0, 1, 2, 1, 2, 3, 2, 3, 1]);
+		mesh.indices.extend_from_within(..3);
+		mesh.submeshes = vec![
+			Submesh {
+				name: "a".into(),
+				material: None,
+				indices: 0..mesh.indices.len() - 3,
+			},
+			Submesh {
+				name: "b".into(),
+				material: None,
+				indices: mesh.indices.len() - 3..mesh.indices.len(),
+			},
+		];
+
+		// A triangle with a zero index, collinear points, and a repeat of the one before.
+		assert_eq!(remove_degenerate_triangles(&mut mesh), 3);
+		assert_eq!(mesh.indices.len() / 3, triangles + 2);
+		assert_eq!(mesh.submeshes[0].indices, 0..(triangles + 1) * 3);
+		assert_eq!(mesh.submeshes[1].indices.len(), 3);
+
+		mesh.vertices.push(copy);
+		assert_eq!(remove_unused_vertices(&mut mesh), 1);
+	}
+
+	#[test]
+	fn optimize() {
+		let mut mesh = grid::grid(1.0, 1.0, 64, 64);
+
+		// Shuffle the triangles.
+		let triangles: Vec<[usize; 3]> = mesh
+			.indices
+			.chunks_exact(3)
+			.map(|t| [t[0], t[1], t[2]])
+			.collect();
+		let count = triangles.len();
+		mesh.indices = (0..count)
+			.flat_map(|i| triangles[i * 7919 % count])
+			.collect();
+
+		let before = average_cache_miss_ratio(&mesh.indices, 16);
+		optimize_vertex_cache(&mut mesh);
+		let after = average_cache_miss_ratio(&mesh.indices, 16);
+
+		assert!(after < 0.8 && after < before / 2.0);
+
+		let mut sorted: Vec<_> = mesh.indices.chunks_exact(3).map(|t| t.to_vec()).collect();
+		sorted.sort();
+		let mut expected: Vec<_> = triangles.iter().map(|t| t.to_vec()).collect();
+		expected.sort();
+		assert_eq!(sorted, expected);
+
+		optimize_vertex_fetch(&mut mesh);
+		assert_eq!(&mesh.indices[..3], [0, 1, 2]);
+		assert_eq!(compact_indices(&mesh).unwrap().len(), mesh.indices.len());
+
+		mesh.indices.push(mesh.vertices.len());
+		assert_eq!(compact_indices(&mesh), Err(IndexError::IncompleteTriangle));
+		mesh.indices.extend([0, 0]);
+		assert!(matches!(
+			compact_indices(&mesh),
+			Err(IndexError::OutOfRange { .. })
+		));
+	}
+}
@@ -0,0 +1,642 @@
+//! Convex hulls of point sets with quickhull, and approximate convex decomposition of concave meshes into a set of
+//! hulls, for collision shapes and bounding volumes.
+
+use crate::bvh::{Aabb, Bvh, Ray};
+use crate::mesh::{Mesh, MeshBuilder, normalize_or_zero};
+
+use math::Vec3;
+use math::primitives::{Measure, TriMesh};
+use std::collections::{HashMap, HashSet};
+
+/// Distance within which points count as on a plane, relative to the size of the point set.
+const PLANE_TOLERANCE: f32 = 1e-5;
+
+/// Largest number of split planes that are tried per axis when splitting a part of a decomposition.
+const MAX_SPLIT_CANDIDATES: usize = 16;
+
+/// Relative extra cost of splitting a part across its shortest side instead of its longest.
+const AXIS_BIAS: f32 = 0.1;
+/// Cost of the difference in volume between the halves of a split, relative to the hull volume outside of them.
+/// Without it, shaving thin slices off of a part can look better than splitting it in the middle.
+const BALANCE_WEIGHT: f32 = 0.1;
+/// Offset of the rays that find the inside of a mesh, relative to the voxel size, so that they don't pass exactly
+/// through the vertices and edges of grid-aligned meshes.
+const RAY_JITTER: f32 = 0.0123;
+
+/// Triangle of the hull under construction.
+struct Face {
+	vertices: [usize; 3],
+	normal: Vec3,
+	offset: f32,
+	/// Points that are above the face and not yet in the hull.
+	outside: Vec<usize>,
+	alive: bool,
+}
+
+impl Face {
+	fn new(points: &[Vec3], vertices: [usize; 3]) -> Self {
+		let [a, b, c] = vertices.map(|v| points[v]);
+		let normal = normalize_or_zero((b - a).cross(c - a));
+
+		Self {
+			vertices,
+			normal,
+			offset: normal.dot(a),
+			outside: Vec::new(),
+			alive: true,
+		}
+	}
+
+	fn distance(&self, p: Vec3) -> f32 {
+		self.normal.dot(p) - self.offset
+	}
+
+	fn edges(&self) -> [(usize, usize); 3] {
+		let [a, b, c] = self.vertices;
+		[(a, b), (b, c), (c, a)]
+	}
+}
+
+/// Index of the point that maximizes `key`.
+fn max_by(indices: impl Iterator<Item = usize>, key: impl Fn(usize) -> f32) -> Option<usize> {
+	indices.max_by(|&a, &b| key(a).total_cmp(&key(b)))
+}
+
+/// Adds every point to the face it's farthest above, if it's above any.
+fn assign_points(
+	points: &[Vec3],
+	faces: &mut [Face],
+	candidates: &[usize],
+	tolerance: f32,
+	new: &[usize],
+) {
+	for &point in candidates {
+		let face = max_by(new.iter().copied(), |face| {
+			faces[face].distance(points[point])
+		});
+
+		if let Some(face) = face
+			&& faces[face].distance(points[point]) > tolerance
+		{
+			faces[face].outside.push(point);
+		}
+	}
+}
+
+/// Creates the convex hull of a set of points, with quickhull. Points within a small tolerance of a face are
+/// considered on it, so coplanar and duplicated points don't produce slivers. The vertices of the hull are shared
+/// between its triangles.
+///
+/// Points in a plane give a flat, double-sided polygon, and fewer points or points on a line give an empty mesh.
+/// NaN and infinite points are ignored.
+pub fn convex_hull(points: &[Vec3]) -> Mesh {
+	let points: Vec<Vec3> = points
+		.iter()
+		.copied()
+		.filter(|p| p.x.is_finite() && p.y.is_finite() && p.z.is_finite())
+		.collect();
+	let points = &points[..];
+
+	if points.len() < 3 {
+		return Mesh::new();
+	}
+
+	let tolerance = Aabb::from_points(points.iter().copied()).size().length() * PLANE_TOLERANCE;
+	let all = || 0..points.len();
+
+	// The extreme points along the axes that are farthest apart span the first edge.
+	let extremes: Vec<usize> = [Vec3::X, Vec3::Y, Vec3::Z]
+		.into_iter()
+		.flat_map(|axis| {
+			let key = |i: usize| points[i].dot(*axis);
+			[
+				max_by(all(), key).unwrap(),
+				max_by(all(), |i| -key(i)).unwrap(),
+			]
+		})
+		.collect();
+
+	let (a, b) = extremes
+		.iter()
+		.flat_map(|&a| extremes.iter().map(move |&b| (a, b)))
+		.max_by(|&(a0, b0), &(a1, b1)| {
+			let d0 = points[a0].distance_sq(points[b0]);
+			d0.total_cmp(&points[a1].distance_sq(points[b1]))
+		})
+		.unwrap();
+
+	let direction = points[b] - points[a];
+	let line_distance =
+		|i: usize| (points[i] - points[a]).cross(direction).length() / direction.length();
+	let c = max_by(all(), line_distance).unwrap();
+
+	if direction.length() <= tolerance || line_distance(c) <= tolerance {
+		return Mesh::new();
+	}
+
+	let base = Face::new(points, [a, b, c]);
+	let d = max_by(all(), |i| base.distance(points[i]).abs()).unwrap();
+
+	if base.distance(points[d]).abs() <= tolerance {
+		return planar_hull(points, base.normal, tolerance);
+	}
+
+	// Faces of the first tetrahedron face away from its opposite vertex.
+	let mut faces: Vec<Face> = [
+		([a, b, c], d),
+		([a, d, b], c),
+		([b, d, c], a),
+		([c, d, a], b),
+	]
+	.into_iter()
+	.map(|(mut vertices, opposite)| {
+		if Face::new(points, vertices).distance(points[opposite]) > 0.0 {
+			vertices.swap(1, 2);
+		}
+		Face::new(points, vertices)
+	})
+	.collect();
+
+	// Face of every directed edge.
+	let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+	for (i, face) in faces.iter().enumerate() {
+		for edge in face.edges() {
+			edges.insert(edge, i);
+		}
+	}
+
+	let candidates: Vec<usize> = all().filter(|i| ![a, b, c, d].contains(i)).collect();
+	assign_points(points, &mut faces, &candidates, tolerance, &[0, 1, 2, 3]);
+
+	let mut pending: Vec<usize> = (0..4).collect();
+
+	while let Some(current) = pending.pop() {
+		if !faces[current].alive || faces[current].outside.is_empty() {
+			continue;
+		}
+
+		let face = &faces[current];
+		let eye = max_by(face.outside.iter().copied(), |i| face.distance(points[i])).unwrap();
+		let eye_point = points[eye];
+
+		// Faces that see the eye point, connected to the current one.
+		let mut visible = HashSet::from([current]);
+		let mut stack = vec![current];
+
+		while let Some(face) = stack.pop() {
+			for (a, b) in faces[face].edges() {
+				// An edge whose twin is missing, which only degenerate input can cause, is on the horizon.
+				let Some(&neighbor) = edges.get(&(b, a)) else {
+					continue;
+				};
+
+				if !visible.contains(&neighbor) && faces[neighbor].distance(eye_point) > tolerance {
+					visible.insert(neighbor);
+					stack.push(neighbor);
+				}
+			}
+		}
+
+		let mut horizon = Vec::new();
+		let mut orphans = Vec::new();
+
+		for &face in &visible {
+			for (a, b) in faces[face].edges() {
+				if edges
+					.get(&(b, a))
+					.is_none_or(|neighbor| !visible.contains(neighbor))
+				{
+					horizon.push((a, b));
+				}
+			}
+		}
+
+		for &face in &visible {
+			for edge in faces[face].edges() {
+				edges.remove(&edge);
+			}
+
+			faces[face].alive = false;
+			orphans.extend(faces[face].outside.drain(..).filter(|&i| i != eye));
+		}
+
+		// The horizon is connected to the eye point with faces that keep its winding.
+		let new: Vec<usize> = horizon
+			.into_iter()
+			.map(|(a, b)| {
+				let face = faces.len();
+				faces.push(Face::new(points, [a, b, eye]));
+				for edge in faces[face].edges() {
+					edges.insert(edge, face);
+				}
+				face
+			})
+			.collect();
+
+		assign_points(points, &mut faces, &orphans, tolerance, &new);
+		pending.extend(new);
+	}
+
+	let mut mesh = MeshBuilder::new();
+	let mut vertices = HashMap::new();
+
+	for face in faces.iter().filter(|face| face.alive) {
+		let [a, b, c] = face.vertices.map(|v| {
+			*vertices
+				.entry(v)
+				.or_insert_with(|| mesh.add_vertex(points[v].into()))
+		});
+		mesh.add_triangle(a, b, c);
+	}
+
+	mesh.build()
+}
+
+/// Convex hull of points in a plane with the given normal, with a side facing each way.
+fn planar_hull(points: &[Vec3], normal: Vec3, tolerance: f32) -> Mesh {
+	let u = if normal.x.abs() < 0.9 {
+		*Vec3::X
+	} else {
+		*Vec3::Y
+	};
+	let u = *normal.cross(u).normalize();
+	let v = normal.cross(u);
+
+	let mut sorted: Vec<usize> = (0..points.len()).collect();
+	let project = |i: usize| (points[i].dot(u), points[i].dot(v));
+	sorted.sort_by(|&a, &b| {
+		let (a, b) = (project(a), project(b));
+		a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+	});
+
+	// Monotone chain, counterclockwise around the normal.
+	let cross = |o: usize, a: usize, b: usize| {
+		let (o, a, b) = (project(o), project(a), project(b));
+		(a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+	};
+
+	let mut polygon: Vec<usize> = Vec::new();
+
+	for pass in [sorted.clone(), sorted.into_iter().rev().collect()] {
+		let start = polygon.len();
+
+		for i in pass {
+			while polygon.len() >= start + 2
+				&& cross(polygon[polygon.len() - 2], polygon[polygon.len() - 1], i)
+					<= tolerance * tolerance
+			{
+				polygon.pop();
+			}
+			polygon.push(i);
+		}
+
+		// The last point is the first of the other chain.
+		polygon.pop();
+	}
+
+	let mut mesh = MeshBuilder::new();
+
+	for side in [false, true] {
+		let base = mesh.mesh.vertices.len();
+		for &i in &polygon {
+			mesh.add_vertex(points[i].into());
+		}
+
+		for i in 1..polygon.len().saturating_sub(1) {
+			if side {
+				mesh.add_triangle(base, base + i + 1, base + i);
+			} else {
+				mesh.add_triangle(base, base + i, base + i + 1);
+			}
+		}
+	}
+
+	mesh.build()
+}
+
+/// Volume enclosed by a closed mesh.
+fn volume(mesh: &Mesh) -> f32 {
+	let vertices: Vec<Vec3> = mesh.vertices.iter().map(|vertex| vertex.p).collect();
+	let mesh = TriMesh {
+		vertices: &vertices,
+		indices: &mesh.indices,
+	};
+	mesh.volume()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecompositionSettings {
+	/// Number of voxels along the longest side of the mesh, which is the precision of the decomposition.
+	pub resolution: usize,
+	/// Largest number of hulls.
+	pub max_hulls: usize,
+	/// Parts are split while more than this fraction of the volume of their hull is outside of the mesh.
+	pub max_concavity: f32,
+}
+
+impl Default for DecompositionSettings {
+	fn default() -> Self {
+		Self {
+			resolution: 32,
+			max_hulls: 16,
+			max_concavity: 0.05,
+		}
+	}
+}
+
+/// Voxels inside of a mesh, on a grid with cubic voxels.
+struct Voxels {
+	origin: Vec3,
+	size: f32,
+}
+
+impl Voxels {
+	fn corners(&self, voxel: [usize; 3]) -> impl Iterator<Item = Vec3> + '_ {
+		(0..8).map(move |corner| {
+			let [x, y, z] = [0, 1, 2].map(|axis| (voxel[axis] + (corner >> axis & 1)) as f32);
+			self.origin + Vec3::new(x, y, z) * self.size
+		})
+	}
+
+	/// Hull of a part, from the corners of its voxels on the border of the part.
+	fn hull(&self, part: &HashSet<[usize; 3]>) -> Mesh {
+		let mut points = Vec::new();
+
+		for &voxel in part {
+			let border = [0, 1, 2].into_iter().any(|axis| {
+				if voxel[axis] == 0 {
+					return true;
+				}
+
+				let mut neighbors = [voxel; 2];
+				neighbors[0][axis] -= 1;
+				neighbors[1][axis] += 1;
+				neighbors.iter().any(|neighbor| !part.contains(neighbor))
+			});
+
+			if border {
+				points.extend(self.corners(voxel));
+			}
+		}
+
+		// Corners are shared by up to eight voxels.
+		let mut seen = HashSet::new();
+		points.retain(|p| seen.insert([p.x, p.y, p.z].map(f32::to_bits)));
+
+		convex_hull(&points)
+	}
+
+	/// Fraction of the hull volume that is outside of the part.
+	fn concavity(&self, part: &HashSet<[usize; 3]>, hull: &Mesh) -> f32 {
+		let hull_volume = volume(hull);
+		let part_volume = part.len() as f32 * self.size.powi(3);
+
+		if hull_volume <= 0.0 {
+			return 0.0;
+		}
+
+		(1.0 - part_volume / hull_volume).max(0.0)
+	}
+}
+
+/// Voxels of the inside and the surface of a mesh. The inside is found by casting rays along X and counting the
+/// crossings, so the mesh should be closed.
+fn voxelize(mesh: &Mesh, resolution: usize) -> (Voxels, HashSet<[usize; 3]>) {
+	let bounds = Aabb::from_points(mesh.vertices.iter().map(|vertex| vertex.p));
+	let extent = bounds.size();
+	let size = extent.x.max(extent.y).max(extent.z) / resolution as f32;
+	let counts = [extent.x, extent.y, extent.z].map(|e| ((e / size).ceil() as usize).max(1));
+
+	let voxels = Voxels {
+		origin: bounds.min,
+		size,
+	};
+	let bvh = Bvh::new(mesh);
+	let mut inside = HashSet::new();
+	let center = |x: usize, y: usize, z: usize| {
+		voxels.origin + Vec3::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5) * size
+	};
+
+	for z in 0..counts[2] {
+		for y in 0..counts[1] {
+			// Distances along the ray where it crosses the surface.
+			let jitter = Vec3::new(0.0, RAY_JITTER, RAY_JITTER * 0.7) * size;
+			let start = center(0, y, z) + jitter - *Vec3::X * size;
+			let mut crossings = Vec::new();
+			let mut t = 0.0;
+
+			while let Some(hit) =
+				bvh.intersect(&Ray::new(start + *Vec3::X * t, *Vec3::X), f32::INFINITY)
+			{
+				t += hit.t + size * 1e-4;
+				crossings.push(t);
+			}
+
+			for x in 0..counts[0] {
+				let t = (x + 1) as f32 * size;
+				let odd = crossings.iter().filter(|&&crossing| crossing < t).count() % 2 == 1;
+
+				// Voxels the surface passes through are kept, so that thin parts aren't lost.
+				let near = bvh.closest_point(center(x, y, z), size * 0.87).is_some();
+
+				if odd || near {
+					inside.insert([x, y, z]);
+				}
+			}
+		}
+	}
+
+	(voxels, inside)
+}
+
+/// Splits a part along the axis-aligned plane that minimizes the hull volume outside of the two halves, preferring
+/// halves of similar size.
+fn split(voxels: &Voxels, part: &HashSet<[usize; 3]>) -> Option<[HashSet<[usize; 3]>; 2]> {
+	let mut best: Option<(f32, [HashSet<[usize; 3]>; 2])> = None;
+
+	let min = [0, 1, 2].map(|axis| part.iter().map(|voxel| voxel[axis]).min());
+	let max = [0, 1, 2].map(|axis| part.iter().map(|voxel| voxel[axis]).max());
+	let extent = [0, 1, 2].map(|axis| Some(max[axis]? - min[axis]? + 1));
+	let longest = extent.into_iter().max()??;
+
+	for axis in [0, 1, 2] {
+		let (min, max) = (min[axis]?, max[axis]?);
+		// Splits along the shorter sides cost a little more. Cutting a ring in the plane of the ring reduces the
+		// hull volume as much as cutting it across, but the halves would still span its hole.
+		let bias = 1.0 + AXIS_BIAS * (1.0 - extent[axis]? as f32 / longest as f32);
+		let step = (max - min).div_ceil(MAX_SPLIT_CANDIDATES).max(1);
+
+		for plane in (min + 1..=max).step_by(step) {
+			let (below, above): (HashSet<_>, HashSet<_>) =
+				part.iter().partition(|voxel| voxel[axis] < plane);
+
+			let outside: f32 = [&below, &above]
+				.iter()
+				.map(|half| {
+					let hull_volume = volume(&voxels.hull(half));
+					(hull_volume - half.len() as f32 * voxels.size.powi(3)).max(0.0)
+				})
+				.sum();
+			let imbalance = below.len().abs_diff(above.len()) as f32 * voxels.size.powi(3);
+			let cost = (outside + BALANCE_WEIGHT * imbalance) * bias;
+
+			if best.as_ref().is_none_or(|(best_cost, _)| cost < *best_cost) {
+				best = Some((cost, [below, above]));
+			}
+		}
+	}
+
+	best.map(|(_, halves)| halves)
+}
+
+/// Approximates a closed, concave mesh by a set of convex hulls. The mesh is voxelized, and parts of it are split
+/// by axis-aligned planes until their hulls are close to them or there are `max_hulls` parts. Hulls cover the
+/// mesh, extended by up to a voxel.
+pub fn convex_decomposition(mesh: &Mesh, settings: &DecompositionSettings) -> Vec<Mesh> {
+	if mesh.indices.is_empty() {
+		return Vec::new();
+	}
+
+	let (voxels, inside) = voxelize(mesh, settings.resolution.max(1));
+
+	let hull = voxels.hull(&inside);
+	let concavity = voxels.concavity(&inside, &hull);
+	let mut parts = vec![(inside, hull, concavity)];
+
+	while parts.len() < settings.max_hulls.max(1) {
+		// The part whose hull is the worst fit is split first.
+		let Some((worst, _)) = parts
+			.iter()
+			.enumerate()
+			.filter(|(_, (part, _, concavity))| {
+				*concavity > settings.max_concavity && part.len() > 1
+			})
+			.max_by(|(_, a), (_, b)| a.2.total_cmp(&b.2))
+		else {
+			break;
+		};
+
+		let (part, _, _) = parts.swap_remove(worst);
+		let Some(halves) = split(&voxels, &part) else {
+			break;
+		};
+
+		for half in halves {
+			let hull = voxels.hull(&half);
+			let concavity = voxels.concavity(&half, &hull);
+			parts.push((half, hull, concavity));
+		}
+	}
+
+	parts.into_iter().map(|(_, hull, _)| hull).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::primitives::{sphere, torus};
+
+	#[test]
+	fn hull() {
+		// Points on a grid have many coplanar and collinear points on the faces of the hull.
+		let mut points = Vec::new();
+		for z in 0..5 {
+			for y in 0..5 {
+				for x in 0..5 {
+					points.push(Vec3::new(x as f32, y as f32, z as f32) * 0.5);
+				}
+			}
+		}
+
+		let hull = convex_hull(&points);
+		assert_eq!(hull.vertices.len(), 8);
+		assert_eq!(hull.indices.len(), 36);
+		assert!((volume(&hull) - 8.0).abs() < 1e-4);
+
+		// The hull of a convex mesh has the same shape.
+		let sphere = sphere::sphere(1.0, 24, 12);
+		let points: Vec<Vec3> = sphere.vertices.iter().map(|vertex| vertex.p).collect();
+		let hull = convex_hull(&points);
+		assert!((volume(&hull) - volume(&sphere)).abs() < 1e-4);
+
+		// Points in a plane, with their hull as a polygon with a side facing each way.
+		let square: Vec<Vec3> = points.iter().map(|p| Vec3::new(p.x, p.y, 0.0)).collect();
+		let hull = convex_hull(&square);
+		assert_eq!(hull.indices.len(), 2 * 3 * (24 - 2));
+		assert!(volume(&hull).abs() < 1e-6);
+
+		assert!(
+			convex_hull(&[Vec3::ZERO, *Vec3::X, *Vec3::X * 2.0])
+				.indices
+				.is_empty()
+		);
+	}
+
+	#[test]
+	fn degenerate_points() {
+		let mut points: Vec<Vec3> = (0..8)
+			.map(|corner| {
+				Vec3::new(
+					(corner & 1) as f32,
+					(corner >> 1 & 1) as f32,
+					(corner >> 2) as f32,
+				)
+			})
+			.collect();
+
+		// Duplicated and non-finite points don't change the hull.
+		points.extend_from_within(..);
+		points.extend([
+			Vec3::new(f32::NAN, 0.5, 0.5),
+			Vec3::splat(f32::INFINITY),
+			Vec3::new(0.5, f32::NEG_INFINITY, 0.5),
+		]);
+		points.extend_from_within(..4);
+
+		let hull = convex_hull(&points);
+		assert_eq!(hull.vertices.len(), 8);
+		assert_eq!(hull.indices.len(), 36);
+		assert!((volume(&hull) - 1.0).abs() < 1e-5);
+
+		// The same with points in a plane.
+		let square: Vec<Vec3> = points.iter().map(|p| Vec3::new(p.x, p.y, 0.0)).collect();
+		let hull = convex_hull(&square);
+		assert_eq!(hull.indices.len(), 2 * 3 * 2);
+
+		assert!(convex_hull(&[Vec3::splat(f32::NAN); 4]).indices.is_empty());
+	}
+
+	#[test]
+	fn decomposition() {
+		let torus = torus::torus(32, 16, 1.0, 0.25);
+		let hulls = convex_decomposition(
+			&torus,
+			&DecompositionSettings {
+				resolution: 20,
+				..Default::default()
+			},
+		);
+
+		assert!(hulls.len() > 4 && hulls.len() <= 16);
+		assert!(hulls.iter().map(volume).sum::<f32>() > volume(&torus));
+
+		let inside = |hull: &Mesh, p: Vec3| {
+			hull.indices.chunks_exact(3).all(|t| {
+				let [a, b, c] = [0, 1, 2].map(|i| hull.vertices[t[i]].p);
+				(b - a).cross(c - a).dot(p - a) <= 1e-4
+			})
+		};
+
+		// The hulls cover the torus, but not the hole in it.
+		for vertex in &torus.vertices {
+			assert!(hulls.iter().any(|hull| inside(hull, vertex.p)));
+		}
+
+		for i in 0..8 {
+			let (sin, cos) = (i as f32 * std::f32::consts::PI / 4.0).sin_cos();
+			for p in [Vec3::ZERO, Vec3::new(cos, sin, 0.0) * 0.5] {
+				assert!(hulls.iter().all(|hull| !inside(hull, p)));
+			}
+		}
+	}
+}
@@ -0,0 +1,61 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Error while reading a mesh file.
+#[derive(Debug)]
+pub enum ParseError {
+	Io(io::Error),
+	/// The file is malformed. `line` is set for text formats.
+	Invalid {
+		line: Option<usize>,
+		message: String,
+	},
+}
+
+impl ParseError {
+	pub(crate) fn at_line(line: usize, message: impl Into<String>) -> Self {
+		Self::Invalid {
+			line: Some(line),
+			message: message.into(),
+		}
+	}
+
+	pub(crate) fn invalid(message: impl Into<String>) -> Self {
+		Self::Invalid {
+			line: None,
+			message: message.into(),
+		}
+	}
+}
+
+impl fmt::Display for ParseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Io(error) => write!(f, "{error}"),
+			Self::Invalid {
+				line: Some(line),
+				message,
+			} => write!(f, "Line {line}: {message}"),
+			Self::Invalid {
+				line: None,
+				message,
+			} => write!(f, "{message}"),
+		}
+	}
+}
+
+impl Error for ParseError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			Self::Io(error) => Some(error),
+			Self::Invalid { .. } => None,
+		}
+	}
+}
+
+impl From<io::Error> for ParseError {
+	fn from(error: io::Error) -> Self {
+		Self::Io(error)
+	}
+}
@@ -0,0 +1,1287 @@
+//! glTF 2.0 files, both `.gltf` with external or embedded buffers and binary `.glb`.
+
+use super::ParseError;
+use super::json::Json;
+use crate::mesh::{
+	AttributeValue, COLOR, Mesh, NormalMode, Submesh, TANGENT, Vertex, VertexGroups,
+	calculate_normals, calculate_tangents, calculate_vert_normals, uv_name,
+};
+use crate::morph::MorphTarget;
+
+use math::{Mat3, Mat4, Quaternion, UnitQuaternion, Vec2, Vec3, Vec4, transform::Transform3};
+use std::io;
+
+/// Contents of a glTF file.
+///
+/// Nodes, meshes, skins, cameras and lights are stored in file order, so they can be referenced by index.
+pub struct Gltf {
+	pub nodes: Vec<GltfNode>,
+	pub meshes: Vec<GltfMesh>,
+	pub skins: Vec<GltfSkin>,
+	pub cameras: Vec<GltfCamera>,
+	pub lights: Vec<GltfLight>,
+	/// Root nodes of the default scene, or of the first scene if there is no default.
+	pub root_nodes: Vec<usize>,
+}
+
+pub struct GltfNode {
+	pub name: String,
+	/// Transform relative to the parent node.
+	pub transform: Transform3,
+	pub children: Vec<usize>,
+	pub mesh: Option<usize>,
+	pub skin: Option<usize>,
+	pub camera: Option<usize>,
+	/// Light of the `KHR_lights_punctual` extension.
+	pub light: Option<usize>,
+}
+
+pub struct GltfMesh {
+	pub name: String,
+	/// All primitives merged into a single mesh, with one submesh per primitive that is named after its material.
+	///
+	/// Skinned meshes have a vertex group per joint of their skin. Morph targets are dense and named after the
+	/// `targetNames` extra if present. Tangent deltas are ignored.
+	pub mesh: Mesh,
+	/// Default morph target weights.
+	pub weights: Vec<f32>,
+}
+
+pub struct GltfSkin {
+	pub name: String,
+	/// Node of every joint, in the order of the vertex groups.
+	pub joints: Vec<usize>,
+	pub inverse_bind_matrices: Vec<Mat4>,
+	/// Common root node of the joints.
+	pub skeleton: Option<usize>,
+}
+
+pub enum GltfCamera {
+	Perspective {
+		/// Vertical field of view in radians.
+		yfov: f32,
+		aspect_ratio: Option<f32>,
+		znear: f32,
+		zfar: Option<f32>,
+	},
+	Orthographic {
+		xmag: f32,
+		ymag: f32,
+		znear: f32,
+		zfar: f32,
+	},
+}
+
+pub struct GltfLight {
+	pub name: String,
+	pub kind: GltfLightKind,
+	/// Linear RGB.
+	pub color: [f32; 3],
+	/// Candela for point and spot lights, lux for directional lights.
+	pub intensity: f32,
+	pub range: Option<f32>,
+}
+
+pub enum GltfLightKind {
+	Directional,
+	Point,
+	Spot {
+		inner_cone_angle: f32,
+		outer_cone_angle: f32,
+	},
+}
+
+const GLB_MAGIC: u32 = 0x46546C67;
+const GLB_CHUNK_JSON: u32 = 0x4E4F534A;
+const GLB_CHUNK_BIN: u32 = 0x004E4942;
+
+impl Gltf {
+	/// Reads a `.gltf` or `.glb` file. `read_uri` reads external buffers, with URIs relative to the file.
+	pub fn read(
+		bytes: &[u8],
+		read_uri: impl Fn(&str) -> io::Result<Vec<u8>>,
+	) -> Result<Self, ParseError> {
+		let (json, bin) = parse_json(bytes)?;
+
+		let buffers = json
+			.get("buffers")
+			.map_or(&[][..], Json::as_array)
+			.iter()
+			.enumerate()
+			.map(
+				|(i, buffer)| match buffer.get("uri").and_then(Json::as_str) {
+					Some(uri) => match uri.strip_prefix("data:") {
+						Some(data) => decode_data_uri(data),
+						None => Ok(read_uri(&decode_uri(uri))?),
+					},
+					// Only the first buffer of a GLB file may refer to the binary chunk.
+					None if i == 0 => bin
+						.map(<[u8]>::to_vec)
+						.ok_or_else(|| ParseError::invalid("Buffer without data")),
+					None => Err(ParseError::invalid("Buffer without data")),
+				},
+			)
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let accessors = Accessors {
+			json: &json,
+			buffers,
+		};
+
+		let mut gltf = Gltf {
+			nodes: array(&json, "nodes").iter().map(read_node).collect(),
+			meshes: array(&json, "meshes")
+				.iter()
+				.map(|mesh| read_mesh(&json, &accessors, mesh))
+				.collect::<Result<_, _>>()?,
+			skins: array(&json, "skins")
+				.iter()
+				.map(|skin| read_skin(&accessors, skin))
+				.collect::<Result<_, _>>()?,
+			cameras: array(&json, "cameras")
+				.iter()
+				.map(read_camera)
+				.collect::<Result<_, _>>()?,
+			lights: json
+				.get("extensions")
+				.and_then(|extensions| extensions.get("KHR_lights_punctual"))
+				.map_or(&[][..], |lights| array(lights, "lights"))
+				.iter()
+				.map(read_light)
+				.collect::<Result<_, _>>()?,
+			root_nodes: Vec::new(),
+		};
+
+		let scene = json.get("scene").and_then(Json::as_usize).unwrap_or(0);
+		gltf.root_nodes = array(&json, "scenes")
+			.get(scene)
+			.map_or(&[][..], |scene| array(scene, "nodes"))
+			.iter()
+			.filter_map(Json::as_usize)
+			.collect();
+
+		gltf.validate()?;
+		gltf.name_vertex_groups();
+
+		Ok(gltf)
+	}
+
+	fn validate(&self) -> Result<(), ParseError> {
+		let check = |index: Option<usize>, count: usize, kind: &str| match index {
+			Some(index) if index >= count => Err(ParseError::invalid(format!(
+				"Reference to {kind} {index} is out of range"
+			))),
+			_ => Ok(()),
+		};
+
+		for node in &self.nodes {
+			check(node.mesh, self.meshes.len(), "mesh")?;
+			check(node.skin, self.skins.len(), "skin")?;
+			check(node.camera, self.cameras.len(), "camera")?;
+			check(node.light, self.lights.len(), "light")?;
+
+			for &child in &node.children {
+				check(Some(child), self.nodes.len(), "node")?;
+			}
+		}
+
+		for skin in &self.skins {
+			for &joint in &skin.joints {
+				check(Some(joint), self.nodes.len(), "node")?;
+			}
+		}
+
+		// Vertex groups are the joints of the skin the mesh is used with.
+		for node in &self.nodes {
+			let (Some(mesh), Some(skin)) = (node.mesh, node.skin) else {
+				continue;
+			};
+
+			for &(joint, _) in &self.meshes[mesh].mesh.vertex_groups.values {
+				check(Some(joint), self.skins[skin].joints.len(), "joint")?;
+			}
+		}
+
+		for &root in &self.root_nodes {
+			check(Some(root), self.nodes.len(), "node")?;
+		}
+
+		// The hierarchy must be a forest: every node has at most one parent, and all nodes are reached from the
+		// nodes without a parent. Nodes that aren't are part of, or below, a cycle.
+		let mut has_parent = vec![false; self.nodes.len()];
+
+		for node in &self.nodes {
+			for &child in &node.children {
+				if std::mem::replace(&mut has_parent[child], true) {
+					return Err(ParseError::invalid(format!(
+						"Node {child} has more than one parent"
+					)));
+				}
+			}
+		}
+
+		let mut stack: Vec<usize> = (0..self.nodes.len()).filter(|&i| !has_parent[i]).collect();
+		let mut reached = 0;
+
+		while let Some(node) = stack.pop() {
+			reached += 1;
+			stack.extend(&self.nodes[node].children);
+		}
+
+		if reached < self.nodes.len() {
+			return Err(ParseError::invalid("Node hierarchy contains a cycle"));
+		}
+
+		Ok(())
+	}
+
+	/// Names the vertex groups of skinned meshes after the joints of the first skin they are used with.
+	fn name_vertex_groups(&mut self) {
+		for node in &self.nodes {
+			let (Some(mesh), Some(skin)) = (node.mesh, node.skin) else {
+				continue;
+			};
+
+			let groups = &mut self.meshes[mesh].mesh.vertex_groups;
+
+			if groups.names.is_empty() && !groups.values.is_empty() {
+				groups.names = self.skins[skin]
+					.joints
+					.iter()
+					.map(|&joint| self.nodes[joint].name.clone())
+					.collect();
+			}
+		}
+	}
+
+	/// URIs of the external buffers of a `.gltf` or `.glb` file, as passed to `read_uri` by [`Gltf::read`].
+	pub fn external_uris(bytes: &[u8]) -> Result<Vec<String>, ParseError> {
+		let (json, _) = parse_json(bytes)?;
+
+		Ok(array(&json, "buffers")
+			.iter()
+			.filter_map(|buffer| buffer.get("uri").and_then(Json::as_str))
+			.filter(|uri| !uri.starts_with("data:"))
+			.map(decode_uri)
+			.collect())
+	}
+
+	/// World transforms of all nodes of the scene, together with the path of the node like `/Armature/Hips`.
+	pub fn world_transforms(&self) -> Vec<(usize, String, Transform3)> {
+		let mut result = Vec::new();
+		let mut stack: Vec<(usize, String, Transform3)> = self
+			.root_nodes
+			.iter()
+			.rev()
+			.map(|&root| (root, String::new(), Transform3::IDENTITY))
+			.collect();
+
+		while let Some((index, parent_path, parent_transform)) = stack.pop() {
+			let node = &self.nodes[index];
+			let name = match node.name.as_str() {
+				"" => format!("node{index}"),
+				name => name.to_string(),
+			};
+
+			let path = format!("{parent_path}/{name}");
+			let transform = parent_transform * node.transform;
+
+			for &child in node.children.iter().rev() {
+				stack.push((child, path.clone(), transform));
+			}
+
+			result.push((index, path, transform));
+		}
+
+		result
+	}
+}
+
+fn array<'a>(json: &'a Json, key: &str) -> &'a [Json] {
+	json.get(key).map_or(&[], Json::as_array)
+}
+
+fn index(json: &Json, key: &str) -> Option<usize> {
+	json.get(key).and_then(Json::as_usize)
+}
+
+fn name(json: &Json) -> String {
+	json.get("name")
+		.and_then(Json::as_str)
+		.unwrap_or_default()
+		.to_string()
+}
+
+/// Splits a GLB file into its JSON and binary chunk.
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), ParseError> {
+	let read_u32 = |offset: usize| {
+		bytes
+			.get(offset..offset + 4)
+			.map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+			.ok_or_else(|| ParseError::invalid("Truncated GLB file"))
+	};
+
+	if read_u32(4)? != 2 {
+		return Err(ParseError::invalid("Unsupported GLB version"));
+	}
+
+	let length = (read_u32(8)? as usize).min(bytes.len());
+
+	let mut json = None;
+	let mut bin = None;
+	let mut offset = 12;
+
+	while offset + 8 <= length {
+		let chunk_length = read_u32(offset)? as usize;
+		let chunk_type = read_u32(offset + 4)?;
+
+		let chunk = bytes
+			.get(offset + 8..offset + 8 + chunk_length)
+			.ok_or_else(|| ParseError::invalid("Truncated GLB chunk"))?;
+
+		match chunk_type {
+			GLB_CHUNK_JSON if json.is_none() => json = Some(chunk),
+			GLB_CHUNK_BIN if bin.is_none() => bin = Some(chunk),
+			_ => {}
+		}
+
+		// Chunks are padded to 4 bytes.
+		offset += 8 + chunk_length.next_multiple_of(4);
+	}
+
+	let json = json.ok_or_else(|| ParseError::invalid("GLB file without JSON chunk"))?;
+	Ok((json, bin))
+}
+
+/// Parses the JSON of a `.gltf` or `.glb` file, and returns it with the binary chunk of a GLB file.
+fn parse_json(bytes: &[u8]) -> Result<(Json, Option<&[u8]>), ParseError> {
+	let (json, bin) = if bytes.starts_with(&GLB_MAGIC.to_le_bytes()) {
+		split_glb(bytes)?
+	} else {
+		(bytes, None)
+	};
+
+	let json = std::str::from_utf8(json)
+		.map_err(|_| ParseError::invalid("glTF JSON is not valid UTF-8"))?;
+	let json = Json::parse(json)?;
+
+	let version = json
+		.get("asset")
+		.and_then(|asset| asset.get("version"))
+		.and_then(Json::as_str)
+		.unwrap_or_default();
+
+	if !version.starts_with("2.") {
+		return Err(ParseError::invalid(format!(
+			"Unsupported glTF version {version}"
+		)));
+	}
+
+	Ok((json, bin))
+}
+
+/// Decodes a base64 `data:` URI, without the `data:` prefix.
+fn decode_data_uri(data: &str) -> Result<Vec<u8>, ParseError> {
+	let (_, encoded) = data
+		.split_once(";base64,")
+		.ok_or_else(|| ParseError::invalid("Only base64 data URIs are supported"))?;
+
+	let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+	let mut bits = 0u32;
+	let mut bit_count = 0;
+
+	for c in encoded.bytes().take_while(|&c| c != b'=') {
+		let value = match c {
+			b'A'..=b'Z' => c - b'A',
+			b'a'..=b'z' => c - b'a' + 26,
+			b'0'..=b'9' => c - b'0' + 52,
+			b'+' => 62,
+			b'/' => 63,
+			_ => return Err(ParseError::invalid("Invalid base64 data")),
+		};
+
+		bits = (bits << 6) | value as u32;
+		bit_count += 6;
+
+		if bit_count >= 8 {
+			bit_count -= 8;
+			bytes.push((bits >> bit_count) as u8);
+		}
+	}
+
+	Ok(bytes)
+}
+
+/// Decodes percent-encoded characters of a relative URI, like `%20` for spaces.
+fn decode_uri(uri: &str) -> String {
+	let bytes = uri.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+
+	while i < bytes.len() {
+		let escaped = (bytes[i] == b'%')
+			.then(|| bytes.get(i + 1..i + 3))
+			.flatten()
+			.and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+
+		match escaped {
+			Some(byte) => {
+				decoded.push(byte);
+				i += 3;
+			}
+			None => {
+				decoded.push(bytes[i]);
+				i += 1;
+			}
+		}
+	}
+
+	String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn read_node(json: &Json) -> GltfNode {
+	let transform = match json.get("matrix").and_then(Json::as_f32_array::<16>) {
+		Some(matrix) => decompose(&matrix),
+		None => {
+			let [tx, ty, tz] = json
+				.get("translation")
+				.and_then(Json::as_f32_array)
+				.unwrap_or([0.0; 3]);
+			let [i, j, k, w] = json
+				.get("rotation")
+				.and_then(Json::as_f32_array)
+				.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+			let [sx, sy, sz] = json
+				.get("scale")
+				.and_then(Json::as_f32_array)
+				.unwrap_or([1.0; 3]);
+
+			Transform3 {
+				translation: Vec3::new(tx, ty, tz),
+				rotation: Quaternion { i, j, k, w }.normalize(),
+				scale: Vec3::new(sx, sy, sz),
+			}
+		}
+	};
+
+	GltfNode {
+		name: name(json),
+		transform,
+		children: array(json, "children")
+			.iter()
+			.filter_map(Json::as_usize)
+			.collect(),
+		mesh: index(json, "mesh"),
+		skin: index(json, "skin"),
+		camera: index(json, "camera"),
+		light: json
+			.get("extensions")
+			.and_then(|extensions| extensions.get("KHR_lights_punctual"))
+			.and_then(|light| index(light, "light")),
+	}
+}
+
+/// Splits a column-major matrix into translation, rotation and scale. Shear is lost.
+fn decompose(m: &[f32; 16]) -> Transform3 {
+	let column = |c: usize| Vec3::new(m[c * 4], m[c * 4 + 1], m[c * 4 + 2]);
+	let (x, y, z) = (column(0), column(1), column(2));
+
+	let mut scale = Vec3::new(x.length(), y.length(), z.length());
+
+	// A negative determinant is a mirroring, which is folded into the x scale.
+	if x.cross(y).dot(z) < 0.0 {
+		scale.x = -scale.x;
+	}
+
+	let safe_div = |v: Vec3, s: f32| if s != 0.0 { v * (1.0 / s) } else { v };
+	let (x, y, z) = (
+		safe_div(x, scale.x),
+		safe_div(y, scale.y),
+		safe_div(z, scale.z),
+	);
+
+	Transform3 {
+		translation: Vec3::new(m[12], m[13], m[14]),
+		rotation: UnitQuaternion::from_matrix3(Mat3::from_axes(x, y, z)),
+		scale,
+	}
+}
+
+/// Typed views into the buffers of a glTF file.
+struct Accessors<'a> {
+	json: &'a Json,
+	buffers: Vec<Vec<u8>>,
+}
+
+/// Elements of an accessor, with `components` values per element.
+struct AccessorData {
+	values: Vec<f64>,
+	components: usize,
+}
+
+impl AccessorData {
+	fn vec2(&self) -> Vec<Vec2> {
+		self.elements()
+			.map(|e| Vec2::new(e[0] as f32, e[1] as f32))
+			.collect()
+	}
+
+	fn vec3(&self) -> Vec<Vec3> {
+		self.elements()
+			.map(|e| Vec3::new(e[0] as f32, e[1] as f32, e[2] as f32))
+			.collect()
+	}
+
+	/// Elements as [`Vec4`], with `w` set to 1 for elements with 3 components like RGB colors.
+	fn vec4(&self) -> Vec<Vec4> {
+		self.elements()
+			.map(|e| {
+				let w = e.get(3).copied().unwrap_or(1.0);
+				Vec4::new(e[0] as f32, e[1] as f32, e[2] as f32, w as f32)
+			})
+			.collect()
+	}
+
+	fn elements(&self) -> impl Iterator<Item = &[f64]> {
+		self.values.chunks_exact(self.components)
+	}
+}
+
+impl Accessors<'_> {
+	fn read(&self, accessor: usize) -> Result<AccessorData, ParseError> {
+		let json = array(self.json, "accessors")
+			.get(accessor)
+			.ok_or_else(|| ParseError::invalid(format!("Accessor {accessor} is out of range")))?;
+
+		let components = match json.get("type").and_then(Json::as_str) {
+			Some("SCALAR") => 1,
+			Some("VEC2") => 2,
+			Some("VEC3") => 3,
+			Some("VEC4") | Some("MAT2") => 4,
+			Some("MAT3") => 9,
+			Some("MAT4") => 16,
+			_ => return Err(ParseError::invalid("Invalid accessor type")),
+		};
+
+		let count = index(json, "count").unwrap_or(0);
+
+		// Every element takes up at least a byte, so larger counts can only come from a malformed file. This also
+		// bounds accessors without a buffer view, which are allocated as is.
+		if count > self.buffers.iter().map(Vec::len).sum() {
+			return Err(ParseError::invalid(format!(
+				"Accessor {accessor} has more elements than the buffers have bytes"
+			)));
+		}
+
+		let component_type = index(json, "componentType").unwrap_or(0);
+		let normalized = json.get("normalized") == Some(&Json::Bool(true));
+
+		// Accessors without a buffer view are all zeros.
+		let mut values = match index(json, "bufferView") {
+			Some(view) => self.read_view(
+				view,
+				index(json, "byteOffset").unwrap_or(0),
+				count,
+				components,
+				component_type,
+				normalized,
+			)?,
+			None => vec![0.0; count * components],
+		};
+
+		if let Some(sparse) = json.get("sparse") {
+			let sparse_count = index(sparse, "count").unwrap_or(0);
+			let indices = sparse.get("indices").unwrap_or(&Json::Null);
+			let sparse_values = sparse.get("values").unwrap_or(&Json::Null);
+
+			let indices = self.read_view(
+				index(indices, "bufferView").unwrap_or(usize::MAX),
+				index(indices, "byteOffset").unwrap_or(0),
+				sparse_count,
+				1,
+				index(indices, "componentType").unwrap_or(0),
+				false,
+			)?;
+			let sparse_values = self.read_view(
+				index(sparse_values, "bufferView").unwrap_or(usize::MAX),
+				index(sparse_values, "byteOffset").unwrap_or(0),
+				sparse_count,
+				components,
+				component_type,
+				normalized,
+			)?;
+
+			for (i, &element) in indices.iter().enumerate() {
+				let element = element as usize;
+
+				if element >= count {
+					return Err(ParseError::invalid("Sparse accessor index is out of range"));
+				}
+
+				values[element * components..(element + 1) * components]
+					.copy_from_slice(&sparse_values[i * components..(i + 1) * components]);
+			}
+		}
+
+		Ok(AccessorData { values, components })
+	}
+
+	fn read_view(
+		&self,
+		view: usize,
+		offset: usize,
+		count: usize,
+		components: usize,
+		component_type: usize,
+		normalized: bool,
+	) -> Result<Vec<f64>, ParseError> {
+		let view_json = array(self.json, "bufferViews")
+			.get(view)
+			.ok_or_else(|| ParseError::invalid(format!("Buffer view {view} is out of range")))?;
+
+		let buffer = index(view_json, "buffer")
+			.and_then(|buffer| self.buffers.get(buffer))
+			.ok_or_else(|| ParseError::invalid("Buffer is out of range"))?;
+
+		let view_offset = index(view_json, "byteOffset").unwrap_or(0);
+		let view_length = index(view_json, "byteLength").unwrap_or(0);
+
+		let view = view_offset
+			.checked_add(view_length)
+			.and_then(|end| buffer.get(view_offset..end))
+			.ok_or_else(|| ParseError::invalid("Buffer view is out of range"))?;
+
+		let size = match component_type {
+			5120 | 5121 => 1,
+			5122 | 5123 => 2,
+			5125 | 5126 => 4,
+			_ => return Err(ParseError::invalid("Invalid accessor component type")),
+		};
+
+		let stride = match index(view_json, "byteStride") {
+			Some(stride) if stride > 0 => stride,
+			_ => size * components,
+		};
+
+		// The last element must fit into the view, before anything is allocated for the elements.
+		let end = count
+			.checked_sub(1)
+			.map_or(Some(0), |last| {
+				last.checked_mul(stride)?
+					.checked_add(offset)?
+					.checked_add(size * components)
+			})
+			.ok_or_else(|| ParseError::invalid("Accessor is out of range"))?;
+
+		if end > view.len() {
+			return Err(ParseError::invalid("Accessor is out of range"));
+		}
+
+		let mut values = Vec::with_capacity(count * components);
+
+		for element in 0..count {
+			for component in 0..components {
+				let start = offset + element * stride + component * size;
+				let b = view
+					.get(start..start + size)
+					.ok_or_else(|| ParseError::invalid("Accessor is out of range"))?;
+
+				let value = match (component_type, normalized) {
+					(5120, false) => b[0] as i8 as f64,
+					(5120, true) => (b[0] as i8 as f64 / 127.0).max(-1.0),
+					(5121, false) => b[0] as f64,
+					(5121, true) => b[0] as f64 / 255.0,
+					(5122, false) => i16::from_le_bytes([b[0], b[1]]) as f64,
+					(5122, true) => (i16::from_le_bytes([b[0], b[1]]) as f64 / 32767.0).max(-1.0),
+					(5123, false) => u16::from_le_bytes([b[0], b[1]]) as f64,
+					(5123, true) => u16::from_le_bytes([b[0], b[1]]) as f64 / 65535.0,
+					(5125, _) => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+					_ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+				};
+
+				values.push(value);
+			}
+		}
+
+		Ok(values)
+	}
+}
+
+fn read_mesh(json: &Json, accessors: &Accessors, mesh_json: &Json) -> Result<GltfMesh, ParseError> {
+	let mut mesh = Mesh::new();
+	// Whether each vertex has a normal in the file, the others are calculated.
+	let mut has_normal = Vec::new();
+	let mut skin_weights: Vec<Vec<(usize, f32)>> = Vec::new();
+
+	let target_names: Vec<String> = match mesh_json
+		.get("extras")
+		.map(|extras| array(extras, "targetNames"))
+	{
+		Some(names) if !names.is_empty() => names
+			.iter()
+			.map(|name| name.as_str().unwrap_or_default().to_string())
+			.collect(),
+		_ => {
+			let count = array(mesh_json, "primitives")
+				.first()
+				.map_or(0, |primitive| array(primitive, "targets").len());
+			(0..count).map(|i| format!("target{i}")).collect()
+		}
+	};
+	let mut morph_targets: Vec<MorphTarget> = target_names
+		.into_iter()
+		.map(|name| MorphTarget::dense(name, Vec::new(), Vec::new()))
+		.collect();
+
+	for (primitive_index, primitive) in array(mesh_json, "primitives").iter().enumerate() {
+		let attributes = primitive.get("attributes").unwrap_or(&Json::Null);
+
+		let Some(positions) = index(attributes, "POSITION") else {
+			continue;
+		};
+		let positions = accessors.read(positions)?.vec3();
+
+		let base = mesh.vertices.len();
+		let count = positions.len();
+
+		let indices: Vec<usize> = match index(primitive, "indices") {
+			Some(indices) => accessors
+				.read(indices)?
+				.values
+				.iter()
+				.map(|&i| i as usize)
+				.collect(),
+			None => (0..count).collect(),
+		};
+
+		if let Some(&index) = indices.iter().find(|&&i| i >= count) {
+			return Err(ParseError::invalid(format!(
+				"Vertex index {index} is out of range for {count} vertices"
+			)));
+		}
+
+		let triangles = match index(primitive, "mode").unwrap_or(4) {
+			4 => indices,
+			5 => (2..indices.len())
+				.flat_map(|i| {
+					// Every other triangle of a strip is flipped to keep the winding.
+					if i % 2 == 0 {
+						[indices[i - 2], indices[i - 1], indices[i]]
+					} else {
+						[indices[i - 1], indices[i - 2], indices[i]]
+					}
+				})
+				.collect(),
+			6 => (2..indices.len())
+				.flat_map(|i| [indices[0], indices[i - 1], indices[i]])
+				.collect(),
+			// Points and lines have no surface to render.
+			_ => continue,
+		};
+
+		let normals = match index(attributes, "NORMAL") {
+			Some(normals) => Some(accessors.read(normals)?.vec3()),
+			None => None,
+		};
+
+		for (i, &p) in positions.iter().enumerate() {
+			let n = normals.as_ref().and_then(|normals| normals.get(i).copied());
+			mesh.vertices.push(Vertex {
+				p,
+				n: n.unwrap_or(Vec3::ZERO),
+			});
+			has_normal.push(n.is_some());
+		}
+
+		let start = mesh.indices.len();
+		mesh.indices
+			.extend(triangles.iter().map(|&index| base + index));
+
+		// Unnamed materials are named after their index, so that they stay apart.
+		let material = index(primitive, "material").and_then(|material| {
+			match name(array(json, "materials").get(material)?) {
+				name if name.is_empty() => Some(format!("material{material}")),
+				name => Some(name),
+			}
+		});
+
+		mesh.submeshes.push(Submesh {
+			name: format!("primitive{primitive_index}"),
+			material,
+			indices: start..mesh.indices.len(),
+		});
+
+		for (name, accessor) in attributes.as_object() {
+			let Some(accessor) = accessor.as_usize() else {
+				continue;
+			};
+
+			if let Some(set) = name.strip_prefix("TEXCOORD_") {
+				let Ok(set) = set.parse::<usize>() else {
+					continue;
+				};
+				append(
+					&mut mesh,
+					&uv_name(set),
+					base,
+					accessors.read(accessor)?.vec2(),
+				);
+			} else if name == "TANGENT" {
+				append(&mut mesh, TANGENT, base, accessors.read(accessor)?.vec4());
+			} else if name == "COLOR_0" {
+				append(&mut mesh, COLOR, base, accessors.read(accessor)?.vec4());
+			} else if let Some(set) = name.strip_prefix("JOINTS_") {
+				let weights = index(attributes, &format!("WEIGHTS_{set}"))
+					.ok_or_else(|| ParseError::invalid(format!("{name} without weights")))?;
+
+				let joints = accessors.read(accessor)?;
+				let weights = accessors.read(weights)?;
+
+				if joints.elements().count() != count || weights.elements().count() != count {
+					return Err(ParseError::invalid(format!(
+						"{name} or its weights don't have an element for each of the {count} vertices"
+					)));
+				}
+
+				skin_weights.resize(base + count, Vec::new());
+
+				for (i, (joints, weights)) in joints.elements().zip(weights.elements()).enumerate()
+				{
+					for (&joint, &weight) in joints.iter().zip(weights) {
+						if weight > 0.0 {
+							skin_weights[base + i].push((joint as usize, weight as f32));
+						}
+					}
+				}
+			}
+		}
+
+		// All primitives have the same targets, deltas of vertices of other primitives are zero.
+		for (morph_target, target) in morph_targets.iter_mut().zip(array(primitive, "targets")) {
+			for (attribute, deltas) in [
+				("POSITION", &mut morph_target.positions),
+				("NORMAL", &mut morph_target.normals),
+			] {
+				if let Some(accessor) = index(target, attribute) {
+					deltas.resize(base, Vec3::ZERO);
+					deltas.extend(accessors.read(accessor)?.vec3());
+				}
+			}
+		}
+	}
+
+	let vertex_count = mesh.vertices.len();
+	mesh.attributes.resize(vertex_count);
+
+	for target in &mut morph_targets {
+		target.positions.resize(vertex_count, Vec3::ZERO);
+		if !target.normals.is_empty() {
+			target.normals.resize(vertex_count, Vec3::ZERO);
+		}
+	}
+	mesh.morph_targets = morph_targets;
+
+	let flat = !has_normal.is_empty() && has_normal.iter().all(|has_normal| !has_normal);
+
+	if !flat && has_normal.iter().any(|has_normal| !has_normal) {
+		let normals: Vec<Vec3> = mesh.vertices.iter().map(|vertex| vertex.n).collect();
+		calculate_vert_normals(&mut mesh);
+
+		for ((vertex, normal), has_normal) in mesh.vertices.iter_mut().zip(normals).zip(has_normal)
+		{
+			if has_normal {
+				vertex.n = normal;
+			}
+		}
+	}
+
+	if !skin_weights.is_empty() {
+		skin_weights.resize(vertex_count, Vec::new());
+		mesh.vertex_groups = vertex_groups(&skin_weights);
+	}
+
+	// glTF requires flat normals for primitives without normals. This splits vertices, so it comes after all
+	// channels are added.
+	if flat {
+		calculate_normals(&mut mesh, NormalMode::Flat);
+	}
+
+	// glTF asks for MikkTSpace tangents when none are given, which these approximate.
+	if mesh.attributes.tangents().is_none() {
+		calculate_tangents(&mut mesh);
+	}
+
+	// A single primitive without material is the whole mesh.
+	if let [submesh] = mesh.submeshes.as_slice()
+		&& submesh.material.is_none()
+	{
+		mesh.submeshes.clear();
+	}
+
+	Ok(GltfMesh {
+		name: name(mesh_json),
+		mesh,
+		weights: array(mesh_json, "weights")
+			.iter()
+			.filter_map(Json::as_f32)
+			.collect(),
+	})
+}
+
+/// Appends the values of a primitive to an attribute channel, padding vertices of previous primitives with zero.
+fn append<T: AttributeValue>(mesh: &mut Mesh, name: &str, base: usize, values: Vec<T>) {
+	if mesh.attributes.get_untyped(name).is_none() {
+		mesh.attributes.insert::<T>(name, Vec::new());
+	}
+
+	if let Some(channel) = mesh
+		.attributes
+		.get_untyped_mut(name)
+		.and_then(T::values_mut)
+	{
+		channel.resize(base, T::ZERO);
+		channel.extend(values);
+	}
+}
+
+/// Vertex groups from the joint weights of every vertex, normalized so that they sum to 1.
+fn vertex_groups(weights: &[Vec<(usize, f32)>]) -> VertexGroups {
+	let mut groups = VertexGroups {
+		names: Vec::new(),
+		lookup: vec![0],
+		values: Vec::new(),
+	};
+
+	for vertex in weights {
+		let total: f32 = vertex.iter().map(|(_, weight)| weight).sum();
+
+		for &(joint, weight) in vertex {
+			groups.values.push((joint, weight / total));
+		}
+
+		groups.lookup.push(groups.values.len());
+	}
+
+	groups
+}
+
+fn read_skin(accessors: &Accessors, json: &Json) -> Result<GltfSkin, ParseError> {
+	let joints: Vec<usize> = array(json, "joints")
+		.iter()
+		.filter_map(Json::as_usize)
+		.collect();
+
+	let inverse_bind_matrices = match index(json, "inverseBindMatrices") {
+		Some(accessor) => accessors
+			.read(accessor)?
+			.elements()
+			.map(|m| {
+				let m: [f32; 16] = std::array::from_fn(|i| m[i] as f32);
+				// glTF matrices are column-major.
+				Mat4::from_array(m).transpose()
+			})
+			.collect(),
+		None => vec![Mat4::IDENTITY; joints.len()],
+	};
+
+	Ok(GltfSkin {
+		name: name(json),
+		joints,
+		inverse_bind_matrices,
+		skeleton: index(json, "skeleton"),
+	})
+}
+
+fn read_camera(json: &Json) -> Result<GltfCamera, ParseError> {
+	let number = |object: &Json, key: &str| object.get(key).and_then(Json::as_f32);
+
+	match json.get("type").and_then(Json::as_str) {
+		Some("perspective") => {
+			let perspective = json.get("perspective").unwrap_or(&Json::Null);
+			Ok(GltfCamera::Perspective {
+				yfov: number(perspective, "yfov").unwrap_or(0.8),
+				aspect_ratio: number(perspective, "aspectRatio"),
+				znear: number(perspective, "znear").unwrap_or(0.01),
+				zfar: number(perspective, "zfar"),
+			})
+		}
+		Some("orthographic") => {
+			let orthographic = json.get("orthographic").unwrap_or(&Json::Null);
+			Ok(GltfCamera::Orthographic {
+				xmag: number(orthographic, "xmag").unwrap_or(1.0),
+				ymag: number(orthographic, "ymag").unwrap_or(1.0),
+				znear: number(orthographic, "znear").unwrap_or(0.0),
+				zfar: number(orthographic, "zfar").unwrap_or(100.0),
+			})
+		}
+		_ => Err(ParseError::invalid("Invalid camera type")),
+	}
+}
+
+fn read_light(json: &Json) -> Result<GltfLight, ParseError> {
+	let kind = match json.get("type").and_then(Json::as_str) {
+		Some("directional") => GltfLightKind::Directional,
+		Some("point") => GltfLightKind::Point,
+		Some("spot") => {
+			let spot = json.get("spot").unwrap_or(&Json::Null);
+			GltfLightKind::Spot {
+				inner_cone_angle: spot
+					.get("innerConeAngle")
+					.and_then(Json::as_f32)
+					.unwrap_or(0.0),
+				outer_cone_angle: spot
+					.get("outerConeAngle")
+					.and_then(Json::as_f32)
+					.unwrap_or(std::f32::consts::FRAC_PI_4),
+			}
+		}
+		_ => return Err(ParseError::invalid("Invalid light type")),
+	};
+
+	Ok(GltfLight {
+		name: name(json),
+		kind,
+		color: json
+			.get("color")
+			.and_then(Json::as_f32_array)
+			.unwrap_or([1.0; 3]),
+		intensity: json.get("intensity").and_then(Json::as_f32).unwrap_or(1.0),
+		range: json.get("range").and_then(Json::as_f32),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Encodes bytes as base64, for embedding buffers in test files.
+	fn base64(bytes: &[u8]) -> String {
+		const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+		bytes
+			.chunks(3)
+			.flat_map(|chunk| {
+				let b = [
+					chunk[0],
+					*chunk.get(1).unwrap_or(&0),
+					*chunk.get(2).unwrap_or(&0),
+				];
+				let bits = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+				(0..4).map(move |i| match i <= chunk.len() {
+					true => ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char,
+					false => '=',
+				})
+			})
+			.collect()
+	}
+
+	#[test]
+	fn read() {
+		let mut buffer = Vec::new();
+		// Positions of a triangle.
+		for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+			buffer.extend_from_slice(&value.to_le_bytes());
+		}
+		// Indices.
+		for index in [0u16, 1, 2, 0] {
+			buffer.extend_from_slice(&index.to_le_bytes());
+		}
+		// Joints and normalized weights.
+		for joints in [[0u8, 1, 0, 0], [1, 0, 0, 0], [1, 0, 0, 0]] {
+			buffer.extend_from_slice(&joints);
+		}
+		for weights in [[128u8, 128, 0, 0], [255, 0, 0, 0], [255, 0, 0, 0]] {
+			buffer.extend_from_slice(&weights);
+		}
+
+		let json = format!(
+			r#"{{
+				"asset": {{ "version": "2.0" }},
+				"scene": 0,
+				"scenes": [{{ "nodes": [0] }}],
+				"nodes": [
+					{{ "name": "Root", "translation": [1, 2, 3], "children": [1, 2] }},
+					{{ "name": "Body", "mesh": 0, "skin": 0 }},
+					{{ "name": "Hips", "matrix": [2, 0, 0, 0, 0, 2, 0, 0, 0, 0, 2, 0, 5, 0, 0, 1] }}
+				],
+				"meshes": [{{
+					"primitives": [{{
+						"attributes": {{ "POSITION": 0, "JOINTS_0": 2, "WEIGHTS_0": 3 }},
+						"indices": 1,
+						"targets": [{{ "POSITION": 0 }}]
+					}}],
+					"weights": [0.5]
+				}}],
+				"skins": [{{ "joints": [2, 0] }}],
+				"buffers": [{{ "byteLength": {}, "uri": "data:application/octet-stream;base64,{}" }}],
+				"bufferViews": [
+					{{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+					{{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }},
+					{{ "buffer": 0, "byteOffset": 44, "byteLength": 24 }}
+				],
+				"accessors": [
+					{{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }},
+					{{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }},
+					{{ "bufferView": 2, "componentType": 5121, "count": 3, "type": "VEC4" }},
+					{{ "bufferView": 2, "byteOffset": 12, "componentType": 5121, "normalized": true, "count": 3, "type": "VEC4" }}
+				]
+			}}"#,
+			buffer.len(),
+			base64(&buffer)
+		);
+
+		let gltf = Gltf::read(json.as_bytes(), |_| unreachable!()).unwrap();
+		let mesh = &gltf.meshes[0].mesh;
+
+		assert_eq!(mesh.indices, [0, 1, 2]);
+		assert!(mesh.vertices.iter().all(|vertex| vertex.n == *Vec3::Z));
+
+		// Weights are normalized and the groups are named after the joints.
+		let groups = &mesh.vertex_groups;
+		assert_eq!(groups.names, ["Hips", "Root"]);
+		assert_eq!(groups.lookup, [0, 2, 3, 4]);
+		assert_eq!(groups.values[0], (0, 0.5));
+		assert_eq!(groups.values[2], (1, 1.0));
+
+		let target = &mesh.morph_targets[0];
+		assert_eq!(target.name, "target0");
+		assert_eq!(gltf.meshes[0].weights, [0.5]);
+		assert!(target.positions[1] == Vec3::new(1.0, 0.0, 0.0));
+
+		let paths: Vec<_> = gltf
+			.world_transforms()
+			.into_iter()
+			.map(|(_, path, transform)| (path, transform.translation, transform.scale))
+			.collect();
+
+		assert_eq!(paths.len(), 3);
+		assert_eq!(paths[1].0, "/Root/Body");
+		assert!(paths[2].0 == "/Root/Hips" && paths[2].1 == Vec3::new(6.0, 2.0, 3.0));
+		assert!(paths[2].2 == Vec3::new(2.0, 2.0, 2.0));
+	}
+
+	#[test]
+	fn glb() {
+		let json = br#"{"asset":{"version":"2.0"},"buffers":[{"byteLength":4}]}"#;
+		let json_length = json.len().next_multiple_of(4);
+
+		let mut glb = Vec::new();
+		glb.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+		glb.extend_from_slice(&2u32.to_le_bytes());
+		glb.extend_from_slice(&((12 + 8 + json_length + 8 + 4) as u32).to_le_bytes());
+		glb.extend_from_slice(&(json_length as u32).to_le_bytes());
+		glb.extend_from_slice(&GLB_CHUNK_JSON.to_le_bytes());
+		glb.extend_from_slice(json);
+		glb.resize(12 + 8 + json_length, b' ');
+		glb.extend_from_slice(&4u32.to_le_bytes());
+		glb.extend_from_slice(&GLB_CHUNK_BIN.to_le_bytes());
+		glb.extend_from_slice(&[1, 2, 3, 4]);
+
+		let (_, bin) = split_glb(&glb).unwrap();
+		assert!(Gltf::external_uris(&glb).unwrap().is_empty());
+		assert_eq!(bin, Some(&[1, 2, 3, 4][..]));
+		assert!(Gltf::read(&glb, |_| unreachable!()).is_ok());
+		assert!(Gltf::read(&glb[..20], |_| unreachable!()).is_err());
+	}
+
+	#[test]
+	fn external_buffers() {
+		let json = br#"{
+			"asset": { "version": "2.0" },
+			"buffers": [
+				{ "byteLength": 4, "uri": "mesh%20data.bin" },
+				{ "byteLength": 0, "uri": "data:application/octet-stream;base64," }
+			]
+		}"#;
+
+		assert_eq!(Gltf::external_uris(json).unwrap(), ["mesh data.bin"]);
+		assert!(
+			Gltf::read(json, |uri| {
+				assert_eq!(uri, "mesh data.bin");
+				Ok(vec![0; 4])
+			})
+			.is_ok()
+		);
+	}
+
+	#[test]
+	fn malformed() {
+		let read = |json: &str| {
+			let json = format!(r#"{{ "asset": {{ "version": "2.0" }}, {json} }}"#);
+			Gltf::read(json.as_bytes(), |_| unreachable!())
+		};
+
+		// Unnamed materials get a name of their own.
+		let buffer = base64(&[0; 36]);
+		let gltf = read(&format!(
+			r#""meshes": [{{ "primitives": [
+				{{ "attributes": {{ "POSITION": 0 }}, "material": 0 }},
+				{{ "attributes": {{ "POSITION": 0 }}, "material": 1 }}
+			] }}],
+			"materials": [{{}}, {{}}],
+			"buffers": [{{ "byteLength": 36, "uri": "data:application/octet-stream;base64,{buffer}" }}],
+			"bufferViews": [{{ "buffer": 0, "byteLength": 36 }}],
+			"accessors": [{{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }}]"#
+		))
+		.unwrap();
+		let materials: Vec<_> = gltf.meshes[0]
+			.mesh
+			.submeshes
+			.iter()
+			.map(|submesh| submesh.material.as_deref())
+			.collect();
+		assert_eq!(materials, [Some("material0"), Some("material1")]);
+
+		// Counts beyond the size of the buffers fail before anything is allocated for them.
+		for accessor in [
+			r#"{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3" }"#,
+			r#"{ "componentType": 5126, "count": 1000000000000, "type": "VEC3" }"#,
+			r#"{ "bufferView": 0, "byteOffset": 18446744073709551615, "componentType": 5126, "count": 1, "type": "VEC3" }"#,
+		] {
+			let gltf = read(&format!(
+				r#""meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }} }}] }}],
+				"buffers": [{{ "byteLength": 36, "uri": "data:application/octet-stream;base64,{buffer}" }}],
+				"bufferViews": [{{ "buffer": 0, "byteLength": 36 }}],
+				"accessors": [{accessor}]"#
+			));
+			assert!(gltf.is_err());
+		}
+
+		// Joints and weights must have an element for every vertex, and refer to joints of the skin.
+		let mut skinned = vec![0; 36];
+		for joint in [0, 0, 0, 5] {
+			skinned.extend([joint, 0, 0, 0]);
+		}
+		for _ in 0..4 {
+			skinned.extend([255, 0, 0, 0]);
+		}
+		let skinned = base64(&skinned);
+
+		for (joints, count, valid) in [(0, 3, true), (0, 4, false), (4, 3, false)] {
+			let gltf = read(&format!(
+				r#""nodes": [{{ "mesh": 0, "skin": 0 }}, {{}}],
+				"skins": [{{ "joints": [1] }}],
+				"meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0, "JOINTS_0": 1, "WEIGHTS_0": 2 }} }}] }}],
+				"buffers": [{{ "byteLength": 68, "uri": "data:application/octet-stream;base64,{skinned}" }}],
+				"bufferViews": [
+					{{ "buffer": 0, "byteLength": 36 }},
+					{{ "buffer": 0, "byteOffset": 36, "byteLength": 16 }},
+					{{ "buffer": 0, "byteOffset": 52, "byteLength": 16 }}
+				],
+				"accessors": [
+					{{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }},
+					{{ "bufferView": 1, "byteOffset": {joints}, "componentType": 5121, "count": {count}, "type": "VEC4" }},
+					{{ "bufferView": 2, "componentType": 5121, "normalized": true, "count": {count}, "type": "VEC4" }}
+				]"#
+			));
+			assert_eq!(gltf.is_ok(), valid);
+		}
+
+		assert!(read(r#""nodes": [{ "children": [1] }, { "children": [0] }]"#).is_err());
+		assert!(read(r#""nodes": [{ "children": [0] }]"#).is_err());
+		assert!(read(r#""nodes": [{ "children": [2] }, { "children": [2] }, {}]"#).is_err());
+		assert!(read(r#""nodes": [{ "children": [1, 2] }, { "children": [2] }, {}]"#).is_err());
+		assert!(read(r#""nodes": [{ "children": [1] }, {}]"#).is_ok());
+	}
+}
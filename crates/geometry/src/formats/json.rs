@@ -0,0 +1,348 @@
+//! Minimal JSON parser for the glTF importer.
+
+use super::ParseError;
+
+/// Deepest nesting of arrays and objects, so that malformed files can't overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Json {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Json>),
+	Object(Vec<(String, Json)>),
+}
+
+impl Json {
+	pub fn parse(source: &str) -> Result<Self, ParseError> {
+		let mut parser = Parser {
+			bytes: source.as_bytes(),
+			position: 0,
+			depth: 0,
+		};
+
+		let value = parser.value()?;
+		parser.skip_whitespace();
+
+		if parser.position != parser.bytes.len() {
+			return Err(parser.error("Trailing characters"));
+		}
+
+		Ok(value)
+	}
+
+	/// Member of an object, or `None` if this is not an object or has no such member.
+	pub fn get(&self, key: &str) -> Option<&Json> {
+		match self {
+			Self::Object(members) => members
+				.iter()
+				.find(|(name, _)| name == key)
+				.map(|(_, value)| value),
+			_ => None,
+		}
+	}
+
+	pub fn as_f64(&self) -> Option<f64> {
+		match self {
+			Self::Number(number) => Some(*number),
+			_ => None,
+		}
+	}
+
+	pub fn as_f32(&self) -> Option<f32> {
+		self.as_f64().map(|number| number as f32)
+	}
+
+	pub fn as_usize(&self) -> Option<usize> {
+		self.as_f64()
+			.filter(|number| *number >= 0.0 && number.fract() == 0.0)
+			.map(|number| number as usize)
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			Self::String(string) => Some(string),
+			_ => None,
+		}
+	}
+
+	pub fn as_array(&self) -> &[Json] {
+		match self {
+			Self::Array(elements) => elements,
+			_ => &[],
+		}
+	}
+
+	pub fn as_object(&self) -> &[(String, Json)] {
+		match self {
+			Self::Object(members) => members,
+			_ => &[],
+		}
+	}
+
+	/// Array of numbers with exactly `N` elements.
+	pub fn as_f32_array<const N: usize>(&self) -> Option<[f32; N]> {
+		let elements = self.as_array();
+
+		if elements.len() != N {
+			return None;
+		}
+
+		let mut array = [0.0; N];
+		for (value, element) in array.iter_mut().zip(elements) {
+			*value = element.as_f32()?;
+		}
+		Some(array)
+	}
+}
+
+struct Parser<'a> {
+	bytes: &'a [u8],
+	position: usize,
+	/// Number of arrays and objects that contain the current value.
+	depth: usize,
+}
+
+impl Parser<'_> {
+	fn error(&self, message: &str) -> ParseError {
+		ParseError::invalid(format!("{message} at JSON offset {}", self.position))
+	}
+
+	fn skip_whitespace(&mut self) {
+		while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+			self.position += 1;
+		}
+	}
+
+	fn peek(&self) -> Option<u8> {
+		self.bytes.get(self.position).copied()
+	}
+
+	fn expect(&mut self, byte: u8) -> Result<(), ParseError> {
+		self.skip_whitespace();
+
+		if self.peek() != Some(byte) {
+			return Err(self.error(&format!("Expected '{}'", byte as char)));
+		}
+
+		self.position += 1;
+		Ok(())
+	}
+
+	fn literal(&mut self, literal: &str, value: Json) -> Result<Json, ParseError> {
+		if !self.bytes[self.position..].starts_with(literal.as_bytes()) {
+			return Err(self.error("Invalid literal"));
+		}
+
+		self.position += literal.len();
+		Ok(value)
+	}
+
+	fn value(&mut self) -> Result<Json, ParseError> {
+		self.skip_whitespace();
+
+		match self.peek() {
+			Some(b'{') => self.nested(Self::object),
+			Some(b'[') => self.nested(Self::array),
+			Some(b'"') => self.string().map(Json::String),
+			Some(b't') => self.literal("true", Json::Bool(true)),
+			Some(b'f') => self.literal("false", Json::Bool(false)),
+			Some(b'n') => self.literal("null", Json::Null),
+			Some(b'-' | b'0'..=b'9') => self.number(),
+			_ => Err(self.error("Expected value")),
+		}
+	}
+
+	fn nested(
+		&mut self,
+		parse: fn(&mut Self) -> Result<Json, ParseError>,
+	) -> Result<Json, ParseError> {
+		if self.depth == MAX_DEPTH {
+			return Err(self.error("Too deeply nested"));
+		}
+
+		self.depth += 1;
+		let value = parse(self);
+		self.depth -= 1;
+		value
+	}
+
+	fn object(&mut self) -> Result<Json, ParseError> {
+		self.expect(b'{')?;
+		let mut members = Vec::new();
+
+		self.skip_whitespace();
+		if self.peek() == Some(b'}') {
+			self.position += 1;
+			return Ok(Json::Object(members));
+		}
+
+		loop {
+			self.skip_whitespace();
+			let key = self.string()?;
+			self.expect(b':')?;
+			members.push((key, self.value()?));
+
+			self.skip_whitespace();
+			match self.peek() {
+				Some(b',') => self.position += 1,
+				Some(b'}') => {
+					self.position += 1;
+					return Ok(Json::Object(members));
+				}
+				_ => return Err(self.error("Expected ',' or '}'")),
+			}
+		}
+	}
+
+	fn array(&mut self) -> Result<Json, ParseError> {
+		self.expect(b'[')?;
+		let mut elements = Vec::new();
+
+		self.skip_whitespace();
+		if self.peek() == Some(b']') {
+			self.position += 1;
+			return Ok(Json::Array(elements));
+		}
+
+		loop {
+			elements.push(self.value()?);
+
+			self.skip_whitespace();
+			match self.peek() {
+				Some(b',') => self.position += 1,
+				Some(b']') => {
+					self.position += 1;
+					return Ok(Json::Array(elements));
+				}
+				_ => return Err(self.error("Expected ',' or ']'")),
+			}
+		}
+	}
+
+	fn number(&mut self) -> Result<Json, ParseError> {
+		let start = self.position;
+
+		while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+			self.position += 1;
+		}
+
+		std::str::from_utf8(&self.bytes[start..self.position])
+			.ok()
+			.and_then(|number| number.parse().ok())
+			.map(Json::Number)
+			.ok_or_else(|| self.error("Invalid number"))
+	}
+
+	fn string(&mut self) -> Result<String, ParseError> {
+		if self.peek() != Some(b'"') {
+			return Err(self.error("Expected string"));
+		}
+		self.position += 1;
+
+		let mut bytes = Vec::new();
+
+		loop {
+			let Some(byte) = self.peek() else {
+				return Err(self.error("Unterminated string"));
+			};
+			self.position += 1;
+
+			match byte {
+				b'"' => break,
+				b'\\' => {
+					let Some(escape) = self.peek() else {
+						return Err(self.error("Unterminated string"));
+					};
+					self.position += 1;
+
+					let unescaped = match escape {
+						b'"' => '"',
+						b'\\' => '\\',
+						b'/' => '/',
+						b'b' => '\u{8}',
+						b'f' => '\u{c}',
+						b'n' => '\n',
+						b'r' => '\r',
+						b't' => '\t',
+						b'u' => self.unicode_escape()?,
+						_ => return Err(self.error("Invalid escape")),
+					};
+
+					bytes.extend_from_slice(unescaped.encode_utf8(&mut [0; 4]).as_bytes());
+				}
+				_ => bytes.push(byte),
+			}
+		}
+
+		String::from_utf8(bytes).map_err(|_| self.error("Invalid UTF-8"))
+	}
+
+	/// Parses the digits of a `\u` escape, including a following low surrogate.
+	fn unicode_escape(&mut self) -> Result<char, ParseError> {
+		let high = self.hex4()?;
+
+		let code = if (0xD800..0xDC00).contains(&high) {
+			if !self.bytes[self.position..].starts_with(b"\\u") {
+				return Err(self.error("Unpaired surrogate"));
+			}
+			self.position += 2;
+
+			let low = self.hex4()?;
+			0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF)
+		} else {
+			high
+		};
+
+		char::from_u32(code).ok_or_else(|| self.error("Invalid unicode escape"))
+	}
+
+	fn hex4(&mut self) -> Result<u32, ParseError> {
+		let digits = self
+			.bytes
+			.get(self.position..self.position + 4)
+			.and_then(|digits| std::str::from_utf8(digits).ok())
+			.and_then(|digits| u32::from_str_radix(digits, 16).ok())
+			.ok_or_else(|| self.error("Invalid unicode escape"))?;
+
+		self.position += 4;
+		Ok(digits)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse() {
+		let json =
+			Json::parse(r#" {"a": [1, -2.5e1, true, null], "b": {"c": "x\"\u00e9\ud83d\ude00"}} "#)
+				.unwrap();
+
+		assert_eq!(
+			json.get("a").unwrap().as_array(),
+			[
+				Json::Number(1.0),
+				Json::Number(-25.0),
+				Json::Bool(true),
+				Json::Null
+			]
+		);
+		assert_eq!(
+			json.get("b")
+				.and_then(|b| b.get("c"))
+				.and_then(Json::as_str),
+			Some("x\"é😀")
+		);
+
+		assert!(Json::parse("[1, 2").is_err());
+		assert!(Json::parse("{} x").is_err());
+
+		let nested = |depth: usize| "[".repeat(depth) + &"]".repeat(depth);
+		assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+		assert!(Json::parse(&nested(100_000)).is_err());
+	}
+}
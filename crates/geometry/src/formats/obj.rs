@@ -0,0 +1,605 @@
+//! Wavefront OBJ meshes and MTL material libraries.
+
+use super::ParseError;
+use crate::mesh::{
+	AUTO_SMOOTH_ANGLE, Mesh, NormalMode, Submesh, Vertex, calculate_normals, calculate_tangents,
+	calculate_vert_normals, uv_name,
+};
+
+use asset::{Asset, AssetLoader, BlobReader, BlobWriter, LoadContext, LoadError};
+use math::{Vec2, Vec3};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Contents of an OBJ file.
+pub struct ObjFile {
+	/// Groups (`g`), objects (`o`) and material changes (`usemtl`) become submeshes.
+	pub mesh: Mesh,
+	/// Paths of the material libraries (`mtllib`), relative to the OBJ file.
+	pub material_libraries: Vec<String>,
+}
+
+/// Reads an OBJ file, triangulating its polygons.
+///
+/// Vertices are split where faces use different UVs or normals for the same position. Normals are calculated for
+/// vertices that don't have one, UVs are stored in the `uv0` channel.
+pub fn read_obj(source: &str) -> Result<ObjFile, ParseError> {
+	let mut positions = Vec::new();
+	let mut uvs = Vec::new();
+	let mut normals = Vec::new();
+
+	let mut mesh = Mesh::new();
+	let mut vertex_uvs = Vec::new();
+	let mut vertex_normals = Vec::new();
+	// Index of every distinct (position, uv, normal) combination.
+	let mut vertex_indices = HashMap::new();
+
+	let mut material_libraries = Vec::new();
+	let mut group = String::new();
+	let mut material = None;
+	let mut submesh_start = 0;
+
+	for (line_index, line) in source.lines().enumerate() {
+		let line_number = line_index + 1;
+		let line = line.split('#').next().unwrap_or_default();
+		let mut tokens = line.split_whitespace();
+
+		let Some(keyword) = tokens.next() else {
+			continue;
+		};
+
+		match keyword {
+			"v" => positions.push(parse_vec3(&mut tokens, line_number)?),
+			"vn" => normals.push(parse_vec3(&mut tokens, line_number)?),
+			"vt" => {
+				let u = parse_f32(tokens.next(), line_number)?;
+				// The v coordinate is optional for 1D textures.
+				let v = tokens
+					.next()
+					.map_or(Ok(0.0), |token| parse_f32(Some(token), line_number))?;
+				uvs.push(Vec2::new(u, v));
+			}
+			"f" => {
+				let mut corners = Vec::new();
+
+				for token in tokens {
+					let mut parts = token.split('/');
+
+					let position = parse_index(parts.next(), positions.len(), line_number)?
+						.ok_or_else(|| {
+							ParseError::at_line(line_number, "Face corner without position")
+						})?;
+					let uv = parse_index(parts.next(), uvs.len(), line_number)?;
+					let normal = parse_index(parts.next(), normals.len(), line_number)?;
+
+					let index =
+						*vertex_indices
+							.entry((position, uv, normal))
+							.or_insert_with(|| {
+								mesh.vertices.push(Vertex {
+									p: positions[position],
+									n: Vec3::ZERO,
+								});
+								vertex_uvs.push(uv.map(|i| uvs[i]));
+								vertex_normals.push(normal.map(|i| normals[i]));
+								mesh.vertices.len() - 1
+							});
+
+					corners.push(index);
+				}
+
+				if corners.len() < 3 {
+					return Err(ParseError::at_line(
+						line_number,
+						"Face with less than 3 vertices",
+					));
+				}
+
+				let points: Vec<Vec3> = corners.iter().map(|&i| mesh.vertices[i].p).collect();
+
+				for triangle in triangulate(&points) {
+					mesh.indices.extend(triangle.map(|i| corners[i]));
+				}
+			}
+			"g" | "o" | "usemtl" => {
+				finish_submesh(&mut mesh, &group, &material, submesh_start);
+				submesh_start = mesh.indices.len();
+
+				let name = tokens.collect::<Vec<_>>().join(" ");
+
+				if keyword == "usemtl" {
+					material = Some(name);
+				} else {
+					group = name;
+				}
+			}
+			"mtllib" => material_libraries.extend(tokens.map(str::to_string)),
+			// Smoothing groups, lines, points and free-form geometry are not supported.
+			_ => {}
+		}
+	}
+
+	finish_submesh(&mut mesh, &group, &material, submesh_start);
+
+	// A single unnamed part is the whole mesh.
+	if let [submesh] = mesh.submeshes.as_slice()
+		&& submesh.name.is_empty()
+		&& submesh.material.is_none()
+	{
+		mesh.submeshes.clear();
+	}
+
+	let has_normals = vertex_normals.iter().any(Option::is_some);
+
+	if has_normals {
+		calculate_vert_normals(&mut mesh);
+
+		for (vertex, normal) in mesh.vertices.iter_mut().zip(&vertex_normals) {
+			if let Some(normal) = normal {
+				vertex.n = *normal;
+			}
+		}
+	}
+
+	if vertex_uvs.iter().any(Option::is_some) {
+		let uvs = vertex_uvs
+			.iter()
+			.map(|uv| uv.unwrap_or(Vec2::ZERO))
+			.collect();
+		mesh.attributes.insert::<Vec2>(&uv_name(0), uvs);
+	}
+
+	// Without any normals, faces are smooth except along sharp edges, which splits vertices.
+	if !has_normals {
+		calculate_normals(&mut mesh, NormalMode::AutoSmooth(AUTO_SMOOTH_ANGLE));
+	}
+
+	calculate_tangents(&mut mesh);
+
+	Ok(ObjFile {
+		mesh,
+		material_libraries,
+	})
+}
+
+fn finish_submesh(mesh: &mut Mesh, group: &str, material: &Option<String>, start: usize) {
+	if start < mesh.indices.len() {
+		mesh.submeshes.push(Submesh {
+			name: group.to_string(),
+			material: material.clone(),
+			indices: start..mesh.indices.len(),
+		});
+	}
+}
+
+fn parse_f32(token: Option<&str>, line: usize) -> Result<f32, ParseError> {
+	let token = token.ok_or_else(|| ParseError::at_line(line, "Missing number"))?;
+	token
+		.parse()
+		.map_err(|_| ParseError::at_line(line, format!("Invalid number {token}")))
+}
+
+fn parse_vec3<'a>(
+	tokens: &mut impl Iterator<Item = &'a str>,
+	line: usize,
+) -> Result<Vec3, ParseError> {
+	Ok(Vec3::new(
+		parse_f32(tokens.next(), line)?,
+		parse_f32(tokens.next(), line)?,
+		parse_f32(tokens.next(), line)?,
+	))
+}
+
+/// Parses a 1-based index, or a negative index relative to the end, into a 0-based index.
+/// Returns `None` for empty indices like the UV in `1//2`.
+fn parse_index(
+	token: Option<&str>,
+	count: usize,
+	line: usize,
+) -> Result<Option<usize>, ParseError> {
+	let Some(token) = token.filter(|token| !token.is_empty()) else {
+		return Ok(None);
+	};
+
+	let index: i64 = token
+		.parse()
+		.map_err(|_| ParseError::at_line(line, format!("Invalid index {token}")))?;
+
+	let resolved = match index {
+		0 => None,
+		1.. => Some(index - 1),
+		_ => Some(count as i64 + index),
+	};
+
+	match resolved {
+		Some(resolved) if (0..count as i64).contains(&resolved) => Ok(Some(resolved as usize)),
+		_ => Err(ParseError::at_line(
+			line,
+			format!("Index {index} is out of range"),
+		)),
+	}
+}
+
+/// Triangulates a polygon by ear clipping in the plane of its normal, which also handles concave polygons.
+/// Returns triangles of indices into `points`.
+pub(crate) fn triangulate(points: &[Vec3]) -> Vec<[usize; 3]> {
+	let fan = |corners: &[usize]| {
+		(1..corners.len() - 1)
+			.map(|i| [corners[0], corners[i], corners[i + 1]])
+			.collect::<Vec<_>>()
+	};
+
+	let mut remaining: Vec<usize> = (0..points.len()).collect();
+
+	if points.len() == 3 {
+		return fan(&remaining);
+	}
+
+	// Newell's method, which is robust for non-planar polygons.
+	let mut normal = Vec3::ZERO;
+	for (i, a) in points.iter().enumerate() {
+		let b = points[(i + 1) % points.len()];
+		normal += Vec3::new(
+			(a.y - b.y) * (a.z + b.z),
+			(a.z - b.z) * (a.x + b.x),
+			(a.x - b.x) * (a.y + b.y),
+		);
+	}
+
+	if normal.length_sq() <= f32::MIN_POSITIVE {
+		return fan(&remaining);
+	}
+
+	let normal = *normal.normalize();
+	let axis = if normal.x.abs() < 0.9 {
+		Vec3::X
+	} else {
+		Vec3::Y
+	};
+	let u = *(*axis - normal * normal.dot(*axis)).normalize();
+	let v = normal.cross(u);
+
+	// Counter-clockwise polygons have a positive area in this plane.
+	let projected: Vec<Vec2> = points
+		.iter()
+		.map(|p| Vec2::new(p.dot(u), p.dot(v)))
+		.collect();
+
+	let mut triangles = Vec::with_capacity(points.len() - 2);
+
+	while remaining.len() > 3 {
+		let count = remaining.len();
+
+		let ear = (0..count).find(|&i| {
+			let [a, b, c] = [(i + count - 1) % count, i, (i + 1) % count].map(|i| remaining[i]);
+			let [pa, pb, pc] = [a, b, c].map(|i| projected[i]);
+
+			// Reflex and degenerate corners are no ears.
+			if (pb - pa).cross(pc - pb) <= 0.0 {
+				return false;
+			}
+
+			!remaining.iter().any(|&j| {
+				j != a && j != b && j != c && {
+					let p = projected[j];
+					(pb - pa).cross(p - pa) >= 0.0
+						&& (pc - pb).cross(p - pb) >= 0.0
+						&& (pa - pc).cross(p - pc) >= 0.0
+				}
+			})
+		});
+
+		// Self-intersecting polygons may have no ears left.
+		let Some(i) = ear else {
+			triangles.extend(fan(&remaining));
+			return triangles;
+		};
+
+		triangles.push([(i + count - 1) % count, i, (i + 1) % count].map(|i| remaining[i]));
+		remaining.remove(i);
+	}
+
+	triangles.extend(fan(&remaining));
+	triangles
+}
+
+/// Writes a mesh as OBJ, with the `uv0` channel as texture coordinates and submeshes as groups.
+pub fn write_obj(mesh: &Mesh, writer: &mut impl Write) -> io::Result<()> {
+	let uvs = mesh.attributes.uv(0);
+
+	for vertex in &mesh.vertices {
+		writeln!(writer, "v {} {} {}", vertex.p.x, vertex.p.y, vertex.p.z)?;
+	}
+
+	for uv in uvs.into_iter().flatten() {
+		writeln!(writer, "vt {} {}", uv.x, uv.y)?;
+	}
+
+	for vertex in &mesh.vertices {
+		writeln!(writer, "vn {} {} {}", vertex.n.x, vertex.n.y, vertex.n.z)?;
+	}
+
+	let write_faces = |writer: &mut dyn Write, indices: &[usize]| -> io::Result<()> {
+		for triangle in indices.chunks_exact(3) {
+			write!(writer, "f")?;
+
+			for index in triangle {
+				let index = index + 1;
+
+				if uvs.is_some() {
+					write!(writer, " {index}/{index}/{index}")?;
+				} else {
+					write!(writer, " {index}//{index}")?;
+				}
+			}
+
+			writeln!(writer)?;
+		}
+
+		Ok(())
+	};
+
+	if mesh.submeshes.is_empty() {
+		return write_faces(writer, &mesh.indices);
+	}
+
+	for submesh in &mesh.submeshes {
+		writeln!(writer, "g {}", submesh.name)?;
+
+		if let Some(material) = &submesh.material {
+			writeln!(writer, "usemtl {material}")?;
+		}
+
+		write_faces(writer, &mesh.indices[submesh.indices.clone()])?;
+	}
+
+	Ok(())
+}
+
+/// Materials of an MTL file.
+#[derive(Default)]
+pub struct MaterialLibrary {
+	pub materials: Vec<ObjMaterial>,
+}
+
+impl Asset for MaterialLibrary {}
+
+impl MaterialLibrary {
+	pub fn get(&self, name: &str) -> Option<&ObjMaterial> {
+		self.materials.iter().find(|material| material.name == name)
+	}
+}
+
+/// Material of an MTL file. Texture paths are relative to the MTL file.
+pub struct ObjMaterial {
+	pub name: String,
+	/// `Ka`
+	pub ambient: [f32; 3],
+	/// `Kd`
+	pub diffuse: [f32; 3],
+	/// `Ks`
+	pub specular: [f32; 3],
+	/// `Ke`
+	pub emissive: [f32; 3],
+	/// `Ns`
+	pub specular_exponent: f32,
+	/// `d`, or 1 - `Tr`
+	pub opacity: f32,
+	/// `map_Kd`
+	pub diffuse_texture: Option<String>,
+	/// `norm`, or `bump` and `map_Bump` which are commonly used for normal maps.
+	pub normal_texture: Option<String>,
+}
+
+impl ObjMaterial {
+	fn new(name: String) -> Self {
+		Self {
+			name,
+			ambient: [0.0; 3],
+			diffuse: [0.8; 3],
+			specular: [0.0; 3],
+			emissive: [0.0; 3],
+			specular_exponent: 0.0,
+			opacity: 1.0,
+			diffuse_texture: None,
+			normal_texture: None,
+		}
+	}
+}
+
+/// Reads an MTL file. Unsupported statements are ignored.
+pub fn read_mtl(source: &str) -> Result<MaterialLibrary, ParseError> {
+	let mut library = MaterialLibrary::default();
+
+	for (line_index, line) in source.lines().enumerate() {
+		let line_number = line_index + 1;
+		let line = line.split('#').next().unwrap_or_default();
+		let mut tokens = line.split_whitespace();
+
+		let Some(keyword) = tokens.next() else {
+			continue;
+		};
+
+		if keyword == "newmtl" {
+			let name = tokens.collect::<Vec<_>>().join(" ");
+			library.materials.push(ObjMaterial::new(name));
+			continue;
+		}
+
+		let Some(material) = library.materials.last_mut() else {
+			return Err(ParseError::at_line(
+				line_number,
+				format!("{keyword} before newmtl"),
+			));
+		};
+
+		let color = |tokens: &mut std::str::SplitWhitespace| -> Result<[f32; 3], ParseError> {
+			Ok(parse_vec3(tokens, line_number)?.into())
+		};
+
+		// Texture statements may have options before the file name, like `-bm 1.0 normal.png`.
+		let texture = |tokens: std::str::SplitWhitespace| tokens.last().map(str::to_string);
+
+		match keyword {
+			"Ka" => material.ambient = color(&mut tokens)?,
+			"Kd" => material.diffuse = color(&mut tokens)?,
+			"Ks" => material.specular = color(&mut tokens)?,
+			"Ke" => material.emissive = color(&mut tokens)?,
+			"Ns" => material.specular_exponent = parse_f32(tokens.next(), line_number)?,
+			"d" => material.opacity = parse_f32(tokens.next(), line_number)?,
+			"Tr" => material.opacity = 1.0 - parse_f32(tokens.next(), line_number)?,
+			"map_Kd" => material.diffuse_texture = texture(tokens),
+			"norm" | "bump" | "map_Bump" => material.normal_texture = texture(tokens),
+			_ => {}
+		}
+	}
+
+	Ok(library)
+}
+
+pub struct ObjLoader;
+
+impl AssetLoader for ObjLoader {
+	type Asset = Mesh;
+
+	fn extensions(&self) -> &[&str] {
+		&["obj"]
+	}
+
+	fn version(&self) -> u32 {
+		1
+	}
+
+	fn load(&self, ctx: &mut LoadContext) -> Result<Mesh, LoadError> {
+		let bytes = ctx.cooked(|ctx| {
+			let obj = read_obj(&String::from_utf8_lossy(&ctx.read()?))?;
+
+			let mut writer = BlobWriter::new();
+			writer.write(obj.material_libraries.len() as u64);
+			for library in &obj.material_libraries {
+				writer.write_str(library);
+			}
+			obj.mesh.write_cooked(&mut writer)?;
+
+			Ok(writer.finish())
+		})?;
+
+		let mut reader = BlobReader::new(&bytes);
+		let directory = ctx.path().parent().unwrap_or(Path::new("")).to_path_buf();
+
+		// Material libraries are dependencies, so they can be looked up by path once the mesh is loaded.
+		for _ in 0..reader.read::<u64>()? {
+			ctx.load::<MaterialLibrary>(directory.join(reader.read_string()?));
+		}
+
+		Ok(Mesh::read_cooked(&mut reader)?)
+	}
+}
+
+pub struct MtlLoader;
+
+impl AssetLoader for MtlLoader {
+	type Asset = MaterialLibrary;
+
+	fn extensions(&self) -> &[&str] {
+		&["mtl"]
+	}
+
+	fn load(&self, ctx: &mut LoadContext) -> Result<MaterialLibrary, LoadError> {
+		Ok(read_mtl(&String::from_utf8_lossy(&ctx.read()?))?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn read() {
+		let source = "
+			mtllib materials.mtl
+			v 0 0 0
+			v 1 0 0
+			v 1 1 0
+			v 0.5 0.25 0 # Makes the polygon concave
+			v 0 1 0
+			vt 0 0
+			vt 1 1
+			vn 0 0 1
+			g body
+			usemtl skin
+			f 1/1/1 2/1/1 3/2/1 4/2/1 5/1/1
+			g eyes
+			f -1//1 -2//1 -3//1
+		";
+
+		let obj = read_obj(source).unwrap();
+		let mesh = &obj.mesh;
+
+		assert_eq!(obj.material_libraries, ["materials.mtl"]);
+
+		// The pentagon has 3 triangles, all facing +Z.
+		assert_eq!(mesh.indices.len(), 12);
+		for triangle in mesh.indices.chunks_exact(3) {
+			let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i]].p);
+			assert!((b - a).cross(c - a).z > 0.0);
+		}
+
+		// The last face uses positions of the first one, but without UVs.
+		assert_eq!(mesh.vertices.len(), 8);
+		assert_eq!(mesh.attributes.uv(0).unwrap().len(), 8);
+
+		assert_eq!(mesh.submeshes.len(), 2);
+		assert_eq!(mesh.submeshes[0].name, "body");
+		assert_eq!(mesh.submeshes[0].material.as_deref(), Some("skin"));
+		assert_eq!(mesh.submeshes[0].indices, 0..9);
+		assert_eq!(mesh.submeshes[1].name, "eyes");
+		assert_eq!(mesh.submeshes[1].indices, 9..12);
+
+		assert!(read_obj("f 1 2 3").is_err());
+	}
+
+	#[test]
+	fn round_trip() {
+		let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nf 1/1 2/2 3/3\n";
+		let mesh = read_obj(source).unwrap().mesh;
+
+		let mut written = Vec::new();
+		write_obj(&mesh, &mut written).unwrap();
+		let read = read_obj(std::str::from_utf8(&written).unwrap())
+			.unwrap()
+			.mesh;
+
+		assert_eq!(read.indices, mesh.indices);
+		assert!(read.attributes.uv(0) == mesh.attributes.uv(0));
+		assert!(
+			read.vertices
+				.iter()
+				.zip(&mesh.vertices)
+				.all(|(a, b)| a.p == b.p && a.n == b.n)
+		);
+	}
+
+	#[test]
+	fn mtl() {
+		let source = "
+			newmtl skin
+			Kd 1 0.5 0.25
+			d 0.5
+			map_Kd textures/skin.png
+			bump -bm 0.5 textures/skin_normal.png
+		";
+
+		let library = read_mtl(source).unwrap();
+		let skin = library.get("skin").unwrap();
+
+		assert_eq!(skin.diffuse, [1.0, 0.5, 0.25]);
+		assert_eq!(skin.opacity, 0.5);
+		assert_eq!(skin.diffuse_texture.as_deref(), Some("textures/skin.png"));
+		assert_eq!(
+			skin.normal_texture.as_deref(),
+			Some("textures/skin_normal.png")
+		);
+	}
+}
@@ -0,0 +1,486 @@
+//! Stanford PLY meshes and point clouds, in ASCII and binary encoding.
+
+use super::ParseError;
+use super::obj::triangulate;
+use crate::mesh::{
+	AUTO_SMOOTH_ANGLE, COLOR, Mesh, NormalMode, Vertex, calculate_normals, calculate_tangents,
+	uv_name,
+};
+
+use math::{Vec2, Vec3, Vec4};
+use std::io::{self, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlyFormat {
+	Ascii,
+	BinaryLittleEndian,
+	BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum PlyType {
+	I8,
+	U8,
+	I16,
+	U16,
+	I32,
+	U32,
+	F32,
+	F64,
+}
+
+impl PlyType {
+	fn parse(name: &str) -> Option<Self> {
+		Some(match name {
+			"char" | "int8" => Self::I8,
+			"uchar" | "uint8" => Self::U8,
+			"short" | "int16" => Self::I16,
+			"ushort" | "uint16" => Self::U16,
+			"int" | "int32" => Self::I32,
+			"uint" | "uint32" => Self::U32,
+			"float" | "float32" => Self::F32,
+			"double" | "float64" => Self::F64,
+			_ => return None,
+		})
+	}
+
+	fn size(self) -> usize {
+		match self {
+			Self::I8 | Self::U8 => 1,
+			Self::I16 | Self::U16 => 2,
+			Self::I32 | Self::U32 | Self::F32 => 4,
+			Self::F64 => 8,
+		}
+	}
+}
+
+enum Property {
+	Scalar {
+		name: String,
+		ty: PlyType,
+	},
+	List {
+		name: String,
+		count_ty: PlyType,
+		item_ty: PlyType,
+	},
+}
+
+struct Element {
+	name: String,
+	count: usize,
+	properties: Vec<Property>,
+}
+
+/// Values of the body of a PLY file, in file order.
+enum Body<'a> {
+	Ascii(std::str::SplitAsciiWhitespace<'a>),
+	Binary {
+		bytes: &'a [u8],
+		position: usize,
+		big_endian: bool,
+	},
+}
+
+impl Body<'_> {
+	fn read(&mut self, ty: PlyType) -> Result<f64, ParseError> {
+		match self {
+			Self::Ascii(tokens) => {
+				let token = tokens
+					.next()
+					.ok_or_else(|| ParseError::invalid("Unexpected end of PLY data"))?;
+				token
+					.parse()
+					.map_err(|_| ParseError::invalid(format!("Invalid PLY value {token}")))
+			}
+			Self::Binary {
+				bytes,
+				position,
+				big_endian,
+			} => {
+				let size = ty.size();
+				let mut b = [0; 8];
+				b[..size].copy_from_slice(
+					bytes
+						.get(*position..*position + size)
+						.ok_or_else(|| ParseError::invalid("Unexpected end of PLY data"))?,
+				);
+				*position += size;
+
+				if *big_endian {
+					b[..size].reverse();
+				}
+
+				Ok(match ty {
+					PlyType::I8 => b[0] as i8 as f64,
+					PlyType::U8 => b[0] as f64,
+					PlyType::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+					PlyType::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+					PlyType::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+					PlyType::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+					PlyType::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+					PlyType::F64 => f64::from_le_bytes(b),
+				})
+			}
+		}
+	}
+}
+
+/// Reads a PLY file. Files without faces are point clouds, which result in a mesh without indices.
+///
+/// Vertex normals (`nx`, `ny`, `nz`), colors (`red`, `green`, `blue`, `alpha`) and texture coordinates
+/// (`s`, `t` or `u`, `v`) are read if present. Polygons are triangulated.
+pub fn read_ply(bytes: &[u8]) -> Result<Mesh, ParseError> {
+	let (format, elements, body) = read_header(bytes)?;
+
+	let mut body = match format {
+		PlyFormat::Ascii => Body::Ascii(
+			std::str::from_utf8(body)
+				.map_err(|_| ParseError::invalid("PLY data is not valid UTF-8"))?
+				.split_ascii_whitespace(),
+		),
+		_ => Body::Binary {
+			bytes: body,
+			position: 0,
+			big_endian: format == PlyFormat::BinaryBigEndian,
+		},
+	};
+
+	let mut mesh = Mesh::new();
+	let mut normals = Vec::new();
+	let mut colors = Vec::new();
+	let mut uvs = Vec::new();
+
+	for element in &elements {
+		for _ in 0..element.count {
+			let mut scalars = [None; 12];
+			let mut face = Vec::new();
+
+			for property in &element.properties {
+				match property {
+					Property::Scalar { name, ty } => {
+						let value = body.read(*ty)?;
+
+						// Integer colors are in the range 0-255.
+						let value = match (name.as_str(), ty) {
+							("red" | "green" | "blue" | "alpha", PlyType::U8) => value / 255.0,
+							_ => value,
+						};
+
+						let slot = match name.as_str() {
+							"x" => 0,
+							"y" => 1,
+							"z" => 2,
+							"nx" => 3,
+							"ny" => 4,
+							"nz" => 5,
+							"red" => 6,
+							"green" => 7,
+							"blue" => 8,
+							"alpha" => 9,
+							"s" | "u" | "texture_u" => 10,
+							"t" | "v" | "texture_v" => 11,
+							_ => continue,
+						};
+
+						scalars[slot] = Some(value as f32);
+					}
+					Property::List {
+						name,
+						count_ty,
+						item_ty,
+					} => {
+						let count = body.read(*count_ty)? as usize;
+						let items = (0..count)
+							.map(|_| body.read(*item_ty))
+							.collect::<Result<Vec<_>, _>>()?;
+
+						if name == "vertex_indices" || name == "vertex_index" {
+							face = items.into_iter().map(|i| i as usize).collect();
+						}
+					}
+				}
+			}
+
+			match element.name.as_str() {
+				"vertex" => {
+					let value = |slot: usize| scalars[slot].unwrap_or_default();
+
+					mesh.vertices.push(Vertex {
+						p: Vec3::new(value(0), value(1), value(2)),
+						n: Vec3::new(value(3), value(4), value(5)),
+					});
+
+					normals.push(scalars[3].is_some());
+					colors.push(scalars[6].is_some().then(|| {
+						Vec4::new(value(6), value(7), value(8), scalars[9].unwrap_or(1.0))
+					}));
+					uvs.push(
+						scalars[10]
+							.is_some()
+							.then(|| Vec2::new(value(10), value(11))),
+					);
+				}
+				"face" => {
+					if face.len() < 3 {
+						continue;
+					}
+
+					if let Some(&index) = face.iter().find(|&&i| i >= mesh.vertices.len()) {
+						return Err(ParseError::invalid(format!(
+							"Face index {index} is out of range"
+						)));
+					}
+
+					let points: Vec<Vec3> = face.iter().map(|&i| mesh.vertices[i].p).collect();
+
+					for triangle in triangulate(&points) {
+						mesh.indices.extend(triangle.map(|i| face[i]));
+					}
+				}
+				_ => {}
+			}
+		}
+	}
+
+	let missing_normals = !mesh.indices.is_empty() && normals.iter().any(|has_normal| !has_normal);
+
+	if colors.iter().any(Option::is_some) {
+		let colors = colors.iter().map(|c| c.unwrap_or(Vec4::ONE)).collect();
+		mesh.attributes.insert::<Vec4>(COLOR, colors);
+	}
+
+	if uvs.iter().any(Option::is_some) {
+		let uvs = uvs.iter().map(|uv| uv.unwrap_or(Vec2::ZERO)).collect();
+		mesh.attributes.insert::<Vec2>(&uv_name(0), uvs);
+	}
+
+	// Splits vertices along sharp edges, so this comes after all channels are added.
+	if missing_normals {
+		calculate_normals(&mut mesh, NormalMode::AutoSmooth(AUTO_SMOOTH_ANGLE));
+	}
+
+	calculate_tangents(&mut mesh);
+
+	Ok(mesh)
+}
+
+fn read_header(bytes: &[u8]) -> Result<(PlyFormat, Vec<Element>, &[u8]), ParseError> {
+	const END: &[u8] = b"end_header";
+
+	let end = bytes
+		.windows(END.len())
+		.position(|window| window == END)
+		.ok_or_else(|| ParseError::invalid("PLY file without end_header"))?;
+
+	// The body starts after the line break that follows `end_header`.
+	let body_start = bytes[end..]
+		.iter()
+		.position(|&b| b == b'\n')
+		.map_or(bytes.len(), |i| end + i + 1);
+
+	let header = std::str::from_utf8(&bytes[..end])
+		.map_err(|_| ParseError::invalid("PLY header is not valid UTF-8"))?;
+
+	let mut lines = header.lines().enumerate();
+
+	if lines.next().map(|(_, line)| line.trim()) != Some("ply") {
+		return Err(ParseError::invalid("Not a PLY file"));
+	}
+
+	let mut format = None;
+	let mut elements: Vec<Element> = Vec::new();
+
+	for (line_index, line) in lines {
+		let line_number = line_index + 1;
+		let tokens: Vec<&str> = line.split_whitespace().collect();
+		let error = |message: &str| ParseError::at_line(line_number, message);
+		let parse_type = |name: &str| PlyType::parse(name).ok_or_else(|| error("Invalid type"));
+
+		match tokens.as_slice() {
+			["format", name, _version] => {
+				format = Some(match *name {
+					"ascii" => PlyFormat::Ascii,
+					"binary_little_endian" => PlyFormat::BinaryLittleEndian,
+					"binary_big_endian" => PlyFormat::BinaryBigEndian,
+					_ => return Err(error("Invalid format")),
+				});
+			}
+			["element", name, count] => elements.push(Element {
+				name: name.to_string(),
+				count: count.parse().map_err(|_| error("Invalid element count"))?,
+				properties: Vec::new(),
+			}),
+			["property", "list", count_ty, item_ty, name] => {
+				let element = elements
+					.last_mut()
+					.ok_or_else(|| error("Property before element"))?;
+				element.properties.push(Property::List {
+					name: name.to_string(),
+					count_ty: parse_type(count_ty)?,
+					item_ty: parse_type(item_ty)?,
+				});
+			}
+			["property", ty, name] => {
+				let element = elements
+					.last_mut()
+					.ok_or_else(|| error("Property before element"))?;
+				element.properties.push(Property::Scalar {
+					name: name.to_string(),
+					ty: parse_type(ty)?,
+				});
+			}
+			["comment" | "obj_info", ..] | [] => {}
+			_ => return Err(error("Invalid header line")),
+		}
+	}
+
+	let format = format.ok_or_else(|| ParseError::invalid("PLY file without format"))?;
+	Ok((format, elements, &bytes[body_start..]))
+}
+
+/// Writes a mesh as PLY, with vertex normals, the [`COLOR`] channel and the `uv0` channel.
+pub fn write_ply(mesh: &Mesh, writer: &mut impl Write, format: PlyFormat) -> io::Result<()> {
+	let colors = mesh.attributes.colors();
+	let uvs = mesh.attributes.uv(0);
+
+	let format_name = match format {
+		PlyFormat::Ascii => "ascii",
+		PlyFormat::BinaryLittleEndian => "binary_little_endian",
+		PlyFormat::BinaryBigEndian => "binary_big_endian",
+	};
+
+	writeln!(writer, "ply")?;
+	writeln!(writer, "format {format_name} 1.0")?;
+	writeln!(writer, "element vertex {}", mesh.vertices.len())?;
+	for name in ["x", "y", "z", "nx", "ny", "nz"] {
+		writeln!(writer, "property float {name}")?;
+	}
+	if colors.is_some() {
+		for name in ["red", "green", "blue", "alpha"] {
+			writeln!(writer, "property uchar {name}")?;
+		}
+	}
+	if uvs.is_some() {
+		writeln!(writer, "property float s")?;
+		writeln!(writer, "property float t")?;
+	}
+	writeln!(writer, "element face {}", mesh.indices.len() / 3)?;
+	writeln!(writer, "property list uchar int vertex_indices")?;
+	writeln!(writer, "end_header")?;
+
+	let mut floats = Vec::new();
+	let mut bytes = Vec::new();
+
+	for (i, vertex) in mesh.vertices.iter().enumerate() {
+		floats.clear();
+		floats.extend([vertex.p.x, vertex.p.y, vertex.p.z]);
+		floats.extend([vertex.n.x, vertex.n.y, vertex.n.z]);
+
+		bytes.clear();
+		if let Some(colors) = colors {
+			let color: [f32; 4] = colors[i].into();
+			bytes.extend(color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+		}
+
+		if format == PlyFormat::Ascii {
+			let values = floats
+				.iter()
+				.map(f32::to_string)
+				.chain(bytes.iter().map(u8::to_string))
+				.chain(
+					uvs.iter()
+						.flat_map(|uvs| [uvs[i].x, uvs[i].y].map(|v| v.to_string())),
+				)
+				.collect::<Vec<_>>();
+			writeln!(writer, "{}", values.join(" "))?;
+		} else {
+			for value in &floats {
+				write_binary(writer, format, &value.to_le_bytes())?;
+			}
+			writer.write_all(&bytes)?;
+			for value in uvs.iter().flat_map(|uvs| [uvs[i].x, uvs[i].y]) {
+				write_binary(writer, format, &value.to_le_bytes())?;
+			}
+		}
+	}
+
+	for triangle in mesh.indices.chunks_exact(3) {
+		if format == PlyFormat::Ascii {
+			writeln!(writer, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?;
+		} else {
+			writer.write_all(&[3])?;
+			for &index in triangle {
+				write_binary(writer, format, &(index as i32).to_le_bytes())?;
+			}
+		}
+	}
+
+	Ok(())
+}
+
+/// Writes little endian bytes in the byte order of the format.
+fn write_binary(writer: &mut impl Write, format: PlyFormat, bytes: &[u8]) -> io::Result<()> {
+	if format == PlyFormat::BinaryBigEndian {
+		let reversed: Vec<u8> = bytes.iter().rev().copied().collect();
+		writer.write_all(&reversed)
+	} else {
+		writer.write_all(bytes)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::primitives::{platonic, sphere, torus};
+
+	#[test]
+	fn round_trip() {
+		let mut torus = torus::torus(12, 8, 1.0, 0.25);
+
+		// Colors that are exactly representable in 8 bits.
+		let colors = (0..torus.vertices.len())
+			.map(|i| Vec4::new((i % 256) as f32 / 255.0, 0.0, 1.0, 1.0))
+			.collect();
+		torus.attributes.insert::<Vec4>(COLOR, colors);
+
+		let meshes = [platonic::icosahedron(), sphere::sphere(1.0, 8, 6), torus];
+
+		for mesh in &meshes {
+			for format in [
+				PlyFormat::Ascii,
+				PlyFormat::BinaryLittleEndian,
+				PlyFormat::BinaryBigEndian,
+			] {
+				let mut bytes = Vec::new();
+				write_ply(mesh, &mut bytes, format).unwrap();
+				let read = read_ply(&bytes).unwrap();
+
+				assert_eq!(read.indices, mesh.indices);
+				assert!(
+					read.vertices
+						.iter()
+						.zip(&mesh.vertices)
+						.all(|(a, b)| a.p == b.p && a.n == b.n)
+				);
+				assert!(read.attributes.colors() == mesh.attributes.colors());
+			}
+		}
+	}
+
+	#[test]
+	fn point_cloud() {
+		let source = b"ply\nformat ascii 1.0\ncomment scan\nelement vertex 2\nproperty double x\nproperty double y\nproperty double z\nproperty uchar red\nproperty uchar green\nproperty uchar blue\nend_header\n0 0 0 255 0 0\n1 2 3 0 255 0\n";
+
+		let mesh = read_ply(source).unwrap();
+
+		assert!(mesh.indices.is_empty());
+		assert!(mesh.vertices[1].p == Vec3::new(1.0, 2.0, 3.0));
+		assert!(mesh.attributes.colors().unwrap()[1] == Vec4::new(0.0, 1.0, 0.0, 1.0));
+
+		assert!(
+			read_ply(b"ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nend_header\n0")
+				.is_err()
+		);
+	}
+}
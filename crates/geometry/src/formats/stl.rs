@@ -0,0 +1,213 @@
+//! STL triangle soups, in ASCII and binary encoding.
+
+use super::ParseError;
+use crate::mesh::{Mesh, Vertex, normalize_or_zero};
+
+use math::Vec3;
+use std::io::{self, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StlFormat {
+	Ascii,
+	Binary,
+}
+
+/// Reads an STL file.
+///
+/// STL files have no shared vertices, so every triangle gets its own three vertices with the facet normal, which
+/// gives a flat shaded mesh. Facet normals are recalculated from the winding when the file has none.
+pub fn read_stl(bytes: &[u8]) -> Result<Mesh, ParseError> {
+	// Binary files may also start with `solid`, so their size is checked as well.
+	let binary_size = bytes.get(80..84).map(|count| {
+		84 + 50 * u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize
+	});
+
+	let triangles = if bytes.starts_with(b"solid") && binary_size != Some(bytes.len()) {
+		read_ascii(bytes)?
+	} else {
+		read_binary(bytes)?
+	};
+
+	let mut mesh = Mesh::new();
+
+	for [normal, a, b, c] in triangles {
+		let normal = match normal.length_sq() > f32::MIN_POSITIVE {
+			true => normal,
+			false => face_normal(a, b, c),
+		};
+
+		for p in [a, b, c] {
+			mesh.indices.push(mesh.vertices.len());
+			mesh.vertices.push(Vertex { p, n: normal });
+		}
+	}
+
+	Ok(mesh)
+}
+
+/// Facet normal and vertices of every triangle.
+fn read_ascii(bytes: &[u8]) -> Result<Vec<[Vec3; 4]>, ParseError> {
+	let source = std::str::from_utf8(bytes)
+		.map_err(|_| ParseError::invalid("STL file is not valid UTF-8"))?;
+
+	let mut triangles = Vec::new();
+	let mut normal = Vec3::ZERO;
+	let mut vertices = Vec::new();
+
+	for (line_index, line) in source.lines().enumerate() {
+		let line_number = line_index + 1;
+		let tokens: Vec<&str> = line.split_whitespace().collect();
+
+		let vec3 = |tokens: &[&str]| -> Result<Vec3, ParseError> {
+			let value = |token: &str| {
+				token.parse().map_err(|_| {
+					ParseError::at_line(line_number, format!("Invalid number {token}"))
+				})
+			};
+
+			match tokens {
+				[x, y, z] => Ok(Vec3::new(value(x)?, value(y)?, value(z)?)),
+				_ => Err(ParseError::at_line(line_number, "Expected 3 numbers")),
+			}
+		};
+
+		match tokens.as_slice() {
+			["facet", "normal", rest @ ..] => {
+				normal = vec3(rest)?;
+				vertices.clear();
+			}
+			["vertex", rest @ ..] => vertices.push(vec3(rest)?),
+			["endfacet"] => {
+				let [a, b, c] = vertices[..] else {
+					return Err(ParseError::at_line(line_number, "Facet without 3 vertices"));
+				};
+				triangles.push([normal, a, b, c]);
+			}
+			_ => {}
+		}
+	}
+
+	Ok(triangles)
+}
+
+fn read_binary(bytes: &[u8]) -> Result<Vec<[Vec3; 4]>, ParseError> {
+	let count = bytes
+		.get(80..84)
+		.map(|count| u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize)
+		.ok_or_else(|| ParseError::invalid("Truncated STL header"))?;
+
+	let data = bytes
+		.get(84..84 + count * 50)
+		.ok_or_else(|| ParseError::invalid("Truncated STL file"))?;
+
+	Ok(data
+		.chunks_exact(50)
+		.map(|triangle| {
+			let float = |i: usize| {
+				let b = &triangle[i * 4..i * 4 + 4];
+				f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+			};
+
+			// Followed by a 2 byte attribute, which is unused.
+			std::array::from_fn(|v| Vec3::new(float(v * 3), float(v * 3 + 1), float(v * 3 + 2)))
+		})
+		.collect())
+}
+
+fn face_normal(a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+	normalize_or_zero((b - a).cross(c - a))
+}
+
+/// Writes the triangles of a mesh as STL, with normals calculated from the triangle winding.
+pub fn write_stl(mesh: &Mesh, writer: &mut impl Write, format: StlFormat) -> io::Result<()> {
+	let triangles = mesh.indices.chunks_exact(3).map(|triangle| {
+		let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i]].p);
+		[face_normal(a, b, c), a, b, c]
+	});
+
+	match format {
+		StlFormat::Ascii => {
+			writeln!(writer, "solid mesh")?;
+
+			for [normal, a, b, c] in triangles {
+				writeln!(
+					writer,
+					"facet normal {} {} {}",
+					normal.x, normal.y, normal.z
+				)?;
+				writeln!(writer, "outer loop")?;
+				for p in [a, b, c] {
+					writeln!(writer, "vertex {} {} {}", p.x, p.y, p.z)?;
+				}
+				writeln!(writer, "endloop")?;
+				writeln!(writer, "endfacet")?;
+			}
+
+			writeln!(writer, "endsolid mesh")?;
+		}
+		StlFormat::Binary => {
+			// The header must not start with `solid`, or readers may take it for an ASCII file.
+			let mut header = [0; 80];
+			header[..6].copy_from_slice(b"binary");
+			writer.write_all(&header)?;
+			writer.write_all(&((mesh.indices.len() / 3) as u32).to_le_bytes())?;
+
+			for triangle in triangles {
+				for v in triangle {
+					for value in [v.x, v.y, v.z] {
+						writer.write_all(&value.to_le_bytes())?;
+					}
+				}
+				writer.write_all(&[0, 0])?;
+			}
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::primitives::{cylinder, grid, platonic, sphere};
+
+	#[test]
+	fn round_trip() {
+		let meshes = [
+			platonic::hexahedron(),
+			sphere::sphere(2.0, 8, 6),
+			cylinder::cylinder(0.5, 2.0, 8, 2, true),
+			grid::grid(1.0, 1.0, 4, 4),
+		];
+
+		for mesh in &meshes {
+			for format in [StlFormat::Ascii, StlFormat::Binary] {
+				let mut bytes = Vec::new();
+				write_stl(mesh, &mut bytes, format).unwrap();
+				let read = read_stl(&bytes).unwrap();
+
+				assert_eq!(read.indices.len(), mesh.indices.len());
+
+				// Triangles keep their vertices and winding, but vertices are no longer shared.
+				for (i, &index) in mesh.indices.iter().enumerate() {
+					assert!(read.vertices[read.indices[i]].p == mesh.vertices[index].p);
+				}
+
+				for triangle in read.indices.chunks_exact(3) {
+					let [a, b, c] = [0, 1, 2].map(|i| read.vertices[triangle[i]]);
+					assert!(a.n == face_normal(a.p, b.p, c.p));
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn missing_normals() {
+		let source = b"solid test\nfacet normal 0 0 0\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\nendloop\nendfacet\nendsolid test\n";
+
+		let mesh = read_stl(source).unwrap();
+		assert!(mesh.vertices.iter().all(|vertex| vertex.n == *Vec3::Z));
+
+		assert!(read_stl(b"solid test\nfacet normal 0 0 1\nvertex 0 0 0\nendfacet\n").is_err());
+	}
+}
@@ -0,0 +1,988 @@
+//! Half-edge mesh with polygon faces, for topology queries and local edits.
+//!
+//! Every edge is stored as a pair of opposite half-edges. Half-edges on the boundary have no face and are linked
+//! into loops around the holes, so the one-ring of a boundary vertex can be traversed like any other.
+
+use crate::formats::obj::triangulate;
+use crate::mesh::{Mesh, Vertex, VertexAttributes, calculate_vert_normals};
+
+use math::Vec3;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VertexId(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct HalfEdgeId(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FaceId(pub usize);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TopologyError {
+	/// The edge from the first to the second vertex is used twice in the same direction, by more than two faces
+	/// or by faces with opposite winding.
+	NonManifoldEdge(usize, usize),
+	/// The face with this index has less than 3 distinct vertices or refers to a vertex that does not exist.
+	InvalidFace(usize),
+}
+
+impl fmt::Display for TopologyError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::NonManifoldEdge(a, b) => write!(f, "Non-manifold edge from vertex {a} to {b}"),
+			Self::InvalidFace(face) => write!(f, "Invalid face {face}"),
+		}
+	}
+}
+
+impl std::error::Error for TopologyError {}
+
+#[derive(Clone)]
+struct VertexData {
+	position: Vec3,
+	/// Outgoing half-edge, on the boundary if the vertex is on the boundary. `None` for isolated vertices.
+	half_edge: Option<HalfEdgeId>,
+}
+
+#[derive(Clone)]
+struct HalfEdge {
+	origin: VertexId,
+	twin: HalfEdgeId,
+	next: HalfEdgeId,
+	prev: HalfEdgeId,
+	face: Option<FaceId>,
+}
+
+#[derive(Clone)]
+struct FaceData {
+	half_edge: HalfEdgeId,
+}
+
+/// Polygon mesh with half-edge connectivity.
+///
+/// Removed elements leave unused ids behind, which are skipped by the iterators and dropped by
+/// [`HalfEdgeMesh::to_mesh`]. Accessing a removed element panics.
+#[derive(Clone, Default)]
+pub struct HalfEdgeMesh {
+	vertices: Vec<Option<VertexData>>,
+	half_edges: Vec<Option<HalfEdge>>,
+	faces: Vec<Option<FaceData>>,
+	/// Per-vertex channels, indexed by [`VertexId`]. Edits interpolate them for new vertices.
+	pub attributes: VertexAttributes,
+}
+
+impl HalfEdgeMesh {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Builds the connectivity of a triangle mesh. Triangles that use a vertex twice are skipped.
+	///
+	/// Vertices are connected by index only, so vertices that are split at UV or normal seams become boundaries.
+	pub fn from_mesh(mesh: &Mesh) -> Result<Self, TopologyError> {
+		let positions: Vec<Vec3> = mesh.vertices.iter().map(|vertex| vertex.p).collect();
+		let triangles = mesh
+			.indices
+			.chunks_exact(3)
+			.filter(|t| t[0] != t[1] && t[1] != t[2] && t[2] != t[0]);
+
+		let mut half_edge_mesh = Self::from_polygons(&positions, triangles)?;
+		half_edge_mesh.attributes = mesh.attributes.clone();
+		Ok(half_edge_mesh)
+	}
+
+	/// Builds a mesh from polygons with counter-clockwise vertex indices into `positions`.
+	pub fn from_polygons<F: AsRef<[usize]>>(
+		positions: &[Vec3],
+		faces: impl IntoIterator<Item = F>,
+	) -> Result<Self, TopologyError> {
+		let mut mesh = Self {
+			vertices: positions
+				.iter()
+				.map(|&position| {
+					Some(VertexData {
+						position,
+						half_edge: None,
+					})
+				})
+				.collect(),
+			..Default::default()
+		};
+
+		let mut edges = HashMap::new();
+
+		for (face_index, face) in faces.into_iter().enumerate() {
+			let face = face.as_ref();
+			let n = face.len();
+
+			let repeated = (0..n).any(|i| face[i + 1..].contains(&face[i]));
+			if n < 3 || repeated || face.iter().any(|&v| v >= positions.len()) {
+				return Err(TopologyError::InvalidFace(face_index));
+			}
+
+			let first = mesh.half_edges.len();
+			let f = FaceId(mesh.faces.len());
+			mesh.faces.push(Some(FaceData {
+				half_edge: HalfEdgeId(first),
+			}));
+
+			for i in 0..n {
+				let (a, b) = (face[i], face[(i + 1) % n]);
+				let h = HalfEdgeId(first + i);
+
+				if edges.insert((a, b), h).is_some() {
+					return Err(TopologyError::NonManifoldEdge(a, b));
+				}
+
+				mesh.half_edges.push(Some(HalfEdge {
+					origin: VertexId(a),
+					twin: h,
+					next: HalfEdgeId(first + (i + 1) % n),
+					prev: HalfEdgeId(first + (i + n - 1) % n),
+					face: Some(f),
+				}));
+				mesh.vertex_mut(VertexId(a)).half_edge = Some(h);
+			}
+		}
+
+		// Pair the half-edges, adding boundary half-edges for edges with a single face.
+		let mut boundary = Vec::new();
+
+		for i in 0..mesh.half_edges.len() {
+			let h = HalfEdgeId(i);
+			let (a, b) = (mesh.origin(h), mesh.origin(mesh.next(h)));
+
+			let twin = match edges.get(&(b.0, a.0)) {
+				Some(&twin) => twin,
+				None => {
+					let twin = mesh.push_half_edge(b, h);
+					boundary.push(twin);
+					twin
+				}
+			};
+
+			mesh.half_edge_mut(h).twin = twin;
+		}
+
+		// The boundary half-edge that follows another is found by rotating around their shared vertex through the
+		// faces, which also separates the fans of vertices where several boundaries meet.
+		for &b in &boundary {
+			let mut next = mesh.twin(b);
+			while mesh.face(next).is_some() {
+				next = mesh.twin(mesh.prev(next));
+			}
+
+			mesh.half_edge_mut(b).next = next;
+			mesh.half_edge_mut(next).prev = b;
+		}
+
+		for &b in &boundary {
+			let origin = mesh.origin(b);
+			mesh.vertex_mut(origin).half_edge = Some(b);
+		}
+
+		Ok(mesh)
+	}
+
+	/// Triangulates the polygons and drops removed elements. Normals are calculated from the faces.
+	pub fn to_mesh(&self) -> Mesh {
+		let mut remap = vec![usize::MAX; self.vertices.len()];
+		let mut kept = Vec::new();
+		let mut mesh = Mesh::new();
+
+		for v in self.vertex_ids() {
+			remap[v.0] = kept.len();
+			kept.push(v.0);
+			mesh.vertices.push(Vertex {
+				p: self.position(v),
+				n: Vec3::ZERO,
+			});
+		}
+
+		for f in self.face_ids() {
+			let vertices: Vec<usize> = self.face_vertices(f).map(|v| remap[v.0]).collect();
+
+			if vertices.len() == 3 {
+				mesh.indices.extend_from_slice(&vertices);
+			} else {
+				let points: Vec<Vec3> = vertices.iter().map(|&v| mesh.vertices[v].p).collect();
+
+				for triangle in triangulate(&points) {
+					mesh.indices.extend(triangle.map(|i| vertices[i]));
+				}
+			}
+		}
+
+		mesh.attributes = self.attributes.gather(&kept);
+		calculate_vert_normals(&mut mesh);
+		mesh
+	}
+
+	fn vertex(&self, v: VertexId) -> &VertexData {
+		self.vertices[v.0].as_ref().expect("Vertex was removed")
+	}
+
+	fn vertex_mut(&mut self, v: VertexId) -> &mut VertexData {
+		self.vertices[v.0].as_mut().expect("Vertex was removed")
+	}
+
+	fn half_edge(&self, h: HalfEdgeId) -> &HalfEdge {
+		self.half_edges[h.0]
+			.as_ref()
+			.expect("Half-edge was removed")
+	}
+
+	fn half_edge_mut(&mut self, h: HalfEdgeId) -> &mut HalfEdge {
+		self.half_edges[h.0]
+			.as_mut()
+			.expect("Half-edge was removed")
+	}
+
+	fn face_data(&self, f: FaceId) -> &FaceData {
+		self.faces[f.0].as_ref().expect("Face was removed")
+	}
+
+	fn face_data_mut(&mut self, f: FaceId) -> &mut FaceData {
+		self.faces[f.0].as_mut().expect("Face was removed")
+	}
+
+	pub fn vertex_ids(&self) -> impl Iterator<Item = VertexId> + '_ {
+		(0..self.vertices.len())
+			.filter(|&i| self.vertices[i].is_some())
+			.map(VertexId)
+	}
+
+	pub fn half_edge_ids(&self) -> impl Iterator<Item = HalfEdgeId> + '_ {
+		(0..self.half_edges.len())
+			.filter(|&i| self.half_edges[i].is_some())
+			.map(HalfEdgeId)
+	}
+
+	/// One half-edge of every edge.
+	pub fn edge_ids(&self) -> impl Iterator<Item = HalfEdgeId> + '_ {
+		self.half_edge_ids().filter(|&h| h < self.twin(h))
+	}
+
+	pub fn face_ids(&self) -> impl Iterator<Item = FaceId> + '_ {
+		(0..self.faces.len())
+			.filter(|&i| self.faces[i].is_some())
+			.map(FaceId)
+	}
+
+	pub fn vertex_count(&self) -> usize {
+		self.vertex_ids().count()
+	}
+
+	pub fn edge_count(&self) -> usize {
+		self.half_edge_ids().count() / 2
+	}
+
+	pub fn face_count(&self) -> usize {
+		self.face_ids().count()
+	}
+
+	pub fn contains_vertex(&self, v: VertexId) -> bool {
+		self.vertices.get(v.0).is_some_and(Option::is_some)
+	}
+
+	pub fn contains_half_edge(&self, h: HalfEdgeId) -> bool {
+		self.half_edges.get(h.0).is_some_and(Option::is_some)
+	}
+
+	pub fn contains_face(&self, f: FaceId) -> bool {
+		self.faces.get(f.0).is_some_and(Option::is_some)
+	}
+
+	pub fn position(&self, v: VertexId) -> Vec3 {
+		self.vertex(v).position
+	}
+
+	pub fn set_position(&mut self, v: VertexId, position: Vec3) {
+		self.vertex_mut(v).position = position;
+	}
+
+	/// Outgoing half-edge of a vertex, on the boundary if the vertex is on the boundary.
+	pub fn vertex_half_edge(&self, v: VertexId) -> Option<HalfEdgeId> {
+		self.vertex(v).half_edge
+	}
+
+	pub fn face_half_edge(&self, f: FaceId) -> HalfEdgeId {
+		self.face_data(f).half_edge
+	}
+
+	pub fn origin(&self, h: HalfEdgeId) -> VertexId {
+		self.half_edge(h).origin
+	}
+
+	pub fn target(&self, h: HalfEdgeId) -> VertexId {
+		self.origin(self.twin(h))
+	}
+
+	pub fn twin(&self, h: HalfEdgeId) -> HalfEdgeId {
+		self.half_edge(h).twin
+	}
+
+	pub fn next(&self, h: HalfEdgeId) -> HalfEdgeId {
+		self.half_edge(h).next
+	}
+
+	pub fn prev(&self, h: HalfEdgeId) -> HalfEdgeId {
+		self.half_edge(h).prev
+	}
+
+	/// Face to the left of the half-edge, `None` on the boundary.
+	pub fn face(&self, h: HalfEdgeId) -> Option<FaceId> {
+		self.half_edge(h).face
+	}
+
+	/// Half-edges of the loop that `h` is part of, starting at `h`. This is a face or a boundary loop.
+	pub fn loop_half_edges(&self, h: HalfEdgeId) -> impl Iterator<Item = HalfEdgeId> + '_ {
+		std::iter::successors(Some(h), move |&e| Some(self.next(e)).filter(|&e| e != h))
+	}
+
+	/// Half-edges of a face in counter-clockwise order.
+	pub fn face_half_edges(&self, f: FaceId) -> impl Iterator<Item = HalfEdgeId> + '_ {
+		self.loop_half_edges(self.face_half_edge(f))
+	}
+
+	pub fn face_vertices(&self, f: FaceId) -> impl Iterator<Item = VertexId> + '_ {
+		self.face_half_edges(f).map(|h| self.origin(h))
+	}
+
+	/// Number of vertices of a face.
+	pub fn face_valence(&self, f: FaceId) -> usize {
+		self.face_half_edges(f).count()
+	}
+
+	/// Normal of a face, which is also correct for non-planar polygons.
+	pub fn face_normal(&self, f: FaceId) -> Vec3 {
+		let points: Vec<Vec3> = self.face_vertices(f).map(|v| self.position(v)).collect();
+
+		// Newell's method
+		let normal = (0..points.len()).fold(Vec3::ZERO, |sum, i| {
+			sum + points[i].cross(points[(i + 1) % points.len()])
+		});
+
+		*normal.normalize()
+	}
+
+	pub fn face_centroid(&self, f: FaceId) -> Vec3 {
+		let (sum, count) = self
+			.face_vertices(f)
+			.fold((Vec3::ZERO, 0), |(sum, count), v| {
+				(sum + self.position(v), count + 1)
+			});
+
+		sum * (1.0 / count as f32)
+	}
+
+	/// Outgoing half-edges of a vertex, rotating clockwise around it.
+	pub fn vertex_half_edges(&self, v: VertexId) -> impl Iterator<Item = HalfEdgeId> + '_ {
+		let start = self.vertex_half_edge(v);
+
+		std::iter::successors(start, move |&h| {
+			Some(self.twin(self.prev(h))).filter(|&h| Some(h) != start)
+		})
+	}
+
+	/// Vertices connected to a vertex by an edge, i.e. its one-ring.
+	pub fn vertex_neighbors(&self, v: VertexId) -> impl Iterator<Item = VertexId> + '_ {
+		self.vertex_half_edges(v).map(|h| self.target(h))
+	}
+
+	pub fn vertex_faces(&self, v: VertexId) -> impl Iterator<Item = FaceId> + '_ {
+		self.vertex_half_edges(v).filter_map(|h| self.face(h))
+	}
+
+	/// Number of edges of a vertex.
+	pub fn valence(&self, v: VertexId) -> usize {
+		self.vertex_half_edges(v).count()
+	}
+
+	/// Half-edge from `a` to `b`, if they are connected.
+	pub fn find_half_edge(&self, a: VertexId, b: VertexId) -> Option<HalfEdgeId> {
+		self.vertex_half_edges(a).find(|&h| self.target(h) == b)
+	}
+
+	pub fn is_boundary_half_edge(&self, h: HalfEdgeId) -> bool {
+		self.face(h).is_none()
+	}
+
+	/// Whether the edge of `h` has a face on only one side.
+	pub fn is_boundary_edge(&self, h: HalfEdgeId) -> bool {
+		self.is_boundary_half_edge(h) || self.is_boundary_half_edge(self.twin(h))
+	}
+
+	/// Whether a vertex is on the boundary or isolated.
+	pub fn is_boundary_vertex(&self, v: VertexId) -> bool {
+		self.vertex_half_edge(v)
+			.is_none_or(|h| self.is_boundary_half_edge(h))
+	}
+
+	/// Whether the mesh has no boundary.
+	pub fn is_closed(&self) -> bool {
+		self.half_edge_ids().all(|h| !self.is_boundary_half_edge(h))
+	}
+
+	/// Boundary loops, each starting at its first half-edge.
+	pub fn boundary_loops(&self) -> Vec<Vec<HalfEdgeId>> {
+		let mut visited = HashSet::new();
+		let mut loops = Vec::new();
+
+		for h in self.half_edge_ids() {
+			if self.is_boundary_half_edge(h) && visited.insert(h) {
+				let boundary: Vec<_> = self.loop_half_edges(h).collect();
+				visited.extend(boundary.iter().copied());
+				loops.push(boundary);
+			}
+		}
+
+		loops
+	}
+
+	/// Vertices where several fans of faces meet, like the tip of two cones. Their one-ring does not reach all of
+	/// their edges. Edges are always manifold, as they cannot be built otherwise.
+	pub fn non_manifold_vertices(&self) -> Vec<VertexId> {
+		let mut edge_counts = vec![0; self.vertices.len()];
+		for h in self.half_edge_ids() {
+			edge_counts[self.origin(h).0] += 1;
+		}
+
+		self.vertex_ids()
+			.filter(|&v| self.valence(v) != edge_counts[v.0])
+			.collect()
+	}
+
+	pub fn is_manifold(&self) -> bool {
+		self.non_manifold_vertices().is_empty()
+	}
+
+	/// Euler characteristic `V - E + F`, 2 for closed meshes of genus 0.
+	pub fn euler_characteristic(&self) -> isize {
+		self.vertex_count() as isize - self.edge_count() as isize + self.face_count() as isize
+	}
+
+	/// Adds an isolated vertex. Its attributes are zero.
+	pub fn add_vertex(&mut self, position: Vec3) -> VertexId {
+		self.add_blended_vertex(position, &[])
+	}
+
+	/// Adds an isolated vertex with attributes blended from other vertices.
+	fn add_blended_vertex(&mut self, position: Vec3, weights: &[(usize, f32)]) -> VertexId {
+		let v = VertexId(self.vertices.len());
+		self.vertices.push(Some(VertexData {
+			position,
+			half_edge: None,
+		}));
+		self.attributes.set_blend(v.0, weights);
+		v
+	}
+
+	/// Adds a half-edge that forms a loop on its own, to be linked up by the caller.
+	fn push_half_edge(&mut self, origin: VertexId, twin: HalfEdgeId) -> HalfEdgeId {
+		let h = HalfEdgeId(self.half_edges.len());
+		self.half_edges.push(Some(HalfEdge {
+			origin,
+			twin,
+			next: h,
+			prev: h,
+			face: None,
+		}));
+		h
+	}
+
+	/// Links half-edges into a loop of a face.
+	fn set_loop(&mut self, half_edges: &[HalfEdgeId], face: FaceId) {
+		let n = half_edges.len();
+
+		for (i, &h) in half_edges.iter().enumerate() {
+			let half_edge = self.half_edge_mut(h);
+			half_edge.next = half_edges[(i + 1) % n];
+			half_edge.prev = half_edges[(i + n - 1) % n];
+			half_edge.face = Some(face);
+		}
+
+		self.face_data_mut(face).half_edge = half_edges[0];
+	}
+
+	/// Sets the outgoing half-edge of a vertex to `h`, or to a boundary half-edge in its one-ring.
+	fn set_vertex_half_edge(&mut self, v: VertexId, h: HalfEdgeId) {
+		self.vertex_mut(v).half_edge = Some(h);
+
+		let boundary = self
+			.vertex_half_edges(v)
+			.find(|&h| self.is_boundary_half_edge(h));
+		self.vertex_mut(v).half_edge = Some(boundary.unwrap_or(h));
+	}
+
+	/// Rotates an edge between two triangles to connect their opposite vertices instead.
+	///
+	/// Returns `false` and leaves the mesh unchanged if the edge is on the boundary, a face is not a triangle or
+	/// the opposite vertices are already connected.
+	pub fn flip_edge(&mut self, h: HalfEdgeId) -> bool {
+		let t = self.twin(h);
+
+		let (Some(f0), Some(f1)) = (self.face(h), self.face(t)) else {
+			return false;
+		};
+
+		if self.face_valence(f0) != 3 || self.face_valence(f1) != 3 {
+			return false;
+		}
+
+		let (a, b) = (self.origin(h), self.origin(t));
+		let (hn, hp) = (self.next(h), self.prev(h));
+		let (tn, tp) = (self.next(t), self.prev(t));
+		let (c, d) = (self.origin(hp), self.origin(tp));
+
+		if c == d || self.find_half_edge(c, d).is_some() {
+			return false;
+		}
+
+		// Triangles (a, b, c) and (b, a, d) become (a, d, c) and (d, b, c).
+		self.half_edge_mut(h).origin = d;
+		self.half_edge_mut(t).origin = c;
+		self.set_loop(&[h, hp, tn], f0);
+		self.set_loop(&[t, tp, hn], f1);
+
+		if self.vertex_half_edge(a) == Some(h) {
+			self.vertex_mut(a).half_edge = Some(tn);
+		}
+		if self.vertex_half_edge(b) == Some(t) {
+			self.vertex_mut(b).half_edge = Some(hn);
+		}
+
+		true
+	}
+
+	/// Splits a face in two by connecting two of its vertices. Returns the new half-edge from `a` to `b`, or
+	/// `None` if the vertices are not in the face or already adjacent in it.
+	pub fn split_face(&mut self, f: FaceId, a: VertexId, b: VertexId) -> Option<HalfEdgeId> {
+		let half_edges: Vec<_> = self.face_half_edges(f).collect();
+
+		let ha = half_edges.iter().position(|&h| self.origin(h) == a)?;
+		let hb = half_edges.iter().position(|&h| self.origin(h) == b)?;
+		let n = half_edges.len();
+
+		if a == b || (ha + 1) % n == hb || (hb + 1) % n == ha {
+			return None;
+		}
+
+		// `f` keeps the half-edges from `a` to `b`, the new face gets the ones from `b` to `a`.
+		let f_half_edges: Vec<_> = (0..n)
+			.map(|i| half_edges[(ha + i) % n])
+			.take((hb + n - ha) % n)
+			.collect();
+		let g_half_edges: Vec<_> = (0..n)
+			.map(|i| half_edges[(hb + i) % n])
+			.take((ha + n - hb) % n)
+			.collect();
+
+		let ab = self.push_half_edge(a, HalfEdgeId(self.half_edges.len() + 1));
+		let ba = self.push_half_edge(b, ab);
+
+		let g = FaceId(self.faces.len());
+		self.faces.push(Some(FaceData { half_edge: ab }));
+
+		self.set_loop(&[f_half_edges, vec![ba]].concat(), f);
+		self.set_loop(&[g_half_edges, vec![ab]].concat(), g);
+
+		Some(ab)
+	}
+
+	/// Inserts a vertex on an edge at `t` from the origin to the target of `h`, with interpolated attributes.
+	/// Adjacent triangles are split to stay triangles, other faces get an additional vertex.
+	///
+	/// Afterwards, `h` ends at the new vertex.
+	pub fn split_edge(&mut self, h: HalfEdgeId, t: f32) -> VertexId {
+		let twin = self.twin(h);
+		let (a, b) = (self.origin(h), self.origin(twin));
+
+		let position = self.position(a) * (1.0 - t) + self.position(b) * t;
+		let m = self.add_blended_vertex(position, &[(a.0, 1.0 - t), (b.0, t)]);
+
+		let (hn, tn) = (self.next(h), self.next(twin));
+		let (hf, tf) = (self.face(h), self.face(twin));
+		let triangles = [hf, tf].map(|f| f.map(|f| self.face_valence(f) == 3));
+
+		// `h` becomes a to m and `twin` becomes b to m, followed by the new half-edges from m.
+		let h2 = self.push_half_edge(m, twin);
+		let t2 = self.push_half_edge(m, h);
+
+		for (first, second, next, face, first_twin) in [(h, h2, hn, hf, t2), (twin, t2, tn, tf, h2)]
+		{
+			self.half_edge_mut(first).next = second;
+			self.half_edge_mut(first).twin = first_twin;
+			let half_edge = self.half_edge_mut(second);
+			half_edge.prev = first;
+			half_edge.next = next;
+			half_edge.face = face;
+			self.half_edge_mut(next).prev = second;
+		}
+
+		self.set_vertex_half_edge(m, h2);
+
+		for (second, face, triangle) in [(h2, hf, triangles[0]), (t2, tf, triangles[1])] {
+			if let Some(f) = face
+				&& triangle == Some(true)
+			{
+				let opposite = self.target(self.next(second));
+				self.split_face(f, m, opposite);
+			}
+		}
+
+		m
+	}
+
+	/// Whether collapsing the edge of `h` keeps the mesh manifold, and does not collapse it into a single
+	/// triangle or leave edges without faces.
+	pub fn can_collapse_edge(&self, h: HalfEdgeId) -> bool {
+		let t = self.twin(h);
+		let (a, b) = (self.origin(h), self.origin(t));
+
+		// Would pinch the mesh at the merged vertex.
+		if self.is_boundary_vertex(a) && self.is_boundary_vertex(b) && !self.is_boundary_edge(h) {
+			return false;
+		}
+
+		let mut opposites = Vec::new();
+
+		for e in [h, t] {
+			if self.loop_half_edges(e).count() != 3 {
+				continue;
+			}
+
+			let (next, prev) = (self.next(e), self.prev(e));
+			let opposite = self.origin(prev);
+
+			// The remaining edges are merged, which leaves an edge without faces if neither has one.
+			let next_face = self.face(self.twin(next));
+			let prev_face = self.face(self.twin(prev));
+			if next_face.is_none() && prev_face.is_none() {
+				return false;
+			}
+
+			// Would leave two faces with the same vertices, like when collapsing a tetrahedron.
+			if self.face(e).is_some()
+				&& !self.is_boundary_vertex(opposite)
+				&& self.valence(opposite) <= 3
+			{
+				return false;
+			}
+
+			opposites.push(opposite);
+		}
+
+		if opposites.len() == 2 && opposites[0] == opposites[1] {
+			return false;
+		}
+
+		// Link condition: the only vertices connected to both are the ones opposite the edge.
+		let a_neighbors: HashSet<_> = self.vertex_neighbors(a).collect();
+		self.vertex_neighbors(b)
+			.filter(|v| a_neighbors.contains(v))
+			.all(|v| opposites.contains(&v))
+	}
+
+	/// Merges the vertices of an edge into the origin of `h`, which is moved to `position`. Attributes are
+	/// interpolated at the projection of `position` onto the edge. Triangles of the edge are removed, other faces
+	/// lose a vertex.
+	///
+	/// Returns the merged vertex, or `None` and leaves the mesh unchanged if [`Self::can_collapse_edge`] is false.
+	pub fn collapse_edge(&mut self, h: HalfEdgeId, position: Vec3) -> Option<VertexId> {
+		if !self.can_collapse_edge(h) {
+			return None;
+		}
+
+		let t = self.twin(h);
+		let (a, b) = (self.origin(h), self.origin(t));
+		let (pa, pb) = (self.position(a), self.position(b));
+
+		let edge = pb - pa;
+		let s = match edge.length_sq() > f32::MIN_POSITIVE {
+			true => math::clamp((position - pa).dot(edge) / edge.length_sq(), 0.0, 1.0),
+			false => 0.5,
+		};
+
+		let ring: Vec<_> = self
+			.vertex_half_edges(a)
+			.chain(self.vertex_half_edges(b))
+			.collect();
+		let mut opposites = Vec::new();
+
+		for e in [h, t] {
+			let (next, prev) = (self.next(e), self.prev(e));
+
+			if self.loop_half_edges(e).count() == 3 {
+				// Remove the triangle and make the twins of its other edges twins of each other.
+				let (next_twin, prev_twin) = (self.twin(next), self.twin(prev));
+				self.half_edge_mut(next_twin).twin = prev_twin;
+				self.half_edge_mut(prev_twin).twin = next_twin;
+
+				let opposite = self.origin(prev);
+				if self.vertex_half_edge(opposite) == Some(prev) {
+					self.vertex_mut(opposite).half_edge = Some(next_twin);
+				}
+				opposites.push((opposite, next_twin));
+
+				if let Some(f) = self.face(e) {
+					self.faces[f.0] = None;
+				}
+				self.half_edges[next.0] = None;
+				self.half_edges[prev.0] = None;
+			} else {
+				self.half_edge_mut(prev).next = next;
+				self.half_edge_mut(next).prev = prev;
+
+				if let Some(f) = self.face(e)
+					&& self.face_half_edge(f) == e
+				{
+					self.face_data_mut(f).half_edge = next;
+				}
+			}
+		}
+
+		self.half_edges[h.0] = None;
+		self.half_edges[t.0] = None;
+		self.vertices[b.0] = None;
+
+		for &e in &ring {
+			if let Some(half_edge) = &mut self.half_edges[e.0] {
+				half_edge.origin = a;
+			}
+		}
+
+		self.set_position(a, position);
+		self.attributes.set_blend(a.0, &[(a.0, 1.0 - s), (b.0, s)]);
+
+		let outgoing = ring.into_iter().find(|&e| self.contains_half_edge(e));
+		self.vertex_mut(a).half_edge = outgoing;
+		if let Some(outgoing) = outgoing {
+			self.set_vertex_half_edge(a, outgoing);
+		}
+
+		for (opposite, outgoing) in opposites {
+			let outgoing = self.vertex_half_edge(opposite).unwrap_or(outgoing);
+			self.set_vertex_half_edge(opposite, outgoing);
+		}
+
+		Some(a)
+	}
+
+	/// Extrudes a face by `offset`. The face is moved onto copies of its vertices, which are connected to the
+	/// original vertices by a ring of quads. Returns the side faces.
+	pub fn extrude_face(&mut self, f: FaceId, offset: Vec3) -> Vec<FaceId> {
+		let half_edges: Vec<_> = self.face_half_edges(f).collect();
+		let n = half_edges.len();
+
+		let old: Vec<_> = half_edges.iter().map(|&h| self.origin(h)).collect();
+		let new: Vec<_> = old
+			.iter()
+			.map(|&v| self.add_blended_vertex(self.position(v) + offset, &[(v.0, 1.0)]))
+			.collect();
+
+		for i in 0..n {
+			self.half_edge_mut(half_edges[i]).origin = new[i];
+			self.vertex_mut(new[i]).half_edge = Some(half_edges[i]);
+		}
+
+		// Side quad `i` goes from old vertex `i` to `i + 1`, up to the new vertices and back down.
+		let first = self.half_edges.len();
+		let quad = |i: usize, corner: usize| HalfEdgeId(first + 4 * (i % n) + corner);
+		let mut sides = Vec::new();
+
+		for i in 0..n {
+			let j = (i + 1) % n;
+			let h = half_edges[i];
+			let t = self.twin(h);
+
+			self.push_half_edge(old[i], t);
+			self.push_half_edge(old[j], quad(j, 3));
+			self.push_half_edge(new[j], h);
+			self.push_half_edge(new[i], quad(i + n - 1, 1));
+
+			self.half_edge_mut(t).twin = quad(i, 0);
+			self.half_edge_mut(h).twin = quad(i, 2);
+
+			let side = FaceId(self.faces.len());
+			self.faces.push(Some(FaceData {
+				half_edge: quad(i, 0),
+			}));
+			self.set_loop(&[0, 1, 2, 3].map(|corner| quad(i, corner)), side);
+			sides.push(side);
+
+			if self.vertex_half_edge(old[i]) == Some(h) {
+				self.vertex_mut(old[i]).half_edge = Some(quad(i, 0));
+			}
+		}
+
+		sides
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::primitives::{grid, sphere};
+	use crate::test_support::quad_cube;
+
+	/// Checks that all links are consistent.
+	fn validate(mesh: &HalfEdgeMesh) {
+		for h in mesh.half_edge_ids() {
+			assert_eq!(mesh.twin(mesh.twin(h)), h);
+			assert_ne!(mesh.twin(h), h);
+			assert_eq!(mesh.prev(mesh.next(h)), h);
+			assert_eq!(mesh.origin(mesh.next(h)), mesh.target(h));
+			assert_eq!(mesh.face(mesh.next(h)), mesh.face(h));
+			assert!(mesh.contains_vertex(mesh.origin(h)));
+		}
+
+		for f in mesh.face_ids() {
+			assert!(mesh.face_valence(f) >= 3);
+			assert!(mesh.face_half_edges(f).all(|h| mesh.face(h) == Some(f)));
+		}
+
+		for v in mesh.vertex_ids() {
+			if let Some(h) = mesh.vertex_half_edge(v) {
+				assert_eq!(mesh.origin(h), v);
+				let boundary = mesh
+					.vertex_half_edges(v)
+					.any(|h| mesh.is_boundary_half_edge(h));
+				assert_eq!(mesh.is_boundary_half_edge(h), boundary);
+			}
+		}
+	}
+
+	#[test]
+	fn topology() {
+		let sphere = sphere::sphere(1.0, 8, 6);
+		let mesh = HalfEdgeMesh::from_mesh(&sphere).unwrap();
+		validate(&mesh);
+
+		assert!(mesh.is_closed() && mesh.is_manifold());
+		assert_eq!(mesh.euler_characteristic(), 2);
+		assert_eq!(mesh.valence(VertexId(0)), 8);
+		assert_eq!(mesh.to_mesh().indices, sphere.indices);
+
+		let grid = HalfEdgeMesh::from_mesh(&grid::grid(1.0, 1.0, 4, 3)).unwrap();
+		validate(&grid);
+
+		let loops = grid.boundary_loops();
+		assert_eq!(loops.len(), 1);
+		assert_eq!(loops[0].len(), 2 * (3 + 2));
+		assert_eq!(grid.euler_characteristic(), 1);
+
+		// Two triangles that only share a vertex.
+		let positions = [
+			Vec3::ZERO,
+			Vec3::new(1.0, 0.0, 0.0),
+			Vec3::new(0.0, 1.0, 0.0),
+		];
+		let positions = [positions, positions.map(|p| p * -1.0)].concat();
+		let bowtie = HalfEdgeMesh::from_polygons(&positions, [[0, 1, 2], [0, 4, 5]]).unwrap();
+		validate(&bowtie);
+		assert_eq!(bowtie.non_manifold_vertices(), [VertexId(0)]);
+
+		assert_eq!(
+			HalfEdgeMesh::from_polygons(&positions, [[0, 1, 2], [0, 1, 5]]).err(),
+			Some(TopologyError::NonManifoldEdge(0, 1))
+		);
+		assert_eq!(
+			HalfEdgeMesh::from_polygons(&positions, [[0, 1, 1]]).err(),
+			Some(TopologyError::InvalidFace(0))
+		);
+	}
+
+	#[test]
+	fn polygons() {
+		let mut mesh = quad_cube();
+		validate(&mesh);
+		assert_eq!(
+			(mesh.vertex_count(), mesh.edge_count(), mesh.face_count()),
+			(8, 12, 6)
+		);
+		assert!(mesh.face_ids().all(|f| mesh.face_valence(f) == 4));
+		assert_eq!(mesh.to_mesh().indices.len(), 36);
+
+		let top = FaceId(2);
+		let normal = mesh.face_normal(top);
+		let sides = mesh.extrude_face(top, normal);
+		validate(&mesh);
+
+		assert_eq!(sides.len(), 4);
+		assert_eq!((mesh.vertex_count(), mesh.face_count()), (12, 10));
+		assert!(mesh.is_closed() && mesh.is_manifold());
+		assert_eq!(mesh.euler_characteristic(), 2);
+
+		let diagonal = mesh.split_face(top, VertexId(8), VertexId(10)).unwrap();
+		validate(&mesh);
+		assert_eq!(mesh.face_valence(top), 3);
+		assert_eq!(mesh.face_count(), 11);
+		assert!(mesh.flip_edge(diagonal));
+		validate(&mesh);
+	}
+
+	#[test]
+	fn edits() {
+		let mut mesh = HalfEdgeMesh::from_mesh(&sphere::sphere(1.0, 8, 6)).unwrap();
+		mesh.attributes.insert(
+			"index",
+			(0..mesh.vertex_count()).map(|i| i as f32).collect(),
+		);
+
+		let h = mesh.find_half_edge(VertexId(1), VertexId(9)).unwrap();
+		assert!(mesh.flip_edge(h));
+		assert!(mesh.find_half_edge(VertexId(1), VertexId(9)).is_none());
+		validate(&mesh);
+
+		// Splitting an edge adds a vertex, 3 edges and 2 faces.
+		let h = mesh.find_half_edge(VertexId(9), VertexId(10)).unwrap();
+		let m = mesh.split_edge(h, 0.25);
+		validate(&mesh);
+		assert_eq!(mesh.valence(m), 4);
+		assert_eq!(mesh.euler_characteristic(), 2);
+		assert_eq!(mesh.attributes.get::<f32>("index").unwrap()[m.0], 9.25);
+
+		let before = (mesh.vertex_count(), mesh.edge_count(), mesh.face_count());
+		let h = mesh.find_half_edge(m, VertexId(10)).unwrap();
+		let position = mesh.position(VertexId(10));
+		assert_eq!(mesh.collapse_edge(h, position), Some(m));
+		validate(&mesh);
+
+		// Collapsing removes a vertex, 3 edges and 2 faces.
+		let after = (mesh.vertex_count(), mesh.edge_count(), mesh.face_count());
+		assert_eq!(after, (before.0 - 1, before.1 - 3, before.2 - 2));
+		assert!(mesh.is_closed() && mesh.is_manifold());
+		assert_eq!(mesh.attributes.get::<f32>("index").unwrap()[m.0], 10.0);
+
+		// Collapse edges until the mesh is as small as it gets.
+		loop {
+			let Some(h) = mesh.edge_ids().find(|&h| mesh.can_collapse_edge(h)) else {
+				break;
+			};
+			let position = mesh.position(mesh.origin(h));
+			mesh.collapse_edge(h, position).unwrap();
+			validate(&mesh);
+			assert!(mesh.is_manifold());
+			assert_eq!(mesh.euler_characteristic(), 2);
+		}
+		assert_eq!(mesh.vertex_count(), 4);
+
+		// Collapsing a grid keeps its boundary.
+		let mut grid = HalfEdgeMesh::from_mesh(&grid::grid(1.0, 1.0, 3, 3)).unwrap();
+		loop {
+			let Some(h) = grid.edge_ids().find(|&h| grid.can_collapse_edge(h)) else {
+				break;
+			};
+			let position = grid.position(grid.origin(h));
+			grid.collapse_edge(h, position).unwrap();
+			validate(&grid);
+			assert!(grid.is_manifold());
+			assert_eq!(grid.euler_characteristic(), 1);
+			assert_eq!(grid.boundary_loops().len(), 1);
+		}
+		assert_eq!(grid.face_count(), 1);
+	}
+}
@@ -0,0 +1,630 @@
+//! Extraction of isosurfaces from scalar fields and signed distance functions.
+//!
+//! Samples below the iso value are inside of the surface, and normals point towards increasing values, which is
+//! outwards for signed distances.
+
+use crate::bvh::Aabb;
+use crate::cleanup;
+use crate::mesh::{
+	AUTO_SMOOTH_ANGLE, Mesh, NormalMode, Vertex, calculate_normals, normalize_or_zero,
+};
+
+use math::Vec3;
+use std::collections::HashMap;
+
+/// Eigenvalues of the error function of a dual contouring vertex below this fraction of the largest one are
+/// ignored, which keeps the vertices of flat and nearly flat areas from drifting along the surface.
+const SINGULAR_THRESHOLD: f32 = 0.1;
+
+/// Bisection steps that refine where the surface of a signed distance function crosses an edge of the grid.
+const CROSSING_STEPS: usize = 10;
+
+/// Sweeps of Jacobi rotations, which converge quickly for 3x3 matrices.
+const JACOBI_SWEEPS: usize = 8;
+
+/// Step of the central differences that approximate the gradient of a signed distance function, relative to the
+/// sample spacing.
+const GRADIENT_STEP: f32 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+	/// Vertices on the edges of the grid with smooth normals. Sharp features are rounded off.
+	MarchingCubes,
+	/// A vertex in every cell, placed to keep sharp features, with hard edges where the surface bends sharply.
+	DualContouring,
+}
+
+/// Scalar values sampled on a regular grid.
+#[derive(Clone)]
+pub struct ScalarField {
+	/// Position of the first sample.
+	pub origin: Vec3,
+	/// Distance between neighboring samples.
+	pub spacing: f32,
+	/// Number of samples along every axis.
+	pub size: [usize; 3],
+	/// Samples in X, then Y, then Z order.
+	pub values: Vec<f32>,
+}
+
+impl ScalarField {
+	pub fn new(origin: Vec3, spacing: f32, size: [usize; 3]) -> Self {
+		Self {
+			origin,
+			spacing,
+			size,
+			values: vec![0.0; size[0] * size[1] * size[2]],
+		}
+	}
+
+	/// Samples a function at every grid point.
+	pub fn from_fn(origin: Vec3, spacing: f32, size: [usize; 3], f: impl Fn(Vec3) -> f32) -> Self {
+		let mut field = Self::new(origin, spacing, size);
+
+		for z in 0..size[2] {
+			for y in 0..size[1] {
+				for x in 0..size[0] {
+					let index = field.index([x, y, z]);
+					field.values[index] = f(field.position([x, y, z]));
+				}
+			}
+		}
+
+		field
+	}
+
+	/// Samples a function in the bounds, extended to whole samples, with a border of samples around them so that
+	/// surfaces up to the bounds are closed.
+	pub fn from_bounds(bounds: &Aabb, spacing: f32, f: impl Fn(Vec3) -> f32) -> Self {
+		let extent = bounds.size();
+		let size = [extent.x, extent.y, extent.z].map(|e| (e / spacing).ceil() as usize + 3);
+		let origin = bounds.min - Vec3::new(spacing, spacing, spacing);
+
+		Self::from_fn(origin, spacing, size, f)
+	}
+
+	fn index(&self, [x, y, z]: [usize; 3]) -> usize {
+		x + self.size[0] * (y + self.size[1] * z)
+	}
+
+	pub fn get(&self, sample: [usize; 3]) -> f32 {
+		self.values[self.index(sample)]
+	}
+
+	pub fn set(&mut self, sample: [usize; 3], value: f32) {
+		let index = self.index(sample);
+		self.values[index] = value;
+	}
+
+	/// Position of a sample.
+	pub fn position(&self, [x, y, z]: [usize; 3]) -> Vec3 {
+		self.origin + Vec3::new(x as f32, y as f32, z as f32) * self.spacing
+	}
+
+	/// Gradient at a sample, from central differences inside of the grid and one-sided ones at its border.
+	fn sample_gradient(&self, sample: [usize; 3]) -> Vec3 {
+		let [x, y, z] = [0, 1, 2].map(|axis| {
+			let mut lo = sample;
+			let mut hi = sample;
+			lo[axis] = lo[axis].saturating_sub(1);
+			hi[axis] = (hi[axis] + 1).min(self.size[axis] - 1);
+
+			let steps = (hi[axis] - lo[axis]).max(1) as f32;
+			(self.get(hi) - self.get(lo)) / (steps * self.spacing)
+		});
+
+		Vec3::new(x, y, z)
+	}
+
+	/// Gradient at a point in the grid, interpolated trilinearly from the gradients at the samples.
+	pub fn gradient(&self, p: Vec3) -> Vec3 {
+		let local = (p - self.origin) * (1.0 / self.spacing);
+		let local = [local.x, local.y, local.z];
+
+		let lo =
+			[0, 1, 2].map(|axis| (local[axis].floor().max(0.0) as usize).min(self.size[axis] - 1));
+		let t = [0, 1, 2].map(|axis| (local[axis] - lo[axis] as f32).clamp(0.0, 1.0));
+
+		let mut gradient = Vec3::ZERO;
+
+		for corner in 0..8 {
+			let mut sample = lo;
+			let mut weight = 1.0;
+
+			for axis in [0, 1, 2] {
+				if corner >> axis & 1 == 1 {
+					sample[axis] = (sample[axis] + 1).min(self.size[axis] - 1);
+					weight *= t[axis];
+				} else {
+					weight *= 1.0 - t[axis];
+				}
+			}
+
+			gradient += self.sample_gradient(sample) * weight;
+		}
+
+		gradient
+	}
+}
+
+/// Extracts the surface where a scalar field has the iso value.
+pub fn extract(field: &ScalarField, iso: f32, method: Method) -> Mesh {
+	let surface = Surface {
+		field,
+		iso,
+		sdf: None,
+		gradient: &|p| field.gradient(p),
+	};
+
+	surface.extract(method)
+}
+
+/// Extracts the surface of a signed distance function in the bounds, sampled with the given spacing. Crossings,
+/// normals and sharp features come from the function itself, so they are more precise than those of a sampled
+/// field.
+pub fn extract_sdf(sdf: impl Fn(Vec3) -> f32, bounds: &Aabb, spacing: f32, method: Method) -> Mesh {
+	let field = ScalarField::from_bounds(bounds, spacing, &sdf);
+	let h = spacing * GRADIENT_STEP;
+
+	let gradient = |p: Vec3| {
+		let [x, y, z] = [Vec3::X, Vec3::Y, Vec3::Z].map(|axis| {
+			let offset = *axis * h;
+			(sdf(p + offset) - sdf(p - offset)) / (2.0 * h)
+		});
+		Vec3::new(x, y, z)
+	};
+
+	let surface = Surface {
+		field: &field,
+		iso: 0.0,
+		sdf: Some(&sdf),
+		gradient: &gradient,
+	};
+
+	surface.extract(method)
+}
+
+/// Surface of a scalar field, with the function it was sampled from if there is one.
+struct Surface<'a> {
+	field: &'a ScalarField,
+	iso: f32,
+	sdf: Option<&'a dyn Fn(Vec3) -> f32>,
+	gradient: &'a dyn Fn(Vec3) -> Vec3,
+}
+
+impl Surface<'_> {
+	fn inside(&self, value: f32) -> bool {
+		value < self.iso
+	}
+
+	/// Where the surface crosses the edge between two samples, with the unit normal of the surface there.
+	fn crossing(&self, a: [usize; 3], b: [usize; 3]) -> (Vec3, Vec3) {
+		let (mut pa, mut pb) = (self.field.position(a), self.field.position(b));
+		let (mut va, mut vb) = (self.field.get(a), self.field.get(b));
+
+		if let Some(sdf) = self.sdf {
+			for _ in 0..CROSSING_STEPS {
+				let p = (pa + pb) * 0.5;
+				let v = sdf(p);
+
+				if self.inside(v) == self.inside(va) {
+					(pa, va) = (p, v);
+				} else {
+					(pb, vb) = (p, v);
+				}
+			}
+		}
+
+		let t = (self.iso - va) / (vb - va);
+		let p = pa + (pb - pa) * t;
+
+		(p, normalize_or_zero((self.gradient)(p)))
+	}
+
+	fn extract(&self, method: Method) -> Mesh {
+		if self.field.size.iter().any(|&size| size < 2) {
+			return Mesh::new();
+		}
+
+		let mut mesh = match method {
+			Method::MarchingCubes => marching_cubes(self),
+			Method::DualContouring => dual_contouring(self),
+		};
+
+		// Crossings at samples put vertices of different edges at the same position.
+		cleanup::weld_vertices(&mut mesh, 0.0);
+		cleanup::remove_degenerate_triangles(&mut mesh);
+
+		if method == Method::DualContouring {
+			calculate_normals(&mut mesh, NormalMode::AutoSmooth(AUTO_SMOOTH_ANGLE));
+		}
+
+		mesh
+	}
+}
+
+/// Offset of a corner of a cell, with the X, Y and Z offsets in bits 0, 1 and 2.
+fn corner_offset(cell: [usize; 3], corner: usize) -> [usize; 3] {
+	[0, 1, 2].map(|axis| cell[axis] + (corner >> axis & 1))
+}
+
+/// Corners of the faces of a cell, counterclockwise when seen from outside of the cell.
+fn cell_faces() -> [[usize; 4]; 6] {
+	let mut faces = [[0; 4]; 6];
+
+	for axis in [0, 1, 2] {
+		let u = 1 << ((axis + 1) % 3);
+		let v = 1 << ((axis + 2) % 3);
+
+		for side in [0, 1] {
+			let base = side << axis;
+			let face = [base, base + u, base + u + v, base + v];
+
+			faces[axis * 2 + side] = match side {
+				1 => face,
+				_ => [face[0], face[3], face[2], face[1]],
+			};
+		}
+	}
+
+	faces
+}
+
+/// Marching cubes, with the polygons of every cell found by walking the contour along its faces. Faces with two
+/// diagonal inside corners are resolved by the value at their center, so neighboring cells agree and the surface
+/// has no holes.
+fn marching_cubes(surface: &Surface) -> Mesh {
+	let field = surface.field;
+	let faces = cell_faces();
+	let mut mesh = Mesh::new();
+	// Vertex of every crossed edge, by its first sample and axis.
+	let mut edge_vertices: HashMap<([usize; 3], usize), usize> = HashMap::new();
+
+	for z in 0..field.size[2] - 1 {
+		for y in 0..field.size[1] - 1 {
+			for x in 0..field.size[0] - 1 {
+				let cell = [x, y, z];
+				let values: [f32; 8] =
+					std::array::from_fn(|corner| field.get(corner_offset(cell, corner)));
+				let inside = values.map(|value| surface.inside(value));
+
+				if inside.iter().all(|&inside| inside) || inside.iter().all(|&inside| !inside) {
+					continue;
+				}
+
+				let mut vertex = |a: usize, b: usize| {
+					let (a, b) = (a.min(b), a.max(b));
+					let sample = corner_offset(cell, a);
+					let axis = (a ^ b).trailing_zeros() as usize;
+
+					*edge_vertices.entry((sample, axis)).or_insert_with(|| {
+						let (p, n) = surface.crossing(sample, corner_offset(cell, b));
+						mesh.vertices.push(Vertex { p, n });
+						mesh.vertices.len() - 1
+					})
+				};
+
+				// Contour segments, from the crossing where the walk enters the inside to where it leaves it.
+				let mut next = HashMap::new();
+
+				for face in &faces {
+					// Crossings in walking order, starting where an inside run is entered, so that they alternate
+					// between entering and leaving.
+					let first = (0..4).find(|&i| !inside[face[i]] && inside[face[(i + 1) % 4]]);
+					let Some(first) = first else {
+						continue;
+					};
+
+					let crossings: Vec<usize> = (first..first + 4)
+						.map(|i| (face[i % 4], face[(i + 1) % 4]))
+						.filter(|&(a, b)| inside[a] != inside[b])
+						.map(|(a, b)| vertex(a, b))
+						.collect();
+
+					// With two inside runs, they are connected if the center of the face is inside.
+					if let [enter0, leave0, enter1, leave1] = crossings[..]
+						&& surface.inside(face.iter().map(|&c| values[c]).sum::<f32>() / 4.0)
+					{
+						next.insert(enter0, leave1);
+						next.insert(enter1, leave0);
+					} else {
+						for pair in crossings.chunks_exact(2) {
+							next.insert(pair[0], pair[1]);
+						}
+					}
+				}
+
+				while let Some(&start) = next.keys().next() {
+					let mut polygon = vec![start];
+					let mut current = next.remove(&start).unwrap();
+
+					while current != start {
+						polygon.push(current);
+						current = next.remove(&current).unwrap();
+					}
+
+					for i in 1..polygon.len() - 1 {
+						mesh.indices
+							.extend([polygon[0], polygon[i], polygon[i + 1]]);
+					}
+				}
+			}
+		}
+	}
+
+	mesh
+}
+
+/// Eigenvalues and eigenvectors, as the columns of the second matrix, of a symmetric 3x3 matrix, found with
+/// Jacobi rotations.
+fn symmetric_eigen(mut a: [[f32; 3]; 3]) -> ([f32; 3], [[f32; 3]; 3]) {
+	let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+	for _ in 0..JACOBI_SWEEPS {
+		for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+			if a[p][q].abs() <= f32::MIN_POSITIVE {
+				continue;
+			}
+
+			// Rotation that zeroes a[p][q].
+			let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+			let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+			let c = 1.0 / (t * t + 1.0).sqrt();
+			let s = t * c;
+
+			for k in [0, 1, 2] {
+				let (akp, akq) = (a[k][p], a[k][q]);
+				a[k][p] = c * akp - s * akq;
+				a[k][q] = s * akp + c * akq;
+			}
+			for k in [0, 1, 2] {
+				let (apk, aqk) = (a[p][k], a[q][k]);
+				a[p][k] = c * apk - s * aqk;
+				a[q][k] = s * apk + c * aqk;
+			}
+			for row in &mut v {
+				let (vp, vq) = (row[p], row[q]);
+				row[p] = c * vp - s * vq;
+				row[q] = s * vp + c * vq;
+			}
+		}
+	}
+
+	([a[0][0], a[1][1], a[2][2]], v)
+}
+
+/// Position in a cell that is closest to the tangent planes at its crossings. Directions in which the planes
+/// don't constrain the position, like along a flat area or an edge, keep the mass point of the crossings. The
+/// position is kept in the cell.
+fn cell_vertex(crossings: &[(Vec3, Vec3)], min: Vec3, max: Vec3) -> Vec3 {
+	let mass_point =
+		crossings.iter().fold(Vec3::ZERO, |sum, &(p, _)| sum + p) * (1.0 / crossings.len() as f32);
+
+	// Normal equations of the planes, relative to the mass point for precision.
+	let mut ata = [[0.0; 3]; 3];
+	let mut atb = Vec3::ZERO;
+
+	for &(p, n) in crossings {
+		let n_array: [f32; 3] = n.into();
+		for (row, &ni) in ata.iter_mut().zip(&n_array) {
+			for (value, &nj) in row.iter_mut().zip(&n_array) {
+				*value += ni * nj;
+			}
+		}
+		atb += n * n.dot(p - mass_point);
+	}
+
+	// Least squares solution with the pseudo-inverse, ignoring small eigenvalues.
+	let (eigenvalues, eigenvectors) = symmetric_eigen(ata);
+	let largest = eigenvalues.iter().copied().fold(0.0, f32::max);
+	let mut offset = Vec3::ZERO;
+
+	for (i, &eigenvalue) in eigenvalues.iter().enumerate() {
+		if eigenvalue > largest * SINGULAR_THRESHOLD {
+			let v = Vec3::new(eigenvectors[0][i], eigenvectors[1][i], eigenvectors[2][i]);
+			offset += v * (v.dot(atb) / eigenvalue);
+		}
+	}
+
+	let p = mass_point + offset;
+
+	Vec3::new(
+		p.x.clamp(min.x, max.x),
+		p.y.clamp(min.y, max.y),
+		p.z.clamp(min.z, max.z),
+	)
+}
+
+/// Dual contouring, with a vertex in every cell the surface passes through and a quad for every crossed edge
+/// between four cells.
+fn dual_contouring(surface: &Surface) -> Mesh {
+	let field = surface.field;
+	let mut mesh = Mesh::new();
+	let mut cell_vertices: HashMap<[usize; 3], usize> = HashMap::new();
+
+	for z in 0..field.size[2] - 1 {
+		for y in 0..field.size[1] - 1 {
+			for x in 0..field.size[0] - 1 {
+				let cell = [x, y, z];
+				let values: [f32; 8] =
+					std::array::from_fn(|corner| field.get(corner_offset(cell, corner)));
+				let mut crossings = Vec::new();
+
+				for a in 0..8 {
+					for axis in [0, 1, 2] {
+						let b = a | 1 << axis;
+						if b == a || surface.inside(values[a]) == surface.inside(values[b]) {
+							continue;
+						}
+
+						crossings
+							.push(surface.crossing(corner_offset(cell, a), corner_offset(cell, b)));
+					}
+				}
+
+				if crossings.is_empty() {
+					continue;
+				}
+
+				let min = field.position(cell);
+				let max = field.position(corner_offset(cell, 7));
+
+				cell_vertices.insert(cell, mesh.vertices.len());
+				mesh.vertices.push(Vertex {
+					p: cell_vertex(&crossings, min, max),
+					n: Vec3::ZERO,
+				});
+			}
+		}
+	}
+
+	// Every crossed edge inside the grid is surrounded by four cells with a vertex. Edges on the border of the grid
+	// are missing cells, so the surface is open there.
+	for z in 0..field.size[2] {
+		for y in 0..field.size[1] {
+			for x in 0..field.size[0] {
+				let a = [x, y, z];
+
+				for axis in [0, 1, 2] {
+					let u = (axis + 1) % 3;
+					let v = (axis + 2) % 3;
+
+					if a[axis] + 1 >= field.size[axis]
+						|| a[u] == 0 || a[v] == 0
+						|| a[u] + 1 >= field.size[u]
+						|| a[v] + 1 >= field.size[v]
+					{
+						continue;
+					}
+
+					let mut b = a;
+					b[axis] += 1;
+
+					let (inside_a, inside_b) =
+						(surface.inside(field.get(a)), surface.inside(field.get(b)));
+					if inside_a == inside_b {
+						continue;
+					}
+
+					let cell = |du: usize, dv: usize| {
+						let mut cell = a;
+						cell[u] -= 1 - du;
+						cell[v] -= 1 - dv;
+						cell_vertices[&cell]
+					};
+
+					// Counterclockwise around the edge when seen from its outside end.
+					let mut quad = [cell(0, 0), cell(1, 0), cell(1, 1), cell(0, 1)];
+					if inside_b {
+						quad.reverse();
+					}
+
+					mesh.indices
+						.extend([quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]);
+				}
+			}
+		}
+	}
+
+	mesh
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use math::primitives::{Cuboid, Sdf, Sphere};
+
+	/// Every edge is shared by exactly two triangles, in opposite directions. Vertices that are split at hard edges
+	/// are the same.
+	fn is_closed(mesh: &Mesh) -> bool {
+		let positions: Vec<Vec3> = mesh.vertices.iter().map(|vertex| vertex.p).collect();
+		let welded = cleanup::weld_positions(&positions, 0.0);
+		let mut edges = HashMap::new();
+
+		for triangle in mesh.indices.chunks_exact(3) {
+			for i in 0..3 {
+				let edge = (welded[triangle[i]], welded[triangle[(i + 1) % 3]]);
+				*edges.entry(edge).or_insert(0) += 1;
+			}
+		}
+
+		edges
+			.iter()
+			.all(|(&(a, b), &count)| count == 1 && edges.get(&(b, a)) == Some(&1))
+	}
+
+	fn volume(mesh: &Mesh) -> f32 {
+		mesh.indices
+			.chunks_exact(3)
+			.map(|t| {
+				let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[t[i]].p);
+				a.dot(b.cross(c)) / 6.0
+			})
+			.sum()
+	}
+
+	#[test]
+	fn marching_cubes() {
+		let sphere = Sphere { radius: 1.0 };
+		let bounds = Aabb::from_points([Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0)]);
+		let mesh = extract_sdf(|p| sphere.distance(p), &bounds, 0.1, Method::MarchingCubes);
+
+		assert!(is_closed(&mesh));
+		assert!((volume(&mesh) / (4.0 / 3.0 * math::PI) - 1.0).abs() < 0.02);
+
+		for vertex in &mesh.vertices {
+			assert!(vertex.p.length() > 0.99 && vertex.p.length() < 1.01);
+			assert!(vertex.n.dot(vertex.p) > 0.99);
+		}
+
+		// Metaballs in a sampled field, which touch so that there are ambiguous faces.
+		let field = ScalarField::from_fn(Vec3::ZERO, 0.25, [16, 12, 12], |p| {
+			let balls = [Vec3::new(1.4, 1.4, 1.4), Vec3::new(2.4, 1.6, 1.4)];
+			-balls
+				.iter()
+				.map(|&c| 1.0 / (p - c).length_sq())
+				.sum::<f32>()
+		});
+		assert!(is_closed(&extract(&field, -2.0, Method::MarchingCubes)));
+	}
+
+	#[test]
+	fn dual_contouring() {
+		let cuboid = Cuboid {
+			half_size: Vec3::new(0.52, 0.37, 0.44),
+		};
+		let bounds = Aabb::from_points([-cuboid.half_size, cuboid.half_size]);
+		let mesh = extract_sdf(|p| cuboid.distance(p), &bounds, 0.1, Method::DualContouring);
+
+		assert!(is_closed(&mesh));
+		assert!((volume(&mesh) - 8.0 * 0.52 * 0.37 * 0.44).abs() < 1e-3);
+
+		// Corners are kept sharp.
+		let corner = mesh
+			.vertices
+			.iter()
+			.map(|vertex| vertex.p.length())
+			.fold(0.0, f32::max);
+		assert!((corner - cuboid.half_size.length()).abs() < 1e-3);
+
+		// Faces are flat, with normals along the axes.
+		for vertex in &mesh.vertices {
+			let n = vertex.n;
+			assert!(n.x.abs().max(n.y.abs()).max(n.z.abs()) > 0.999);
+		}
+
+		// A surface that crosses the border of the grid is open there.
+		let field = ScalarField::from_fn(Vec3::ZERO, 1.0, [4, 4, 4], |p| p.z - 1.2);
+		let mesh = extract(&field, 0.0, Method::DualContouring);
+		assert_eq!(mesh.indices.len(), 2 * 2 * 6);
+		assert!(
+			mesh.vertices
+				.iter()
+				.all(|vertex| (vertex.p.z - 1.2).abs() < 1e-5)
+		);
+		assert_eq!(
+			extract(&field, 0.0, Method::MarchingCubes).indices.len(),
+			3 * 3 * 6
+		);
+	}
+}
@@ -1,10 +1,44 @@
 pub mod bone_deform;
+pub mod bvh;
+pub mod cleanup;
+pub mod convex_hull;
+pub mod half_edge;
+pub mod isosurface;
 pub mod mesh;
+pub mod morph;
+pub mod simplify;
+pub mod subdivide;
+pub mod validate;
+
+pub use convex_hull::{DecompositionSettings, convex_decomposition, convex_hull};
+pub use simplify::simplify;
+pub use validate::{Report, validate};
+
+pub mod formats {
+	mod error;
+	pub mod gltf;
+	mod json;
+	pub mod obj;
+	pub mod ply;
+	pub mod stl;
+
+	pub use error::*;
+}
 
 pub mod primitives {
+	pub mod capsule;
+	pub mod cone;
 	pub mod cylinder;
+	pub mod disk;
 	pub mod grid;
+	pub mod icosphere;
 	pub mod platonic;
+	pub mod rounded_box;
 	pub mod sphere;
 	pub mod torus;
+
+	mod revolve;
 }
+
+#[cfg(test)]
+mod test_support;
@@ -1,19 +1,32 @@
-use asset::Asset;
-use math::Vec3;
+use crate::morph::MorphTarget;
+use asset::{Asset, BlobReader, BlobWriter, Pod};
+use math::{Vec2, Vec3, Vec4};
+use std::collections::HashMap;
+use std::io;
+use std::ops::{Add, Mul, Range};
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct Mesh {
 	pub vertices: Vec<Vertex>,
 	pub indices: Vec<usize>,
 	pub vertex_groups: VertexGroups,
+	pub attributes: VertexAttributes,
+	/// Named parts of the mesh, empty if the whole mesh is a single part.
+	pub submeshes: Vec<Submesh>,
+	pub morph_targets: Vec<MorphTarget>,
 }
 
+/// Laid out like `graphics::scene::Vertex`, so cooked vertices can be copied as is.
+#[repr(C)]
+#[derive(Clone, Copy)]
 pub struct Vertex {
 	pub p: Vec3,
 	pub n: Vec3,
 }
 
-#[derive(Default)]
+unsafe impl Pod for Vertex {}
+
+#[derive(Clone, Default)]
 pub struct AttributeGroup<T> {
 	/// Attribute names.
 	pub names: Vec<String>,
@@ -25,10 +38,475 @@ pub struct AttributeGroup<T> {
 
 pub type VertexGroups = AttributeGroup<f32>;
 
+impl<T: Copy> AttributeGroup<T> {
+	/// Values of the given primitives, in order.
+	pub fn gather(&self, primitives: &[usize]) -> Self {
+		let mut lookup = Vec::new();
+		let mut values = Vec::new();
+
+		if !self.lookup.is_empty() {
+			lookup.push(0);
+
+			for &primitive in primitives {
+				let range = self.lookup[primitive]..self.lookup[primitive + 1];
+				values.extend_from_slice(&self.values[range]);
+				lookup.push(values.len());
+			}
+		}
+
+		Self {
+			names: self.names.clone(),
+			lookup,
+			values,
+		}
+	}
+}
+
+/// Range of triangles that form a part of a mesh, like an OBJ group.
+#[derive(Clone)]
+pub struct Submesh {
+	pub name: String,
+	/// Name of the material assigned in the source file.
+	pub material: Option<String>,
+	/// Range of [`Mesh::indices`], a multiple of 3.
+	pub indices: Range<usize>,
+}
+
+/// Name of the tangent channel. Tangents store the bitangent sign (handedness) in `w`.
+pub const TANGENT: &str = "tangent";
+/// Name of the vertex color channel, in linear RGBA.
+pub const COLOR: &str = "color";
+
+/// Name of the texture coordinate channel `set`, i.e. `uv0` to `uvN`.
+pub fn uv_name(set: usize) -> String {
+	format!("uv{set}")
+}
+
+/// Named per-vertex channels besides position and normal, with one value for every vertex of the mesh.
+///
+/// Channels are [`uv_name`], [`TANGENT`], [`COLOR`] or custom channels with any other name.
+#[derive(Clone, Default)]
+pub struct VertexAttributes {
+	channels: Vec<(String, AttributeValues)>,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum AttributeValues {
+	F32(Vec<f32>),
+	Vec2(Vec<Vec2>),
+	Vec3(Vec<Vec3>),
+	Vec4(Vec<Vec4>),
+}
+
+/// Value type of an attribute channel.
+pub trait AttributeValue: Copy + Sized {
+	/// Value of vertices that have not been assigned one.
+	const ZERO: Self;
+
+	fn values(values: &AttributeValues) -> Option<&Vec<Self>>;
+	fn values_mut(values: &mut AttributeValues) -> Option<&mut Vec<Self>>;
+	fn into_values(values: Vec<Self>) -> AttributeValues;
+}
+
+macro_rules! impl_attribute_value {
+	($type:ty, $variant:ident, $zero:expr) => {
+		impl AttributeValue for $type {
+			const ZERO: Self = $zero;
+
+			fn values(values: &AttributeValues) -> Option<&Vec<Self>> {
+				match values {
+					AttributeValues::$variant(values) => Some(values),
+					_ => None,
+				}
+			}
+
+			fn values_mut(values: &mut AttributeValues) -> Option<&mut Vec<Self>> {
+				match values {
+					AttributeValues::$variant(values) => Some(values),
+					_ => None,
+				}
+			}
+
+			fn into_values(values: Vec<Self>) -> AttributeValues {
+				AttributeValues::$variant(values)
+			}
+		}
+	};
+}
+
+impl_attribute_value!(f32, F32, 0.0);
+impl_attribute_value!(Vec2, Vec2, Vec2::ZERO);
+impl_attribute_value!(Vec3, Vec3, Vec3::ZERO);
+impl_attribute_value!(Vec4, Vec4, Vec4::ZERO);
+
+impl AttributeValues {
+	pub fn len(&self) -> usize {
+		match self {
+			Self::F32(values) => values.len(),
+			Self::Vec2(values) => values.len(),
+			Self::Vec3(values) => values.len(),
+			Self::Vec4(values) => values.len(),
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Resizes the channel, filling new values with zero.
+	pub fn resize(&mut self, len: usize) {
+		match self {
+			Self::F32(values) => values.resize(len, f32::ZERO),
+			Self::Vec2(values) => values.resize(len, Vec2::ZERO),
+			Self::Vec3(values) => values.resize(len, Vec3::ZERO),
+			Self::Vec4(values) => values.resize(len, Vec4::ZERO),
+		}
+	}
+
+	/// Values of the given vertices, in order.
+	pub fn gather(&self, vertices: &[usize]) -> Self {
+		fn gather<T: Copy>(values: &[T], vertices: &[usize]) -> Vec<T> {
+			vertices.iter().map(|&vertex| values[vertex]).collect()
+		}
+
+		match self {
+			Self::F32(values) => Self::F32(gather(values, vertices)),
+			Self::Vec2(values) => Self::Vec2(gather(values, vertices)),
+			Self::Vec3(values) => Self::Vec3(gather(values, vertices)),
+			Self::Vec4(values) => Self::Vec4(gather(values, vertices)),
+		}
+	}
+
+	/// Sets the value of `vertex` to the weighted sum of the values of other vertices, extending the channel with
+	/// zeros if `vertex` is past the end.
+	pub fn set_blend(&mut self, vertex: usize, weights: &[(usize, f32)]) {
+		fn blend<T: AttributeValue + Add<Output = T> + Mul<f32, Output = T>>(
+			values: &mut Vec<T>,
+			vertex: usize,
+			weights: &[(usize, f32)],
+		) {
+			let value = weights
+				.iter()
+				.fold(T::ZERO, |sum, &(i, weight)| sum + values[i] * weight);
+
+			if vertex >= values.len() {
+				values.resize(vertex + 1, T::ZERO);
+			}
+			values[vertex] = value;
+		}
+
+		match self {
+			Self::F32(values) => blend(values, vertex, weights),
+			Self::Vec2(values) => blend(values, vertex, weights),
+			Self::Vec3(values) => blend(values, vertex, weights),
+			Self::Vec4(values) => blend(values, vertex, weights),
+		}
+	}
+}
+
+impl VertexAttributes {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn get<T: AttributeValue>(&self, name: &str) -> Option<&[T]> {
+		T::values(self.get_untyped(name)?).map(Vec::as_slice)
+	}
+
+	pub fn get_mut<T: AttributeValue>(&mut self, name: &str) -> Option<&mut [T]> {
+		T::values_mut(self.get_untyped_mut(name)?).map(Vec::as_mut_slice)
+	}
+
+	pub fn get_untyped(&self, name: &str) -> Option<&AttributeValues> {
+		self.channels
+			.iter()
+			.find(|(channel, _)| channel == name)
+			.map(|(_, values)| values)
+	}
+
+	pub fn get_untyped_mut(&mut self, name: &str) -> Option<&mut AttributeValues> {
+		self.channels
+			.iter_mut()
+			.find(|(channel, _)| channel == name)
+			.map(|(_, values)| values)
+	}
+
+	/// Adds a channel, replacing an existing channel with the same name.
+	pub fn insert<T: AttributeValue>(&mut self, name: &str, values: Vec<T>) {
+		self.insert_untyped(name, T::into_values(values));
+	}
+
+	pub fn insert_untyped(&mut self, name: &str, values: AttributeValues) {
+		match self.get_untyped_mut(name) {
+			Some(channel) => *channel = values,
+			None => self.channels.push((name.to_string(), values)),
+		}
+	}
+
+	pub fn remove(&mut self, name: &str) -> Option<AttributeValues> {
+		let index = self
+			.channels
+			.iter()
+			.position(|(channel, _)| channel == name)?;
+		Some(self.channels.remove(index).1)
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (&str, &AttributeValues)> {
+		self.channels
+			.iter()
+			.map(|(name, values)| (name.as_str(), values))
+	}
+
+	pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut AttributeValues)> {
+		self.channels
+			.iter_mut()
+			.map(|(name, values)| (name.as_str(), values))
+	}
+
+	pub fn len(&self) -> usize {
+		self.channels.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.channels.is_empty()
+	}
+
+	pub fn uv(&self, set: usize) -> Option<&[Vec2]> {
+		self.get(&uv_name(set))
+	}
+
+	pub fn tangents(&self) -> Option<&[Vec4]> {
+		self.get(TANGENT)
+	}
+
+	pub fn colors(&self) -> Option<&[Vec4]> {
+		self.get(COLOR)
+	}
+
+	/// Resizes all channels, filling new values with zero.
+	pub fn resize(&mut self, len: usize) {
+		for (_, values) in &mut self.channels {
+			values.resize(len);
+		}
+	}
+
+	/// Channels with the values of the given vertices, in order.
+	pub fn gather(&self, vertices: &[usize]) -> Self {
+		Self {
+			channels: self
+				.channels
+				.iter()
+				.map(|(name, values)| (name.clone(), values.gather(vertices)))
+				.collect(),
+		}
+	}
+
+	/// Sets the values of `vertex` in all channels to the weighted sum of the values of other vertices, like for a
+	/// vertex that is inserted between them. See [`AttributeValues::set_blend`].
+	pub fn set_blend(&mut self, vertex: usize, weights: &[(usize, f32)]) {
+		for (_, values) in &mut self.channels {
+			values.set_blend(vertex, weights);
+		}
+	}
+}
+
+/// Tags of the attribute value types in a cooked mesh.
+const COOKED_F32: u32 = 0;
+const COOKED_VEC2: u32 = 1;
+const COOKED_VEC3: u32 = 2;
+const COOKED_VEC4: u32 = 3;
+
 impl Mesh {
 	pub fn new() -> Self {
 		Default::default()
 	}
+
+	/// Writes the mesh in its cooked form, with vertices in GPU layout and u32 indices.
+	///
+	/// Fails if an index doesn't fit in 32 bits.
+	pub fn write_cooked(&self, writer: &mut BlobWriter) -> io::Result<()> {
+		let indices = cooked_indices(&self.indices)?;
+
+		writer.write_slice(&self.vertices);
+		writer.write_slice(&indices);
+
+		let groups = &self.vertex_groups;
+		let lookup = cooked_indices(&groups.lookup)?;
+		let values = groups
+			.values
+			.iter()
+			.map(|(i, v)| Ok([cooked_index(*i)?, v.to_bits()]))
+			.collect::<io::Result<Vec<[u32; 2]>>>()?;
+
+		writer.write(groups.names.len() as u64);
+		for name in &groups.names {
+			writer.write_str(name);
+		}
+		writer.write_slice(&lookup);
+		writer.write_slice(&values);
+
+		writer.write(self.attributes.len() as u64);
+		for (name, values) in self.attributes.iter() {
+			writer.write_str(name);
+
+			match values {
+				AttributeValues::F32(values) => {
+					writer.write(COOKED_F32);
+					writer.write_slice(values);
+				}
+				AttributeValues::Vec2(values) => {
+					let values: Vec<[f32; 2]> = values.iter().map(|v| (*v).into()).collect();
+					writer.write(COOKED_VEC2);
+					writer.write_slice(&values);
+				}
+				AttributeValues::Vec3(values) => {
+					let values: Vec<[f32; 3]> = values.iter().map(|v| (*v).into()).collect();
+					writer.write(COOKED_VEC3);
+					writer.write_slice(&values);
+				}
+				AttributeValues::Vec4(values) => {
+					let values: Vec<[f32; 4]> = values.iter().map(|v| (*v).into()).collect();
+					writer.write(COOKED_VEC4);
+					writer.write_slice(&values);
+				}
+			}
+		}
+
+		writer.write(self.submeshes.len() as u64);
+		for submesh in &self.submeshes {
+			writer.write_str(&submesh.name);
+			writer.write(submesh.material.is_some() as u8);
+			writer.write_str(submesh.material.as_deref().unwrap_or_default());
+			writer.write(submesh.indices.start as u64);
+			writer.write(submesh.indices.end as u64);
+		}
+
+		writer.write(self.morph_targets.len() as u64);
+		for target in &self.morph_targets {
+			let vertices = cooked_indices(target.vertices.as_deref().unwrap_or_default())?;
+			let positions: Vec<[f32; 3]> = target.positions.iter().map(|&p| p.into()).collect();
+			let normals: Vec<[f32; 3]> = target.normals.iter().map(|&n| n.into()).collect();
+
+			writer.write_str(&target.name);
+			writer.write(target.vertices.is_some() as u8);
+			writer.write_slice(&vertices);
+			writer.write_slice(&positions);
+			writer.write_slice(&normals);
+		}
+
+		Ok(())
+	}
+
+	/// Reads a mesh written by [`Mesh::write_cooked`].
+	pub fn read_cooked(reader: &mut BlobReader) -> io::Result<Self> {
+		let vertices = reader.read_vec::<Vertex>()?;
+		let indices = reader.read_vec::<u32>()?;
+
+		let name_count = reader.read::<u64>()?;
+		let names = (0..name_count)
+			.map(|_| reader.read_string())
+			.collect::<io::Result<_>>()?;
+		let lookup = reader.read_vec::<u32>()?;
+		let values = reader.read_vec::<[u32; 2]>()?;
+
+		let mut attributes = VertexAttributes::new();
+		let attribute_count = reader.read::<u64>()?;
+
+		for _ in 0..attribute_count {
+			let name = reader.read_string()?;
+
+			let values = match reader.read::<u32>()? {
+				COOKED_F32 => AttributeValues::F32(reader.read_vec()?),
+				COOKED_VEC2 => AttributeValues::Vec2(
+					reader
+						.read_vec::<[f32; 2]>()?
+						.into_iter()
+						.map(|[x, y]| Vec2::new(x, y))
+						.collect(),
+				),
+				COOKED_VEC3 => AttributeValues::Vec3(
+					reader
+						.read_vec::<[f32; 3]>()?
+						.into_iter()
+						.map(|[x, y, z]| Vec3::new(x, y, z))
+						.collect(),
+				),
+				COOKED_VEC4 => AttributeValues::Vec4(
+					reader
+						.read_vec::<[f32; 4]>()?
+						.into_iter()
+						.map(|[x, y, z, w]| Vec4::new(x, y, z, w))
+						.collect(),
+				),
+				tag => {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!("Invalid attribute type {tag} in cooked mesh"),
+					));
+				}
+			};
+
+			attributes.insert_untyped(&name, values);
+		}
+
+		let submesh_count = reader.read::<u64>()?;
+		let submeshes = (0..submesh_count)
+			.map(|_| {
+				let name = reader.read_string()?;
+				let has_material = reader.read::<u8>()? != 0;
+				let material = reader.read_string()?;
+				let start = reader.read::<u64>()? as usize;
+				let end = reader.read::<u64>()? as usize;
+
+				Ok(Submesh {
+					name,
+					material: has_material.then_some(material),
+					indices: start..end,
+				})
+			})
+			.collect::<io::Result<_>>()?;
+
+		let target_count = reader.read::<u64>()?;
+		let morph_targets = (0..target_count)
+			.map(|_| {
+				let name = reader.read_string()?;
+				let sparse = reader.read::<u8>()? != 0;
+				let vertices = reader.read_vec::<u32>()?;
+				let mut read_vec3s = || -> io::Result<Vec<Vec3>> {
+					let values = reader.read_vec::<[f32; 3]>()?;
+					Ok(values
+						.into_iter()
+						.map(|[x, y, z]| Vec3::new(x, y, z))
+						.collect())
+				};
+				let positions = read_vec3s()?;
+				let normals = read_vec3s()?;
+
+				Ok(MorphTarget {
+					name,
+					vertices: sparse.then(|| vertices.into_iter().map(|v| v as usize).collect()),
+					positions,
+					normals,
+				})
+			})
+			.collect::<io::Result<_>>()?;
+
+		Ok(Self {
+			vertices,
+			indices: indices.into_iter().map(|i| i as usize).collect(),
+			vertex_groups: VertexGroups {
+				names,
+				lookup: lookup.into_iter().map(|i| i as usize).collect(),
+				values: values
+					.into_iter()
+					.map(|[i, v]| (i as usize, f32::from_bits(v)))
+					.collect(),
+			},
+			attributes,
+			submeshes,
+			morph_targets,
+		})
+	}
 }
 
 #[derive(Default)]
@@ -64,36 +542,567 @@ impl MeshBuilder {
 			.extend_from_slice(&[v0, v1, v2, v0, v2, v3]);
 	}
 
+	/// Sets the value of a vertex in an attribute channel, adding the channel if needed.
+	///
+	/// Vertices that are never assigned a value are zero.
+	///
+	/// # Panics
+	///
+	/// Panics if the channel already exists with a different value type.
+	pub fn set_attribute<T: AttributeValue>(&mut self, vertex: usize, name: &str, value: T) {
+		let vertex_count = self.mesh.vertices.len();
+		let attributes = &mut self.mesh.attributes;
+
+		if attributes.get_untyped(name).is_none() {
+			attributes.insert::<T>(name, Vec::new());
+		}
+
+		let values = T::values_mut(attributes.get_untyped_mut(name).unwrap())
+			.unwrap_or_else(|| panic!("Attribute {name} has a different type"));
+
+		values.resize(vertex_count, T::ZERO);
+		values[vertex] = value;
+	}
+
+	pub fn set_uv(&mut self, vertex: usize, set: usize, uv: [f32; 2]) {
+		self.set_attribute(vertex, &uv_name(set), Vec2::new(uv[0], uv[1]));
+	}
+
+	/// Sets the tangent of a vertex, with `sign` being the handedness of the bitangent.
+	pub fn set_tangent(&mut self, vertex: usize, tangent: [f32; 3], sign: f32) {
+		self.set_attribute(
+			vertex,
+			TANGENT,
+			Vec4::new(tangent[0], tangent[1], tangent[2], sign),
+		);
+	}
+
+	pub fn set_color(&mut self, vertex: usize, color: [f32; 4]) {
+		self.set_attribute(
+			vertex,
+			COLOR,
+			Vec4::new(color[0], color[1], color[2], color[3]),
+		);
+	}
+
 	pub fn build(self) -> Mesh {
 		let mut mesh = self.mesh;
+		mesh.attributes.resize(mesh.vertices.len());
 		calculate_vert_normals(&mut mesh);
 		mesh
 	}
+
+	/// Like [`MeshBuilder::build`], but with the given normal for every vertex instead of calculated ones.
+	pub fn build_with_normals(self, normals: &[Vec3]) -> Mesh {
+		let mut mesh = self.mesh;
+		assert_eq!(normals.len(), mesh.vertices.len());
+		mesh.attributes.resize(mesh.vertices.len());
+
+		for (vertex, &normal) in mesh.vertices.iter_mut().zip(normals) {
+			vertex.n = normal;
+		}
+		mesh
+	}
+}
+
+/// Threshold of [`NormalMode::AutoSmooth`] for imported meshes without normals, in radians.
+pub const AUTO_SMOOTH_ANGLE: f32 = math::PI / 6.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalMode {
+	/// Every triangle gets its own vertices with the triangle normal.
+	Flat,
+	/// Normals are averaged over all triangles at a position, also across UV seams and other split vertices.
+	Smooth,
+	/// Like [`NormalMode::Smooth`], but edges with an angle between their triangles above the threshold, in
+	/// radians, are hard edges along which vertices are split.
+	AutoSmooth(f32),
+}
+
+/// Unit normal of a triangle and its angle at every corner, or `None` for degenerate triangles.
+fn cooked_index(index: usize) -> io::Result<u32> {
+	u32::try_from(index).map_err(|_| {
+		io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("Index {index} does not fit in 32 bits"),
+		)
+	})
+}
+
+fn cooked_indices(indices: &[usize]) -> io::Result<Vec<u32>> {
+	indices.iter().map(|&i| cooked_index(i)).collect()
+}
+
+fn weighted_face_normal(p: [Vec3; 3]) -> Option<(Vec3, [f32; 3])> {
+	let normal = (p[1] - p[0]).cross(p[2] - p[0]);
+
+	if normal.length_sq() <= f32::MIN_POSITIVE {
+		return None;
+	}
+
+	let angles = [0, 1, 2].map(|corner| {
+		let e0 = p[(corner + 1) % 3] - p[corner];
+		let e1 = p[(corner + 2) % 3] - p[corner];
+		e0.cross(e1).length().atan2(e0.dot(e1))
+	});
+
+	Some((*normal.normalize(), angles))
+}
+
+/// Normalizes `v`, or returns zero when it is too short to have a direction.
+pub(crate) fn normalize_or_zero(v: Vec3) -> Vec3 {
+	if v.length_sq() > f32::MIN_POSITIVE {
+		*v.normalize()
+	} else {
+		Vec3::ZERO
+	}
 }
 
+/// Calculates smooth vertex normals from the normals of the triangles around every vertex, weighted by the
+/// corner angle so that the result doesn't depend on how faces are triangulated.
+///
+/// Unlike [`calculate_normals`], normals are not averaged across split vertices and no vertices are added.
 pub fn calculate_vert_normals(mesh: &mut Mesh) {
 	for vertex in &mut mesh.vertices {
 		vertex.n = Vec3::ZERO;
 	}
 
 	for face in mesh.indices.chunks_exact(3) {
-		let v0 = &mesh.vertices[face[0]];
-		let v1 = &mesh.vertices[face[1]];
-		let v2 = &mesh.vertices[face[2]];
+		let p = [face[0], face[1], face[2]].map(|i| mesh.vertices[i].p);
 
-		let e0 = v1.p - v0.p;
-		let e1 = v2.p - v0.p;
+		if let Some((normal, angles)) = weighted_face_normal(p) {
+			for (&i, angle) in face.iter().zip(angles) {
+				mesh.vertices[i].n += normal * angle;
+			}
+		}
+	}
 
-		let normal = e0.cross(e1);
+	for vertex in &mut mesh.vertices {
+		vertex.n = normalize_or_zero(vertex.n);
+	}
+}
+
+fn find_root(parents: &mut [usize], mut x: usize) -> usize {
+	while parents[x] != x {
+		parents[x] = parents[parents[x]];
+		x = parents[x];
+	}
+	x
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+	let (a, b) = (find_root(parents, a), find_root(parents, b));
+	parents[a.max(b)] = a.min(b);
+}
+
+/// Calculates angle weighted vertex normals, splitting vertices whose triangles don't share a normal in `mode`.
+///
+/// Split vertices are appended to the mesh, with the attributes and vertex groups of the original vertex.
+pub fn calculate_normals(mesh: &mut Mesh, mode: NormalMode) {
+	let faces: Vec<_> = mesh
+		.indices
+		.chunks_exact(3)
+		.map(|face| weighted_face_normal([face[0], face[1], face[2]].map(|i| mesh.vertices[i].p)))
+		.collect();
+
+	// Triangle corners are merged into groups that share a normal. Corners are matched by position, so smoothing
+	// crosses UV seams.
+	let positions: Vec<Vec3> = mesh.vertices.iter().map(|vertex| vertex.p).collect();
+	let welded = crate::cleanup::weld_positions(&positions, 0.0);
+	let position = |corner: usize| welded[mesh.indices[corner]];
+
+	let corner_count = faces.len() * 3;
+	let mut groups: Vec<usize> = (0..corner_count).collect();
+
+	match mode {
+		NormalMode::Flat => {}
+		NormalMode::Smooth => {
+			let mut first = HashMap::new();
+			for corner in 0..corner_count {
+				let other = *first.entry(position(corner)).or_insert(corner);
+				union(&mut groups, corner, other);
+			}
+		}
+		NormalMode::AutoSmooth(angle) => {
+			let min_cos = angle.cos();
+			let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+
+			for corner in 0..corner_count {
+				let next = corner / 3 * 3 + (corner + 1) % 3;
+				let (a, b) = (position(corner), position(next));
+
+				if a != b {
+					edges
+						.entry((a.min(b), a.max(b)))
+						.or_default()
+						.push(corner / 3);
+				}
+			}
+
+			for ((a, b), edge_faces) in edges {
+				for (i, &f) in edge_faces.iter().enumerate() {
+					for &g in &edge_faces[i + 1..] {
+						// Degenerate triangles are smooth, so they don't split vertices.
+						let smooth = match (faces[f], faces[g]) {
+							(Some((nf, _)), Some((ng, _))) => nf.dot(ng) >= min_cos,
+							_ => true,
+						};
+
+						if !smooth {
+							continue;
+						}
 
-		for &i in face {
-			mesh.vertices[i].n += normal;
+						for p in [a, b] {
+							let corner =
+								|face: usize| (face * 3..face * 3 + 3).find(|&c| position(c) == p);
+							if let (Some(cf), Some(cg)) = (corner(f), corner(g)) {
+								union(&mut groups, cf, cg);
+							}
+						}
+					}
+				}
+			}
 		}
 	}
 
-	for vertex in &mut mesh.vertices {
-		vertex.n = *vertex.n.normalize();
+	let mut normals = vec![Vec3::ZERO; corner_count];
+	for corner in 0..corner_count {
+		if let Some((normal, angles)) = faces[corner / 3] {
+			let root = find_root(&mut groups, corner);
+			normals[root] += normal * angles[corner % 3];
+		}
+	}
+
+	// The first group of every vertex keeps the vertex, others get a copy.
+	let vertex_count = mesh.vertices.len();
+	let mut vertex_roots = vec![None; vertex_count];
+	let mut copies = HashMap::new();
+	let mut sources: Vec<usize> = (0..vertex_count).collect();
+
+	for corner in 0..corner_count {
+		let root = find_root(&mut groups, corner);
+		let vertex = mesh.indices[corner];
+
+		let index = match vertex_roots[vertex] {
+			None => {
+				vertex_roots[vertex] = Some(root);
+				vertex
+			}
+			Some(group) if group == root => vertex,
+			Some(_) => *copies.entry((vertex, root)).or_insert_with(|| {
+				sources.push(vertex);
+				sources.len() - 1
+			}),
+		};
+
+		mesh.indices[corner] = index;
+	}
+
+	if sources.len() > vertex_count {
+		mesh.vertices = sources.iter().map(|&v| mesh.vertices[v]).collect();
+		mesh.attributes = mesh.attributes.gather(&sources);
+		mesh.vertex_groups = mesh.vertex_groups.gather(&sources);
+
+		for target in &mut mesh.morph_targets {
+			*target = target.gather(&sources);
+		}
+	}
+
+	for corner in 0..corner_count {
+		let root = find_root(&mut groups, corner);
+		mesh.vertices[mesh.indices[corner]].n = normalize_or_zero(normals[root]);
 	}
 }
 
+/// Calculates tangents from the vertex normals and the `uv0` channel, and stores them in the [`TANGENT`] channel.
+/// Does nothing if the mesh has no `uv0` channel.
+///
+/// The result approximates MikkTSpace: face tangents are projected onto the plane of the vertex normal and
+/// weighted by the corner angle, and faces with degenerate UVs take the tangent of their vertices without
+/// contributing to it. Vertices are only split by UV orientation, where faces with mirrored UVs share them, so both
+/// sides get their own tangent and bitangent sign. Unlike MikkTSpace, vertices are never split because the
+/// tangents of their faces diverge.
+/// Split vertices are appended to the mesh, with the attributes and vertex groups of the original vertex.
+pub fn calculate_tangents(mesh: &mut Mesh) {
+	let Some(uvs) = mesh.attributes.uv(0) else {
+		return;
+	};
+
+	// Tangent and UV orientation of every face, `None` for faces with degenerate UVs.
+	let faces: Vec<Option<(Vec3, bool)>> = mesh
+		.indices
+		.chunks_exact(3)
+		.map(|face| {
+			let p = [face[0], face[1], face[2]].map(|i| mesh.vertices[i].p);
+			let t = [face[0], face[1], face[2]].map(|i| uvs[i]);
+
+			let d1 = p[1] - p[0];
+			let d2 = p[2] - p[0];
+			let t21 = t[1] - t[0];
+			let t31 = t[2] - t[0];
+
+			let signed_area = t21.x * t31.y - t21.y * t31.x;
+
+			if signed_area.abs() <= f32::MIN_POSITIVE {
+				return None;
+			}
+
+			let tangent = (d1 * t31.y - d2 * t21.y) * signed_area.signum();
+			Some((tangent, signed_area > 0.0))
+		})
+		.collect();
+
+	// Faces with negative orientation get a copy of vertices that are also used by faces with positive orientation.
+	let vertex_count = mesh.vertices.len();
+	let corner_count = faces.len() * 3;
+	let mut orientations = vec![[false; 2]; vertex_count];
+
+	for corner in 0..corner_count {
+		if let Some((_, positive)) = faces[corner / 3] {
+			orientations[mesh.indices[corner]][positive as usize] = true;
+		}
+	}
+
+	let mut copies = HashMap::new();
+	let mut sources: Vec<usize> = (0..vertex_count).collect();
+
+	for corner in 0..corner_count {
+		let vertex = mesh.indices[corner];
+
+		if let Some((_, false)) = faces[corner / 3]
+			&& orientations[vertex] == [true, true]
+		{
+			mesh.indices[corner] = *copies.entry(vertex).or_insert_with(|| {
+				sources.push(vertex);
+				sources.len() - 1
+			});
+		}
+	}
+
+	if sources.len() > vertex_count {
+		mesh.vertices = sources.iter().map(|&v| mesh.vertices[v]).collect();
+		mesh.attributes = mesh.attributes.gather(&sources);
+		mesh.vertex_groups = mesh.vertex_groups.gather(&sources);
+
+		for target in &mut mesh.morph_targets {
+			*target = target.gather(&sources);
+		}
+	}
+
+	// Sum of angle weighted tangents, and bitangent sign, of every vertex.
+	let mut sums = vec![(Vec3::ZERO, 1.0); mesh.vertices.len()];
+
+	for corner in 0..corner_count {
+		let Some((tangent, positive)) = faces[corner / 3] else {
+			continue;
+		};
+
+		let face = &mesh.indices[corner / 3 * 3..corner / 3 * 3 + 3];
+		let i = corner % 3;
+		let n = mesh.vertices[face[i]].n;
+		let p = |i: usize| mesh.vertices[face[i % 3]].p;
+		let project = |v: Vec3| v - n * n.dot(v);
+
+		let tangent = project(tangent);
+		let e0 = project(p(i + 1) - p(i));
+		let e1 = project(p(i + 2) - p(i));
+
+		if [tangent, e0, e1]
+			.iter()
+			.any(|v| v.length_sq() <= f32::MIN_POSITIVE)
+		{
+			continue;
+		}
+
+		let angle = math::clamp(e0.normalize().dot(*e1.normalize()), -1.0, 1.0).acos();
+
+		let (sum, sign) = &mut sums[face[i]];
+		*sum += *tangent.normalize() * angle;
+		*sign = if positive { 1.0 } else { -1.0 };
+	}
+
+	let tangents = mesh
+		.vertices
+		.iter()
+		.zip(&sums)
+		.map(|(vertex, &(sum, sign))| {
+			if sum.length_sq() > f32::MIN_POSITIVE {
+				sum.normalize().extend(sign)
+			} else {
+				// No face with valid UVs, any tangent perpendicular to the normal will do.
+				let axis = if vertex.n.x.abs() < 0.9 {
+					Vec3::X
+				} else {
+					Vec3::Y
+				};
+				let tangent = *axis - vertex.n * vertex.n.dot(*axis);
+				tangent.normalize().extend(1.0)
+			}
+		})
+		.collect();
+
+	mesh.attributes.insert::<Vec4>(TANGENT, tangents);
+}
+
 impl Asset for Mesh {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::primitives::{cylinder, platonic};
+
+	#[test]
+	fn normal_modes() {
+		let mut cube = platonic::hexahedron();
+		assert_eq!(cube.vertices.len(), 36);
+		assert!(cube.vertices.iter().all(|vertex| {
+			let n = vertex.n;
+			[n.x, n.y, n.z].iter().filter(|x| x.abs() > 0.999).count() == 1
+		}));
+
+		// Corners with one and with two triangles of a face count the same.
+		calculate_normals(&mut cube, NormalMode::Smooth);
+		assert!(
+			cube.vertices
+				.iter()
+				.all(|vertex| vertex.n.dot(*vertex.p.normalize()) > 0.9999)
+		);
+
+		calculate_normals(&mut cube, NormalMode::AutoSmooth(AUTO_SMOOTH_ANGLE));
+		assert_eq!(cube.vertices.len(), 36);
+
+		let mut uv_sphere = crate::primitives::sphere::sphere(1.0, 16, 8);
+		let vertex_count = uv_sphere.vertices.len();
+		calculate_normals(&mut uv_sphere, NormalMode::AutoSmooth(AUTO_SMOOTH_ANGLE));
+		assert_eq!(uv_sphere.vertices.len(), vertex_count);
+
+		// The rim of the caps is split from the sides.
+		let mesh = cylinder::cylinder(1.0, 2.0, 16, 1, true);
+		let vertex_count = mesh.vertices.len();
+		assert!(mesh.vertices[vertex_count - 1].n == -*Vec3::Z);
+		assert!(mesh.vertices[0].n.z.abs() < 1e-6);
+
+		let mut mesh = crate::test_support::weld_positions_ignoring_normals(mesh);
+		calculate_normals(&mut mesh, NormalMode::AutoSmooth(AUTO_SMOOTH_ANGLE));
+		assert_eq!(mesh.vertices.len(), vertex_count);
+	}
+
+	#[test]
+	fn attributes() {
+		let mut builder = MeshBuilder::new();
+		for p in [
+			[0.0, 0.0, 0.0],
+			[1.0, 0.0, 0.0],
+			[1.0, 1.0, 0.0],
+			[0.0, 1.0, 0.0],
+		] {
+			builder.add_vertex(p);
+		}
+		builder.add_quad(0, 1, 2, 3);
+		builder.set_uv(1, 0, [1.0, 0.0]);
+		builder.set_color(2, [1.0, 0.5, 0.25, 1.0]);
+		builder.set_attribute(0, "weight", 0.5f32);
+
+		// Channels cover every vertex, with zero for the vertices that weren't assigned a value.
+		let mut mesh = builder.build();
+		assert_eq!(mesh.attributes.len(), 3);
+		assert!(
+			mesh.attributes.uv(0).unwrap()
+				== [Vec2::ZERO, Vec2::new(1.0, 0.0), Vec2::ZERO, Vec2::ZERO]
+		);
+		assert!(mesh.attributes.colors().unwrap()[2] == Vec4::new(1.0, 0.5, 0.25, 1.0));
+		assert_eq!(
+			mesh.attributes.get::<f32>("weight"),
+			Some(&[0.5, 0.0, 0.0, 0.0][..])
+		);
+		assert!(mesh.attributes.get::<Vec2>("weight").is_none());
+
+		mesh.attributes.insert("weight", vec![1.0f32; 4]);
+		assert_eq!(mesh.attributes.get::<f32>("weight"), Some(&[1.0; 4][..]));
+
+		let mut writer = BlobWriter::new();
+		mesh.write_cooked(&mut writer).unwrap();
+		let bytes = writer.finish();
+		let cooked = Mesh::read_cooked(&mut BlobReader::new(&bytes)).unwrap();
+
+		let names: Vec<&str> = cooked.attributes.iter().map(|(name, _)| name).collect();
+		assert_eq!(names, ["uv0", COLOR, "weight"]);
+		for (name, values) in mesh.attributes.iter() {
+			assert!(cooked.attributes.get_untyped(name) == Some(values));
+		}
+
+		assert!(mesh.attributes.remove(COLOR).is_some());
+		assert!(mesh.attributes.colors().is_none());
+
+		mesh.indices[0] = u32::MAX as usize + 1;
+		assert!(mesh.write_cooked(&mut BlobWriter::new()).is_err());
+	}
+
+	/// Quads along X in the XY plane, facing +Z, with the given U for every column of vertices and V going up.
+	fn quad_strip(us: &[f32]) -> Mesh {
+		let mut builder = MeshBuilder::new();
+		for (x, &u) in us.iter().enumerate() {
+			for y in 0..2 {
+				let vertex = builder.add_vertex([x as f32, y as f32, 0.0]);
+				builder.set_uv(vertex, 0, [u, y as f32]);
+			}
+		}
+		for x in 0..us.len() - 1 {
+			builder.add_quad(2 * x, 2 * x + 2, 2 * x + 3, 2 * x + 1);
+		}
+		builder.build()
+	}
+
+	#[test]
+	fn mirrored_tangents() {
+		// The UVs are mirrored at the middle column, so it is split.
+		let mut mesh = quad_strip(&[0.0, 1.0, 0.0]);
+		calculate_tangents(&mut mesh);
+		assert_eq!(mesh.vertices.len(), 8);
+
+		let tangents = mesh.attributes.tangents().unwrap();
+		for triangle in mesh.indices.chunks_exact(3) {
+			let left = triangle.iter().all(|&i| mesh.vertices[i].p.x <= 1.0);
+			let expected = match left {
+				true => Vec4::new(1.0, 0.0, 0.0, 1.0),
+				false => Vec4::new(-1.0, 0.0, 0.0, -1.0),
+			};
+			assert!(
+				triangle
+					.iter()
+					.all(|&i| (tangents[i] - expected).length() < 1e-5)
+			);
+		}
+	}
+
+	#[test]
+	fn degenerate_uv_tangents() {
+		// The right quad has no extent in U, so its outer vertices have no tangent of their own.
+		let mut mesh = quad_strip(&[0.0, 1.0, 1.0]);
+		calculate_tangents(&mut mesh);
+		assert_eq!(mesh.vertices.len(), 6);
+
+		let tangents = mesh.attributes.tangents().unwrap();
+		for (vertex, tangent) in mesh.vertices.iter().zip(tangents) {
+			let xyz = Vec3::new(tangent.x, tangent.y, tangent.z);
+			assert!((xyz.length() - 1.0).abs() < 1e-5);
+			assert!(xyz.dot(vertex.n).abs() < 1e-5);
+			assert_eq!(tangent.w, 1.0);
+
+			if vertex.p.x <= 1.0 {
+				assert!((xyz - *Vec3::X).length() < 1e-5);
+			}
+		}
+
+		// Without any UV extent, every vertex gets some tangent perpendicular to its normal.
+		let mut mesh = quad_strip(&[0.5, 0.5]);
+		calculate_tangents(&mut mesh);
+		assert!(
+			mesh.attributes
+				.tangents()
+				.unwrap()
+				.iter()
+				.all(|t| t.z.abs() < 1e-5 && t.w == 1.0)
+		);
+	}
+}
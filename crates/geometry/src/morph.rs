@@ -0,0 +1,410 @@
+//! Morph targets, also called blend shapes: offsets of vertex positions and normals that are added to a mesh by
+//! weight, like the expressions of a face.
+
+use crate::mesh::{Mesh, Vertex};
+use gpu::{self, BufferImpl, CmdListImpl, DeviceImpl};
+use math::Vec3;
+
+/// A morph target of a [`Mesh`], with deltas for every vertex or for some of them.
+#[derive(Clone, Default)]
+pub struct MorphTarget {
+	pub name: String,
+	/// Vertices the deltas apply to, or `None` if there is a delta for every vertex.
+	pub vertices: Option<Vec<usize>>,
+	pub positions: Vec<Vec3>,
+	/// Normal deltas, in the same order as `positions`, or empty if the target doesn't change normals.
+	pub normals: Vec<Vec3>,
+}
+
+impl MorphTarget {
+	pub fn dense(name: impl Into<String>, positions: Vec<Vec3>, normals: Vec<Vec3>) -> Self {
+		Self {
+			name: name.into(),
+			vertices: None,
+			positions,
+			normals,
+		}
+	}
+
+	pub fn sparse(
+		name: impl Into<String>,
+		vertices: Vec<usize>,
+		positions: Vec<Vec3>,
+		normals: Vec<Vec3>,
+	) -> Self {
+		Self {
+			name: name.into(),
+			vertices: Some(vertices),
+			positions,
+			normals,
+		}
+	}
+
+	/// Whether the deltas fit a mesh with `vertex_count` vertices: sparse targets have a vertex for every delta and
+	/// only refer to vertices of the mesh, dense ones have a delta for every vertex, and normal deltas are either
+	/// missing or there for every position delta.
+	pub fn is_valid(&self, vertex_count: usize) -> bool {
+		let vertices_valid = match &self.vertices {
+			Some(vertices) => {
+				vertices.len() == self.positions.len() && vertices.iter().all(|&v| v < vertex_count)
+			}
+			None => self.positions.len() == vertex_count,
+		};
+
+		vertices_valid && (self.normals.is_empty() || self.normals.len() == self.positions.len())
+	}
+
+	/// Vertex, position delta and normal delta of every vertex the target changes.
+	pub fn deltas(&self) -> impl Iterator<Item = (usize, Vec3, Vec3)> + '_ {
+		self.positions.iter().enumerate().map(|(i, &position)| {
+			let vertex = self.vertices.as_ref().map_or(i, |vertices| vertices[i]);
+			let normal = self.normals.get(i).copied().unwrap_or(Vec3::ZERO);
+			(vertex, position, normal)
+		})
+	}
+
+	/// The target for a mesh made of the given vertices, in order, of the mesh it belongs to.
+	pub fn gather(&self, vertices: &[usize]) -> Self {
+		let Some(sparse) = &self.vertices else {
+			let gather = |values: &[Vec3]| match values.is_empty() {
+				true => Vec::new(),
+				false => vertices.iter().map(|&v| values[v]).collect(),
+			};
+
+			return Self::dense(&self.name, gather(&self.positions), gather(&self.normals));
+		};
+
+		// Vertices may be gathered more than once, like when they are split.
+		let mut new_vertices = vec![Vec::new(); self.vertex_bound(vertices)];
+		for (new, &old) in vertices.iter().enumerate() {
+			if let Some(list) = new_vertices.get_mut(old) {
+				list.push(new);
+			}
+		}
+
+		let mut target = Self::sparse(&self.name, Vec::new(), Vec::new(), Vec::new());
+
+		for (i, &old) in sparse.iter().enumerate() {
+			for &new in new_vertices.get(old).into_iter().flatten() {
+				target.vertices.as_mut().unwrap().push(new);
+				target.positions.push(self.positions[i]);
+
+				if !self.normals.is_empty() {
+					target.normals.push(self.normals[i]);
+				}
+			}
+		}
+
+		target
+	}
+
+	fn vertex_bound(&self, vertices: &[usize]) -> usize {
+		let sparse = self.vertices.iter().flatten();
+		sparse.chain(vertices).max().map_or(0, |&v| v + 1)
+	}
+}
+
+/// Weights of the morph targets of an entity's mesh, in the order of [`Mesh::morph_targets`].
+#[derive(Clone, Default)]
+pub struct MorphWeights(pub Vec<f32>);
+
+/// Adds the morph targets of a mesh to its vertices, scaled by their weights. Missing weights are zero, and targets
+/// that don't fit the mesh, see [`MorphTarget::is_valid`], are ignored.
+///
+/// Normals that a weighted delta changed are renormalized, unless they cancelled out. This is the same rule as
+/// the GPU deformation in `morph-deform.slang`, so both give the same result.
+pub fn apply_morph_targets(mesh: &Mesh, weights: &[f32]) -> Vec<Vertex> {
+	let mut vertices = mesh.vertices.clone();
+	let mut changed_normals = vec![false; vertices.len()];
+
+	for (target, &weight) in mesh.morph_targets.iter().zip(weights) {
+		if weight == 0.0 || !target.is_valid(vertices.len()) {
+			continue;
+		}
+
+		for (v, position, normal) in target.deltas() {
+			vertices[v].p += position * weight;
+
+			if normal != Vec3::ZERO {
+				vertices[v].n += normal * weight;
+				changed_normals[v] = true;
+			}
+		}
+	}
+
+	for (vertex, changed) in vertices.iter_mut().zip(changed_normals) {
+		if changed && vertex.n.length_sq() > f32::MIN_POSITIVE {
+			vertex.n = *vertex.n.normalize();
+		}
+	}
+
+	vertices
+}
+
+/// Delta of a vertex in the GPU buffers.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GpuDelta {
+	target: u32,
+	position: [f32; 3],
+	normal: [f32; 3],
+}
+
+/// Per vertex offsets into the deltas, and the deltas of all targets, grouped by vertex. Invalid targets are left
+/// out, like in [`apply_morph_targets`].
+fn to_gpu_data(mesh: &Mesh) -> (Vec<u32>, Vec<GpuDelta>) {
+	let mut vertex_deltas = vec![Vec::new(); mesh.vertices.len()];
+
+	for (target, morph_target) in mesh.morph_targets.iter().enumerate() {
+		if !morph_target.is_valid(mesh.vertices.len()) {
+			continue;
+		}
+
+		for (v, position, normal) in morph_target.deltas() {
+			if position != Vec3::ZERO || normal != Vec3::ZERO {
+				vertex_deltas[v].push(GpuDelta {
+					target: target as u32,
+					position: position.into(),
+					normal: normal.into(),
+				});
+			}
+		}
+	}
+
+	let mut lookup = vec![0];
+	let mut deltas = Vec::new();
+
+	for vertex in vertex_deltas {
+		deltas.extend(vertex);
+		lookup.push(deltas.len() as u32);
+	}
+
+	(lookup, deltas)
+}
+
+#[repr(C)]
+struct PushConstants {
+	num_vertices: u32,
+
+	in_vertices: u32,
+	out_vertices: u32,
+
+	lookup_stream: u32,
+	delta_stream: u32,
+	weights: u32,
+}
+
+/// Applies morph targets in a compute shader. The output can be skinned by passing it to
+/// [`BoneDeform::execute`](crate::bone_deform::BoneDeform::execute).
+pub struct MorphDeform {
+	num_vertices: usize,
+	target_count: usize,
+	lookup_buffer: gpu::Buffer,
+	deltas_buffer: gpu::Buffer,
+	weights_buffer: gpu::Buffer,
+	transformed_vertex_buffer: gpu::Buffer,
+	compute_pipeline: gpu::ComputePipeline,
+}
+
+impl MorphDeform {
+	pub fn new(
+		device: &mut gpu::Device,
+		shader_compiler: &gpu::ShaderCompiler,
+		mesh: &Mesh,
+	) -> Self {
+		let shader = shader_compiler.compile("geometry/morph-deform.slang", "main");
+
+		let descriptor_layout = gpu::DescriptorLayout {
+			push_constants: Some(gpu::PushConstantBinding {
+				size: size_of::<PushConstants>() as u32,
+			}),
+			bindings: Some(vec![
+				gpu::DescriptorBinding::bindless_srv(1),
+				gpu::DescriptorBinding::bindless_uav(2),
+			]),
+			static_samplers: None,
+		};
+
+		let compute_pipeline = device
+			.create_compute_pipeline(&gpu::ComputePipelineDesc {
+				cs: &shader,
+				descriptor_layout: &descriptor_layout,
+			})
+			.unwrap();
+
+		let (lookup, deltas) = to_gpu_data(mesh);
+		let target_count = mesh.morph_targets.len();
+
+		let lookup_buffer = device
+			.create_buffer(&gpu::BufferDesc {
+				size: size_of::<u32>() * lookup.len(),
+				usage: gpu::BufferUsage::SHADER_RESOURCE,
+				memory: gpu::Memory::GpuOnly,
+			})
+			.unwrap();
+
+		// Buffers can't be empty.
+		let deltas_buffer = device
+			.create_buffer(&gpu::BufferDesc {
+				size: size_of::<GpuDelta>() * deltas.len().max(1),
+				usage: gpu::BufferUsage::SHADER_RESOURCE,
+				memory: gpu::Memory::GpuOnly,
+			})
+			.unwrap();
+
+		gpu::upload_buffer(device, &lookup_buffer, gpu::slice_as_u8_slice(&lookup));
+		gpu::upload_buffer(device, &deltas_buffer, gpu::slice_as_u8_slice(&deltas));
+
+		let weights_buffer = device
+			.create_buffer(&gpu::BufferDesc {
+				size: size_of::<f32>() * target_count.max(1),
+				usage: gpu::BufferUsage::SHADER_RESOURCE,
+				memory: gpu::Memory::CpuToGpu,
+			})
+			.unwrap();
+
+		let transformed_vertex_buffer = device
+			.create_buffer(&gpu::BufferDesc {
+				size: size_of::<Vertex>() * mesh.vertices.len(),
+				usage: gpu::BufferUsage::SHADER_RESOURCE | gpu::BufferUsage::UNORDERED_ACCESS,
+				memory: gpu::Memory::GpuOnly,
+			})
+			.unwrap();
+
+		let morph_deform = Self {
+			num_vertices: mesh.vertices.len(),
+			target_count,
+			lookup_buffer,
+			deltas_buffer,
+			weights_buffer,
+			transformed_vertex_buffer,
+			compute_pipeline,
+		};
+
+		morph_deform.update_weights(&[]);
+		morph_deform
+	}
+
+	/// Sets the weight of every target. Missing weights are zero, extra ones are ignored.
+	pub fn update_weights(&self, weights: &[f32]) {
+		let mut all_weights = vec![0.0; self.target_count];
+		let count = weights.len().min(self.target_count);
+		all_weights[..count].copy_from_slice(&weights[..count]);
+
+		let ptr = self.weights_buffer.cpu_ptr() as *mut f32;
+		unsafe {
+			std::ptr::copy_nonoverlapping(all_weights.as_ptr(), ptr, all_weights.len());
+		}
+	}
+
+	pub fn execute(&self, cmd: &gpu::CmdList, vertex_srv: u32) {
+		let push_constants = PushConstants {
+			num_vertices: self.num_vertices as u32,
+			in_vertices: vertex_srv,
+			out_vertices: self.transformed_vertex_buffer.uav_index().unwrap(),
+			lookup_stream: self.lookup_buffer.srv_index().unwrap(),
+			delta_stream: self.deltas_buffer.srv_index().unwrap(),
+			weights: self.weights_buffer.srv_index().unwrap(),
+		};
+
+		cmd.set_compute_pipeline(&self.compute_pipeline);
+		cmd.compute_push_constants(0, gpu::as_u8_slice(&push_constants));
+
+		cmd.dispatch([push_constants.num_vertices.div_ceil(32), 1, 1]);
+
+		cmd.barriers(&gpu::Barriers::global());
+	}
+
+	pub fn get_transformed_vertex_buffer(&self) -> &gpu::Buffer {
+		&self.transformed_vertex_buffer
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::primitives::grid;
+
+	#[test]
+	fn morph_targets() {
+		let mut mesh = grid::grid(1.0, 1.0, 3, 3);
+		let up = |count: usize| vec![*Vec3::Z; count];
+
+		mesh.morph_targets = vec![
+			MorphTarget::dense("raise", up(9), Vec::new()),
+			MorphTarget::sparse("corner", vec![0, 8], up(2), vec![*Vec3::X; 2]),
+		];
+
+		let vertices = apply_morph_targets(&mesh, &[0.5, 1.0]);
+		assert!(vertices[4].p == mesh.vertices[4].p + *Vec3::Z * 0.5);
+		assert!(vertices[8].p == mesh.vertices[8].p + *Vec3::Z * 1.5);
+		assert!(vertices[8].n == *(*Vec3::Z + *Vec3::X).normalize());
+
+		// The GPU data has the deltas grouped by vertex.
+		let (lookup, deltas) = to_gpu_data(&mesh);
+		assert_eq!(lookup.len(), 10);
+		assert_eq!(deltas.len(), 11);
+		assert_eq!(lookup[1] - lookup[0], 2);
+		assert_eq!(deltas[1].target, 1);
+
+		// Splitting a vertex splits its deltas.
+		let corner = mesh.morph_targets[1].gather(&[8, 1, 8, 0]);
+		assert_eq!(corner.vertices, Some(vec![3, 0, 2]));
+
+		// Vertices are only welded if their deltas match.
+		mesh.attributes = Default::default();
+		mesh.vertices.extend([mesh.vertices[8], mesh.vertices[8]]);
+		mesh.indices.extend([9, 0, 1, 10, 0, 1]);
+		mesh.morph_targets[0].positions.extend(up(2));
+
+		let corner = &mut mesh.morph_targets[1];
+		corner.vertices.as_mut().unwrap().push(9);
+		corner.positions.push(*Vec3::Z);
+		corner.normals.push(*Vec3::X);
+
+		assert_eq!(crate::cleanup::weld_vertices(&mut mesh, 0.0), 1);
+		assert_eq!(mesh.morph_targets[0].positions.len(), 10);
+		assert_eq!(mesh.morph_targets[1].vertices, Some(vec![0, 8]));
+	}
+
+	#[test]
+	fn normal_renormalization() {
+		let mut mesh = grid::grid(1.0, 1.0, 2, 2);
+		for vertex in &mut mesh.vertices {
+			vertex.n = *Vec3::Z * 2.0;
+		}
+
+		mesh.morph_targets = vec![
+			MorphTarget::sparse("move", vec![0], vec![*Vec3::X], Vec::new()),
+			MorphTarget::sparse("tilt", vec![1], vec![Vec3::ZERO], vec![*Vec3::X * 2.0]),
+			MorphTarget::sparse("flatten", vec![2], vec![Vec3::ZERO], vec![*Vec3::Z * -2.0]),
+		];
+
+		// Only normals changed by a weighted delta are renormalized, and those that cancel out are left alone.
+		let vertices = apply_morph_targets(&mesh, &[1.0, 1.0, 1.0]);
+		assert!(vertices[0].n == *Vec3::Z * 2.0);
+		assert!(vertices[1].n == *(*Vec3::Z + *Vec3::X).normalize());
+		assert!(vertices[2].n == Vec3::ZERO);
+		assert!(vertices[3].n == *Vec3::Z * 2.0);
+
+		let vertices = apply_morph_targets(&mesh, &[1.0, 0.0]);
+		assert!(vertices[1].n == *Vec3::Z * 2.0);
+	}
+	#[test]
+	fn invalid_targets() {
+		let mut mesh = grid::grid(1.0, 1.0, 2, 2);
+		mesh.morph_targets = vec![
+			MorphTarget::sparse("outside", vec![4], vec![*Vec3::Z], Vec::new()),
+			MorphTarget::sparse("missing", vec![0], vec![*Vec3::Z; 2], Vec::new()),
+			MorphTarget::dense("short", vec![*Vec3::Z; 3], Vec::new()),
+			MorphTarget::dense("normals", vec![*Vec3::Z; 4], vec![*Vec3::X]),
+		];
+		assert!(mesh.morph_targets.iter().all(|target| !target.is_valid(4)));
+
+		// They are ignored instead of reading or writing out of bounds.
+		let vertices = apply_morph_targets(&mesh, &[1.0; 4]);
+		assert!(vertices.iter().zip(&mesh.vertices).all(|(a, b)| a.p == b.p));
+		assert!(to_gpu_data(&mesh).1.is_empty());
+		assert_eq!(crate::cleanup::weld_vertices(&mut mesh, 0.0), 0);
+	}
+}
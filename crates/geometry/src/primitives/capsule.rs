@@ -0,0 +1,84 @@
+use super::super::mesh::{Mesh, MeshBuilder};
+use super::revolve::{ProfilePoint, Uvs, revolve};
+
+use std::f32::consts::PI;
+
+/// Creates a capsule Mesh along the Z axis, matching [`math::primitives::Capsule`].
+/// # Arguments
+/// * `half_length` - Half the height of the cylinder part, excluding the hemispheres.
+/// * `meridians` - Number of 'vertical' lines.
+/// * `rings` - Number of 'horizontal' lines of each hemisphere, including the one at its equator.
+pub fn capsule(radius: f32, half_length: f32, meridians: usize, rings: usize) -> Mesh {
+	assert!(rings >= 1);
+
+	let mut mesh = MeshBuilder::new();
+	let mut normals = Vec::new();
+
+	let delta_theta = PI / 2.0 / rings as f32;
+	let length = PI * radius + 2.0 * half_length;
+
+	let mut profile = Vec::with_capacity(2 * rings + 2);
+
+	for bottom in [false, true] {
+		let (center, range) = match bottom {
+			false => (half_length, 0..=rings),
+			true => (-half_length, rings..=2 * rings),
+		};
+
+		for ring in range {
+			// Without a cylinder part both hemispheres share their equator.
+			if bottom && ring == rings && half_length == 0.0 {
+				continue;
+			}
+
+			let theta = ring as f32 * delta_theta;
+			let (sin_theta, cos_theta) = theta.sin_cos();
+			let arc = theta * radius + if bottom { 2.0 * half_length } else { 0.0 };
+
+			profile.push(ProfilePoint {
+				radius: if ring % (2 * rings) == 0 {
+					0.0
+				} else {
+					radius * sin_theta
+				},
+				z: center + radius * cos_theta,
+				normal: [sin_theta, cos_theta],
+				v: arc / length,
+			});
+		}
+	}
+
+	revolve(
+		&mut mesh,
+		&mut normals,
+		&profile,
+		meridians,
+		Uvs::Cylindrical,
+	);
+
+	mesh.build_with_normals(&normals)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::{check_primitive, measure};
+	use math::primitives::{Capsule, Measure};
+
+	#[test]
+	fn matches_shape() {
+		let shape = Capsule {
+			radius: 0.5,
+			half_length: 1.0,
+		};
+		let mesh = capsule(shape.radius, shape.half_length, 64, 16);
+		let (area, volume) = measure(&mesh);
+		check_primitive(&mesh);
+
+		assert!((area / shape.area() - 1.0).abs() < 0.01);
+		assert!((volume / shape.volume() - 1.0).abs() < 0.01);
+
+		// Without a cylinder in between, the capsule is a sphere.
+		check_primitive(&capsule(1.0, 0.0, 8, 1));
+	}
+}
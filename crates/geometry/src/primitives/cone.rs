@@ -0,0 +1,77 @@
+use super::super::mesh::{Mesh, MeshBuilder};
+use super::disk::cap_profile;
+use super::revolve::{ProfilePoint, Uvs, revolve};
+
+/// Creates a cone Mesh along the Z axis, or a frustum if `top_radius` is not zero.
+/// # Arguments
+/// * `resolution` - Number of vertices on the top and bottom circles.
+/// * `segments` - Number of segments along the height of the cone.
+pub fn cone(
+	bottom_radius: f32,
+	top_radius: f32,
+	height: f32,
+	resolution: usize,
+	segments: usize,
+	caps: bool,
+) -> Mesh {
+	assert!(segments >= 1);
+	assert!(bottom_radius > 0.0 || top_radius > 0.0);
+
+	let mut mesh = MeshBuilder::new();
+	let mut normals = Vec::new();
+
+	let shift_z = height / 2.0;
+
+	// The side is a straight line, so every row has the same normal.
+	let slope = bottom_radius - top_radius;
+	let length = (height * height + slope * slope).sqrt();
+	let normal = [height / length, slope / length];
+
+	let profile = (0..=segments)
+		.map(|s| {
+			let t = s as f32 / segments as f32;
+			ProfilePoint {
+				radius: top_radius + t * slope,
+				z: shift_z - t * height,
+				normal,
+				v: t,
+			}
+		})
+		.collect::<Vec<_>>();
+
+	revolve(
+		&mut mesh,
+		&mut normals,
+		&profile,
+		resolution,
+		Uvs::Cylindrical,
+	);
+
+	if caps {
+		let uvs = || Uvs::Planar(bottom_radius.max(top_radius));
+
+		if top_radius > 0.0 {
+			let profile = cap_profile(top_radius, shift_z, true);
+			revolve(&mut mesh, &mut normals, &profile, resolution, uvs());
+		}
+
+		if bottom_radius > 0.0 {
+			let profile = cap_profile(bottom_radius, -shift_z, false);
+			revolve(&mut mesh, &mut normals, &profile, resolution, uvs());
+		}
+	}
+
+	mesh.build_with_normals(&normals)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::check_primitive;
+
+	#[test]
+	fn cone_and_frustum() {
+		check_primitive(&cone(1.0, 0.0, 2.0, 16, 2, true));
+		check_primitive(&cone(1.0, 0.5, 2.0, 16, 1, true));
+	}
+}
@@ -12,9 +12,9 @@ pub fn cylinder(radius: f32, height: f32, resolution: usize, segments: usize, ca
 
 	let mut mesh = MeshBuilder::new();
 
-	let vertex_count = resolution * (segments + 1) + if caps { 2 } else { 0 };
+	let vertex_count = resolution * (segments + 1) + if caps { 2 * resolution + 2 } else { 0 };
 	let edge_count =
-		resolution * (segments + 1) + resolution * segments + if caps { 2 * resolution } else { 0 };
+		resolution * (segments + 1) + resolution * segments + if caps { 4 * resolution } else { 0 };
 	let face_count = resolution * segments + if caps { 2 * resolution } else { 0 };
 
 	mesh.reserve(vertex_count, edge_count, face_count);
@@ -49,20 +49,26 @@ pub fn cylinder(radius: f32, height: f32, resolution: usize, segments: usize, ca
 	}
 
 	if caps {
-		// TODO: Ensure that the normals are flat for the caps.
-		let v_top = mesh.add_vertex([0.0, 0.0, shift_z]);
-		let v_bottom = mesh.add_vertex([0.0, 0.0, -shift_z]);
+		// The caps have their own rim vertices, so their normals are flat.
+		for (z, top) in [(shift_z, true), (-shift_z, false)] {
+			let center = mesh.add_vertex([0.0, 0.0, z]);
+			let rim = mesh.mesh.vertices.len();
 
-		for r in 0..resolution {
-			let i0 = r;
-			let i1 = (r + 1) % resolution;
-			mesh.add_triangle(v_top, i0, i1);
-		}
+			for r in 0..resolution {
+				let phi = r as f32 * delta_phi;
+				mesh.add_vertex([radius * phi.cos(), radius * phi.sin(), z]);
+			}
 
-		for r in 0..resolution {
-			let i0 = r + resolution * segments;
-			let i1 = (r + 1) % resolution + resolution * segments;
-			mesh.add_triangle(v_bottom, i1, i0);
+			for r in 0..resolution {
+				let i0 = rim + r;
+				let i1 = rim + (r + 1) % resolution;
+
+				if top {
+					mesh.add_triangle(center, i0, i1);
+				} else {
+					mesh.add_triangle(center, i1, i0);
+				}
+			}
 		}
 	}
 
@@ -0,0 +1,58 @@
+use super::super::mesh::{Mesh, MeshBuilder};
+use super::revolve::{ProfilePoint, Uvs, revolve};
+
+/// Creates a disk Mesh in the XY plane, facing +Z, with UVs projected from above.
+/// # Arguments
+/// * `resolution` - Number of vertices on the rim.
+/// * `rings` - Number of concentric rings of faces.
+pub fn disk(radius: f32, resolution: usize, rings: usize) -> Mesh {
+	assert!(rings >= 1);
+
+	let mut mesh = MeshBuilder::new();
+	let mut normals = Vec::new();
+
+	let profile = (0..=rings)
+		.map(|ring| ProfilePoint {
+			radius: radius * ring as f32 / rings as f32,
+			z: 0.0,
+			normal: [0.0, 1.0],
+			v: 0.0,
+		})
+		.collect::<Vec<_>>();
+
+	revolve(
+		&mut mesh,
+		&mut normals,
+		&profile,
+		resolution,
+		Uvs::Planar(radius),
+	);
+
+	mesh.build_with_normals(&normals)
+}
+
+/// Profile of a cap at height `z` with the given radius, facing up or down.
+pub(super) fn cap_profile(radius: f32, z: f32, up: bool) -> [ProfilePoint; 2] {
+	let nz = if up { 1.0 } else { -1.0 };
+	let center = ProfilePoint {
+		radius: 0.0,
+		z,
+		normal: [0.0, nz],
+		v: 0.0,
+	};
+	let rim = ProfilePoint { radius, ..center };
+
+	// Profiles go from the top to the bottom of the surface, which is the center for caps facing up.
+	if up { [center, rim] } else { [rim, center] }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::check_primitive;
+
+	#[test]
+	fn rings() {
+		check_primitive(&disk(1.0, 16, 3));
+	}
+}
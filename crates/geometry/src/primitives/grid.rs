@@ -1,5 +1,6 @@
 use super::super::mesh::{Mesh, MeshBuilder};
 
+/// Creates a grid Mesh in the XY plane, facing +Z, with UVs spanning the whole grid.
 pub fn grid(size_x: f32, size_y: f32, vertices_x: usize, vertices_y: usize) -> Mesh {
 	assert!(vertices_x >= 2);
 	assert!(vertices_y >= 2);
@@ -22,11 +23,16 @@ pub fn grid(size_x: f32, size_y: f32, vertices_x: usize, vertices_y: usize) -> M
 
 	for y in 0..vertices_y {
 		for x in 0..vertices_x {
-			mesh.add_vertex([
+			let vertex = mesh.add_vertex([
 				x as f32 * delta_x - shift_x,
 				y as f32 * delta_y - shift_y,
 				0.0,
 			]);
+
+			// V goes down, like the projected UVs of the disk.
+			let u = x as f32 / faces_x as f32;
+			let v = 1.0 - y as f32 / faces_y as f32;
+			mesh.set_uv(vertex, 0, [u, v]);
 		}
 	}
 
@@ -42,3 +48,16 @@ pub fn grid(size_x: f32, size_y: f32, vertices_x: usize, vertices_y: usize) -> M
 
 	mesh.build()
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::check_primitive;
+
+	#[test]
+	fn uvs() {
+		let mesh = grid(2.0, 1.0, 3, 2);
+		check_primitive(&mesh);
+		assert!(mesh.attributes.uv(0).unwrap()[5] == math::Vec2::new(1.0, 0.0));
+	}
+}
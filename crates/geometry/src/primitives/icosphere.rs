@@ -0,0 +1,96 @@
+use super::super::mesh::{Mesh, MeshBuilder};
+use super::platonic::icosahedron_data;
+
+use math::Vec3;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+/// Creates an icosphere Mesh, an icosahedron whose triangles are split into four `subdivisions` times. The
+/// triangles are more even than those of a UV-sphere.
+///
+/// UVs are spherical, so vertices are split along the seam at -X and at the poles.
+pub fn icosphere(radius: f32, subdivisions: usize) -> Mesh {
+	let (vertices, faces) = icosahedron_data();
+
+	let mut positions: Vec<Vec3> = vertices
+		.iter()
+		.map(|v| Vec3::new(v[0], v[1], v[2]))
+		.collect();
+	let mut triangles = faces.to_vec();
+
+	for _ in 0..subdivisions {
+		let mut midpoints = HashMap::new();
+		let mut midpoint = |a: usize, b: usize| {
+			*midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+				positions.push(*(positions[a] + positions[b]).normalize());
+				positions.len() - 1
+			})
+		};
+
+		triangles = triangles
+			.iter()
+			.flat_map(|&[a, b, c]| {
+				let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+				[[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+			})
+			.collect();
+	}
+
+	let mut mesh = MeshBuilder::new();
+	let mut normals = Vec::new();
+	// Vertex of every position and UV.
+	let mut split = HashMap::new();
+
+	for triangle in triangles {
+		let p = triangle.map(|i| positions[i]);
+		let pole = p.map(|p| p.x.abs() < 1e-6 && p.y.abs() < 1e-6);
+		let mut u = p.map(|p| (0.5 + p.y.atan2(p.x) / (2.0 * PI)) % 1.0);
+
+		// Triangles that cross the seam, or touch it from the positive Y side, wrap around.
+		let max_u = u.iter().copied().fold(0.0, f32::max);
+		for (u, pole) in u.iter_mut().zip(pole) {
+			if !pole && max_u - *u > 0.5 {
+				*u += 1.0;
+			}
+		}
+
+		// Poles have the U of the triangle's other corners.
+		let others: Vec<f32> = (0..3).filter(|&i| !pole[i]).map(|i| u[i]).collect();
+		for i in (0..3).filter(|&i| pole[i]) {
+			u[i] = others.iter().sum::<f32>() / others.len() as f32;
+		}
+
+		let corners = [0, 1, 2].map(|i| {
+			let uv = [u[i], p[i].z.clamp(-1.0, 1.0).acos() / PI];
+			let key = (triangle[i], uv[0].to_bits(), uv[1].to_bits());
+
+			*split.entry(key).or_insert_with(|| {
+				let vertex = mesh.add_vertex((p[i] * radius).into());
+				mesh.set_uv(vertex, 0, uv);
+				normals.push(p[i]);
+				vertex
+			})
+		});
+
+		mesh.add_triangle(corners[0], corners[1], corners[2]);
+	}
+
+	mesh.build_with_normals(&normals)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::check_primitive;
+
+	#[test]
+	fn on_sphere() {
+		let mesh = icosphere(2.0, 3);
+		check_primitive(&mesh);
+		assert!(
+			mesh.vertices
+				.iter()
+				.all(|vertex| (vertex.p.length() - 2.0).abs() < 1e-5)
+		);
+	}
+}
@@ -1,4 +1,11 @@
-use super::super::mesh::{Mesh, MeshBuilder};
+use super::super::mesh::{Mesh, MeshBuilder, NormalMode, calculate_normals};
+
+/// Platonic solids have hard edges, so every triangle gets its own vertices.
+fn build_flat(mesh: MeshBuilder) -> Mesh {
+	let mut mesh = mesh.build();
+	calculate_normals(&mut mesh, NormalMode::Flat);
+	mesh
+}
 
 pub fn tetrahedron() -> Mesh {
 	let mut mesh = MeshBuilder::new();
@@ -20,7 +27,7 @@ pub fn tetrahedron() -> Mesh {
 	mesh.add_triangle(v0, v3, v1);
 	mesh.add_triangle(v3, v2, v1);
 
-	mesh.build()
+	build_flat(mesh)
 }
 
 pub fn hexahedron() -> Mesh {
@@ -46,7 +53,7 @@ pub fn hexahedron() -> Mesh {
 	mesh.add_quad(v3, v7, v6, v2);
 	mesh.add_quad(v1, v5, v4, v0);
 
-	mesh.build()
+	build_flat(mesh)
 }
 
 pub fn octahedron() -> Mesh {
@@ -69,13 +76,11 @@ pub fn octahedron() -> Mesh {
 	mesh.add_triangle(v3, v5, v2);
 	mesh.add_triangle(v2, v5, v1);
 
-	mesh.build()
+	build_flat(mesh)
 }
 
-pub fn icosahedron() -> Mesh {
-	let mut mesh = MeshBuilder::new();
-	mesh.reserve(12, 30, 20);
-
+/// Vertices on the unit sphere and faces of an icosahedron.
+pub(super) fn icosahedron_data() -> ([[f32; 3]; 12], [[usize; 3]; 20]) {
 	// Coordinates on the unit sphere
 	let phi = (1.0 + 5.0_f32.sqrt()) / 2.0;
 	let scale = (1.0 + phi * phi).sqrt();
@@ -121,6 +126,15 @@ pub fn icosahedron() -> Mesh {
 		[7, 11, 2],
 	];
 
+	(vertices, faces)
+}
+
+pub fn icosahedron() -> Mesh {
+	let mut mesh = MeshBuilder::new();
+	mesh.reserve(12, 30, 20);
+
+	let (vertices, faces) = icosahedron_data();
+
 	vertices.iter().for_each(|v| {
 		mesh.add_vertex(*v);
 	});
@@ -129,5 +143,5 @@ pub fn icosahedron() -> Mesh {
 		mesh.add_triangle(f[0], f[1], f[2]);
 	});
 
-	mesh.build()
+	build_flat(mesh)
 }
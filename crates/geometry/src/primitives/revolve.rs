@@ -0,0 +1,89 @@
+use super::super::mesh::MeshBuilder;
+
+use math::Vec3;
+use std::f32::consts::PI;
+
+/// Point of a profile in the XZ plane that is revolved around the Z axis.
+#[derive(Clone, Copy)]
+pub(super) struct ProfilePoint {
+	/// Distance to the Z axis. Points on the axis are poles.
+	pub radius: f32,
+	pub z: f32,
+	/// Normal in the XZ plane, as `[x, z]`.
+	pub normal: [f32; 2],
+	/// V coordinate of the row, for cylindrical UVs.
+	pub v: f32,
+}
+
+pub(super) enum Uvs {
+	/// U goes around the Z axis, V comes from the profile.
+	Cylindrical,
+	/// Projected onto the XY plane, with the given radius mapping to the border of the UV square.
+	Planar(f32),
+}
+
+/// Revolves a profile, which goes from the top to the bottom of the surface, and appends its vertices and
+/// triangles. Every row has a seam vertex so that U goes from 0 to 1, and poles have a vertex per meridian so that
+/// their UVs line up with the row next to them. Normals of the vertices are appended to `normals`.
+pub(super) fn revolve(
+	mesh: &mut MeshBuilder,
+	normals: &mut Vec<Vec3>,
+	profile: &[ProfilePoint],
+	meridians: usize,
+	uvs: Uvs,
+) {
+	assert!(meridians >= 3);
+	assert!(profile.len() >= 2);
+
+	let columns = meridians + 1;
+	let base = mesh.mesh.vertices.len();
+	let delta_phi = 2.0 * PI / meridians as f32;
+
+	for point in profile {
+		let pole = point.radius == 0.0;
+
+		for m in 0..columns {
+			// Poles sit between the meridians of the row next to them. The seam has the exact position of the
+			// first meridian.
+			let column = if pole {
+				m as f32 + 0.5
+			} else {
+				(m % meridians) as f32
+			};
+			let u = if pole { column } else { m as f32 } / meridians as f32;
+			let (sin_phi, cos_phi) = (column * delta_phi).sin_cos();
+
+			let x = point.radius * cos_phi;
+			let y = point.radius * sin_phi;
+			let vertex = mesh.add_vertex([x, y, point.z]);
+
+			let uv = match uvs {
+				Uvs::Cylindrical => [u, point.v],
+				Uvs::Planar(radius) => [0.5 + x / (2.0 * radius), 0.5 - y / (2.0 * radius)],
+			};
+			mesh.set_uv(vertex, 0, uv);
+
+			let [nr, nz] = point.normal;
+			normals.push(Vec3::new(nr * cos_phi, nr * sin_phi, nz));
+		}
+	}
+
+	for (row, pair) in profile.windows(2).enumerate() {
+		let idx0 = base + row * columns;
+		let idx1 = idx0 + columns;
+
+		for m in 0..meridians {
+			let i0 = idx0 + m;
+			let i1 = idx1 + m;
+			let i2 = idx1 + m + 1;
+			let i3 = idx0 + m + 1;
+
+			match (pair[0].radius == 0.0, pair[1].radius == 0.0) {
+				(true, true) => {}
+				(true, false) => mesh.add_triangle(i0, i1, i2),
+				(false, true) => mesh.add_triangle(i0, i1, i3),
+				(false, false) => mesh.add_quad(i0, i1, i2, i3),
+			}
+		}
+	}
+}
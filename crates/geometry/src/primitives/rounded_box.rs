@@ -0,0 +1,128 @@
+use super::super::mesh::{Mesh, MeshBuilder};
+
+use math::Vec3;
+use std::f32::consts::PI;
+
+/// Coordinates of the grid lines of a face along an axis with the given half size. Rounded edges are subdivided
+/// so that their normals are evenly spaced.
+fn grid_lines(half_size: f32, radius: f32, segments: usize) -> Vec<f32> {
+	let inner = half_size - radius;
+	let rounded = |s: usize| inner + radius * (PI / 4.0 * s as f32 / segments as f32).tan();
+
+	let mut lines: Vec<f32> = (1..=segments).rev().map(|s| -rounded(s)).collect();
+	lines.push(-inner);
+	if inner > 0.0 {
+		lines.push(inner);
+	}
+	lines.extend((1..=segments).map(rounded));
+	lines
+}
+
+/// Creates a box Mesh centered at the origin with edges and corners rounded by `radius`. Every face has its own
+/// vertices and UVs.
+/// # Arguments
+/// * `segments` - Number of segments of each rounded edge per face. Edges of adjacent faces meet halfway.
+pub fn rounded_box(half_size: Vec3, radius: f32, segments: usize) -> Mesh {
+	let half_size: [f32; 3] = half_size.into();
+	assert!(radius >= 0.0 && half_size.iter().all(|&h| h >= radius && h > 0.0));
+
+	// Without rounding, the subdivided edges would be degenerate.
+	let segments = if radius > 0.0 { segments.max(1) } else { 0 };
+	let lines = half_size.map(|h| grid_lines(h, radius, segments));
+
+	let mut mesh = MeshBuilder::new();
+	let mut normals = Vec::new();
+
+	let vec3 = |a: [f32; 3]| Vec3::new(a[0], a[1], a[2]);
+
+	for w in [0, 1, 2] {
+		for sign in [1.0, -1.0] {
+			let u = (w + 1) % 3;
+			let v = (w + 2) % 3;
+			let base = mesh.mesh.vertices.len();
+
+			for &cv in &lines[v] {
+				for &cu in &lines[u] {
+					let mut p = [0.0; 3];
+					p[u] = cu;
+					p[v] = cv;
+					p[w] = sign * half_size[w];
+
+					// Points on the box are pushed onto the rounded surface around the inner box.
+					let inner =
+						[0, 1, 2].map(|i| p[i].clamp(radius - half_size[i], half_size[i] - radius));
+					let offset = vec3(p) - vec3(inner);
+
+					let normal = if radius > 0.0 {
+						*offset.normalize()
+					} else {
+						let mut normal = [0.0; 3];
+						normal[w] = sign;
+						vec3(normal)
+					};
+
+					let vertex = mesh.add_vertex((vec3(inner) + normal * radius).into());
+					normals.push(normal);
+
+					// Faces on the negative side are seen mirrored, which is undone for their UVs.
+					let s = (cu / half_size[u] * sign + 1.0) / 2.0;
+					let t = (1.0 - cv / half_size[v]) / 2.0;
+					mesh.set_uv(vertex, 0, [s, t]);
+				}
+			}
+
+			let columns = lines[u].len();
+
+			for row in 0..lines[v].len() - 1 {
+				for column in 0..columns - 1 {
+					let i0 = base + row * columns + column;
+					let i1 = i0 + 1;
+					let i2 = i1 + columns;
+					let i3 = i0 + columns;
+
+					if sign > 0.0 {
+						mesh.add_quad(i0, i1, i2, i3);
+					} else {
+						mesh.add_quad(i0, i3, i2, i1);
+					}
+				}
+			}
+		}
+	}
+
+	mesh.build_with_normals(&normals)
+}
+
+/// Creates a box Mesh centered at the origin, matching [`math::primitives::Cuboid`].
+pub fn cuboid(half_size: Vec3) -> Mesh {
+	rounded_box(half_size, 0.0, 0)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::{check_primitive, measure};
+	use math::primitives::{Cuboid, Measure};
+
+	#[test]
+	fn matches_shape() {
+		let half_size = Vec3::new(1.0, 2.0, 3.0);
+		let shape = Cuboid { half_size };
+		let mesh = cuboid(half_size);
+		let (area, volume) = measure(&mesh);
+		check_primitive(&mesh);
+
+		assert_eq!(mesh.vertices.len(), 24);
+		assert!((area - shape.area()).abs() < 1e-4);
+		assert!((volume - shape.volume()).abs() < 1e-4);
+
+		// A fully rounded cube is a sphere.
+		let mesh = rounded_box(Vec3::new(1.0, 1.0, 1.0), 1.0, 8);
+		check_primitive(&mesh);
+		assert!(
+			mesh.vertices
+				.iter()
+				.all(|vertex| (vertex.p.length() - 1.0).abs() < 1e-5)
+		);
+	}
+}
@@ -0,0 +1,734 @@
+//! Mesh simplification by edge collapses, ordered by quadric error metrics (Garland and Heckbert).
+
+use crate::cleanup;
+use crate::half_edge::{HalfEdgeId, HalfEdgeMesh, TopologyError, VertexId};
+use crate::mesh::{Mesh, NormalMode, Submesh, Vertex, calculate_normals};
+
+use asset::{AssetId, LoadContext};
+use math::Vec3;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+	/// Fraction of the triangles to keep.
+	Ratio(f32),
+	/// Largest distance from the original surface, in mesh units. The distance of a vertex is the area weighted
+	/// root mean square distance to the planes of the original triangles it replaces.
+	Error(f32),
+}
+
+/// Weight of the planes that keep borders in place, relative to the triangle planes.
+const BORDER_WEIGHT: f64 = 10.0;
+
+/// Symmetric 4x4 matrix of the sum of squared distances to a set of planes.
+#[derive(Clone, Copy, Default)]
+struct Quadric {
+	/// Upper triangle, row by row: xx, xy, xz, xw, yy, yz, yw, zz, zw, ww.
+	m: [f64; 10],
+	/// Area of the triangles of the planes, to turn the error into a distance.
+	area: f64,
+}
+
+impl Quadric {
+	fn plane(normal: Vec3, point: Vec3, weight: f64) -> Self {
+		let [x, y, z] = [normal.x, normal.y, normal.z].map(f64::from);
+		let w = -f64::from(normal.dot(point));
+
+		let m = [
+			x * x,
+			x * y,
+			x * z,
+			x * w,
+			y * y,
+			y * z,
+			y * w,
+			z * z,
+			z * w,
+			w * w,
+		];
+
+		Self {
+			m: m.map(|value| value * weight),
+			area: 0.0,
+		}
+	}
+
+	fn add(self, other: Self) -> Self {
+		Self {
+			m: std::array::from_fn(|i| self.m[i] + other.m[i]),
+			area: self.area + other.area,
+		}
+	}
+
+	fn error(&self, p: Vec3) -> f64 {
+		let [x, y, z] = [p.x, p.y, p.z].map(f64::from);
+		let m = &self.m;
+
+		m[0] * x * x
+			+ 2.0 * m[1] * x * y
+			+ 2.0 * m[2] * x * z
+			+ 2.0 * m[3] * x
+			+ m[4] * y * y
+			+ 2.0 * m[5] * y * z
+			+ 2.0 * m[6] * y
+			+ m[7] * z * z
+			+ 2.0 * m[8] * z
+			+ m[9]
+	}
+
+	fn distance(&self, p: Vec3) -> f32 {
+		(self.error(p).max(0.0) / self.area.max(f64::MIN_POSITIVE)).sqrt() as f32
+	}
+
+	/// Position with the smallest error, or `None` if it is not unique, like for planes that are all parallel.
+	fn optimum(&self) -> Option<Vec3> {
+		let m = &self.m;
+		let a = [[m[0], m[1], m[2]], [m[1], m[4], m[5]], [m[2], m[5], m[7]]];
+		let b = [-m[3], -m[6], -m[8]];
+
+		let det3 = |a: [[f64; 3]; 3]| {
+			a[0][0] * (a[1][1] * a[2][2] - a[1][2] * a[2][1])
+				- a[0][1] * (a[1][0] * a[2][2] - a[1][2] * a[2][0])
+				+ a[0][2] * (a[1][0] * a[2][1] - a[1][1] * a[2][0])
+		};
+
+		let det = det3(a);
+		let scale = m[0] + m[4] + m[7];
+
+		if det.abs() <= 1e-9 * scale * scale * scale {
+			return None;
+		}
+
+		// Cramer's rule
+		let solve = |column: usize| {
+			let mut a = a;
+			for row in 0..3 {
+				a[row][column] = b[row];
+			}
+			(det3(a) / det) as f32
+		};
+
+		Some(Vec3::new(solve(0), solve(1), solve(2)))
+	}
+}
+
+/// Edge collapse in the queue, keeping the origin of `half_edge`.
+struct Collapse {
+	distance: f32,
+	half_edge: HalfEdgeId,
+	position: Vec3,
+	/// Versions of the vertices of both groups when the collapse was queued. Queued collapses of vertices that
+	/// changed since are skipped.
+	versions: Vec<u32>,
+}
+
+/// Edges that are collapsed together: an edge, and the edges between the copies of its vertices on the other sides
+/// of attribute seams.
+struct Plan {
+	/// Half-edges from the vertices that are kept to the vertices that are removed.
+	half_edges: Vec<HalfEdgeId>,
+	/// Whether the kept and the removed vertices must stay where they are.
+	fixed: [bool; 2],
+}
+
+impl PartialEq for Collapse {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Collapse {
+	/// Reversed, so the smallest distance is at the top of the heap.
+	fn cmp(&self, other: &Self) -> Ordering {
+		other.distance.total_cmp(&self.distance)
+	}
+}
+
+struct Simplifier {
+	mesh: HalfEdgeMesh,
+	quadrics: Vec<Quadric>,
+	/// Vertices that must not move, between submeshes.
+	locked: Vec<bool>,
+	/// Group of every vertex. Vertices at the same position are split at an attribute seam, and move together.
+	group: Vec<usize>,
+	groups: Vec<Vec<VertexId>>,
+	versions: Vec<u32>,
+	/// Last version given to a vertex. Versions are unique, so that collapses of a vertex that was merged into
+	/// another one are skipped too.
+	version: u32,
+	queue: BinaryHeap<Collapse>,
+}
+
+impl Simplifier {
+	/// Whether a vertex must stay where it is when collapsing the edge of `h`. Border vertices may only move along
+	/// the border.
+	fn is_fixed(&self, v: VertexId, h: HalfEdgeId) -> bool {
+		self.locked[v.0] || (self.mesh.is_boundary_vertex(v) && !self.mesh.is_boundary_edge(h))
+	}
+
+	/// Vertices of the groups of both vertices of the edge of `h`.
+	fn group_vertices(&self, h: HalfEdgeId) -> Vec<VertexId> {
+		let (a, b) = (self.mesh.origin(h), self.mesh.target(h));
+		let mut vertices = self.groups[self.group[a.0]].clone();
+		vertices.extend(&self.groups[self.group[b.0]]);
+		vertices
+	}
+
+	/// Edges to collapse together with the edge of `h`, or `None` if the seams the vertices are on can't stay
+	/// closed.
+	fn plan(&self, h: HalfEdgeId) -> Option<Plan> {
+		let mesh = &self.mesh;
+		let (a, b) = (mesh.origin(h), mesh.target(h));
+		let (kept, removed) = (self.group[a.0], self.group[b.0]);
+
+		if kept == removed {
+			return None;
+		}
+
+		let half_edges: Vec<HalfEdgeId> = self.groups[kept]
+			.iter()
+			.flat_map(|&v| mesh.vertex_half_edges(v))
+			.filter(|&e| self.group[mesh.target(e).0] == removed)
+			.collect();
+		let degree = |v: VertexId| {
+			half_edges
+				.iter()
+				.filter(|&&e| mesh.origin(e) == v || mesh.target(e) == v)
+				.count()
+		};
+
+		let (kept, removed) = (&self.groups[kept], &self.groups[removed]);
+
+		// The copies are connected in pairs along the seam, and collapse together.
+		if kept.len() == removed.len() && kept.iter().chain(removed).all(|&v| degree(v) == 1) {
+			// Pairs that have no vertices or neighbors in common can't affect each other's collapse.
+			let mut seen = HashSet::new();
+			for &e in &half_edges {
+				let (u, w) = (mesh.origin(e), mesh.target(e));
+				let neighborhood: HashSet<_> = [u, w]
+					.into_iter()
+					.chain(mesh.vertex_neighbors(u))
+					.chain(mesh.vertex_neighbors(w))
+					.collect();
+
+				if !neighborhood.into_iter().all(|v| seen.insert(v)) {
+					return None;
+				}
+			}
+
+			let fixed = [0, 1].map(|end| {
+				half_edges.iter().any(|&e| {
+					let v = [mesh.origin(e), mesh.target(e)][end];
+					self.is_fixed(v, e)
+				})
+			});
+
+			return Some(Plan { half_edges, fixed });
+		}
+
+		// An edge that leaves a seam. The vertex off the seam can move onto the one on it, but the seam can't follow.
+		if removed.len() == 1 && degree(b) == 1 {
+			Some(Plan {
+				half_edges: vec![h],
+				fixed: [true, self.is_fixed(b, h)],
+			})
+		} else if kept.len() == 1 && degree(a) == 1 {
+			Some(Plan {
+				half_edges: vec![h],
+				fixed: [self.is_fixed(a, h), true],
+			})
+		} else {
+			None
+		}
+	}
+
+	fn queue_collapse(&mut self, h: HalfEdgeId) {
+		let Some(plan) = self.plan(h) else {
+			return;
+		};
+
+		let mesh = &self.mesh;
+		let (a, b) = (mesh.origin(h), mesh.target(h));
+		let (pa, pb) = (mesh.position(a), mesh.position(b));
+		let vertices = self.group_vertices(h);
+		let quadric = vertices.iter().fold(Quadric::default(), |quadric, v| {
+			quadric.add(self.quadrics[v.0])
+		});
+
+		let (half_edge, position) = match plan.fixed {
+			[true, true] => return,
+			[true, false] => (h, pa),
+			[false, true] => (mesh.twin(h), pb),
+			[false, false] => {
+				let candidates = [quadric.optimum(), Some(pa), Some(pb), Some((pa + pb) * 0.5)];
+				let position = candidates
+					.into_iter()
+					.flatten()
+					.min_by(|p, q| quadric.error(*p).total_cmp(&quadric.error(*q)))
+					.unwrap();
+				(h, position)
+			}
+		};
+
+		self.queue.push(Collapse {
+			distance: quadric.distance(position),
+			half_edge,
+			position,
+			versions: vertices.iter().map(|v| self.versions[v.0]).collect(),
+		});
+	}
+
+	/// Whether moving the vertices of the edge of `h` to `position` turns a remaining triangle upside down.
+	fn flips_triangle(&self, h: HalfEdgeId, position: Vec3) -> bool {
+		let mesh = &self.mesh;
+		let (a, b) = (mesh.origin(h), mesh.target(h));
+
+		[a, b].into_iter().any(|v| {
+			mesh.vertex_faces(v).any(|f| {
+				let vertices: Vec<_> = mesh.face_vertices(f).collect();
+				if vertices.contains(&a) && vertices.contains(&b) {
+					return false;
+				}
+
+				let points = vertices.iter().map(|&u| mesh.position(u));
+				let moved = vertices.iter().map(|&u| match u == v {
+					true => position,
+					false => mesh.position(u),
+				});
+
+				let normal =
+					|points: Vec<Vec3>| (points[1] - points[0]).cross(points[2] - points[0]);
+				let (before, after) = (normal(points.collect()), normal(moved.collect()));
+
+				before.dot(after) <= 0.0
+			})
+		})
+	}
+}
+
+/// Simplifies a triangle mesh by collapsing edges until the target is reached.
+///
+/// Borders and attribute seams only move along themselves. The vertices on both sides of a seam move together,
+/// so that it stays closed, and vertices shared by submeshes don't move. Attributes are interpolated and smooth
+/// normals recalculated, so vertices that are only split for their normals are merged. Vertex groups and morph
+/// targets are not kept.
+///
+/// Triangles that would make an edge non-manifold are kept apart from the rest of the mesh and stay as they are.
+/// Fails only for indices of vertices that don't exist.
+pub fn simplify(mesh: &Mesh, target: Target) -> Result<Mesh, TopologyError> {
+	if let Some(i) = mesh.indices.iter().position(|&v| v >= mesh.vertices.len()) {
+		return Err(TopologyError::InvalidFace(i / 3));
+	}
+
+	let mut mesh = Mesh {
+		vertices: mesh
+			.vertices
+			.iter()
+			.map(|vertex| Vertex {
+				p: vertex.p,
+				n: Vec3::ZERO,
+			})
+			.collect(),
+		indices: mesh.indices.clone(),
+		attributes: mesh.attributes.clone(),
+		submeshes: mesh.submeshes.clone(),
+		..Default::default()
+	};
+	cleanup::weld_vertices(&mut mesh, 0.0);
+
+	// Triangles that use an edge in the same direction as one before them, like the third triangle at an edge of a
+	// scan, get their own vertices. Being copies, these only move together with the vertices they were split from,
+	// which the triangle's edges can't, so they stay in place.
+	let mut copies = Vec::new();
+	let mut edges = HashSet::new();
+
+	for triangle in mesh.indices.chunks_exact_mut(3) {
+		let directed = |t: &[usize]| [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])];
+		if triangle[0] == triangle[1] || triangle[1] == triangle[2] || triangle[2] == triangle[0] {
+			continue;
+		}
+
+		if directed(triangle).iter().any(|edge| edges.contains(edge)) {
+			for v in triangle.iter_mut() {
+				copies.push(*v);
+				*v = mesh.vertices.len() + copies.len() - 1;
+			}
+		}
+
+		edges.extend(directed(triangle));
+	}
+
+	if !copies.is_empty() {
+		let vertices: Vec<usize> = (0..mesh.vertices.len()).chain(copies).collect();
+		mesh.vertices = vertices.iter().map(|&v| mesh.vertices[v]).collect();
+		mesh.attributes = mesh.attributes.gather(&vertices);
+	}
+
+	let mesh = &mesh;
+
+	let triangles: Vec<(usize, [usize; 3])> = mesh
+		.indices
+		.chunks_exact(3)
+		.enumerate()
+		.map(|(i, t)| (i, [t[0], t[1], t[2]]))
+		.filter(|(_, t)| t[0] != t[1] && t[1] != t[2] && t[2] != t[0])
+		.collect();
+
+	let positions: Vec<Vec3> = mesh.vertices.iter().map(|vertex| vertex.p).collect();
+	let mut half_edge_mesh =
+		HalfEdgeMesh::from_polygons(&positions, triangles.iter().map(|(_, t)| t))?;
+	half_edge_mesh.attributes = mesh.attributes.clone();
+
+	let submesh_of = |triangle: usize| {
+		mesh.submeshes
+			.iter()
+			.position(|submesh| submesh.indices.contains(&(triangle * 3)))
+	};
+
+	let group = cleanup::weld_positions(&positions, 0.0);
+	let mut groups = vec![Vec::new(); positions.len()];
+	for (v, &representative) in group.iter().enumerate() {
+		groups[representative].push(VertexId(v));
+	}
+
+	let mut locked = vec![false; positions.len()];
+
+	let mut vertex_submesh = vec![None; positions.len()];
+	for &(triangle, vertices) in &triangles {
+		let submesh = submesh_of(triangle);
+		for v in vertices {
+			match vertex_submesh[v] {
+				None => vertex_submesh[v] = Some(submesh),
+				Some(other) if other != submesh => locked[v] = true,
+				_ => {}
+			}
+		}
+	}
+
+	let mut quadrics = vec![Quadric::default(); positions.len()];
+
+	for f in half_edge_mesh.face_ids() {
+		let vertices: Vec<_> = half_edge_mesh.face_vertices(f).collect();
+		let [p0, p1, p2] = [0, 1, 2].map(|i| positions[vertices[i].0]);
+
+		let cross = (p1 - p0).cross(p2 - p0);
+		let area = 0.5 * cross.length();
+		if area <= f32::MIN_POSITIVE {
+			continue;
+		}
+
+		let normal = cross * (0.5 / area);
+		let quadric = Quadric {
+			area: f64::from(area),
+			..Quadric::plane(normal, p0, f64::from(area))
+		};
+
+		for v in &vertices {
+			quadrics[v.0] = quadrics[v.0].add(quadric);
+		}
+
+		// Planes through border edges, perpendicular to the triangle.
+		for h in half_edge_mesh.face_half_edges(f) {
+			if !half_edge_mesh.is_boundary_edge(h) {
+				continue;
+			}
+
+			let (a, b) = (half_edge_mesh.origin(h), half_edge_mesh.target(h));
+			let edge = positions[b.0] - positions[a.0];
+			let border_normal = edge.cross(normal);
+
+			if border_normal.length_sq() > f32::MIN_POSITIVE {
+				let weight = BORDER_WEIGHT * f64::from(edge.length_sq());
+				let quadric = Quadric::plane(*border_normal.normalize(), positions[a.0], weight);
+				quadrics[a.0] = quadrics[a.0].add(quadric);
+				quadrics[b.0] = quadrics[b.0].add(quadric);
+			}
+		}
+	}
+
+	let mut simplifier = Simplifier {
+		mesh: half_edge_mesh,
+		quadrics,
+		locked,
+		group,
+		groups,
+		versions: vec![0; positions.len()],
+		version: 0,
+		queue: BinaryHeap::new(),
+	};
+
+	let edges: Vec<_> = simplifier.mesh.edge_ids().collect();
+	for h in edges {
+		simplifier.queue_collapse(h);
+	}
+
+	let mut face_count = simplifier.mesh.face_count();
+	let (target_faces, max_distance) = match target {
+		Target::Ratio(ratio) => ((face_count as f32 * ratio).ceil() as usize, f32::INFINITY),
+		Target::Error(distance) => (0, distance),
+	};
+
+	while face_count > target_faces
+		&& let Some(collapse) = simplifier.queue.pop()
+	{
+		if collapse.distance > max_distance {
+			break;
+		}
+
+		let h = collapse.half_edge;
+		if !simplifier.mesh.contains_half_edge(h) {
+			continue;
+		}
+
+		let (a, b) = (simplifier.mesh.origin(h), simplifier.mesh.target(h));
+		let versions: Vec<u32> = simplifier
+			.group_vertices(h)
+			.iter()
+			.map(|v| simplifier.versions[v.0])
+			.collect();
+
+		if versions != collapse.versions {
+			continue;
+		}
+
+		// The neighborhood may have changed since the collapse was queued.
+		let Some(plan) = simplifier.plan(h) else {
+			continue;
+		};
+
+		let moves = collapse.position != simplifier.mesh.position(a);
+		if plan.fixed[1]
+			|| (plan.fixed[0] && moves)
+			|| plan.half_edges.iter().any(|&e| {
+				simplifier.flips_triangle(e, collapse.position)
+					|| !simplifier.mesh.can_collapse_edge(e)
+			}) {
+			continue;
+		}
+
+		for &e in &plan.half_edges {
+			let mesh = &mut simplifier.mesh;
+			let (u, w) = (mesh.origin(e), mesh.target(e));
+			face_count -= [e, mesh.twin(e)]
+				.iter()
+				.filter(|&&e| mesh.face(e).is_some())
+				.count();
+
+			// The pairs don't affect each other, so every collapse succeeds.
+			mesh.collapse_edge(e, collapse.position);
+			simplifier.quadrics[u.0] = simplifier.quadrics[u.0].add(simplifier.quadrics[w.0]);
+		}
+
+		let removed = simplifier.group[b.0];
+		simplifier.groups[removed].clear();
+
+		let kept = simplifier.groups[simplifier.group[a.0]].clone();
+		for &v in &kept {
+			simplifier.version += 1;
+			simplifier.versions[v.0] = simplifier.version;
+		}
+
+		for v in kept {
+			let ring: Vec<_> = simplifier.mesh.vertex_half_edges(v).collect();
+			for h in ring {
+				simplifier.queue_collapse(h);
+			}
+		}
+	}
+
+	// Faces are never added, so the remaining triangles are in their original order.
+	let face_triangles: Vec<usize> = simplifier
+		.mesh
+		.face_ids()
+		.map(|f| triangles[f.0].0)
+		.collect();
+
+	let mut simplified = simplifier.mesh.to_mesh();
+
+	if !mesh.submeshes.is_empty() {
+		let indices = std::mem::take(&mut simplified.indices);
+
+		for (s, submesh) in mesh.submeshes.iter().enumerate() {
+			let start = simplified.indices.len();
+
+			for (i, &triangle) in face_triangles.iter().enumerate() {
+				if submesh_of(triangle) == Some(s) {
+					simplified
+						.indices
+						.extend_from_slice(&indices[i * 3..i * 3 + 3]);
+				}
+			}
+
+			simplified.submeshes.push(Submesh {
+				indices: start..simplified.indices.len(),
+				..submesh.clone()
+			});
+		}
+	}
+
+	// Smoothed across seams, which the normals of the half-edge mesh are not.
+	calculate_normals(&mut simplified, NormalMode::Smooth);
+
+	Ok(simplified)
+}
+
+/// Simplifies a mesh into `levels` levels of detail, each with `ratio` of the triangles of the one before.
+pub fn lod_chain(mesh: &Mesh, levels: usize, ratio: f32) -> Result<Vec<Mesh>, TopologyError> {
+	let mut chain: Vec<Mesh> = Vec::with_capacity(levels);
+
+	for _ in 0..levels {
+		let previous = chain.last().unwrap_or(mesh);
+		chain.push(simplify(previous, Target::Ratio(ratio))?);
+	}
+
+	Ok(chain)
+}
+
+/// Adds the levels of [`lod_chain`] as labeled assets `{label}.lod1` to `{label}.lod{levels}`.
+pub fn add_lod_chain(
+	ctx: &mut LoadContext,
+	label: &str,
+	mesh: &Mesh,
+	levels: usize,
+	ratio: f32,
+) -> Result<Vec<AssetId<Mesh>>, TopologyError> {
+	Ok(lod_chain(mesh, levels, ratio)?
+		.into_iter()
+		.enumerate()
+		.map(|(i, lod)| ctx.add_labeled_asset(&format!("{label}.lod{}", i + 1), lod))
+		.collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::primitives::{grid, sphere};
+
+	#[test]
+	fn sphere() {
+		let mesh = sphere::sphere(1.0, 32, 16);
+		let triangles = mesh.indices.len() / 3;
+
+		let simplified = simplify(&mesh, Target::Ratio(0.25)).unwrap();
+		let simplified_triangles = simplified.indices.len() / 3;
+
+		assert!(simplified_triangles <= triangles / 4 + 1);
+		assert!(simplified_triangles > triangles / 8);
+		assert!(
+			simplified
+				.vertices
+				.iter()
+				.all(|vertex| (vertex.p.length() - 1.0).abs() < 0.1)
+		);
+
+		let chain = lod_chain(&mesh, 3, 0.5).unwrap();
+		let counts: Vec<_> = chain.iter().map(|lod| lod.indices.len() / 3).collect();
+		assert!(counts.windows(2).all(|pair| pair[1] < pair[0]));
+		assert!(counts[0] < triangles);
+	}
+
+	#[test]
+	fn borders_and_seams() {
+		// Two grids side by side, with split vertices where they meet.
+		let mut mesh = grid::grid(1.0, 1.0, 9, 9);
+		let right = grid::grid(1.0, 1.0, 9, 9);
+		let offset = mesh.vertices.len();
+
+		for vertex in &mut mesh.vertices {
+			vertex.p.x -= 0.5;
+		}
+		mesh.vertices
+			.extend(right.vertices.iter().map(|vertex| Vertex {
+				p: vertex.p + Vec3::new(0.5, 0.0, 0.0),
+				n: vertex.n,
+			}));
+		mesh.indices
+			.extend(right.indices.iter().map(|i| i + offset));
+		mesh.attributes = Default::default();
+		mesh.attributes.insert(
+			"side",
+			(0..mesh.vertices.len())
+				.map(|v| (v >= offset) as u32 as f32)
+				.collect(),
+		);
+
+		let simplified = simplify(&mesh, Target::Error(1e-4)).unwrap();
+		assert!(simplified.indices.len() < mesh.indices.len() / 4);
+
+		// Flat, with the outline in place.
+		assert!(simplified.vertices.iter().all(|vertex| vertex.p.z == 0.0));
+
+		let corners = [(-1.0, -0.5), (1.0, -0.5), (1.0, 0.5), (-1.0, 0.5)];
+		for (x, y) in corners {
+			assert!(
+				simplified
+					.vertices
+					.iter()
+					.any(|vertex| vertex.p == Vec3::new(x, y, 0.0))
+			);
+		}
+
+		// The sides don't mix, since collapses never cross the seam.
+		let side = simplified.attributes.get::<f32>("side").unwrap();
+		assert!(side.iter().all(|&s| s == 0.0 || s == 1.0));
+
+		// The seam is simplified too, but stays closed, with a vertex on each side everywhere along it.
+		let seam: Vec<usize> = (0..simplified.vertices.len())
+			.filter(|&v| simplified.vertices[v].p.x == 0.0)
+			.collect();
+		assert!(seam.len() < 18);
+		for &v in &seam {
+			assert!(seam.iter().any(|&other| {
+				simplified.vertices[other].p == simplified.vertices[v].p && side[other] != side[v]
+			}));
+		}
+		// The only boundary is the outline, which is split where the seam meets it.
+		assert_eq!(crate::validate(&simplified).boundary_edges.len(), 6);
+	}
+
+	#[test]
+	fn non_manifold() {
+		// A fin on an edge in the middle of a grid, and a triangle that is there twice.
+		let mut mesh = grid::grid(1.0, 1.0, 9, 9);
+		let [a, b] = [mesh.indices[120], mesh.indices[121]];
+		let fin = mesh.vertices.len();
+		mesh.vertices.push(Vertex {
+			p: (mesh.vertices[a].p + mesh.vertices[b].p) * 0.5 + *Vec3::Z,
+			n: *Vec3::X,
+		});
+		mesh.indices.extend([a, b, fin]);
+		mesh.indices.extend_from_within(..3);
+		mesh.attributes.resize(mesh.vertices.len());
+
+		let simplified = simplify(&mesh, Target::Ratio(0.25)).unwrap();
+		assert!(simplified.indices.len() < mesh.indices.len() / 2);
+
+		// The fin is still there.
+		let fin = mesh.vertices[fin].p;
+		assert!(simplified.vertices.iter().any(|vertex| vertex.p == fin));
+
+		mesh.indices.push(mesh.vertices.len());
+		assert!(simplify(&mesh, Target::Ratio(0.25)).is_err());
+	}
+
+	#[test]
+	fn split_vertices() {
+		// Every triangle has its own vertices, which are merged since normals are recalculated.
+		let mut mesh = sphere::sphere(1.0, 32, 16);
+		calculate_normals(&mut mesh, NormalMode::Flat);
+		let triangles = mesh.indices.len() / 3;
+
+		let simplified = simplify(&mesh, Target::Ratio(0.25)).unwrap();
+		assert!(simplified.indices.len() / 3 <= triangles / 4 + 1);
+		assert!(crate::validate(&simplified).is_closed());
+	}
+}
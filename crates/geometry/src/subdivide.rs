@@ -0,0 +1,509 @@
+//! Catmull-Clark and Loop subdivision surfaces, with semi-sharp creases and corners.
+//!
+//! Every new vertex is a weighted sum of the vertices of the previous level, so vertex attributes are
+//! interpolated with the same weights as the positions.
+
+use crate::half_edge::{HalfEdgeId, HalfEdgeMesh, VertexId};
+
+use math::{PI, Vec3};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheme {
+	/// For quads and other polygons. Every level results in quads.
+	CatmullClark,
+	/// For triangles. Other polygons are split into triangles first.
+	Loop,
+}
+
+/// Sharpness of edges and vertices, with zero being smooth.
+///
+/// Every subdivision level lowers the sharpness by one, so a crease of sharpness 2 stays sharp for two levels
+/// before it is smoothed. Sharpness between 0 and 1 blends between the smooth and sharp rules. Boundary edges
+/// are always sharp.
+#[derive(Clone, Default)]
+pub struct Creases {
+	edges: HashMap<(VertexId, VertexId), f32>,
+	corners: HashMap<VertexId, f32>,
+}
+
+impl Creases {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.edges.is_empty() && self.corners.is_empty()
+	}
+
+	/// Sets the sharpness of the edge between two vertices, in either order.
+	pub fn set_edge(&mut self, a: VertexId, b: VertexId, sharpness: f32) {
+		match sharpness > 0.0 {
+			true => self.edges.insert(edge_key(a, b), sharpness),
+			false => self.edges.remove(&edge_key(a, b)),
+		};
+	}
+
+	pub fn edge(&self, a: VertexId, b: VertexId) -> f32 {
+		self.edges.get(&edge_key(a, b)).copied().unwrap_or(0.0)
+	}
+
+	pub fn set_corner(&mut self, v: VertexId, sharpness: f32) {
+		match sharpness > 0.0 {
+			true => self.corners.insert(v, sharpness),
+			false => self.corners.remove(&v),
+		};
+	}
+
+	pub fn corner(&self, v: VertexId) -> f32 {
+		self.corners.get(&v).copied().unwrap_or(0.0)
+	}
+}
+
+fn edge_key(a: VertexId, b: VertexId) -> (VertexId, VertexId) {
+	(a.min(b), a.max(b))
+}
+
+/// Weights of the vertices of the previous level.
+type Stencil = Vec<(usize, f32)>;
+
+fn scaled(stencil: &[(usize, f32)], scale: f32) -> impl Iterator<Item = (usize, f32)> + '_ {
+	stencil.iter().map(move |&(v, weight)| (v, weight * scale))
+}
+
+fn lerp(a: &[(usize, f32)], b: &[(usize, f32)], t: f32) -> Stencil {
+	scaled(a, 1.0 - t).chain(scaled(b, t)).collect()
+}
+
+/// Subdivides a mesh `levels` times.
+pub fn subdivide(
+	mesh: &HalfEdgeMesh,
+	scheme: Scheme,
+	creases: &Creases,
+	levels: u32,
+) -> HalfEdgeMesh {
+	let mut mesh = mesh.clone();
+	let mut creases = creases.clone();
+
+	for _ in 0..levels {
+		(mesh, creases) = match scheme {
+			Scheme::CatmullClark => catmull_clark(&mesh, &creases),
+			Scheme::Loop => loop_subdivide(&mesh, &creases),
+		};
+	}
+
+	mesh
+}
+
+/// One level of Catmull-Clark subdivision. Returns the refined mesh and its creases.
+///
+/// The refined mesh has the vertices of the original mesh first, in the same order, then a vertex for every face
+/// and then for every edge.
+pub fn catmull_clark(mesh: &HalfEdgeMesh, creases: &Creases) -> (HalfEdgeMesh, Creases) {
+	let mut refinement = Refinement::new(mesh);
+
+	let face_points: HashMap<_, _> = mesh
+		.face_ids()
+		.map(|f| {
+			let vertices: Vec<_> = mesh.face_vertices(f).collect();
+			let weight = 1.0 / vertices.len() as f32;
+			let stencil = vertices.iter().map(|v| (v.0, weight)).collect();
+			(f, refinement.add_vertex(stencil))
+		})
+		.collect();
+
+	let face_stencil = |refinement: &Refinement, h: HalfEdgeId| {
+		mesh.face(h)
+			.map(|f| refinement.stencils[face_points[&f]].clone())
+			.unwrap_or_default()
+	};
+
+	refinement.add_edge_points(mesh, creases, |refinement, h| {
+		let (a, b) = (mesh.origin(h), mesh.target(h));
+		let mut stencil = vec![(a.0, 0.25), (b.0, 0.25)];
+		stencil.extend(scaled(&face_stencil(refinement, h), 0.25));
+		stencil.extend(scaled(&face_stencil(refinement, mesh.twin(h)), 0.25));
+		stencil
+	});
+
+	refinement.set_vertex_points(
+		mesh,
+		creases,
+		|refinement, v| {
+			// (F + 2R + (n - 3) v) / n, with F the average of the face points and R of the edge midpoints.
+			let ring: Vec<_> = mesh.vertex_half_edges(v).collect();
+			let n = ring.len() as f32;
+
+			let mut stencil = vec![(v.0, (n - 3.0) / n + 1.0 / n)];
+			for &h in &ring {
+				stencil.push((mesh.target(h).0, 1.0 / (n * n)));
+				stencil.extend(scaled(&face_stencil(refinement, h), 1.0 / (n * n)));
+			}
+			stencil
+		},
+		|v, a, b| vec![(v.0, 0.75), (a.0, 0.125), (b.0, 0.125)],
+	);
+
+	for f in mesh.face_ids() {
+		for h in mesh.face_half_edges(f) {
+			refinement.faces.push(vec![
+				refinement.vertex_points[&mesh.origin(h)],
+				refinement.edge_points[&h],
+				face_points[&f],
+				refinement.edge_points[&mesh.prev(h)],
+			]);
+		}
+	}
+
+	refinement.finish(mesh)
+}
+
+/// One level of Loop subdivision. Returns the refined mesh and its creases.
+///
+/// The refined mesh has the vertices of the original mesh first, in the same order, then a vertex for every edge.
+pub fn loop_subdivide(mesh: &HalfEdgeMesh, creases: &Creases) -> (HalfEdgeMesh, Creases) {
+	let mut triangulated;
+	let mut mesh = mesh;
+
+	if mesh.face_ids().any(|f| mesh.face_valence(f) > 3) {
+		triangulated = mesh.clone();
+
+		for f in mesh.face_ids() {
+			let mut f = f;
+
+			while triangulated.face_valence(f) > 3 {
+				let half_edges: Vec<_> = triangulated.face_half_edges(f).collect();
+				let (a, b) = (
+					triangulated.origin(half_edges[0]),
+					triangulated.origin(half_edges[2]),
+				);
+
+				// The new half-edge is in the face with the remaining vertices.
+				let h = triangulated.split_face(f, a, b).unwrap();
+				f = triangulated.face(h).unwrap();
+			}
+		}
+
+		mesh = &triangulated;
+	}
+
+	let mut refinement = Refinement::new(mesh);
+
+	refinement.add_edge_points(mesh, creases, |_, h| {
+		let twin = mesh.twin(h);
+		let (a, b) = (mesh.origin(h), mesh.origin(twin));
+		let (c, d) = (mesh.origin(mesh.prev(h)), mesh.origin(mesh.prev(twin)));
+		vec![(a.0, 0.375), (b.0, 0.375), (c.0, 0.125), (d.0, 0.125)]
+	});
+
+	refinement.set_vertex_points(
+		mesh,
+		creases,
+		|_, v| {
+			let neighbors: Vec<_> = mesh.vertex_neighbors(v).collect();
+			let n = neighbors.len() as f32;
+
+			// Loop's original weights.
+			let c = 0.375 + 0.25 * (2.0 * PI / n).cos();
+			let beta = (0.625 - c * c) / n;
+
+			let mut stencil = vec![(v.0, 1.0 - n * beta)];
+			stencil.extend(neighbors.iter().map(|u| (u.0, beta)));
+			stencil
+		},
+		|v, a, b| vec![(v.0, 0.75), (a.0, 0.125), (b.0, 0.125)],
+	);
+
+	for f in mesh.face_ids() {
+		let half_edges: Vec<_> = mesh.face_half_edges(f).collect();
+		let corner = |i: usize| refinement.vertex_points[&mesh.origin(half_edges[i])];
+		let edge = |i: usize| refinement.edge_points[&half_edges[i]];
+
+		let faces = [
+			vec![corner(0), edge(0), edge(2)],
+			vec![edge(0), corner(1), edge(1)],
+			vec![edge(2), edge(1), corner(2)],
+			vec![edge(0), edge(1), edge(2)],
+		];
+		refinement.faces.extend(faces);
+	}
+
+	refinement.finish(mesh)
+}
+
+/// Vertices and faces of the next level, under construction.
+struct Refinement {
+	stencils: Vec<Stencil>,
+	faces: Vec<Vec<usize>>,
+	creases: Creases,
+	vertex_points: HashMap<VertexId, usize>,
+	/// New vertex of every half-edge, shared by twins.
+	edge_points: HashMap<HalfEdgeId, usize>,
+}
+
+impl Refinement {
+	/// Starts with a vertex for every vertex of `mesh`, which are set by [`Self::set_vertex_points`].
+	fn new(mesh: &HalfEdgeMesh) -> Self {
+		let mut refinement = Self {
+			stencils: Vec::new(),
+			faces: Vec::new(),
+			creases: Creases::new(),
+			vertex_points: HashMap::new(),
+			edge_points: HashMap::new(),
+		};
+
+		for v in mesh.vertex_ids() {
+			let index = refinement.add_vertex(vec![(v.0, 1.0)]);
+			refinement.vertex_points.insert(v, index);
+		}
+
+		refinement
+	}
+
+	fn add_vertex(&mut self, stencil: Stencil) -> usize {
+		self.stencils.push(stencil);
+		self.stencils.len() - 1
+	}
+
+	/// Sharpness of an edge, infinite on the boundary.
+	fn sharpness(mesh: &HalfEdgeMesh, creases: &Creases, h: HalfEdgeId) -> f32 {
+		match mesh.is_boundary_edge(h) {
+			true => f32::INFINITY,
+			false => creases.edge(mesh.origin(h), mesh.target(h)),
+		}
+	}
+
+	/// Adds a vertex for every edge, at the midpoint of sharp edges. Edges split into two edges that are one less
+	/// sharp.
+	fn add_edge_points(
+		&mut self,
+		mesh: &HalfEdgeMesh,
+		creases: &Creases,
+		smooth: impl Fn(&Self, HalfEdgeId) -> Stencil,
+	) {
+		for h in mesh.edge_ids() {
+			let (a, b) = (mesh.origin(h), mesh.target(h));
+			let sharpness = Self::sharpness(mesh, creases, h);
+			let sharp = [(a.0, 0.5), (b.0, 0.5)];
+
+			let stencil = match sharpness >= 1.0 {
+				true => sharp.to_vec(),
+				false => lerp(&smooth(self, h), &sharp, sharpness),
+			};
+
+			let index = self.add_vertex(stencil);
+			self.edge_points.insert(h, index);
+			self.edge_points.insert(mesh.twin(h), index);
+
+			if sharpness.is_finite() && sharpness > 1.0 {
+				for v in [a, b] {
+					let v = VertexId(self.vertex_points[&v]);
+					self.creases.set_edge(v, VertexId(index), sharpness - 1.0);
+				}
+			}
+		}
+	}
+
+	/// Sets the vertex points, choosing the rule by the sharp edges of a vertex. Vertices with one sharp edge are
+	/// smooth, two make a crease and more make a corner, as does the corner sharpness. Like USD's default boundary
+	/// interpolation, boundary vertices with a single face are corners as well.
+	fn set_vertex_points(
+		&mut self,
+		mesh: &HalfEdgeMesh,
+		creases: &Creases,
+		smooth: impl Fn(&Self, VertexId) -> Stencil,
+		crease: impl Fn(VertexId, VertexId, VertexId) -> Stencil,
+	) {
+		for v in mesh.vertex_ids() {
+			if mesh.valence(v) == 0 {
+				continue;
+			}
+
+			let sharp_edges: Vec<_> = mesh
+				.vertex_half_edges(v)
+				.map(|h| (mesh.target(h), Self::sharpness(mesh, creases, h)))
+				.filter(|&(_, sharpness)| sharpness > 0.0)
+				.collect();
+
+			let boundary_corner = mesh.is_boundary_vertex(v) && mesh.vertex_faces(v).count() == 1;
+			let corner = match boundary_corner {
+				true => f32::INFINITY,
+				false => creases.corner(v),
+			};
+
+			let sharp = if corner > 0.0 {
+				Some((vec![(v.0, 1.0)], corner))
+			} else {
+				let average =
+					sharp_edges.iter().map(|(_, s)| s).sum::<f32>() / sharp_edges.len() as f32;
+
+				match sharp_edges[..] {
+					[] | [_] => None,
+					[(a, _), (b, _)] => Some((crease(v, a, b), average)),
+					_ => Some((vec![(v.0, 1.0)], average)),
+				}
+			};
+
+			let index = self.vertex_points[&v];
+			self.stencils[index] = match sharp {
+				None => smooth(self, v),
+				Some((sharp, sharpness)) if sharpness >= 1.0 => sharp,
+				Some((sharp, sharpness)) => lerp(&smooth(self, v), &sharp, sharpness),
+			};
+
+			if corner.is_finite() && corner > 1.0 {
+				self.creases.set_corner(VertexId(index), corner - 1.0);
+			}
+		}
+	}
+
+	fn finish(self, mesh: &HalfEdgeMesh) -> (HalfEdgeMesh, Creases) {
+		let positions: Vec<Vec3> = self
+			.stencils
+			.iter()
+			.map(|stencil| {
+				stencil.iter().fold(Vec3::ZERO, |sum, &(v, weight)| {
+					sum + mesh.position(VertexId(v)) * weight
+				})
+			})
+			.collect();
+
+		let mut refined = HalfEdgeMesh::from_polygons(&positions, &self.faces)
+			.expect("Subdivision keeps edges manifold");
+
+		// Blend the attributes of the new vertices after the old ones, then keep only the new ones.
+		let mut attributes = mesh.attributes.clone();
+		let first = attributes
+			.iter()
+			.map(|(_, values)| values.len())
+			.max()
+			.unwrap_or(0);
+
+		for (i, stencil) in self.stencils.iter().enumerate() {
+			attributes.set_blend(first + i, stencil);
+		}
+
+		let new: Vec<_> = (first..first + self.stencils.len()).collect();
+		refined.attributes = attributes.gather(&new);
+
+		(refined, self.creases)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::primitives::{grid, platonic};
+	use crate::test_support::{quad_cube, weld_positions_ignoring_normals};
+
+	fn cube() -> HalfEdgeMesh {
+		let mut mesh = quad_cube();
+		let x = mesh.vertex_ids().map(|v| mesh.position(v).x).collect();
+		mesh.attributes.insert("x", x);
+		mesh
+	}
+
+	#[test]
+	fn catmull_clark_cube() {
+		let mesh = cube();
+		let (refined, _) = catmull_clark(&mesh, &Creases::new());
+
+		assert_eq!(
+			(
+				refined.vertex_count(),
+				refined.edge_count(),
+				refined.face_count()
+			),
+			(26, 48, 24)
+		);
+		assert!(refined.is_closed() && refined.is_manifold());
+		assert!(refined.face_ids().all(|f| refined.face_valence(f) == 4));
+
+		// Corners of the cube are pulled inwards, face centers stay.
+		let a = 1.0 / 3.0_f32.sqrt();
+		assert!((refined.position(VertexId(0)).length() - 5.0 / 9.0).abs() < 1e-5);
+		assert!(refined.vertex_ids().skip(8).take(6).all(|v| {
+			let p = refined.position(v);
+			(p.x.abs().max(p.y.abs()).max(p.z.abs()) - a).abs() < 1e-5
+		}));
+
+		// Attributes use the same weights as positions.
+		let refined = subdivide(&mesh, Scheme::CatmullClark, &Creases::new(), 2);
+		let x = refined.attributes.get::<f32>("x").unwrap();
+		assert!(
+			refined
+				.vertex_ids()
+				.all(|v| (x[v.0] - refined.position(v).x).abs() < 1e-5)
+		);
+	}
+
+	#[test]
+	fn creases() {
+		let mesh = cube();
+		let mut creases = Creases::new();
+		for h in mesh.edge_ids() {
+			creases.set_edge(mesh.origin(h), mesh.target(h), 10.0);
+		}
+
+		// With all edges sharp, the cube keeps its shape.
+		let a = 1.0 / 3.0_f32.sqrt();
+		let refined = subdivide(&mesh, Scheme::CatmullClark, &creases, 2);
+		assert!(refined.vertex_ids().all(|v| {
+			let p = refined.position(v);
+			(p.x.abs().max(p.y.abs()).max(p.z.abs()) - a).abs() < 1e-5
+		}));
+
+		// A corner stays where it is for as many levels as its sharpness.
+		let mut creases = Creases::new();
+		creases.set_corner(VertexId(0), 2.0);
+
+		let refined = subdivide(&mesh, Scheme::CatmullClark, &creases, 2);
+		assert!(refined.position(VertexId(0)) == mesh.position(VertexId(0)));
+		let refined = subdivide(&mesh, Scheme::CatmullClark, &creases, 3);
+		assert!(refined.position(VertexId(0)) != mesh.position(VertexId(0)));
+
+		// Boundaries stay on the plane and corners of the boundary stay in place.
+		let grid = HalfEdgeMesh::from_mesh(&grid::grid(2.0, 2.0, 3, 3)).unwrap();
+		for scheme in [Scheme::CatmullClark, Scheme::Loop] {
+			let refined = subdivide(&grid, scheme, &Creases::new(), 2);
+			assert!(refined.vertex_ids().all(|v| refined.position(v).z == 0.0));
+			assert_eq!(refined.boundary_loops().len(), 1);
+		}
+
+		let quads = [[0, 1, 4, 3], [1, 2, 5, 4], [3, 4, 7, 6], [4, 5, 8, 7]];
+		let positions: Vec<_> = grid.vertex_ids().map(|v| grid.position(v)).collect();
+		let grid = HalfEdgeMesh::from_polygons(&positions, quads).unwrap();
+		let refined = subdivide(&grid, Scheme::CatmullClark, &Creases::new(), 2);
+		assert!(refined.position(VertexId(0)) == grid.position(VertexId(0)));
+	}
+
+	#[test]
+	fn loop_icosahedron() {
+		let mesh =
+			HalfEdgeMesh::from_mesh(&weld_positions_ignoring_normals(platonic::icosahedron()))
+				.unwrap();
+		let (refined, _) = loop_subdivide(&mesh, &Creases::new());
+
+		assert_eq!(
+			(
+				refined.vertex_count(),
+				refined.edge_count(),
+				refined.face_count()
+			),
+			(42, 120, 80)
+		);
+		assert!(refined.is_closed() && refined.is_manifold());
+
+		// The surface shrinks towards the limit surface, which is inside the icosahedron.
+		let refined = subdivide(&mesh, Scheme::Loop, &Creases::new(), 3);
+		assert!(
+			refined
+				.vertex_ids()
+				.all(|v| refined.position(v).length() < 1.0)
+		);
+
+		// Quads are split into triangles first.
+		let (refined, _) = loop_subdivide(&cube(), &Creases::new());
+		assert_eq!(refined.face_count(), 12 * 4);
+	}
+}
@@ -0,0 +1,58 @@
+//! Helpers shared by the tests of several modules.
+
+use crate::cleanup::weld_vertices;
+use crate::half_edge::HalfEdgeMesh;
+use crate::mesh::Mesh;
+use crate::primitives::platonic;
+use math::Vec3;
+use math::primitives::{Measure, TriMesh};
+
+/// Welds vertices at the same position regardless of their normals, for tests that need a control cage from a
+/// mesh with flat normals, like the platonic solids.
+pub(crate) fn weld_positions_ignoring_normals(mut mesh: Mesh) -> Mesh {
+	for vertex in &mut mesh.vertices {
+		vertex.n = Vec3::ZERO;
+	}
+	weld_vertices(&mut mesh, 0.0);
+	mesh
+}
+
+/// The unit cube as six quads.
+pub(crate) fn quad_cube() -> HalfEdgeMesh {
+	let cube = weld_positions_ignoring_normals(platonic::hexahedron());
+	let positions: Vec<Vec3> = cube.vertices.iter().map(|vertex| vertex.p).collect();
+	let quads = cube
+		.indices
+		.chunks_exact(6)
+		.map(|quad| [quad[0], quad[1], quad[2], quad[5]]);
+
+	HalfEdgeMesh::from_polygons(&positions, quads).unwrap()
+}
+
+/// Surface area and volume of a closed mesh.
+pub(crate) fn measure(mesh: &Mesh) -> (f32, f32) {
+	let vertices: Vec<Vec3> = mesh.vertices.iter().map(|vertex| vertex.p).collect();
+	let shape = TriMesh {
+		vertices: &vertices,
+		indices: &mesh.indices,
+	};
+	(shape.area(), shape.volume())
+}
+
+/// Checks that every triangle of a primitive faces the same way as the normals of its vertices,
+/// and that every vertex has a UV.
+pub(crate) fn check_primitive(mesh: &Mesh) {
+	let uvs = mesh.attributes.uv(0).unwrap();
+	assert_eq!(uvs.len(), mesh.vertices.len());
+
+	for triangle in mesh.indices.chunks_exact(3) {
+		let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i]]);
+		let normal = (b.p - a.p).cross(c.p - a.p);
+		assert!(normal.length() > 0.0);
+		assert!([a, b, c].iter().all(|vertex| vertex.n.dot(normal) > 0.0));
+	}
+
+	for vertex in &mesh.vertices {
+		assert!((vertex.n.length() - 1.0).abs() < 1e-5);
+	}
+}
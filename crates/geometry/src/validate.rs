@@ -0,0 +1,252 @@
+//! Mesh validation: checks for data that can't be rendered and for topology problems, and basic statistics.
+//!
+//! Topology is checked on positions, so vertices that are only split for their normals or UVs are the same
+//! vertex. Triangles with invalid indices or positions are left out of the topology checks and statistics.
+
+use crate::bvh::Aabb;
+use crate::cleanup::weld_positions;
+use crate::mesh::Mesh;
+
+use math::Vec3;
+use math::primitives::{Measure, TriMesh};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Triangles whose area is less than this fraction of the square of their longest edge are degenerate.
+const DEGENERATE_TOLERANCE: f32 = 1e-6;
+
+/// Problems and statistics of a mesh, from [`validate`].
+#[derive(Clone, PartialEq)]
+pub struct Report {
+	/// The number of indices is not a multiple of 3. The trailing indices are ignored.
+	pub incomplete_triangle: bool,
+	/// Positions in the index buffer of indices that refer to vertices that don't exist.
+	pub out_of_range_indices: Vec<usize>,
+	/// Vertices with a NaN or infinite position.
+	pub non_finite_vertices: Vec<usize>,
+	/// Morph targets whose deltas don't fit the mesh, see [`MorphTarget::is_valid`](crate::morph::MorphTarget::is_valid).
+	pub invalid_morph_targets: Vec<usize>,
+	/// Triangles without area, including those that use the same position twice.
+	pub degenerate_triangles: Vec<usize>,
+	/// Edges, as pairs of vertices, that are shared by more than two triangles.
+	pub non_manifold_edges: Vec<(usize, usize)>,
+	/// Edges shared by two triangles that both go from the first vertex to the second, so one of them is flipped.
+	pub inconsistent_edges: Vec<(usize, usize)>,
+	/// Edges with a triangle on one side only.
+	pub boundary_edges: Vec<(usize, usize)>,
+	/// Bounds of the finite positions.
+	pub bounds: Aabb,
+	pub area: f32,
+	/// Enclosed volume, if the mesh is closed and consistently wound. Negative when it is inside out.
+	pub volume: Option<f32>,
+}
+
+impl Report {
+	/// Whether the mesh can be uploaded and ray traced. Topology problems don't prevent that.
+	pub fn is_valid(&self) -> bool {
+		!self.incomplete_triangle
+			&& self.out_of_range_indices.is_empty()
+			&& self.non_finite_vertices.is_empty()
+			&& self.invalid_morph_targets.is_empty()
+	}
+
+	/// Whether every edge is shared by exactly two consistently wound triangles.
+	pub fn is_closed(&self) -> bool {
+		self.volume.is_some()
+	}
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let problems = [
+			(self.out_of_range_indices.len(), "out of range indices"),
+			(self.non_finite_vertices.len(), "non-finite positions"),
+			(self.invalid_morph_targets.len(), "invalid morph targets"),
+			(self.degenerate_triangles.len(), "degenerate triangles"),
+			(self.non_manifold_edges.len(), "non-manifold edges"),
+			(self.inconsistent_edges.len(), "inconsistently wound edges"),
+			(self.boundary_edges.len(), "boundary edges"),
+		];
+
+		let mut problems: Vec<String> = problems
+			.into_iter()
+			.filter(|(count, _)| *count > 0)
+			.map(|(count, problem)| format!("{count} {problem}"))
+			.collect();
+
+		if self.incomplete_triangle {
+			problems.insert(0, "index count is not a multiple of 3".into());
+		}
+
+		match problems.is_empty() {
+			true => write!(f, "No problems"),
+			false => write!(f, "{}", problems.join(", ")),
+		}
+	}
+}
+
+/// Checks a mesh for invalid indices, positions and morph targets, degenerate triangles and non-manifold, inconsistently wound
+/// or boundary edges, and measures its bounds, area and volume.
+pub fn validate(mesh: &Mesh) -> Report {
+	let vertex_count = mesh.vertices.len();
+	let positions: Vec<Vec3> = mesh.vertices.iter().map(|vertex| vertex.p).collect();
+	let finite = |p: Vec3| p.x.is_finite() && p.y.is_finite() && p.z.is_finite();
+
+	let out_of_range_indices: Vec<usize> = mesh
+		.indices
+		.iter()
+		.enumerate()
+		.filter(|(_, index)| **index >= vertex_count)
+		.map(|(i, _)| i)
+		.collect();
+
+	let non_finite_vertices: Vec<usize> = (0..vertex_count)
+		.filter(|&v| !finite(positions[v]))
+		.collect();
+
+	let invalid_morph_targets: Vec<usize> = (0..mesh.morph_targets.len())
+		.filter(|&t| !mesh.morph_targets[t].is_valid(vertex_count))
+		.collect();
+
+	let remap = weld_positions(&positions, 0.0);
+	let mut degenerate_triangles = Vec::new();
+	// Triangles that can be measured, and for every edge between welded vertices, its triangles' directions.
+	let mut valid_indices = Vec::with_capacity(mesh.indices.len());
+	let mut edges: HashMap<(usize, usize), Vec<bool>> = HashMap::new();
+
+	for (t, triangle) in mesh.indices.chunks_exact(3).enumerate() {
+		let valid = triangle
+			.iter()
+			.all(|&index| index < vertex_count && finite(positions[index]));
+		if !valid {
+			continue;
+		}
+
+		let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i]]);
+		let longest = [b - a, c - b, a - c]
+			.iter()
+			.map(|edge| edge.length())
+			.fold(0.0, f32::max);
+		let welded = [0, 1, 2].map(|i| remap[triangle[i]]);
+
+		valid_indices.extend_from_slice(triangle);
+
+		if (b - a).cross(c - a).length() <= DEGENERATE_TOLERANCE * longest * longest
+			|| welded[0] == welded[1]
+			|| welded[1] == welded[2]
+			|| welded[2] == welded[0]
+		{
+			degenerate_triangles.push(t);
+			continue;
+		}
+
+		for i in 0..3 {
+			let (from, to) = (welded[i], welded[(i + 1) % 3]);
+			edges
+				.entry((from.min(to), from.max(to)))
+				.or_default()
+				.push(from < to);
+		}
+	}
+
+	let mut non_manifold_edges = Vec::new();
+	let mut inconsistent_edges = Vec::new();
+	let mut boundary_edges = Vec::new();
+
+	for (&edge, directions) in &edges {
+		match directions[..] {
+			[_] => boundary_edges.push(edge),
+			[first, second] if first == second => inconsistent_edges.push(edge),
+			[_, _] => {}
+			_ => non_manifold_edges.push(edge),
+		}
+	}
+
+	for edges in [
+		&mut non_manifold_edges,
+		&mut inconsistent_edges,
+		&mut boundary_edges,
+	] {
+		edges.sort_unstable();
+	}
+
+	let measured = TriMesh {
+		vertices: &positions,
+		indices: &valid_indices,
+	};
+	let closed = valid_indices.len() == mesh.indices.len() / 3 * 3
+		&& non_manifold_edges.is_empty()
+		&& inconsistent_edges.is_empty()
+		&& boundary_edges.is_empty();
+
+	Report {
+		incomplete_triangle: !mesh.indices.len().is_multiple_of(3),
+		out_of_range_indices,
+		bounds: Aabb::from_points(positions.iter().copied().filter(|&p| finite(p))),
+		area: measured.area(),
+		volume: closed.then(|| measured.volume()),
+		non_finite_vertices,
+		invalid_morph_targets,
+		degenerate_triangles,
+		non_manifold_edges,
+		inconsistent_edges,
+		boundary_edges,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::morph::MorphTarget;
+	use crate::primitives::{grid::grid, sphere::sphere};
+
+	#[test]
+	fn problems() {
+		// A UV sphere has seam vertices, which are welded for the topology checks.
+		let mesh = sphere(1.0, 32, 16);
+		let report = validate(&mesh);
+		assert!(report.is_valid() && report.is_closed());
+		assert!(report.degenerate_triangles.is_empty());
+		assert!((report.bounds.size() - Vec3::splat(2.0)).length() < 1e-2);
+		assert!((report.volume.unwrap() / (4.0 / 3.0 * std::f32::consts::PI) - 1.0).abs() < 0.05);
+
+		let mut mesh = grid(1.0, 1.0, 3, 3);
+		let report = validate(&mesh);
+		assert!(report.is_valid() && !report.is_closed());
+		assert_eq!(report.boundary_edges.len(), 8);
+		assert!((report.area - 1.0).abs() < 1e-5);
+
+		// Flipping a triangle makes the edges it shares inconsistent.
+		mesh.indices.swap(0, 1);
+		assert_eq!(validate(&mesh).inconsistent_edges.len(), 2);
+		mesh.indices.swap(0, 1);
+
+		// The edges of a duplicated triangle are used by three triangles, or twice in the same direction.
+		let first = mesh.indices[..3].to_vec();
+		mesh.indices.extend_from_slice(&first);
+		let report = validate(&mesh);
+		assert!(!report.non_manifold_edges.is_empty());
+		assert_eq!(
+			report.non_manifold_edges.len() + report.inconsistent_edges.len(),
+			3
+		);
+
+		mesh.indices.push(mesh.vertices.len());
+		mesh.vertices[0].p.x = f32::NAN;
+		let report = validate(&mesh);
+		assert!(!report.is_valid() && report.incomplete_triangle);
+		assert_eq!(report.out_of_range_indices, [mesh.indices.len() - 1]);
+		assert_eq!(report.non_finite_vertices, [0]);
+
+		mesh.morph_targets = vec![
+			MorphTarget::dense("dense", vec![Vec3::ZERO; mesh.vertices.len()], Vec::new()),
+			MorphTarget::sparse(
+				"sparse",
+				vec![mesh.vertices.len()],
+				vec![Vec3::ZERO],
+				Vec::new(),
+			),
+		];
+		assert_eq!(validate(&mesh).invalid_morph_targets, [1]);
+	}
+}
@@ -1353,22 +1353,43 @@ pub fn upload_buffer<D: DeviceImpl>(device: &mut D, buffer: &D::Buffer, data: &[
 	device.queue_wait();
 }
 
+/// Uploads `data` to all mip levels of a texture.
+///
+/// The mip levels are tightly packed in `data`, starting with the full resolution level 0.
 pub fn upload_texture<D: DeviceImpl>(
 	device: &mut D,
 	texture: &D::Texture,
 	desc: &TextureDesc,
 	data: &[u8],
 ) {
-	let size_bytes = desc.format.size(desc.width, desc.height, desc.depth) as usize;
-	assert_eq!(size_bytes, data.len());
+	let mip_size = |mip: u32| -> (u64, u64) {
+		(
+			at_mip_level(desc.width as u32, mip) as u64,
+			at_mip_level(desc.height as u32, mip) as u64,
+		)
+	};
+
+	let size_bytes: u64 = (0..desc.mip_levels)
+		.map(|mip| {
+			let (width, height) = mip_size(mip);
+			desc.format.size(width, height, desc.depth)
+		})
+		.sum();
+	assert_eq!(size_bytes as usize, data.len());
 
 	// TODO: Relax constraints below, make texture uploading more robust in general.
-	assert_eq!(desc.mip_levels, 1);
 	assert_eq!(desc.depth, 1);
 
-	let row_pitch = desc.format.row_pitch(desc.width);
-	let upload_pitch = align_pow2(row_pitch, 256); // TODO: hardcoded alignment
-	let upload_size = desc.height * upload_pitch;
+	// Offset of every mip level in the upload buffer, each level starts at an aligned offset.
+	let mut upload_offsets = Vec::with_capacity(desc.mip_levels as usize);
+	let mut upload_size = 0;
+
+	for mip in 0..desc.mip_levels {
+		let (width, height) = mip_size(mip);
+		let upload_pitch = align_pow2(desc.format.row_pitch(width), 256); // TODO: hardcoded alignment
+		upload_offsets.push(upload_size);
+		upload_size = align_pow2(upload_size + height * upload_pitch, 512); // TODO: hardcoded alignment
+	}
 
 	let cmd = device.create_cmd_list(1);
 
@@ -1382,11 +1403,21 @@ pub fn upload_texture<D: DeviceImpl>(
 
 	unsafe {
 		let mut src_ptr = data.as_ptr();
-		let mut dst_ptr = upload_buffer.cpu_ptr();
-		for _ in 0..desc.height {
-			std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, row_pitch as usize);
-			src_ptr = src_ptr.add(row_pitch as usize);
-			dst_ptr = dst_ptr.add(upload_pitch as usize);
+
+		for mip in 0..desc.mip_levels {
+			let (width, height) = mip_size(mip);
+			let row_pitch = desc.format.row_pitch(width);
+			let upload_pitch = align_pow2(row_pitch, 256);
+
+			let mut dst_ptr = upload_buffer
+				.cpu_ptr()
+				.add(upload_offsets[mip as usize] as usize);
+
+			for _ in 0..height {
+				std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, row_pitch as usize);
+				src_ptr = src_ptr.add(row_pitch as usize);
+				dst_ptr = dst_ptr.add(upload_pitch as usize);
+			}
 		}
 	}
 
@@ -1396,16 +1427,21 @@ pub fn upload_texture<D: DeviceImpl>(
 		new_layout: TextureLayout::CopyDst,
 	}]));
 
-	cmd.copy_buffer_to_texture(
-		&upload_buffer,
-		0,
-		upload_pitch as u32,
-		texture,
-		0,
-		0,
-		[0, 0, 0],
-		[desc.width as u32, desc.height as u32, 1],
-	);
+	for mip in 0..desc.mip_levels {
+		let (width, height) = mip_size(mip);
+		let upload_pitch = align_pow2(desc.format.row_pitch(width), 256);
+
+		cmd.copy_buffer_to_texture(
+			&upload_buffer,
+			upload_offsets[mip as usize],
+			upload_pitch as u32,
+			texture,
+			mip,
+			0,
+			[0, 0, 0],
+			[width as u32, height as u32, 1],
+		);
+	}
 
 	cmd.barriers(&Barriers::texture(&[TextureBarrier {
 		texture,
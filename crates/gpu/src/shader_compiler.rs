@@ -1,20 +1,40 @@
 use shader_slang::{self as slang, Downcast};
+use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+
+/// Reads a shader source by its path relative to the shader root, e.g. `pathtracer/kernel.slang`.
+type ReadSource = dyn Fn(&str) -> io::Result<Vec<u8>> + Send + Sync;
 
 pub struct ShaderCompiler {
 	global_session: slang::GlobalSession,
 	backend: super::Backend,
+	read: Box<ReadSource>,
+	/// Slang resolves imports on the native file system, so the sources are copied here before compiling.
+	staging_dir: PathBuf,
 }
 
 impl ShaderCompiler {
-	pub fn new(backend: super::Backend) -> Self {
+	/// Creates a compiler that reads shader sources and their imports with `read`,
+	/// e.g. from a virtual file system.
+	pub fn new(
+		backend: super::Backend,
+		read: impl Fn(&str) -> io::Result<Vec<u8>> + Send + Sync + 'static,
+	) -> Self {
 		Self {
 			global_session: slang::GlobalSession::new().unwrap(),
 			backend,
+			read: Box::new(read),
+			staging_dir: std::env::temp_dir().join(format!("shaders-{}", std::process::id())),
 		}
 	}
 
+	/// Compiles an entry point of `file`, relative to the shader root.
 	pub fn compile(&self, file: &str, entry_point_name: &str) -> Vec<u8> {
-		let search_path = std::ffi::CString::new("shaders").unwrap();
+		self.stage(file).unwrap();
+
+		let search_path = std::ffi::CString::new(self.staging_dir.to_str().unwrap()).unwrap();
+		let file = self.staging_dir.join(file);
 
 		let session_options = slang::CompilerOptions::default()
 			.optimization(slang::OptimizationLevel::High)
@@ -40,7 +60,7 @@ impl ShaderCompiler {
 
 		let session = self.global_session.create_session(&session_desc).unwrap();
 
-		let module = session.load_module(file).unwrap();
+		let module = session.load_module(file.to_str().unwrap()).unwrap();
 		let entry_point = module.find_entry_point_by_name(entry_point_name).unwrap();
 
 		let program = session
@@ -58,4 +78,52 @@ impl ShaderCompiler {
 			.as_slice()
 			.to_vec()
 	}
+	/// Copies `file` and everything it imports or includes to the staging directory.
+	fn stage(&self, file: &str) -> io::Result<()> {
+		let mut staged = HashSet::from([file.to_string()]);
+		let mut pending = vec![(file.to_string(), (self.read)(file)?)];
+
+		while let Some((file, source)) = pending.pop() {
+			let dir = file.rsplit_once('/').map_or("", |(dir, _)| dir);
+
+			for module in imports(&String::from_utf8_lossy(&source)) {
+				// Like Slang, look next to the importing file first, then from the root.
+				let path = format!("{}.slang", module.replace('.', "/").replace('_', "-"));
+				let candidates = match dir {
+					"" => vec![path],
+					dir => vec![format!("{dir}/{path}"), path],
+				};
+
+				if candidates.iter().any(|path| staged.contains(path)) {
+					continue;
+				}
+
+				// Missing imports are left for Slang to report.
+				if let Some((path, source)) = candidates
+					.into_iter()
+					.find_map(|path| (self.read)(&path).ok().map(|source| (path, source)))
+				{
+					staged.insert(path.clone());
+					pending.push((path, source));
+				}
+			}
+
+			let staged_path = self.staging_dir.join(&file);
+			std::fs::create_dir_all(staged_path.parent().unwrap())?;
+			std::fs::write(staged_path, source)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Names of the modules imported or included by a Slang source, e.g. `pathtracer.sampling`.
+fn imports(source: &str) -> impl Iterator<Item = &str> {
+	source.lines().filter_map(|line| {
+		let line = line.trim();
+		let module = line
+			.strip_prefix("import ")
+			.or_else(|| line.strip_prefix("__include "))?;
+		Some(module.trim().trim_end_matches(';').trim())
+	})
 }
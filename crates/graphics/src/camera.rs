@@ -120,7 +120,7 @@ fn focal_length_to_fov(focal_length: f32, sensor_size: f32) -> f32 {
 	2.0 * (0.5 * sensor_size / focal_length).atan()
 }
 
-fn fov_to_focal_length(fov: f32, sensor_size: f32) -> f32 {
+pub(crate) fn fov_to_focal_length(fov: f32, sensor_size: f32) -> f32 {
 	0.5 * sensor_size / (0.5 * fov).tan()
 }
 
@@ -27,8 +27,7 @@ impl ImportanceMap {
 	pub fn setup(device: &mut gpu::Device, shader_compiler: &gpu::ShaderCompiler) -> Self {
 		// Setup env map prepare shader.
 
-		let shader =
-			shader_compiler.compile("shaders/pathtracer/kernels/env-map-prepare.slang", "main");
+		let shader = shader_compiler.compile("pathtracer/kernels/env-map-prepare.slang", "main");
 
 		let descriptor_layout = gpu::DescriptorLayout {
 			push_constants: Some(gpu::PushConstantBinding {
@@ -135,6 +134,11 @@ impl ImportanceMap {
 		self.dirty = false;
 	}
 
+	/// Recomputes the importance map on the next update, e.g. after the env map changed.
+	pub fn invalidate(&mut self) {
+		self.dirty = true;
+	}
+
 	pub fn base_mip(&self) -> u32 {
 		gpu::max_mip_level(RESOLUTION as u32)
 	}
@@ -0,0 +1,264 @@
+use super::camera::{Camera, fov_to_focal_length};
+use super::scene::{Renderable, SphereLight, read_transform, write_transform};
+use asset::{Asset, AssetLoader, BlobReader, BlobWriter, LoadContext, LoadError};
+use ecs::{Name, World};
+use geometry::formats::gltf::{Gltf, GltfCamera, GltfLightKind, GltfSkin};
+use geometry::mesh::Mesh;
+use geometry::morph::MorphWeights;
+use math::{Mat4, PI, transform::Transform3};
+use std::io;
+
+/// Radius of the sphere lights that stand in for glTF point and spot lights, which have no size.
+const PUNCTUAL_LIGHT_RADIUS: f32 = 0.05;
+
+/// Entities imported from a glTF file, spawned into a [`World`] with [`spawn_gltf_scene`].
+///
+/// The node hierarchy is flattened into world transforms. Meshes are labeled assets of the file.
+pub struct GltfScene {
+	pub nodes: Vec<GltfSceneNode>,
+	/// Skins of the file, with joints referring to [`GltfSceneNode::index`].
+	pub skins: Vec<GltfSkin>,
+}
+
+impl Asset for GltfScene {}
+
+pub struct GltfSceneNode {
+	/// Path of the node in the hierarchy, like `/Armature/Hips`.
+	pub path: String,
+	/// Index of the node in the file.
+	pub index: usize,
+	pub transform: Transform3,
+	/// Skin that deforms the node's mesh.
+	pub skin: Option<usize>,
+	pub components: Vec<GltfComponent>,
+}
+
+pub enum GltfComponent {
+	/// A mesh with the default weights of its morph targets.
+	Renderable(Renderable, MorphWeights),
+	Camera(Camera),
+	SphereLight(SphereLight),
+}
+
+pub struct GltfLoader;
+
+impl AssetLoader for GltfLoader {
+	type Asset = GltfScene;
+
+	fn extensions(&self) -> &[&str] {
+		&["gltf", "glb"]
+	}
+
+	fn version(&self) -> u32 {
+		1
+	}
+
+	fn load(&self, ctx: &mut LoadContext) -> Result<GltfScene, LoadError> {
+		// External buffers are cooked into the meshes, so changing them must cook again.
+		for uri in Gltf::external_uris(&ctx.read()?)? {
+			ctx.cook_dependency(uri);
+		}
+
+		let bytes = ctx.cooked(|ctx| {
+			let gltf = Gltf::read(&ctx.read()?, |uri| ctx.read_relative(uri))?;
+
+			let mut writer = BlobWriter::new();
+			write_cooked_scene(&gltf, &mut writer)?;
+			Ok(writer.finish())
+		})?;
+
+		read_cooked_scene(&mut BlobReader::new(&bytes), ctx)
+	}
+}
+
+/// Writes the meshes, nodes and skins of the file in their cooked form.
+///
+/// Meshes are stored with their default morph weights. Nodes are stored as their path, index, world transform and
+/// skin, followed by their mesh, camera and light, each behind a flag that says whether the node has one.
+fn write_cooked_scene(gltf: &Gltf, writer: &mut BlobWriter) -> io::Result<()> {
+	writer.write(gltf.meshes.len() as u64);
+	for mesh in &gltf.meshes {
+		// Targets without a default weight are off.
+		let mut weights = mesh.weights.clone();
+		weights.resize(mesh.mesh.morph_targets.len(), 0.0);
+
+		writer.write_slice(&weights);
+		mesh.mesh.write_cooked(writer)?;
+	}
+
+	let world_transforms = gltf.world_transforms();
+	writer.write(world_transforms.len() as u64);
+
+	for (index, path, transform) in world_transforms {
+		let node = &gltf.nodes[index];
+
+		writer.write_str(&path);
+		writer.write(index as u64);
+		write_transform(writer, &transform);
+		write_index(writer, node.skin);
+		write_index(writer, node.mesh);
+
+		// Orthographic cameras are not supported by the path tracer.
+		let camera = match node.camera.map(|camera| &gltf.cameras[camera]) {
+			Some(GltfCamera::Perspective {
+				yfov, aspect_ratio, ..
+			}) => Some((*yfov, *aspect_ratio)),
+			_ => None,
+		};
+
+		writer.write(camera.is_some() as u8);
+		if let Some((yfov, aspect_ratio)) = camera {
+			let default = Camera::default();
+			writer.write(fov_to_focal_length(yfov, default.sensor_height));
+			writer.write(aspect_ratio.map_or(default.sensor_width, |aspect| {
+				default.sensor_height * aspect
+			}));
+		}
+
+		// Spot lights are approximated by point lights, directional lights are not supported.
+		let light = node.light.map(|light| &gltf.lights[light]).filter(|light| {
+			matches!(
+				light.kind,
+				GltfLightKind::Point | GltfLightKind::Spot { .. }
+			)
+		});
+
+		writer.write(light.is_some() as u8);
+		if let Some(light) = light {
+			// Radiance of a sphere with the light's intensity in candela.
+			let radiance = light.intensity / (PI * PUNCTUAL_LIGHT_RADIUS * PUNCTUAL_LIGHT_RADIUS);
+			writer.write(light.color.map(|c| c * radiance));
+		}
+	}
+
+	writer.write(gltf.skins.len() as u64);
+	for skin in &gltf.skins {
+		let joints: Vec<u64> = skin.joints.iter().map(|&joint| joint as u64).collect();
+		let matrices: Vec<[[f32; 4]; 4]> =
+			skin.inverse_bind_matrices.iter().map(|m| m.data).collect();
+
+		writer.write_str(&skin.name);
+		writer.write_slice(&joints);
+		writer.write_slice(&matrices);
+		write_index(writer, skin.skeleton);
+	}
+
+	Ok(())
+}
+
+/// Turns a cooked scene into a [`GltfScene`], adding its meshes as labeled assets.
+fn read_cooked_scene(
+	reader: &mut BlobReader,
+	ctx: &mut LoadContext,
+) -> Result<GltfScene, LoadError> {
+	let mesh_count = reader.read::<u64>()?;
+	let mut meshes = Vec::new();
+
+	for i in 0..mesh_count {
+		let weights = MorphWeights(reader.read_vec()?);
+		let mesh = Mesh::read_cooked(reader)?;
+		meshes.push((ctx.add_labeled_asset(&format!("mesh{i}"), mesh), weights));
+	}
+
+	let node_count = reader.read::<u64>()?;
+	let mut nodes = Vec::new();
+
+	for _ in 0..node_count {
+		let path = reader.read_string()?;
+		let index = reader.read::<u64>()? as usize;
+		let transform = read_transform(reader)?;
+		let skin = read_index(reader)?;
+		let mut components = Vec::new();
+
+		if let Some(mesh) = read_index(reader)? {
+			let (mesh, weights) = meshes
+				.get(mesh)
+				.ok_or("Invalid mesh index in cooked scene")?;
+			components.push(GltfComponent::Renderable(
+				Renderable { mesh: *mesh },
+				weights.clone(),
+			));
+		}
+
+		if reader.read::<u8>()? != 0 {
+			components.push(GltfComponent::Camera(Camera {
+				focal_length: reader.read()?,
+				sensor_width: reader.read()?,
+				..Camera::default()
+			}));
+		}
+
+		if reader.read::<u8>()? != 0 {
+			components.push(GltfComponent::SphereLight(SphereLight {
+				emission: reader.read()?,
+				radius: PUNCTUAL_LIGHT_RADIUS,
+			}));
+		}
+
+		nodes.push(GltfSceneNode {
+			path,
+			index,
+			transform,
+			skin,
+			components,
+		});
+	}
+
+	let skin_count = reader.read::<u64>()?;
+	let mut skins = Vec::new();
+
+	for _ in 0..skin_count {
+		skins.push(GltfSkin {
+			name: reader.read_string()?,
+			joints: reader
+				.read_vec::<u64>()?
+				.into_iter()
+				.map(|joint| joint as usize)
+				.collect(),
+			inverse_bind_matrices: reader
+				.read_vec::<[[f32; 4]; 4]>()?
+				.into_iter()
+				.map(|data| Mat4 { data })
+				.collect(),
+			skeleton: read_index(reader)?,
+		});
+	}
+
+	Ok(GltfScene { nodes, skins })
+}
+
+/// Writes an optional index as a flag that says whether it is there, followed by the index.
+fn write_index(writer: &mut BlobWriter, index: Option<usize>) {
+	writer.write(index.is_some() as u8);
+	writer.write(index.unwrap_or_default() as u64);
+}
+
+/// Reads an index written by [`write_index`].
+fn read_index(reader: &mut BlobReader) -> io::Result<Option<usize>> {
+	let is_some = reader.read::<u8>()? != 0;
+	let index = reader.read::<u64>()? as usize;
+	Ok(is_some.then_some(index))
+}
+
+/// Spawns an entity for every mesh, camera and light of the scene, and for every node without any of them, so
+/// that the whole hierarchy ends up in the world.
+pub fn spawn_gltf_scene(scene: &GltfScene, world: &mut World) {
+	for node in &scene.nodes {
+		if node.components.is_empty() {
+			world.spawn((Name::new(&node.path), node.transform));
+			continue;
+		}
+
+		for component in &node.components {
+			let name = Name::new(&node.path);
+
+			match component {
+				GltfComponent::Renderable(renderable, weights) => {
+					world.spawn((name, node.transform, *renderable, weights.clone()))
+				}
+				GltfComponent::Camera(camera) => world.spawn((name, node.transform, *camera)),
+				GltfComponent::SphereLight(light) => world.spawn((name, node.transform, *light)),
+			};
+		}
+	}
+}
@@ -1,6 +1,7 @@
 pub mod acceleration_structure;
 pub mod camera;
 pub mod env_map;
+pub mod gltf;
 pub mod mipgen;
 pub mod pathtracer;
 pub mod scene;
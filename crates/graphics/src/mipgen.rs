@@ -14,7 +14,7 @@ pub struct MipGen {
 
 impl MipGen {
 	pub fn setup(device: &mut gpu::Device, shader_compiler: &gpu::ShaderCompiler) -> Self {
-		let shader = shader_compiler.compile("shaders/mipgen.slang", "main");
+		let shader = shader_compiler.compile("mipgen.slang", "main");
 
 		let descriptor_layout = gpu::DescriptorLayout {
 			push_constants: Some(gpu::PushConstantBinding {
@@ -37,10 +37,9 @@ impl PathTracer {
 	pub fn new(device: &mut gpu::Device, shader_compiler: &gpu::ShaderCompiler) -> Self {
 		// Create the pipeline
 
-		let shader_raygen = shader_compiler.compile("shaders/pathtracer/kernel.slang", "raygen");
-		let shader_miss = shader_compiler.compile("shaders/pathtracer/kernel.slang", "miss");
-		let shader_closesthit =
-			shader_compiler.compile("shaders/pathtracer/kernel.slang", "closesthit");
+		let shader_raygen = shader_compiler.compile("pathtracer/kernel.slang", "raygen");
+		let shader_miss = shader_compiler.compile("pathtracer/kernel.slang", "miss");
+		let shader_closesthit = shader_compiler.compile("pathtracer/kernel.slang", "closesthit");
 
 		let libraries = vec![
 			gpu::ShaderLibrary {
@@ -267,7 +266,7 @@ impl Compositor {
 		device: &mut gpu::Device,
 		shader_compiler: &gpu::ShaderCompiler,
 	) -> Self {
-		let shader = shader_compiler.compile("shaders/compositor.slang", "main");
+		let shader = shader_compiler.compile("compositor.slang", "main");
 
 		let descriptor_layout = gpu::DescriptorLayout {
 			push_constants: Some(gpu::PushConstantBinding {
@@ -1,25 +1,48 @@
 use super::acceleration_structure::{Blas, Tlas};
 use super::camera::Camera;
 use super::env_map::ImportanceMap;
-use asset::{Asset, AssetId, AssetServer, UntypedAssetId};
+use asset::{
+	Asset, AssetId, AssetLoader, AssetRef, AssetServer, BlobReader, BlobWriter, LoadContext,
+	LoadError, UntypedAssetId,
+};
 use ecs::World;
+use geometry::cleanup::{IndexError, compact_indices};
 use geometry::mesh::Mesh;
+use geometry::validate;
 use gpu::{self, AccelerationStructureImpl, BufferImpl, CmdListImpl, DeviceImpl, TextureImpl};
-use math::{Mat3x4, Mat4, Vec3, transform::Transform3};
+use math::{Mat3x4, Mat4, Quaternion, Unit, Vec3, transform::Transform3};
 
+#[derive(Clone, Copy)]
 pub struct Renderable {
 	pub mesh: AssetId<Mesh>,
 }
 
+impl Renderable {
+	/// Persistent reference to the mesh, to save the component with. The handle is only valid for this run.
+	pub fn mesh_ref(&self, assets: &AssetServer) -> Option<AssetRef> {
+		assets.asset_ref(&self.mesh)
+	}
+
+	/// Component of a saved mesh reference, loading the mesh if needed.
+	pub fn from_mesh_ref(assets: &mut AssetServer, mesh: &AssetRef) -> Option<Self> {
+		Some(Self {
+			mesh: assets.load_ref(mesh)?,
+		})
+	}
+}
+
+#[derive(Clone, Copy)]
 pub struct DomeLight {
 	pub image: AssetId<Image>,
 }
 
+#[derive(Clone, Copy)]
 pub struct SphereLight {
 	pub emission: [f32; 3],
 	pub radius: f32,
 }
 
+#[derive(Clone, Copy)]
 pub struct RectLight {
 	pub emission: [f32; 3],
 	pub width: f32,
@@ -90,6 +113,8 @@ pub struct Image {
 	pub width: u32,
 	pub height: u32,
 	pub data: Vec<[f32; 4]>,
+	/// Mip levels 1 and up, each half the resolution of the previous one.
+	pub mips: Vec<Vec<[f32; 4]>>,
 }
 
 impl Image {
@@ -99,13 +124,106 @@ impl Image {
 			width,
 			height,
 			data,
+			mips: Vec::new(),
 		}
 	}
 
-	pub fn from_file(path: impl AsRef<std::path::Path>) -> Self
-	where
-		Self: Sized,
-	{
+	/// Number of mip levels, including the full resolution level 0.
+	pub fn mip_levels(&self) -> u32 {
+		1 + self.mips.len() as u32
+	}
+
+	/// Generates the full mip chain down to 1x1 with a box filter.
+	pub fn generate_mips(&mut self) {
+		self.mips.clear();
+
+		let (mut width, mut height) = (self.width as usize, self.height as usize);
+
+		while width > 1 || height > 1 {
+			let src = self.mips.last().unwrap_or(&self.data);
+			let (mip_width, mip_height) = ((width / 2).max(1), (height / 2).max(1));
+
+			let mut mip = Vec::with_capacity(mip_width * mip_height);
+
+			for y in 0..mip_height {
+				for x in 0..mip_width {
+					let mut sum = [0.0; 4];
+
+					// Odd sizes clamp to the last row and column.
+					for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+						let sx = (x * 2 + dx).min(width - 1);
+						let sy = (y * 2 + dy).min(height - 1);
+						let texel = src[sy * width + sx];
+
+						for (sum, value) in sum.iter_mut().zip(texel) {
+							*sum += value * 0.25;
+						}
+					}
+
+					mip.push(sum);
+				}
+			}
+
+			self.mips.push(mip);
+			(width, height) = (mip_width, mip_height);
+		}
+	}
+
+	/// Writes the image with all of its mip levels in its cooked form.
+	pub fn write_cooked(&self, writer: &mut BlobWriter) {
+		writer.write(self.width);
+		writer.write(self.height);
+		writer.write_slice(&self.data);
+
+		writer.write(self.mips.len() as u32);
+		for mip in &self.mips {
+			writer.write_slice(mip);
+		}
+	}
+
+	/// Reads an image written by [`Image::write_cooked`].
+	pub fn read_cooked(reader: &mut BlobReader) -> std::io::Result<Self> {
+		let width = reader.read()?;
+		let height = reader.read()?;
+		let data = reader.read_vec()?;
+
+		let mip_count = reader.read::<u32>()?;
+		let mips = (0..mip_count)
+			.map(|_| reader.read_vec())
+			.collect::<std::io::Result<_>>()?;
+
+		Ok(Self {
+			width,
+			height,
+			data,
+			mips,
+		})
+	}
+
+	/// Decodes the first RGBA layer of an OpenEXR file.
+	pub fn from_bytes(bytes: &[u8]) -> exr::error::Result<Self> {
+		exr::prelude::read()
+			.no_deep_data()
+			.largest_resolution_level()
+			.rgba_channels(
+				|resolution, _| {
+					Image::new(
+						resolution.width() as u32,
+						resolution.height() as u32,
+						vec![[0.0, 0.0, 0.0, 0.0]; resolution.width() * resolution.height()],
+					)
+				},
+				|image: &mut Image, position, (r, g, b, a): (f32, f32, f32, f32)| {
+					image.data[image.width as usize * position.y() + position.x()] = [r, g, b, a];
+				},
+			)
+			.first_valid_layer()
+			.all_attributes()
+			.from_buffered(std::io::Cursor::new(bytes))
+			.map(|image| image.layer_data.channel_data.pixels)
+	}
+
+	pub fn from_file(path: impl AsRef<std::path::Path>) -> exr::error::Result<Self> {
 		exr::prelude::read_first_rgba_layer_from_file(
 			path,
 			|resolution, _| {
@@ -119,15 +237,80 @@ impl Image {
 				image.data[image.width as usize * position.y() + position.x()] = [r, g, b, a];
 			},
 		)
-		.unwrap()
-		.layer_data
-		.channel_data
-		.pixels
+		.map(|image| image.layer_data.channel_data.pixels)
+	}
+
+	/// Magenta and black checkerboard with `cells` squares per side, for images that failed to load.
+	pub fn checker(size: u32, cells: u32) -> Self {
+		let cell_size = (size / cells).max(1);
+
+		let data = (0..size * size)
+			.map(|i| {
+				let (x, y) = (i % size / cell_size, i / size / cell_size);
+				if (x + y) % 2 == 0 {
+					[1.0, 0.0, 1.0, 1.0]
+				} else {
+					[0.0, 0.0, 0.0, 1.0]
+				}
+			})
+			.collect();
+
+		let mut image = Self::new(size, size, data);
+		image.generate_mips();
+		image
 	}
 }
 
 impl Asset for Image {}
 
+pub struct ImageLoader;
+
+impl AssetLoader for ImageLoader {
+	type Asset = Image;
+
+	fn extensions(&self) -> &[&str] {
+		&["exr"]
+	}
+
+	fn version(&self) -> u32 {
+		1
+	}
+
+	fn load(&self, ctx: &mut LoadContext) -> Result<Image, LoadError> {
+		let bytes = ctx.cooked(|ctx| {
+			let mut image = Image::from_bytes(&ctx.read()?)?;
+			image.generate_mips();
+
+			let mut writer = BlobWriter::new();
+			image.write_cooked(&mut writer);
+			Ok(writer.finish())
+		})?;
+
+		Ok(Image::read_cooked(&mut BlobReader::new(&bytes))?)
+	}
+}
+
+/// Writes a transform in its cooked form, for loaders that cook entire scenes.
+pub fn write_transform(writer: &mut BlobWriter, t: &Transform3) {
+	let (translation, rotation, scale) = (t.translation, *t.rotation, t.scale);
+	writer.write::<[f32; 3]>(translation.into());
+	writer.write([rotation.i, rotation.j, rotation.k, rotation.w]);
+	writer.write::<[f32; 3]>(scale.into());
+}
+
+/// Reads a transform written by [`write_transform`].
+pub fn read_transform(reader: &mut BlobReader) -> std::io::Result<Transform3> {
+	let [tx, ty, tz] = reader.read::<[f32; 3]>()?;
+	let [i, j, k, w] = reader.read::<[f32; 4]>()?;
+	let [sx, sy, sz] = reader.read::<[f32; 3]>()?;
+
+	Ok(Transform3 {
+		translation: Vec3::new(tx, ty, tz),
+		rotation: Unit::new_unchecked(Quaternion { i, j, k, w }),
+		scale: Vec3::new(sx, sy, sz),
+	})
+}
+
 struct GpuMeshData {
 	vertex_buffer: gpu::Buffer,
 	index_buffer: gpu::Buffer,
@@ -135,7 +318,9 @@ struct GpuMeshData {
 }
 
 impl GpuMeshData {
-	fn from_mesh(device: &mut gpu::Device, mesh: &Mesh) -> Self {
+	fn from_mesh(device: &mut gpu::Device, mesh: &Mesh) -> Result<Self, IndexError> {
+		let indices = compact_indices(mesh)?;
+
 		let vertices: Vec<Vertex> = mesh
 			.vertices
 			.iter()
@@ -144,7 +329,6 @@ impl GpuMeshData {
 				normal: v.n,
 			})
 			.collect();
-		let indices: Vec<u32> = mesh.indices.iter().map(|i| *i as u32).collect();
 
 		let vertex_buffer = device
 			.create_buffer(&gpu::BufferDesc {
@@ -174,11 +358,11 @@ impl GpuMeshData {
 			size_of::<Vertex>(),
 		);
 
-		Self {
+		Ok(Self {
 			vertex_buffer,
 			index_buffer,
 			blas,
-		}
+		})
 	}
 }
 
@@ -193,7 +377,8 @@ pub struct Scene {
 	pub importance_map: ImportanceMap,
 
 	texture_cache: std::collections::HashMap<UntypedAssetId, gpu::Texture>,
-	mesh_cache: std::collections::HashMap<UntypedAssetId, GpuMeshData>,
+	/// Meshes with invalid indices are cached as `None`, so the error is only reported once.
+	mesh_cache: std::collections::HashMap<UntypedAssetId, Option<GpuMeshData>>,
 }
 
 impl Scene {
@@ -239,6 +424,19 @@ impl Scene {
 		device: &mut gpu::Device,
 		cmd: &mut gpu::CmdList,
 	) {
+		// ASSETS
+
+		for event in assets.events::<Image>() {
+			if self.texture_cache.remove(&event.handle().id()).is_some() {
+				// The importance map is derived from the dome light texture.
+				self.importance_map.invalidate();
+			}
+		}
+
+		for event in assets.events::<Mesh>() {
+			self.mesh_cache.remove(&event.handle().id());
+		}
+
 		// CAMERA
 		// TODO: Handle properly when there's no camera in the scene.
 		if let Some((transform, camera)) = world.query::<(&Transform3, &Camera)>().iter().next() {
@@ -262,10 +460,10 @@ impl Scene {
 		let mut infinite_light_count = 0;
 
 		for light in &world.query::<&DomeLight>() {
-			let env_map_srv_index = self
-				.get_texture_from_cache(&light.image, device, assets)
-				.srv_index()
-				.unwrap();
+			let Some(env_map) = self.get_texture_from_cache(&light.image, device, assets) else {
+				continue;
+			};
+			let env_map_srv_index = env_map.srv_index().unwrap();
 
 			// TODO: Currently only supports single dome light
 			self.importance_map.update(cmd, env_map_srv_index);
@@ -334,7 +532,10 @@ impl Scene {
 		let mut instance_index = 0;
 
 		for (transform, renderable) in &world.query::<(&Transform3, &Renderable)>() {
-			let mesh_data = self.get_mesh_from_cache(&renderable.mesh, device, cmd, assets);
+			let Some(mesh_data) = self.get_mesh_from_cache(&renderable.mesh, device, cmd, assets)
+			else {
+				continue;
+			};
 
 			instance_data[instance_index] = Instance {
 				vertex_buffer_id: mesh_data.vertex_buffer.srv_index().unwrap(),
@@ -377,31 +578,41 @@ impl Scene {
 		asset: &AssetId<Image>,
 		device: &mut gpu::Device,
 		assets: &AssetServer,
-	) -> &gpu::Texture {
-		self.texture_cache.entry(asset.id()).or_insert_with(|| {
-			let image = assets.get(asset).unwrap();
+	) -> Option<&gpu::Texture> {
+		// Assets that failed to load are replaced with the placeholder, assets that are still loading are skipped
+		// until they are available.
+		let asset = &assets.or_placeholder(asset);
+		let image = assets.get(asset)?;
 
+		Some(self.texture_cache.entry(asset.id()).or_insert_with(|| {
 			let texture_desc = gpu::TextureDesc {
 				width: image.width as u64,
 				height: image.height as u64,
 				depth: 1,
 				array_size: 1,
-				mip_levels: 1,
+				mip_levels: image.mip_levels(),
 				format: gpu::Format::RGBA32Float,
 				usage: gpu::TextureUsage::SHADER_RESOURCE,
 				layout: gpu::TextureLayout::ShaderResource,
 			};
 
+			let data: Vec<[f32; 4]> = image
+				.data
+				.iter()
+				.chain(image.mips.iter().flatten())
+				.copied()
+				.collect();
+
 			let texture = device.create_texture(&texture_desc).unwrap();
 			gpu::upload_texture(
 				device,
 				&texture,
 				&texture_desc,
-				gpu::slice_as_u8_slice(&image.data),
+				gpu::slice_as_u8_slice(&data),
 			);
 
 			texture
-		})
+		}))
 	}
 
 	fn get_mesh_from_cache(
@@ -410,12 +621,33 @@ impl Scene {
 		device: &mut gpu::Device,
 		cmd: &gpu::CmdList,
 		assets: &AssetServer,
-	) -> &GpuMeshData {
-		self.mesh_cache.entry(asset.id()).or_insert_with(|| {
-			let mesh = assets.get(asset).unwrap();
-			let mut gpu_mesh_data = GpuMeshData::from_mesh(device, mesh);
-			gpu_mesh_data.blas.build(cmd);
-			gpu_mesh_data
-		})
+	) -> Option<&GpuMeshData> {
+		let asset = &assets.or_placeholder(asset);
+		let mesh = assets.get(asset)?;
+
+		self.mesh_cache
+			.entry(asset.id())
+			.or_insert_with(|| {
+				let name = assets.name(asset).unwrap_or("<unnamed>");
+
+				// Out of range indices and non-finite positions break the BLAS build.
+				let report = validate(mesh);
+				if !report.is_valid() {
+					log::error!("Can't upload mesh {name}: {report}");
+					return None;
+				}
+
+				match GpuMeshData::from_mesh(device, mesh) {
+					Ok(mut gpu_mesh_data) => {
+						gpu_mesh_data.blas.build(cmd);
+						Some(gpu_mesh_data)
+					}
+					Err(error) => {
+						log::error!("Can't upload mesh {name}: {error}");
+						None
+					}
+				}
+			})
+			.as_ref()
 	}
 }
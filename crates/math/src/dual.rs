@@ -2,6 +2,7 @@ use super::Number;
 use std::ops::{Add, Div, Mul, Sub};
 
 #[repr(C)]
+#[derive(Clone, Copy, PartialEq)]
 pub struct Dual<T> {
 	pub r: T,
 	pub d: T,
@@ -16,11 +16,12 @@ use num::{Number, NumberOps, cast};
 pub use complex::{Complex, UnitComplex};
 pub use dual::Dual;
 pub use matrix::{Matrix, Matrix2, Matrix3, Matrix4, Vector, Vector2, Vector3, Vector4};
-pub use quaternion::{Quaternion, UnitQuaternion};
+pub use quaternion::{DualQuaternion, Quaternion, UnitQuaternion};
 pub use unit::Unit;
 
 pub type Vec2 = Vector2<f32>;
 pub type Vec3 = Vector3<f32>;
+pub type Vec4 = Vector4<f32>;
 
 pub type Mat3 = Matrix3<f32>;
 pub type Mat4 = Matrix4<f32>;
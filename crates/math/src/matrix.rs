@@ -648,8 +648,20 @@ impl<T: Copy> From<Matrix4<T>> for Matrix<T, 3, 4> {
 	}
 }
 
+impl<T: Copy> From<Vector2<T>> for [T; 2] {
+	fn from(v: Vector2<T>) -> Self {
+		[v.x, v.y]
+	}
+}
+
 impl<T: Copy> From<Vector3<T>> for [T; 3] {
 	fn from(v: Vector3<T>) -> Self {
 		[v.x, v.y, v.z]
 	}
 }
+
+impl<T: Copy> From<Vector4<T>> for [T; 4] {
+	fn from(v: Vector4<T>) -> Self {
+		[v.x, v.y, v.z, v.w]
+	}
+}
@@ -1,7 +1,9 @@
 mod measure;
 mod sample;
+mod sdf;
 mod shapes;
 
 pub use measure::*;
 pub use sample::*;
+pub use sdf::*;
 pub use shapes::*;
@@ -0,0 +1,73 @@
+use super::shapes::*;
+use crate::{Vec2, Vec3};
+
+pub trait Sdf {
+	/// Get the signed distance from a point to the boundary of the shape, which is negative inside of it.
+	fn distance(&self, p: Vec3) -> f32;
+}
+
+impl Sdf for Sphere {
+	fn distance(&self, p: Vec3) -> f32 {
+		p.length() - self.radius
+	}
+}
+
+impl Sdf for Cylinder {
+	fn distance(&self, p: Vec3) -> f32 {
+		let d = Vec2::new(
+			Vec2::new(p.x, p.y).length() - self.radius,
+			p.z.abs() - self.half_height,
+		);
+		d.x.max(d.y).min(0.0) + Vec2::new(d.x.max(0.0), d.y.max(0.0)).length()
+	}
+}
+
+impl Sdf for Capsule {
+	fn distance(&self, p: Vec3) -> f32 {
+		let z = p.z.clamp(-self.half_length, self.half_length);
+		Vec3::new(p.x, p.y, p.z - z).length() - self.radius
+	}
+}
+
+impl Sdf for Cuboid {
+	fn distance(&self, p: Vec3) -> f32 {
+		let d = Vec3::new(
+			p.x.abs() - self.half_size.x,
+			p.y.abs() - self.half_size.y,
+			p.z.abs() - self.half_size.z,
+		);
+		let outside = Vec3::new(d.x.max(0.0), d.y.max(0.0), d.z.max(0.0));
+		d.x.max(d.y).max(d.z).min(0.0) + outside.length()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn distances() {
+		let capsule = Capsule {
+			radius: 1.0,
+			half_length: 2.0,
+		};
+
+		assert_eq!(capsule.distance(Vec3::new(0.0, 0.0, 4.0)), 1.0);
+		assert_eq!(capsule.distance(Vec3::new(3.0, 0.0, 1.0)), 2.0);
+
+		let cylinder = Cylinder {
+			radius: 1.0,
+			half_height: 1.0,
+		};
+
+		assert_eq!(cylinder.distance(Vec3::new(0.0, 0.0, 0.5)), -0.5);
+		assert_eq!(cylinder.distance(Vec3::new(4.0, 0.0, 5.0)), 5.0);
+
+		let cuboid = Cuboid {
+			half_size: Vec3::new(1.0, 2.0, 3.0),
+		};
+
+		assert_eq!(cuboid.distance(Vec3::ZERO), -1.0);
+		assert_eq!(cuboid.distance(Vec3::new(4.0, 6.0, 0.0)), 5.0);
+	}
+}
@@ -190,6 +190,49 @@ impl<T: Float + FloatOps<T>> UnitQuaternion<T> {
 		})
 	}
 
+	/// Creates a unit quaternion from a 3x3 rotation matrix. The matrix must be orthonormal.
+	pub fn from_matrix3(m: Matrix3<T>) -> Self {
+		let quarter = T::ONE / (T::TWO + T::TWO);
+		let trace = m[(0, 0)] + m[(1, 1)] + m[(2, 2)];
+
+		// Computed from the largest of w, i, j and k for numerical stability.
+		let q = if trace > T::ZERO {
+			let s = (trace + T::ONE).sqrt() * T::TWO;
+			Quaternion {
+				i: (m[(2, 1)] - m[(1, 2)]) / s,
+				j: (m[(0, 2)] - m[(2, 0)]) / s,
+				k: (m[(1, 0)] - m[(0, 1)]) / s,
+				w: quarter * s,
+			}
+		} else if m[(0, 0)] > m[(1, 1)] && m[(0, 0)] > m[(2, 2)] {
+			let s = (T::ONE + m[(0, 0)] - m[(1, 1)] - m[(2, 2)]).sqrt() * T::TWO;
+			Quaternion {
+				i: quarter * s,
+				j: (m[(0, 1)] + m[(1, 0)]) / s,
+				k: (m[(0, 2)] + m[(2, 0)]) / s,
+				w: (m[(2, 1)] - m[(1, 2)]) / s,
+			}
+		} else if m[(1, 1)] > m[(2, 2)] {
+			let s = (T::ONE + m[(1, 1)] - m[(0, 0)] - m[(2, 2)]).sqrt() * T::TWO;
+			Quaternion {
+				i: (m[(0, 1)] + m[(1, 0)]) / s,
+				j: quarter * s,
+				k: (m[(1, 2)] + m[(2, 1)]) / s,
+				w: (m[(0, 2)] - m[(2, 0)]) / s,
+			}
+		} else {
+			let s = (T::ONE + m[(2, 2)] - m[(0, 0)] - m[(1, 1)]).sqrt() * T::TWO;
+			Quaternion {
+				i: (m[(0, 2)] + m[(2, 0)]) / s,
+				j: (m[(1, 2)] + m[(2, 1)]) / s,
+				k: quarter * s,
+				w: (m[(1, 0)] - m[(0, 1)]) / s,
+			}
+		};
+
+		q.normalize()
+	}
+
 	/// Converts this unit quaternion to a 3x3 rotation matrix.
 	pub fn to_matrix3(&self) -> Matrix3<T> {
 		let x2 = self.i + self.i;
@@ -301,6 +344,38 @@ impl<T: Number> DualQuaternion<T> {
 	pub const IDENTITY: Self = Self::new(Quaternion::IDENTITY, Quaternion::ZERO);
 }
 
+impl<T: Float + FloatOps<T>> DualQuaternion<T> {
+	/// Creates a unit dual quaternion that rotates and then translates.
+	pub fn from_rotation_translation(rotation: UnitQuaternion<T>, translation: Vector3<T>) -> Self {
+		let half = T::ONE / T::TWO;
+		Self::new(
+			*rotation,
+			Quaternion::from_imag(translation) * *rotation * half,
+		)
+	}
+
+	/// Scales the real part to unit length, making this a rigid transform.
+	pub fn normalize(&self) -> Self {
+		let length = self.r.length();
+		Self::new(self.r / length, self.d / length)
+	}
+
+	/// The rotational part of a unit dual quaternion.
+	pub fn rotation(&self) -> UnitQuaternion<T> {
+		UnitQuaternion::new_unchecked(self.r)
+	}
+
+	/// The translational part of a unit dual quaternion.
+	pub fn translation(&self) -> Vector3<T> {
+		(self.d * self.r.conj()).imag() * T::TWO
+	}
+
+	/// Rotates and translates a point by a unit dual quaternion.
+	pub fn transform_point(&self, point: Vector3<T>) -> Vector3<T> {
+		self.rotation() * point + self.translation()
+	}
+}
+
 impl<T: Neg<Output = T>> Neg for Quaternion<T> {
 	type Output = Quaternion<T>;
 
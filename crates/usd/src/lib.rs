@@ -1,48 +1,312 @@
-use asset::AssetServer;
+use asset::{Asset, AssetLoader, BlobReader, BlobWriter, LoadContext, LoadError};
 use ecs::{Name, World};
-use geometry::mesh::{Mesh, Vertex, VertexGroups};
-use graphics::scene::{DomeLight, Image, RectLight, Renderable, SphereLight};
+use geometry::cleanup;
+use geometry::half_edge::{HalfEdgeMesh, VertexId};
+use geometry::mesh::{
+	AUTO_SMOOTH_ANGLE, Mesh, NormalMode, Vertex, VertexAttributes, VertexGroups, calculate_normals,
+};
+use geometry::morph::{MorphTarget, MorphWeights};
+use geometry::subdivide::{Creases, Scheme, subdivide};
+use graphics::scene::{
+	DomeLight, Image, RectLight, Renderable, SphereLight, read_transform, write_transform,
+};
 use math::{Quaternion, Unit, UnitQuaternion, Vec3, transform::Transform3};
 
-use openusd_rs::{gf, sdf, usd, usd_geom, usd_lux};
+use openusd_rs::{gf, sdf, tf, usd, usd_geom, usd_lux, usd_skel};
+use std::path::{Path, PathBuf};
 
-fn convert_mesh(mesh: &usd_geom::Mesh) -> Mesh {
+/// Subdivision levels of meshes with a subdivision scheme. Every level multiplies the face count by four.
+const SUBDIVISION_LEVELS: u32 = 2;
+
+/// USD sharpness of infinitely sharp creases and corners.
+const INFINITE_SHARPNESS: f32 = 10.0;
+
+/// Converts a mesh with its blend shapes. Subdivided meshes don't keep their blend shapes.
+fn convert_mesh(
+	mesh: &usd_geom::Mesh,
+	blend_shapes: &[(String, usd_skel::BlendShape)],
+) -> Result<Mesh, LoadError> {
 	let points = mesh.points_attr().get::<Vec<gf::Vec3f>>();
-	let normals = mesh.normals_attr().get::<Vec<gf::Vec3f>>();
+	let positions: Vec<Vec3> = points.iter().map(|&p| from_usd_vec3f(p)).collect();
+
+	let scheme = match mesh.subdivision_scheme_attr().get::<tf::Token>().as_str() {
+		"catmullClark" => Some(Scheme::CatmullClark),
+		"loop" => Some(Scheme::Loop),
+		_ => None,
+	};
+
+	// Exporters often duplicate points along UV seams and between faces, which USD keeps as face-varying data.
+	let welded = cleanup::weld_positions(&positions, 0.0);
 
-	let vertices = points
+	if let Some(scheme) = scheme
+		&& let Some(mesh) = subdivide_mesh(mesh, &positions, &welded, scheme)
+	{
+		return Ok(mesh);
+	}
+
+	// Normals are calculated below, so meshes without authored normals are fine.
+	let vertices = positions
 		.iter()
-		.zip(normals.iter())
-		.map(|(p, n)| Vertex {
-			p: Vec3::new(p.x, p.y, p.z),
-			n: Vec3::new(n.x, n.y, n.z),
-		})
-		.collect();
+		.map(|&p| Vertex { p, n: Vec3::ZERO })
+		.collect::<Vec<_>>();
 
 	let triangles = usd_geom::triangulate(mesh);
 
+	if let Some(index) = triangles.iter().find(|&&i| i as usize >= vertices.len()) {
+		return Err(format!(
+			"Vertex index {index} is out of range for {} points",
+			vertices.len()
+		)
+		.into());
+	}
+
 	let indices = triangles.iter().map(|i| *i as usize).collect();
 
 	let mut mesh = Mesh {
 		vertices,
 		indices,
 		vertex_groups: VertexGroups::default(),
+		attributes: VertexAttributes::default(),
+		submeshes: Vec::new(),
+		morph_targets: blend_shapes
+			.iter()
+			.filter_map(|(name, shape)| convert_blend_shape(name, shape, &welded))
+			.collect(),
 	};
 
+	// Without authored normals, vertices only differ by their position.
+	for index in &mut mesh.indices {
+		*index = welded[*index];
+	}
+	cleanup::remove_unused_vertices(&mut mesh);
+	cleanup::remove_degenerate_triangles(&mut mesh);
+
 	// TODO: Use normals from USD mesh.
-	geometry::mesh::calculate_vert_normals(&mut mesh);
+	calculate_normals(&mut mesh, NormalMode::AutoSmooth(AUTO_SMOOTH_ANGLE));
+
+	Ok(mesh)
+}
+
+/// Converts the offsets of a blend shape to a target of the welded points. Returns `None` for blend shapes with
+/// invalid point indices.
+fn convert_blend_shape(
+	name: &str,
+	shape: &usd_skel::BlendShape,
+	welded: &[usize],
+) -> Option<MorphTarget> {
+	let offsets = shape.offsets_attr().get::<Vec<gf::Vec3f>>();
+	let normal_offsets = shape.normal_offsets_attr().get::<Vec<gf::Vec3f>>();
+	let point_indices = shape.point_indices_attr().get::<Vec<i32>>();
+
+	// Without point indices, there is an offset for every point.
+	let points: Vec<usize> = match point_indices.is_empty() {
+		true => (0..offsets.len()).collect(),
+		false => point_indices.iter().map(|&i| i as usize).collect(),
+	};
+
+	if points.len() != offsets.len() || points.iter().any(|&point| point >= welded.len()) {
+		return None;
+	}
+
+	let has_normals = normal_offsets.len() == offsets.len();
+	let mut target = MorphTarget::sparse(name, Vec::new(), Vec::new(), Vec::new());
+	let mut seen = vec![false; welded.len()];
+
+	// Duplicated points share the offset of the first one.
+	for (i, &point) in points.iter().enumerate() {
+		let vertex = welded[point];
+		if std::mem::replace(&mut seen[vertex], true) {
+			continue;
+		}
+
+		target.vertices.as_mut()?.push(vertex);
+		target.positions.push(from_usd_vec3f(offsets[i]));
+
+		if has_normals {
+			target.normals.push(from_usd_vec3f(normal_offsets[i]));
+		}
+	}
+
+	Some(target)
+}
+
+/// Subdivides the control cage of a mesh, with its creases and corners. Returns `None` for cages that can't be
+/// subdivided, like non-manifold ones, which are triangulated instead. Duplicated points are replaced by their
+/// `welded` representative, so faces that share them are connected.
+fn subdivide_mesh(
+	mesh: &usd_geom::Mesh,
+	positions: &[Vec3],
+	welded: &[usize],
+	scheme: Scheme,
+) -> Option<Mesh> {
+	let counts = mesh.face_vertex_counts_attr().get::<Vec<i32>>();
+	let indices = mesh.face_vertex_indices_attr().get::<Vec<i32>>();
+
+	let mut faces = Vec::with_capacity(counts.len());
+	let mut start = 0;
+
+	for &count in &counts {
+		let face = indices.get(start..start + count as usize)?;
+		let face = face.iter().map(|&i| welded.get(i as usize).copied());
+		faces.push(face.collect::<Option<Vec<_>>>()?);
+		start += count as usize;
+	}
+
+	let cage = HalfEdgeMesh::from_polygons(positions, &faces).ok()?;
+	let vertex = |i: i32| welded.get(i as usize).map(|&v| VertexId(v));
+	let sharpness = |s: f32| {
+		if s >= INFINITE_SHARPNESS {
+			f32::INFINITY
+		} else {
+			s
+		}
+	};
+
+	let mut creases = Creases::new();
+
+	// Creases are vertex chains, with a sharpness for every chain or for every edge.
+	let crease_indices = mesh.crease_indices_attr().get::<Vec<i32>>();
+	let crease_lengths = mesh.crease_lengths_attr().get::<Vec<i32>>();
+	let crease_sharpnesses = mesh.crease_sharpnesses_attr().get::<Vec<f32>>();
+	let per_edge = crease_sharpnesses.len() != crease_lengths.len();
+
+	let mut start = 0;
+	let mut edge = 0;
+
+	for (crease, &length) in crease_lengths.iter().enumerate() {
+		let chain = crease_indices.get(start..start + length as usize)?;
+		start += length as usize;
+
+		for pair in chain.windows(2) {
+			let s = crease_sharpnesses.get(if per_edge { edge } else { crease })?;
+			creases.set_edge(vertex(pair[0])?, vertex(pair[1])?, sharpness(*s));
+			edge += 1;
+		}
+	}
+
+	let corner_indices = mesh.corner_indices_attr().get::<Vec<i32>>();
+	let corner_sharpnesses = mesh.corner_sharpnesses_attr().get::<Vec<f32>>();
+
+	for (&corner, &s) in corner_indices.iter().zip(&corner_sharpnesses) {
+		creases.set_corner(vertex(corner)?, sharpness(s));
+	}
+
+	Some(subdivide(&cage, scheme, &creases, SUBDIVISION_LEVELS).to_mesh())
+}
+
+/// Entities imported from a USD stage, spawned into a [`World`] with [`spawn_usd_scene`].
+///
+/// Meshes are labeled assets of the stage, textures are loaded as dependencies.
+pub struct UsdScene {
+	pub prims: Vec<UsdPrim>,
+}
+
+impl Asset for UsdScene {}
 
-	mesh
+pub struct UsdPrim {
+	pub path: String,
+	pub transform: Transform3,
+	pub component: UsdComponent,
 }
 
+pub enum UsdComponent {
+	/// A mesh with all morph target weights at zero.
+	Renderable(Renderable, MorphWeights),
+	SphereLight(SphereLight),
+	RectLight(RectLight),
+	DomeLight(DomeLight),
+}
+
+pub struct UsdLoader;
+
+impl AssetLoader for UsdLoader {
+	type Asset = UsdScene;
+
+	fn extensions(&self) -> &[&str] {
+		&["usd", "usda", "usdc"]
+	}
+
+	fn version(&self) -> u32 {
+		6
+	}
+
+	fn load(&self, ctx: &mut LoadContext) -> Result<UsdScene, LoadError> {
+		for layer in layer_dependencies(ctx) {
+			ctx.cook_dependency(layer);
+		}
+
+		let bytes = ctx.cooked(|ctx| {
+			// The USD library opens stages and their layers by itself, so they can't be read from a pack.
+			let native_path = ctx.native_path().ok_or("USD stages must be loose files")?;
+			let stage = usd::Stage::open(native_path.to_str().ok_or("Invalid stage path")?);
+			let stage_path = ctx.path().to_path_buf();
+
+			let mut writer = BlobWriter::new();
+			let mut transform_stack: Vec<Transform3> = Vec::new();
+
+			traverse_recurse(
+				&stage_path,
+				&stage,
+				&mut writer,
+				&mut transform_stack,
+				&stage.pseudo_root(),
+			)?;
+
+			Ok(writer.finish())
+		})?;
+
+		read_cooked_scene(&mut BlobReader::new(&bytes), ctx)
+	}
+}
+
+/// Layers that the root layer of a stage pulls in as sublayers, references or payloads, directly or through other
+/// layers, relative to the stage. Only text layers are searched for the asset paths they refer to.
+fn layer_dependencies(ctx: &LoadContext) -> Vec<PathBuf> {
+	let mut layers = Vec::new();
+	let mut pending = vec![(PathBuf::new(), ctx.read().unwrap_or_default())];
+
+	while let Some((layer, source)) = pending.pop() {
+		if !source.starts_with(b"#usda") {
+			continue;
+		}
+
+		let source = String::from_utf8_lossy(&source);
+		let directory = layer.parent().unwrap_or(Path::new(""));
+
+		// Asset paths are delimited by `@`, so every other part is one.
+		for asset_path in source.split('@').skip(1).step_by(2) {
+			let path = directory.join(asset_path);
+			let is_layer = path
+				.extension()
+				.and_then(|extension| extension.to_str())
+				.is_some_and(|extension| ["usd", "usda", "usdc"].contains(&extension));
+
+			if is_layer && !layers.contains(&path) {
+				pending.push((path.clone(), ctx.read_relative(&path).unwrap_or_default()));
+				layers.push(path);
+			}
+		}
+	}
+
+	layers
+}
+
+/// Tags of the prims in a cooked stage.
+const COOKED_MESH: u32 = 0;
+const COOKED_SPHERE_LIGHT: u32 = 1;
+const COOKED_RECT_LIGHT: u32 = 2;
+const COOKED_DOME_LIGHT: u32 = 3;
+
+/// Writes every supported prim of the stage in its cooked form.
+///
+/// Each prim is stored as its path, world transform and a tag, followed by the tag's data.
 fn traverse_recurse(
-	stage_path: &str,
+	stage_path: &Path,
 	stage: &usd::Stage,
-	world: &mut World,
+	writer: &mut BlobWriter,
 	transform_stack: &mut Vec<Transform3>,
-	assets: &mut AssetServer,
 	prim: &usd::Prim,
-) {
+) -> Result<(), LoadError> {
 	let xform = usd_geom::XformOp::get_local_transform(prim);
 
 	if let Some(xform) = xform {
@@ -55,17 +319,35 @@ fn traverse_recurse(
 			.fold(Transform3::<f32>::IDENTITY, |acc, xform| acc * *xform)
 	};
 
+	let mut write_header = |writer: &mut BlobWriter, tag: u32| {
+		writer.write_str(&prim.path().to_string());
+		write_transform(writer, &get_transform(transform_stack));
+		writer.write(tag);
+	};
+
 	match prim.type_name().as_str() {
 		"Mesh" => {
 			let mesh = usd_geom::Mesh::define(stage, prim.path().clone());
-			let mesh = convert_mesh(&mesh);
-			let mesh = assets.insert(mesh);
-
-			world.spawn((
-				Name::new(prim.path()),
-				get_transform(transform_stack),
-				Renderable { mesh },
-			));
+
+			let blend_shapes: Vec<_> = prim
+				.children()
+				.into_iter()
+				.filter(|child| child.type_name().as_str() == "BlendShape")
+				.map(|child| {
+					let path = child.path().to_string();
+					let name = path.rsplit('/').next().unwrap_or_default().to_string();
+					(
+						name,
+						usd_skel::BlendShape::define(stage, child.path().clone()),
+					)
+				})
+				.collect();
+
+			let mesh = convert_mesh(&mesh, &blend_shapes)
+				.map_err(|error| format!("Invalid mesh {}: {error}", prim.path()))?;
+
+			write_header(writer, COOKED_MESH);
+			mesh.write_cooked(writer)?;
 		}
 		"SphereLight" => {
 			let light = usd_lux::SphereLight::define(stage, prim.path().clone());
@@ -73,14 +355,9 @@ fn traverse_recurse(
 			let color = from_usd_vec3f(light.color_attr().get::<gf::Vec3f>());
 			let intensity = light.intensity_attr().get::<f32>();
 
-			world.spawn((
-				Name::new(prim.path()),
-				get_transform(transform_stack),
-				SphereLight {
-					emission: (color * intensity).into(),
-					radius: light.radius_attr().get::<f32>(),
-				},
-			));
+			write_header(writer, COOKED_SPHERE_LIGHT);
+			writer.write::<[f32; 3]>((color * intensity).into());
+			writer.write(light.radius_attr().get::<f32>());
 		}
 		"RectLight" => {
 			let light = usd_lux::RectLight::define(stage, prim.path().clone());
@@ -88,15 +365,10 @@ fn traverse_recurse(
 			let color = from_usd_vec3f(light.color_attr().get::<gf::Vec3f>());
 			let intensity = light.intensity_attr().get::<f32>();
 
-			world.spawn((
-				Name::new(prim.path()),
-				get_transform(transform_stack),
-				RectLight {
-					emission: (color * intensity).into(),
-					width: light.width_attr().get::<f32>(),
-					height: light.height_attr().get::<f32>(),
-				},
-			));
+			write_header(writer, COOKED_RECT_LIGHT);
+			writer.write::<[f32; 3]>((color * intensity).into());
+			writer.write(light.width_attr().get::<f32>());
+			writer.write(light.height_attr().get::<f32>());
 		}
 		"DomeLight" => {
 			let light = usd_lux::DomeLight::define(stage, prim.path().clone());
@@ -107,48 +379,87 @@ fn traverse_recurse(
 
 			let texture_file_ref = light.texture_file_attr().get::<sdf::AssetPath>();
 
-			let root_path = std::path::Path::new(stage_path);
-			let parent_path = root_path.parent().unwrap_or(root_path);
+			let parent_path = stage_path.parent().unwrap_or(stage_path);
 			let texuture_path = parent_path.join(texture_file_ref.authored_path.clone()); // TODO: .asset_path()
 
-			let texture = Image::from_file(texuture_path);
-			let texture_asset = assets.insert(texture);
-
-			world.spawn((
-				Name::new(prim.path()),
-				get_transform(transform_stack),
-				DomeLight {
-					image: texture_asset,
-				},
-			));
+			write_header(writer, COOKED_DOME_LIGHT);
+			writer.write_str(texuture_path.to_str().unwrap_or_default());
 		}
 		_ => {}
 	}
 
 	for child in prim.children() {
-		traverse_recurse(stage_path, stage, world, transform_stack, assets, &child);
+		traverse_recurse(stage_path, stage, writer, transform_stack, &child)?;
 	}
 
 	if xform.is_some() {
 		transform_stack.pop();
 	}
-}
 
-pub fn populate_world_from_usd(filepath: &str, world: &mut World, assets: &mut AssetServer) {
-	let stage = usd::Stage::open(filepath);
+	Ok(())
+}
 
-	let pseudo_root = stage.pseudo_root();
+/// Turns a cooked stage into a scene, adding its meshes as labeled assets and loading its textures.
+fn read_cooked_scene(
+	reader: &mut BlobReader,
+	ctx: &mut LoadContext,
+) -> Result<UsdScene, LoadError> {
+	let mut scene = UsdScene { prims: Vec::new() };
+
+	while !reader.is_empty() {
+		let path = reader.read_string()?;
+		let transform = read_transform(reader)?;
+
+		let component = match reader.read::<u32>()? {
+			COOKED_MESH => {
+				let mesh = Mesh::read_cooked(reader)?;
+				let weights = MorphWeights(vec![0.0; mesh.morph_targets.len()]);
+				UsdComponent::Renderable(
+					Renderable {
+						mesh: ctx.add_labeled_asset(&path, mesh),
+					},
+					weights,
+				)
+			}
+			COOKED_SPHERE_LIGHT => UsdComponent::SphereLight(SphereLight {
+				emission: reader.read()?,
+				radius: reader.read()?,
+			}),
+			COOKED_RECT_LIGHT => UsdComponent::RectLight(RectLight {
+				emission: reader.read()?,
+				width: reader.read()?,
+				height: reader.read()?,
+			}),
+			COOKED_DOME_LIGHT => UsdComponent::DomeLight(DomeLight {
+				image: ctx.load::<Image>(reader.read_string()?),
+			}),
+			tag => return Err(format!("Invalid prim tag {tag} in cooked stage").into()),
+		};
+
+		scene.prims.push(UsdPrim {
+			path,
+			transform,
+			component,
+		});
+	}
 
-	let mut transform_stack: Vec<Transform3> = Vec::new();
+	Ok(scene)
+}
 
-	traverse_recurse(
-		filepath,
-		&stage,
-		world,
-		&mut transform_stack,
-		assets,
-		&pseudo_root,
-	);
+/// Spawns an entity for every prim of the scene.
+pub fn spawn_usd_scene(scene: &UsdScene, world: &mut World) {
+	for prim in &scene.prims {
+		let name = Name::new(&prim.path);
+
+		match &prim.component {
+			UsdComponent::Renderable(renderable, weights) => {
+				world.spawn((name, prim.transform, *renderable, weights.clone()))
+			}
+			UsdComponent::SphereLight(light) => world.spawn((name, prim.transform, *light)),
+			UsdComponent::RectLight(light) => world.spawn((name, prim.transform, *light)),
+			UsdComponent::DomeLight(light) => world.spawn((name, prim.transform, *light)),
+		};
+	}
 }
 
 fn from_usd_vec3f(v: gf::Vec3f) -> Vec3 {
@@ -1,6 +1,7 @@
 use std::collections::HashSet;
 
 use crate::time::Time;
+use asset::AssetServer;
 use ecs::{Entity, World};
 
 use super::tabs;
@@ -8,6 +9,7 @@ use super::windows;
 
 pub struct MyContext {
 	pub world: World,
+	pub assets: AssetServer,
 	pub selection: HashSet<Entity>,
 	pub viewport_texture_srv: u32,
 }
@@ -19,7 +21,7 @@ pub struct Editor {
 }
 
 impl Editor {
-	pub fn new() -> Self {
+	pub fn new(assets: AssetServer) -> Self {
 		// TODO: Move earlier into main.rs
 		log::set_logger(&windows::Log {})
 			.map(|()| log::set_max_level(log::LevelFilter::Trace))
@@ -59,6 +61,7 @@ impl Editor {
 			egui_ctx,
 			context: MyContext {
 				world,
+				assets,
 				selection: HashSet::new(),
 				viewport_texture_srv: 0,
 			},
@@ -49,8 +49,8 @@ impl EguiRenderer {
 			})
 			.unwrap();
 
-		let vertex_shader = shader_compiler.compile("shaders/editor/egui.slang", "main_vs");
-		let pixel_shader = shader_compiler.compile("shaders/editor/egui.slang", "main_ps");
+		let vertex_shader = shader_compiler.compile("editor/egui.slang", "main_vs");
+		let pixel_shader = shader_compiler.compile("editor/egui.slang", "main_ps");
 
 		let pipeline_desc = gpu::GraphicsPipelineDesc {
 			vs: Some(&vertex_shader),
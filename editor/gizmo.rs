@@ -185,8 +185,8 @@ impl GizmoRenderer {
 			})
 			.unwrap();
 
-		let vertex_shader = shader_compiler.compile("shaders/editor/gizmo.slang", "main_vs");
-		let pixel_shader = shader_compiler.compile("shaders/editor/gizmo.slang", "main_ps");
+		let vertex_shader = shader_compiler.compile("editor/gizmo.slang", "main_vs");
+		let pixel_shader = shader_compiler.compile("editor/gizmo.slang", "main_ps");
 
 		let pipeline_desc = gpu::GraphicsPipelineDesc {
 			vs: Some(&vertex_shader),
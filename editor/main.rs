@@ -9,19 +9,33 @@ mod windows;
 use engine::*;
 
 use crate::egui_impl::{EguiRenderer, ScreenDesc, get_raw_input, set_full_output};
-use crate::scene::setup_scene;
-use asset::AssetServer;
+use crate::scene::{setup_scene, spawn_pending_scenes};
+use asset::{AssetCache, AssetServer};
+use geometry::formats::obj;
 use gpu::{self, CmdListImpl, DeviceImpl, SurfaceImpl, TextureImpl};
 use graphics::{
 	camera::Camera,
+	gltf::GltfLoader,
 	pathtracer::{Compositor, PathTracer},
-	scene::Scene,
+	scene::{Image, ImageLoader, Scene},
 };
 use math::{Mat4, transform::Transform3};
 use os::{self, App, Window};
 
 fn main() {
 	let mut assets = AssetServer::new();
+	assets.register_loader(ImageLoader);
+	assets.register_loader(usd::UsdLoader);
+	assets.register_loader(obj::ObjLoader);
+	assets.register_loader(obj::MtlLoader);
+	assets.register_loader(GltfLoader);
+	assets.set_vfs(vfs::create_vfs());
+	assets.set_cache(AssetCache::new(vfs::cache_dir()));
+	assets.watch_for_changes(std::time::Duration::from_secs(1));
+
+	// Rendered in place of assets that failed to load.
+	assets.set_placeholder(Image::checker(256, 8));
+	assets.set_placeholder(geometry::primitives::platonic::hexahedron());
 
 	let mut app = os::platform::App::new();
 
@@ -30,7 +44,10 @@ fn main() {
 		power_preference: gpu::PowerPreference::HighPerformance,
 	});
 
-	let shader_compiler = gpu::ShaderCompiler::new(gpu::BACKEND);
+	let shader_vfs = assets.vfs().clone();
+	let shader_compiler = gpu::ShaderCompiler::new(gpu::BACKEND, move |path| {
+		shader_vfs.read(format!("engine://shaders/{path}"))
+	});
 
 	let monitor = &os::platform::App::enumerate_monitors()[0];
 	let mut window = app.create_window(&os::WindowDesc {
@@ -69,9 +86,12 @@ fn main() {
 	let mut compositor = Compositor::new([1920, 1080], &mut device, &shader_compiler);
 	let mut gizmo_renderer = gizmo::GizmoRenderer::new([1920, 1080], &mut device, &shader_compiler);
 
-	let mut editor = editor::Editor::new();
+	let mut editor = editor::Editor::new(assets);
+	let context = &mut editor.context;
 
-	setup_scene(&mut editor.context.world, &mut assets);
+	// Scenes to open can be passed on the command line.
+	let scenes: Vec<String> = std::env::args().skip(1).collect();
+	setup_scene(&mut context.world, &mut context.assets, &scenes);
 
 	while app.run() {
 		surface.update(&mut device, window.size().into());
@@ -111,8 +131,12 @@ fn main() {
 			},
 		);
 
+		let context = &mut editor.context;
+		context.assets.update();
+		spawn_pending_scenes(&mut context.world, &context.assets);
+
 		if let Some((scene, path_tracer)) = &mut renderer {
-			scene.update(&mut editor.context.world, &assets, &mut device, &mut cmd);
+			scene.update(&mut context.world, &context.assets, &mut device, &mut cmd);
 			path_tracer.run(&mut cmd, scene, 20);
 
 			if let Some((camera_transform, camera)) = editor
@@ -19,22 +19,46 @@ impl tabs::Tab<MyContext> for ContentTab {
 		&self.name
 	}
 
-	fn ui(&mut self, ui: &mut egui::Ui, _ctx: &mut MyContext) {
-		ui.horizontal(|ui| {
-			for i in 0..100 {
-				egui::Frame::window(ui.style())
-					.shadow(egui::epaint::Shadow {
-						extrusion: 8.0,
-						color: egui::Color32::from_black_alpha(25),
-					})
-					.inner_margin(egui::Margin::symmetric(10.0, 30.0))
-					.outer_margin(egui::Margin::same(5.0))
-					.fill(egui::Color32::from_gray(48))
-					.stroke(egui::Stroke::new(1.0, egui::Color32::from_gray(255)))
-					.show(ui, |ui| {
-						ui.label(format!("Asset {}", i));
-					});
-			}
+	fn ui(&mut self, ui: &mut egui::Ui, ctx: &mut MyContext) {
+		let assets = &ctx.assets;
+
+		let mut items: Vec<(char, &str)> = Vec::new();
+
+		for (handle, _) in assets.iter::<usd::UsdScene>() {
+			items.push((icons::SCENE_DATA, assets.name(&handle).unwrap_or_default()));
+		}
+		for (handle, _) in assets.iter::<graphics::gltf::GltfScene>() {
+			items.push((icons::SCENE_DATA, assets.name(&handle).unwrap_or_default()));
+		}
+		for (handle, _) in assets.iter::<geometry::mesh::Mesh>() {
+			items.push((icons::MESH_DATA, assets.name(&handle).unwrap_or_default()));
+		}
+		for (handle, _) in assets.iter::<graphics::scene::Image>() {
+			items.push((icons::IMAGE_DATA, assets.name(&handle).unwrap_or_default()));
+		}
+
+		items.sort_by_key(|(_, name)| *name);
+
+		egui::ScrollArea::vertical().show(ui, |ui| {
+			ui.horizontal_wrapped(|ui| {
+				for (icon, name) in items {
+					// Show the file name, or the label of assets inside a file.
+					let short_name = name.rsplit(['/', '\\', '#']).next().unwrap_or(name);
+
+					egui::Frame::window(ui.style())
+						.shadow(egui::epaint::Shadow {
+							extrusion: 8.0,
+							color: egui::Color32::from_black_alpha(25),
+						})
+						.inner_margin(egui::Margin::symmetric(10.0, 30.0))
+						.outer_margin(egui::Margin::same(5.0))
+						.fill(egui::Color32::from_gray(48))
+						.stroke(egui::Stroke::new(1.0, egui::Color32::from_gray(255)))
+						.show(ui, |ui| {
+							ui.label(format!("{icon} {short_name}")).on_hover_text(name);
+						});
+				}
+			});
 		});
 	}
 }
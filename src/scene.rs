@@ -1,10 +1,40 @@
-use asset::AssetServer;
+use asset::{AssetDatabase, AssetId, AssetServer, LoadState};
 use ecs::{Name, World};
 use graphics::camera::Camera;
+use graphics::gltf::{GltfScene, spawn_gltf_scene};
 use math::{PI, UnitQuaternion, Vec3, transform::Transform3};
+use std::path::Path;
+use usd::UsdScene;
 
-pub fn setup_scene(world: &mut World, assets: &mut AssetServer) {
-	usd::populate_world_from_usd("../assets/usd/ybot-scene.usdc", world, assets);
+/// Scene that is loaded by default.
+const DEFAULT_SCENE: &str = "assets://usd/ybot-scene.usdc";
+
+/// Scenes that are spawned into the world once they and their dependencies finished loading.
+pub struct PendingScenes(Vec<PendingScene>);
+
+#[derive(Clone, Copy)]
+enum PendingScene {
+	Usd(AssetId<UsdScene>),
+	Gltf(AssetId<GltfScene>),
+}
+
+/// Loads the USD stages or glTF files at `scenes`, or the default scene if there are none.
+pub fn setup_scene(world: &mut World, assets: &mut AssetServer, scenes: &[String]) {
+	// Packed assets can't be scanned, their GUIDs are registered as they are loaded instead.
+	match AssetDatabase::scan_mounted(assets.vfs(), "assets://") {
+		Ok(database) => assets.set_database(database),
+		Err(error) => log::warn!("Failed to scan asset database: {}", error),
+	}
+
+	world.add_singleton(PendingScenes(Vec::new()));
+
+	if scenes.is_empty() {
+		load_scene(world, assets, DEFAULT_SCENE);
+	}
+
+	for scene in scenes {
+		load_scene(world, assets, scene);
+	}
 
 	world.spawn((
 		Name::new("Camera"),
@@ -16,3 +46,71 @@ pub fn setup_scene(world: &mut World, assets: &mut AssetServer) {
 		},
 	));
 }
+
+/// Starts loading a USD stage or glTF file, which is spawned by [`spawn_pending_scenes`] once it is loaded.
+pub fn load_scene(world: &mut World, assets: &mut AssetServer, path: impl AsRef<Path>) {
+	let path = path.as_ref();
+	let extension = path
+		.extension()
+		.and_then(|extension| extension.to_str())
+		.map(str::to_ascii_lowercase);
+
+	let scene = match extension.as_deref() {
+		Some("gltf" | "glb") => PendingScene::Gltf(assets.load(path)),
+		Some("usd" | "usda" | "usdc") => PendingScene::Usd(assets.load(path)),
+		_ => {
+			log::error!("Unsupported scene format: {}", path.display());
+			return;
+		}
+	};
+
+	match world.get_singleton_mut::<PendingScenes>() {
+		Some(pending) => pending.0.push(scene),
+		None => world.add_singleton(PendingScenes(vec![scene])),
+	}
+}
+
+pub fn spawn_pending_scenes(world: &mut World, assets: &AssetServer) {
+	let Some(pending) = world.get_singleton_mut::<PendingScenes>() else {
+		return;
+	};
+
+	let mut ready = Vec::new();
+
+	pending.0.retain(|scene| {
+		let (state, loaded) = match scene {
+			PendingScene::Usd(scene) => (
+				assets.recursive_load_state(scene),
+				assets.get(scene).is_some(),
+			),
+			PendingScene::Gltf(scene) => (
+				assets.recursive_load_state(scene),
+				assets.get(scene).is_some(),
+			),
+		};
+
+		match state {
+			LoadState::Loading => true,
+			LoadState::Loaded => {
+				ready.push(*scene);
+				false
+			}
+			LoadState::Failed(_) => {
+				// The error is logged by the asset server. Spawn what we have when only a dependency failed,
+				// its placeholder is rendered instead.
+				if loaded {
+					ready.push(*scene);
+				}
+
+				false
+			}
+		}
+	});
+
+	for scene in ready {
+		match scene {
+			PendingScene::Usd(scene) => usd::spawn_usd_scene(assets.get(&scene).unwrap(), world),
+			PendingScene::Gltf(scene) => spawn_gltf_scene(assets.get(&scene).unwrap(), world),
+		}
+	}
+}
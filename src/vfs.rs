@@ -0,0 +1,74 @@
+use asset::{DirectorySource, PackSource, Vfs};
+use std::path::{Path, PathBuf};
+
+/// Creates the file system with the `assets://`, `project://` and `engine://shaders` mount points.
+///
+/// A shipped build mounts the `assets.pack` and `project.pack` files next to the executable.
+/// Without them, the loose `assets` and `project` folders are mounted. These and the `shaders` folder
+/// are looked up next to the executable and in its ancestors, so nothing depends on the build path.
+pub fn create_vfs() -> Vfs {
+	let exe_dir = exe_dir();
+	let mut vfs = Vfs::new();
+
+	for (point, pack_name, directory) in [
+		("assets://", "assets.pack", "assets"),
+		("project://", "project.pack", "project"),
+	] {
+		let pack_path = exe_dir
+			.as_ref()
+			.map(|exe_dir| exe_dir.join(pack_name))
+			.filter(|pack_path| pack_path.is_file());
+
+		if let Some(pack_path) = pack_path {
+			match PackSource::open(&pack_path) {
+				Ok(pack) => {
+					vfs.mount(point, pack);
+					continue;
+				}
+				Err(error) => log::error!("Failed to open {}: {}", pack_path.display(), error),
+			}
+		}
+
+		match find_dir(directory) {
+			Some(directory) => vfs.mount(point, DirectorySource::new(directory)),
+			None => log::warn!("Nothing to mount at {point}, no {pack_name} or {directory} folder"),
+		}
+	}
+
+	match find_dir("shaders") {
+		Some(shader_dir) => vfs.mount("engine://shaders", DirectorySource::new(shader_dir)),
+		None => log::error!("Nothing to mount at engine://shaders, no shaders folder"),
+	}
+
+	vfs
+}
+
+/// Directory for cooked assets, see [`asset::AssetCache`].
+///
+/// Lives next to the loose `assets` folder, or next to the executable when only packs are shipped.
+pub fn cache_dir() -> PathBuf {
+	find_dir("assets")
+		.and_then(|assets| assets.parent().map(Path::to_path_buf))
+		.or_else(exe_dir)
+		.unwrap_or_default()
+		.join("cache")
+}
+
+/// Finds the folder called `name` in the directory of the executable or one of its ancestors,
+/// falling back to the working directory and its ancestors.
+fn find_dir(name: &str) -> Option<PathBuf> {
+	let roots = [exe_dir(), std::env::current_dir().ok()];
+
+	roots
+		.iter()
+		.flatten()
+		.flat_map(|root| root.ancestors())
+		.map(|dir| dir.join(name))
+		.find(|dir| dir.is_dir())
+}
+
+fn exe_dir() -> Option<PathBuf> {
+	std::env::current_exe()
+		.ok()
+		.and_then(|exe| exe.parent().map(Path::to_path_buf))
+}